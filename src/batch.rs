@@ -0,0 +1,404 @@
+use std::{
+    fs,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    bundle::nib::NibBundle,
+    data::Context,
+    export::{
+        json::to_json,
+        tree::{to_tree, TreeOptions},
+        yaml::to_yaml,
+    },
+    validate::{validate, Severity},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Json,
+    Yaml,
+    Tree,
+}
+
+impl OutputFormat {
+    pub fn parse(name: &str) -> Option<OutputFormat> {
+        match name {
+            "json" => Some(OutputFormat::Json),
+            "yaml" => Some(OutputFormat::Yaml),
+            "tree" => Some(OutputFormat::Tree),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Tree => "txt",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    // Worker threads, at least one.
+    pub jobs: usize,
+    // Directory mirroring the input tree that the decoded files are written
+    // to, as "<name>.nib.<extension>". Nothing is written when unset.
+    pub output: Option<PathBuf>,
+    pub format: OutputFormat,
+}
+
+impl Default for BatchOptions {
+    fn default() -> BatchOptions {
+        BatchOptions {
+            jobs: thread::available_parallelism().map_or(1, |jobs| jobs.get()),
+            output: None,
+            format: OutputFormat::Json,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Status {
+    Ok {
+        objects: usize,
+    },
+    // Decoded, but with problems that may make the output incomplete.
+    Warnings {
+        objects: usize,
+        warnings: Vec<String>,
+    },
+    Error {
+        offset: Option<usize>,
+        reason: String,
+    },
+    // Decoding panicked; the message of the panic.
+    Panic(String),
+}
+
+#[derive(Debug)]
+pub struct FileReport {
+    // Relative to the batch root.
+    pub path: PathBuf,
+    pub status: Status,
+    pub output: Option<PathBuf>,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub ok: usize,
+    pub warnings: usize,
+    pub errors: usize,
+    pub panics: usize,
+}
+
+impl Summary {
+    pub fn of(reports: &[FileReport]) -> Summary {
+        let mut summary = Summary::default();
+        for report in reports {
+            match report.status {
+                Status::Ok { .. } => summary.ok += 1,
+                Status::Warnings { .. } => summary.warnings += 1,
+                Status::Error { .. } => summary.errors += 1,
+                Status::Panic(_) => summary.panics += 1,
+            }
+        }
+        summary
+    }
+
+    pub fn total(&self) -> usize {
+        self.ok + self.warnings + self.errors + self.panics
+    }
+
+    pub fn failed(&self) -> bool {
+        self.errors + self.panics > 0
+    }
+}
+
+// Nibs below a directory: .nib files and compiled .nib directories, which
+// are not descended into. Nibs inside .storyboardc directories are included.
+// Symbolic links to directories are not followed, so links that loop back
+// up the tree cannot make the walk go on forever.
+pub fn find_nibs(root: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut nibs = vec![];
+    let mut directories = vec![root.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        for entry in fs::read_dir(&directory)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().is_some_and(|extension| extension == "nib") {
+                nibs.push(path);
+            } else if entry.file_type()?.is_dir() {
+                directories.push(path);
+            }
+        }
+    }
+    nibs.sort();
+    Ok(nibs)
+}
+
+// Decodes every nib below `root` on a pool of worker threads. Each report is
+// passed to `progress` as soon as its file is done; the returned reports are
+// sorted by path. A panic while decoding one file is reported for that file
+// and does not stop the others.
+pub fn run(
+    root: &Path,
+    options: &BatchOptions,
+    progress: impl FnMut(&FileReport),
+) -> std::io::Result<Vec<FileReport>> {
+    let nibs = find_nibs(root)?;
+    let mut reports = run_files(root, &nibs, options, progress);
+    reports.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(reports)
+}
+
+fn run_files(
+    root: &Path,
+    nibs: &[PathBuf],
+    options: &BatchOptions,
+    mut progress: impl FnMut(&FileReport),
+) -> Vec<FileReport> {
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    let mut reports = Vec::with_capacity(nibs.len());
+
+    thread::scope(|scope| {
+        for _ in 0..options.jobs.max(1).min(nibs.len().max(1)) {
+            let sender = sender.clone();
+            let next = &next;
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = nibs.get(index) else {
+                    break;
+                };
+                let report = process(root, path, options);
+                if sender.send(report).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for report in receiver {
+            progress(&report);
+            reports.push(report);
+        }
+    });
+    reports
+}
+
+fn process(root: &Path, path: &Path, options: &BatchOptions) -> FileReport {
+    let start = Instant::now();
+    let relative = path.strip_prefix(root).unwrap_or(path).to_path_buf();
+    let mut output = None;
+
+    let status = match catch_unwind(AssertUnwindSafe(|| decode(path, options.format))) {
+        Ok(Ok((context, rendered, mut warnings))) => {
+            if let (Some(directory), Some(rendered)) = (&options.output, rendered) {
+                let mut name = relative.clone().into_os_string();
+                name.push(".");
+                name.push(options.format.extension());
+                let target = directory.join(name);
+                let written = target
+                    .parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|_| fs::write(&target, rendered));
+                match written {
+                    Ok(()) => output = Some(target),
+                    Err(error) => warnings.push(format!("Could not write output: {}", error)),
+                }
+            }
+            let objects = context.objects.len();
+            if warnings.is_empty() {
+                Status::Ok { objects }
+            } else {
+                Status::Warnings { objects, warnings }
+            }
+        }
+        Ok(Err(status)) => status,
+        Err(payload) => Status::Panic(
+            payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string()),
+        ),
+    };
+
+    FileReport {
+        path: relative,
+        status,
+        output,
+        duration: start.elapsed(),
+    }
+}
+
+// Decodes one nib and renders it, returning the warnings found on the way.
+// Files with structural errors are not rendered, and the rendering is
+// skipped when the format cannot represent the file.
+#[allow(clippy::type_complexity)]
+fn decode(
+    path: &Path,
+    format: OutputFormat,
+) -> Result<(Context, Option<String>, Vec<String>), Status> {
+    let error = |offset: Option<usize>, reason: String| Status::Error { offset, reason };
+    let bundle = NibBundle::open(path).map_err(|e| error(None, e.to_string()))?;
+    let variant = bundle
+        .select(None, None)
+        .ok_or_else(|| error(None, "Contains no runtime nib".to_string()))?;
+    let context = variant
+        .parse()
+        .map_err(|e| error(Some(e.rel_offset), e.reason))?;
+
+    let (errors, mut warnings) = check(&context);
+    if !errors.is_empty() {
+        return Err(error(None, errors.join("; ")));
+    }
+    let rendered = match format {
+        OutputFormat::Json => to_json(&context),
+        OutputFormat::Yaml => to_yaml(&context),
+        OutputFormat::Tree => Ok(to_tree(&context, 0, &TreeOptions::default())),
+    };
+    match rendered {
+        Ok(rendered) => Ok((context, Some(rendered), warnings)),
+        Err(reason) => {
+            warnings.push(reason);
+            Ok((context, None, warnings))
+        }
+    }
+}
+
+// The validation findings that decoding does not catch by itself. Errors,
+// such as references to missing objects or unknown keys, classes and value
+// types, make the output wrong. Warnings, such as overlapping value ranges
+// or objects nothing refers to, leave it usable. Notes are ignored.
+pub fn check(context: &Context) -> (Vec<String>, Vec<String>) {
+    let mut errors = vec![];
+    let mut warnings = vec![];
+    for finding in validate(context) {
+        match finding.severity {
+            Severity::Error => errors.push(finding.message),
+            Severity::Warning => warnings.push(finding.message),
+            Severity::Note => {}
+        }
+    }
+    (errors, warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{
+        batch::{find_nibs, run, BatchOptions, OutputFormat, Status, Summary},
+        raw_model::{builder::ContextBuilder, nib::NibFile},
+    };
+
+    #[test]
+    fn batch() {
+        let root = std::env::temp_dir().join(format!("batch-{}", std::process::id()));
+        let output = root.join("out");
+        fs::create_dir_all(root.join("in/Foo.nib")).unwrap();
+        fs::create_dir_all(root.join("in/sub")).unwrap();
+
+        let nib = |reference: Option<u32>| {
+            let mut builder = ContextBuilder::new();
+            let object = builder.reserve_object();
+            let values = reference
+                .map(|target| vec![builder.object_value("UIView", target)])
+                .unwrap_or_default();
+            let class = builder.class("NSObject", &[]);
+            builder.set_object(object, class, values);
+            NibFile::from_context(&builder.build().unwrap())
+        };
+        fs::write(root.join("in/sub/Plain.nib"), nib(None).buffer()).unwrap();
+        fs::write(root.join("in/Foo.nib/runtime.nib"), nib(Some(0)).buffer()).unwrap();
+        fs::write(root.join("in/Dangling.nib"), nib(Some(5)).buffer()).unwrap();
+        let mut builder = ContextBuilder::new();
+        let class = builder.class("NSObject", &[]);
+        builder.push_object(class, vec![]);
+        builder.push_object(class, vec![]);
+        let orphan = NibFile::from_context(&builder.build().unwrap());
+        fs::write(root.join("in/Orphan.nib"), orphan.buffer()).unwrap();
+        // Cut inside the class name, past the start of the classes section.
+        let plain = nib(None);
+        let broken = &plain.buffer()[..plain.buffer().len() - 3];
+        fs::write(root.join("in/Broken.nib"), broken).unwrap();
+
+        let options = BatchOptions {
+            jobs: 2,
+            output: Some(output.clone()),
+            format: OutputFormat::Json,
+        };
+        let mut seen = 0;
+        let reports = run(&root.join("in"), &options, |_| seen += 1).unwrap();
+        let json = fs::read_to_string(output.join("sub/Plain.nib.json"));
+        let cyclic = fs::read_to_string(output.join("Foo.nib.json"));
+        let dangling_written = output.join("Dangling.nib.json").exists();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(5, seen);
+        assert!(matches!(
+            reports[0].status,
+            Status::Error {
+                offset: Some(55),
+                ..
+            }
+        ));
+        match &reports[1].status {
+            Status::Error {
+                offset: None,
+                reason,
+            } => assert_eq!(
+                "Value 0 references missing object 5, the archive has 1 objects",
+                reason
+            ),
+            status => panic!("unexpected status {:?}", status),
+        }
+        assert!(!dangling_written);
+        assert!(matches!(reports[2].status, Status::Ok { objects: 1 }));
+        assert!(cyclic.unwrap().contains("{\"$ref\": \"o0\"}"));
+        match &reports[3].status {
+            Status::Warnings {
+                objects: 2,
+                warnings,
+            } => assert_eq!(&["Object 1 is not referenced by any object"], &warnings[..]),
+            status => panic!("unexpected status {:?}", status),
+        }
+        assert!(matches!(reports[4].status, Status::Ok { objects: 1 }));
+        assert!(json.unwrap().contains("NSObject"));
+        assert_eq!(
+            Summary {
+                ok: 2,
+                warnings: 1,
+                errors: 2,
+                panics: 0
+            },
+            Summary::of(&reports)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loop() {
+        let root = std::env::temp_dir().join(format!("batch-loop-{}", std::process::id()));
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("sub/Main.nib"), b"").unwrap();
+        std::os::unix::fs::symlink(&root, root.join("sub/up")).unwrap();
+
+        let nibs = find_nibs(&root);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(vec![root.join("sub/Main.nib")], nibs.unwrap());
+    }
+}
@@ -0,0 +1,292 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::zip::{self, ZipArchive};
+
+use super::{
+    list_files,
+    nib::NibBundle,
+    storyboard::{Storyboard, INFO_PLIST},
+};
+
+// A file found in an app, or why it could not be read.
+pub type AppFile = (PathBuf, Result<Vec<u8>, String>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    Nib,
+    Storyboard,
+}
+
+// Outcome of decoding one file: its object count or why it failed.
+#[derive(Debug)]
+pub struct FileResult {
+    pub path: PathBuf,
+    pub result: Result<usize, String>,
+}
+
+// A nib or compiled storyboard found in an app.
+#[derive(Debug)]
+pub struct Item {
+    // Relative to the root of the .app or .ipa.
+    pub path: PathBuf,
+    pub kind: ItemKind,
+    // Language of the enclosing "<locale>.lproj" directory, such as "en" or
+    // "Base".
+    pub locale: Option<String>,
+    // Set for storyboards whose Info.plist could be read.
+    pub storyboard: Option<Storyboard>,
+    pub files: Vec<FileResult>,
+}
+
+impl Item {
+    pub fn errors(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|file| file.result.is_err())
+            .count()
+    }
+}
+
+// Every nib and storyboard in an .app bundle or .ipa archive, each decoded.
+#[derive(Debug)]
+pub struct Inventory {
+    pub path: PathBuf,
+    pub items: Vec<Item>,
+}
+
+impl Inventory {
+    pub fn open(path: &Path) -> io::Result<Inventory> {
+        let mut files = vec![];
+        if path.is_dir() {
+            for file in list_files(path)? {
+                let relative = file.strip_prefix(path).unwrap_or(&file).to_path_buf();
+                if resource_root(&relative).is_some() {
+                    files.push((relative, fs::read(&file).map_err(|e| e.to_string())));
+                }
+            }
+        } else {
+            let buffer = fs::read(path)?;
+            if !zip::is_zip(&buffer) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Neither an .app directory nor an .ipa archive",
+                ));
+            }
+            let archive = ZipArchive::from_buffer(&buffer)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            for entry in archive.entries.iter().filter(|entry| !entry.is_dir()) {
+                let relative = PathBuf::from(&entry.name);
+                if resource_root(&relative).is_some() {
+                    files.push((relative, archive.read(entry).map_err(|e| e.to_string())));
+                }
+            }
+        }
+        Ok(Inventory::from_files(path, files))
+    }
+
+    // Groups files by the nib or storyboard they belong to and decodes them.
+    // Files that could not be read are reported as failed.
+    pub fn from_files(path: &Path, files: Vec<AppFile>) -> Inventory {
+        let mut groups: BTreeMap<PathBuf, Vec<AppFile>> = BTreeMap::new();
+        for (file, data) in files {
+            if let Some(root) = resource_root(&file) {
+                groups.entry(root).or_default().push((file, data));
+            }
+        }
+
+        let items = groups
+            .into_iter()
+            .map(|(root, files)| {
+                let mut results = vec![];
+                let mut readable = vec![];
+                for (file, data) in files {
+                    match data {
+                        Ok(data) => readable.push((file, data)),
+                        Err(error) => results.push(FileResult {
+                            path: file,
+                            result: Err(error),
+                        }),
+                    }
+                }
+                let locale = root
+                    .components()
+                    .rev()
+                    .find_map(|component| match component {
+                        Component::Normal(name) => name.to_str()?.strip_suffix(".lproj"),
+                        _ => None,
+                    })
+                    .map(str::to_string);
+
+                if root.extension().is_some_and(|e| e == "storyboardc") {
+                    let storyboard = match Storyboard::from_files(&root, readable) {
+                        Ok(storyboard) => {
+                            let scene_nibs = storyboard
+                                .scenes
+                                .iter()
+                                .flat_map(|scene| scene.nib.iter().chain(scene.view_nibs.iter()));
+                            for nib in scene_nibs.chain(storyboard.other_nibs.iter()) {
+                                results.extend(parse(nib));
+                            }
+                            Some(storyboard)
+                        }
+                        Err(error) => {
+                            // Unless reading Info.plist already failed.
+                            let info = root.join(INFO_PLIST);
+                            if !results.iter().any(|result| result.path == info) {
+                                results.push(FileResult {
+                                    path: info,
+                                    result: Err(error.to_string()),
+                                });
+                            }
+                            None
+                        }
+                    };
+                    Item {
+                        path: root,
+                        kind: ItemKind::Storyboard,
+                        locale,
+                        storyboard,
+                        files: results,
+                    }
+                } else {
+                    results.extend(parse(&NibBundle::from_files(&root, readable)));
+                    Item {
+                        path: root,
+                        kind: ItemKind::Nib,
+                        locale,
+                        storyboard: None,
+                        files: results,
+                    }
+                }
+            })
+            .collect();
+
+        Inventory {
+            path: path.to_path_buf(),
+            items,
+        }
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.items.iter().map(|item| item.files.len()).sum()
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.items.iter().map(Item::errors).sum()
+    }
+}
+
+// Decodes every variant UIKit may load.
+fn parse(nib: &NibBundle) -> Vec<FileResult> {
+    nib.variants
+        .iter()
+        .filter(|variant| variant.is_runtime())
+        .map(|variant| FileResult {
+            path: variant.path.clone(),
+            result: variant
+                .parse()
+                .map(|context| context.objects.len())
+                .map_err(|e| e.to_string()),
+        })
+        .collect()
+}
+
+// The .storyboardc or .nib a file belongs to: the path up to the first
+// component with either extension.
+fn resource_root(path: &Path) -> Option<PathBuf> {
+    let mut root = PathBuf::new();
+    for component in path.components() {
+        root.push(component);
+        if root
+            .extension()
+            .is_some_and(|e| e == "nib" || e == "storyboardc")
+        {
+            return Some(root);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::{
+        bundle::app::{Inventory, ItemKind},
+        raw_model::{builder::ContextBuilder, nib::NibFile},
+    };
+
+    #[test]
+    fn inventory() {
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, vec![]);
+        let nib = NibFile::from_context(&builder.build().unwrap())
+            .buffer()
+            .to_vec();
+
+        let file = |path: &str, data: Result<Vec<u8>, String>| (PathBuf::from(path), data);
+        let inventory = Inventory::from_files(
+            Path::new("App.ipa"),
+            vec![
+                file("Payload/App.app/Info.plist", Ok(vec![])),
+                file("Payload/App.app/en.lproj/Cell.nib", Ok(nib.clone())),
+                file("Payload/App.app/Menu.nib/runtime.nib", Ok(nib.clone())),
+                file(
+                    "Payload/App.app/Menu.nib/objects-13.0+.nib",
+                    Ok(b"NIBArchive".to_vec()),
+                ),
+                file(
+                    "Payload/App.app/Base.lproj/Main.storyboardc/Info.plist",
+                    Err("CRC mismatch".to_string()),
+                ),
+            ],
+        );
+
+        let summary: Vec<_> = inventory
+            .items
+            .iter()
+            .map(|item| {
+                (
+                    item.path.to_string_lossy().into_owned(),
+                    item.kind,
+                    item.locale.clone(),
+                    item.files.len(),
+                    item.errors(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    "Payload/App.app/Base.lproj/Main.storyboardc".to_string(),
+                    ItemKind::Storyboard,
+                    Some("Base".to_string()),
+                    1,
+                    1
+                ),
+                (
+                    "Payload/App.app/Menu.nib".to_string(),
+                    ItemKind::Nib,
+                    None,
+                    2,
+                    1
+                ),
+                (
+                    "Payload/App.app/en.lproj/Cell.nib".to_string(),
+                    ItemKind::Nib,
+                    Some("en".to_string()),
+                    1,
+                    0
+                ),
+            ],
+            summary
+        );
+        assert_eq!(Ok(1), inventory.items[2].files[0].result);
+    }
+}
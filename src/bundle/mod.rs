@@ -0,0 +1,74 @@
+use std::{
+    cmp::Ordering,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub mod app;
+pub mod nib;
+pub mod storyboard;
+
+// An iOS version like "13.0" or "8.1.2", ordered numerically.
+#[derive(Debug, Clone)]
+pub struct OsVersion(pub Vec<u32>);
+
+impl OsVersion {
+    pub fn parse(version: &str) -> Option<OsVersion> {
+        let components: Option<Vec<u32>> = version.split('.').map(|c| c.parse().ok()).collect();
+        components
+            .filter(|components| !components.is_empty())
+            .map(OsVersion)
+    }
+}
+
+impl Ord for OsVersion {
+    // Missing components count as zero, so 13 == 13.0.
+    fn cmp(&self, other: &OsVersion) -> Ordering {
+        let length = self.0.len().max(other.0.len());
+        for i in 0..length {
+            let a = self.0.get(i).copied().unwrap_or(0);
+            let b = other.0.get(i).copied().unwrap_or(0);
+            match a.cmp(&b) {
+                Ordering::Equal => continue,
+                ordering => return ordering,
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+// Equal exactly when cmp says so, so 13 == 13.0 here as well.
+impl PartialEq for OsVersion {
+    fn eq(&self, other: &OsVersion) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OsVersion {}
+
+impl PartialOrd for OsVersion {
+    fn partial_cmp(&self, other: &OsVersion) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for OsVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let components: Vec<String> = self.0.iter().map(|c| c.to_string()).collect();
+        write!(f, "{}", components.join("."))
+    }
+}
+
+// All files below a directory, sorted, or the path itself when it is a file.
+pub fn list_files(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = vec![];
+    for entry in fs::read_dir(path)? {
+        files.extend(list_files(&entry?.path())?);
+    }
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,274 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{data::Context, raw_model::ParseError};
+
+use super::{list_files, OsVersion};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariantKind {
+    // objects-<version>+.nib, loaded from that iOS version on.
+    Objects,
+    // runtime.nib, loaded when no objects variant applies.
+    Runtime,
+    // keyedobjects.nib, written by older versions of Xcode.
+    KeyedObjects,
+    // designable.nib, the XIB source kept for Interface Builder.
+    Designable,
+    // objects.nib, a typedstream written before keyed archiving.
+    ArchivedObjects,
+    // classes.nib, Interface Builder class descriptions.
+    Classes,
+    // info.nib, Interface Builder document settings.
+    Info,
+    // A plain .nib file instead of a directory.
+    Single,
+    Other,
+}
+
+#[derive(Debug, Clone)]
+pub struct NibVariant {
+    pub name: String,
+    pub path: PathBuf,
+    pub kind: VariantKind,
+    // Minimum iOS version of an objects-<version>+.nib variant.
+    pub min_version: Option<OsVersion>,
+    // Device modifier such as "ipad" from a "~ipad" suffix.
+    pub device: Option<String>,
+    pub data: Vec<u8>,
+}
+
+impl NibVariant {
+    fn new(path: PathBuf, data: Vec<u8>) -> NibVariant {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let stem = name.strip_suffix(".nib").unwrap_or(&name);
+        let (stem, device) = match stem.split_once('~') {
+            Some((stem, device)) => (stem, Some(device.to_string())),
+            None => (stem, None),
+        };
+
+        let min_version = stem
+            .strip_prefix("objects-")
+            .and_then(|version| version.strip_suffix('+'))
+            .and_then(OsVersion::parse);
+        let kind = match stem {
+            _ if min_version.is_some() => VariantKind::Objects,
+            "runtime" => VariantKind::Runtime,
+            "keyedobjects" => VariantKind::KeyedObjects,
+            "designable" => VariantKind::Designable,
+            "objects" => VariantKind::ArchivedObjects,
+            "classes" => VariantKind::Classes,
+            "info" => VariantKind::Info,
+            _ => VariantKind::Other,
+        };
+
+        NibVariant {
+            name,
+            path,
+            kind,
+            min_version,
+            device,
+            data,
+        }
+    }
+
+    // Whether UIKit loads this variant at runtime at all.
+    pub fn is_runtime(&self) -> bool {
+        matches!(
+            self.kind,
+            VariantKind::Objects
+                | VariantKind::Runtime
+                | VariantKind::KeyedObjects
+                | VariantKind::ArchivedObjects
+                | VariantKind::Single
+        )
+    }
+
+    // Decodes the variant. The designable variant holds XIB source and is
+    // compiled instead.
+    pub fn parse(&self) -> Result<Context, ParseError> {
+        Context::from_buffer(self.data.clone())
+    }
+}
+
+// A compiled nib, either a single file or a directory with one nib per
+// deployment target.
+#[derive(Debug, Clone)]
+pub struct NibBundle {
+    pub path: PathBuf,
+    pub variants: Vec<NibVariant>,
+}
+
+impl NibBundle {
+    pub fn open(path: &Path) -> io::Result<NibBundle> {
+        let mut files = vec![];
+        for file in list_files(path)? {
+            let data = fs::read(&file)?;
+            files.push((file, data));
+        }
+        Ok(NibBundle::from_files(path, files))
+    }
+
+    // Builds a bundle from files already in memory, such as entries of an
+    // .ipa. A single file at `path` itself is a plain nib; otherwise the
+    // .nib files directly inside `path` are its variants.
+    pub fn from_files(path: &Path, files: Vec<(PathBuf, Vec<u8>)>) -> NibBundle {
+        let mut variants = vec![];
+        for (file, data) in files {
+            if file == path {
+                let mut variant = NibVariant::new(file, data);
+                variant.kind = VariantKind::Single;
+                variants.push(variant);
+            } else if file.parent() == Some(path)
+                && file.extension().is_some_and(|extension| extension == "nib")
+            {
+                variants.push(NibVariant::new(file, data));
+            }
+        }
+        variants.sort_by(|a, b| a.name.cmp(&b.name));
+        NibBundle {
+            path: path.to_path_buf(),
+            variants,
+        }
+    }
+
+    // The variant UIKit loads on the given iOS version, or on the newest
+    // version when none is given. The newest objects variant the version
+    // satisfies wins, then runtime.nib, keyedobjects.nib and the typedstream
+    // objects.nib. Variants for another device are only used when nothing
+    // generic applies.
+    pub fn select(&self, version: Option<&OsVersion>, device: Option<&str>) -> Option<&NibVariant> {
+        let applies = |variant: &NibVariant| match (&variant.device, device) {
+            (None, _) => true,
+            (Some(a), Some(b)) => a == b,
+            (Some(_), None) => false,
+        };
+        self.select_where(version, &applies)
+            .or_else(|| self.select_where(version, &|_| true))
+    }
+
+    fn select_where(
+        &self,
+        version: Option<&OsVersion>,
+        applies: &dyn Fn(&NibVariant) -> bool,
+    ) -> Option<&NibVariant> {
+        let preference =
+            |variant: &&NibVariant| (variant.device.is_some() as u8, variant.name.clone());
+
+        let mut objects: Vec<&NibVariant> = self
+            .variants
+            .iter()
+            .filter(|variant| variant.kind == VariantKind::Objects)
+            .filter(|variant| applies(variant))
+            .filter(|variant| match (version, &variant.min_version) {
+                (Some(version), Some(minimum)) => minimum <= version,
+                _ => true,
+            })
+            .collect();
+        // Newest version first, device specific before generic.
+        objects.sort_by(|a, b| {
+            b.min_version
+                .cmp(&a.min_version)
+                .then(preference(b).cmp(&preference(a)))
+        });
+        if let Some(variant) = objects.first() {
+            return Some(variant);
+        }
+
+        for kind in [
+            VariantKind::Runtime,
+            VariantKind::KeyedObjects,
+            VariantKind::ArchivedObjects,
+            VariantKind::Single,
+        ] {
+            let mut candidates: Vec<&NibVariant> = self
+                .variants
+                .iter()
+                .filter(|variant| variant.kind == kind)
+                .filter(|variant| applies(variant))
+                .collect();
+            candidates.sort_by_key(|variant| std::cmp::Reverse(preference(variant)));
+            if let Some(variant) = candidates.first() {
+                return Some(variant);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::bundle::{
+        nib::{NibBundle, NibVariant, VariantKind},
+        OsVersion,
+    };
+
+    fn bundle(names: &[&str]) -> NibBundle {
+        NibBundle {
+            path: PathBuf::from("Foo.nib"),
+            variants: names
+                .iter()
+                .map(|name| NibVariant::new(PathBuf::from("Foo.nib").join(name), vec![]))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn variant_names() {
+        let variant = NibVariant::new(PathBuf::from("objects-13.0+~ipad.nib"), vec![]);
+        assert_eq!(VariantKind::Objects, variant.kind);
+        assert_eq!(Some(OsVersion(vec![13, 0])), variant.min_version);
+        assert_eq!(Some(OsVersion(vec![13])), variant.min_version);
+        assert_ne!(Some(OsVersion(vec![13, 0, 1])), variant.min_version);
+        assert_eq!(Some("ipad"), variant.device.as_deref());
+        assert!(!NibVariant::new(PathBuf::from("designable.nib"), vec![]).is_runtime());
+    }
+
+    #[test]
+    fn selection() {
+        let bundle = bundle(&[
+            "runtime.nib",
+            "objects-8.0+.nib",
+            "objects-13.0+.nib",
+            "objects-13.0+~ipad.nib",
+            "designable.nib",
+        ]);
+        let select = |version: &str, device: Option<&str>| {
+            let version = OsVersion::parse(version).unwrap();
+            bundle
+                .select(Some(&version), device)
+                .map(|v| v.name.as_str())
+        };
+        assert_eq!(Some("runtime.nib"), select("7.1", None));
+        assert_eq!(Some("objects-8.0+.nib"), select("12.4", None));
+        assert_eq!(Some("objects-13.0+.nib"), select("13", None));
+        assert_eq!(Some("objects-13.0+~ipad.nib"), select("17.2", Some("ipad")));
+        assert_eq!(
+            Some("objects-13.0+.nib"),
+            bundle.select(None, None).map(|v| v.name.as_str())
+        );
+
+        let legacy = self::bundle(&["keyedobjects.nib", "classes.nib"]);
+        assert_eq!(
+            Some("keyedobjects.nib"),
+            legacy.select(None, None).map(|v| v.name.as_str())
+        );
+        let archived = self::bundle(&["classes.nib", "info.nib", "objects.nib"]);
+        assert_eq!(
+            Some("objects.nib"),
+            archived.select(None, None).map(|v| v.name.as_str())
+        );
+        let ipad = self::bundle(&["runtime~ipad.nib"]);
+        assert_eq!(
+            Some("runtime~ipad.nib"),
+            ipad.select(None, Some("iphone")).map(|v| v.name.as_str())
+        );
+    }
+}
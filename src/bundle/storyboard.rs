@@ -0,0 +1,273 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    plist::{self, Plist},
+    raw_model::ParseError,
+};
+
+use super::{list_files, nib::NibBundle};
+
+pub const INFO_PLIST: &str = "Info.plist";
+pub const NIB_NAMES_KEY: &str = "UIViewControllerIdentifiersToNibNames";
+pub const ENTRY_POINT_KEY: &str = "UIStoryboardDesignatedEntryPointIdentifier";
+pub const VERSION_KEY: &str = "UIStoryboardVersion";
+
+// A view controller of a storyboard, archived in its own nib. Its view is
+// usually split off into separate "<id>-view-<view id>.nib" nibs that are
+// only loaded when the view is first accessed.
+#[derive(Debug)]
+pub struct Scene {
+    pub identifier: String,
+    pub nib_name: String,
+    pub is_initial: bool,
+    // None when the nib named in Info.plist is missing.
+    pub nib: Option<NibBundle>,
+    pub view_nibs: Vec<NibBundle>,
+}
+
+impl Scene {
+    // Class of the view controller: the first top-level object of the scene
+    // nib that is not a placeholder.
+    pub fn view_controller_class(&self) -> Option<String> {
+        let context = self.nib.as_ref()?.select(None, None)?.parse().ok()?;
+        let top_level = context
+            .find_value(0, "UINibTopLevelObjectsKey")?
+            .object_reference()?;
+        context
+            .collection_items(top_level as usize)
+            .into_iter()
+            .filter_map(|item| context.class_name(item as usize))
+            .find(|class| class != "UIProxyObject")
+    }
+}
+
+// A compiled .storyboardc directory.
+#[derive(Debug)]
+pub struct Storyboard {
+    pub path: PathBuf,
+    pub version: Option<i64>,
+    // Identifier of the initial view controller, if the storyboard has one.
+    pub initial: Option<String>,
+    pub scenes: Vec<Scene>,
+    // Nibs in the directory that belong to no scene.
+    pub other_nibs: Vec<NibBundle>,
+}
+
+impl Storyboard {
+    pub fn open(path: &Path) -> io::Result<Storyboard> {
+        let mut files = vec![];
+        for file in list_files(path)? {
+            let data = fs::read(&file)?;
+            files.push((file, data));
+        }
+        Storyboard::from_files(path, files)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    // Builds a storyboard from files already in memory, such as entries of
+    // an .ipa. Files below a "<name>.nib" directory make up one nib.
+    pub fn from_files(
+        path: &Path,
+        files: Vec<(PathBuf, Vec<u8>)>,
+    ) -> Result<Storyboard, ParseError> {
+        let mut info = None;
+        let mut groups: BTreeMap<PathBuf, Vec<(PathBuf, Vec<u8>)>> = BTreeMap::new();
+        for (file, data) in files {
+            let Ok(relative) = file.strip_prefix(path) else {
+                continue;
+            };
+            if relative == Path::new(INFO_PLIST) {
+                info = Some(data);
+            } else if let Some(first) = relative.components().next() {
+                if Path::new(&first).extension().is_some_and(|e| e == "nib") {
+                    groups
+                        .entry(path.join(first))
+                        .or_default()
+                        .push((file, data));
+                }
+            }
+        }
+
+        let info = info.ok_or_else(|| ParseError::new(0, 0, "Info.plist is missing"))?;
+        let info = plist::from_buffer(&info)?;
+        let nibs = groups
+            .into_iter()
+            .map(|(root, files)| NibBundle::from_files(&root, files))
+            .collect();
+        Ok(Storyboard::from_info(path, &info, nibs))
+    }
+
+    fn from_info(path: &Path, info: &Plist, mut nibs: Vec<NibBundle>) -> Storyboard {
+        let initial = info
+            .get(ENTRY_POINT_KEY)
+            .and_then(Plist::as_str)
+            .map(str::to_string);
+        let version = match info.get(VERSION_KEY) {
+            Some(Plist::Integer { value, .. }) => Some(*value),
+            _ => None,
+        };
+
+        let mut scenes: Vec<Scene> = info
+            .get(NIB_NAMES_KEY)
+            .and_then(Plist::as_dictionary)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|(identifier, nib_name)| {
+                let nib_name = nib_name.as_str()?.to_string();
+                // Several identifiers may share a nib.
+                let nib = position(&nibs, |name| name == nib_name).map(|i| nibs[i].clone());
+                Some(Scene {
+                    is_initial: initial.as_deref() == Some(identifier.as_str()),
+                    identifier: identifier.to_owned(),
+                    nib_name,
+                    nib,
+                    view_nibs: vec![],
+                })
+            })
+            .collect();
+        scenes.sort_by(|a, b| (!a.is_initial, &a.identifier).cmp(&(!b.is_initial, &b.identifier)));
+
+        // "UIViewController-BYZ-38-t0r" owns "BYZ-38-t0r-view-8bC-Xf-vdC".
+        for scene in &mut scenes {
+            let Some((_, object_id)) = scene.nib_name.split_once('-') else {
+                continue;
+            };
+            let prefix = format!("{}-view-", object_id);
+            while let Some(i) = position(&nibs, |name| name.starts_with(&prefix)) {
+                scene.view_nibs.push(nibs.remove(i));
+            }
+        }
+        nibs.retain(|nib| {
+            !scenes
+                .iter()
+                .any(|scene| scene.nib.as_ref().is_some_and(|used| used.path == nib.path))
+        });
+
+        Storyboard {
+            path: path.to_path_buf(),
+            version,
+            initial,
+            scenes,
+            other_nibs: nibs,
+        }
+    }
+
+    pub fn initial_scene(&self) -> Option<&Scene> {
+        self.scenes.iter().find(|scene| scene.is_initial)
+    }
+}
+
+// Position of the first nib whose name without extension matches.
+fn position(nibs: &[NibBundle], matches: impl Fn(&str) -> bool) -> Option<usize> {
+    nibs.iter().position(|nib| {
+        nib.path
+            .file_stem()
+            .is_some_and(|stem| matches(&stem.to_string_lossy()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use crate::{
+        bundle::storyboard::{Storyboard, ENTRY_POINT_KEY, NIB_NAMES_KEY},
+        plist::{bplist, Plist},
+        raw_model::{builder::ContextBuilder, nib::NibFile},
+    };
+
+    #[test]
+    fn scenes() {
+        let path = std::env::temp_dir().join(format!("scenes-{}.storyboardc", std::process::id()));
+        fs::create_dir_all(&path).unwrap();
+
+        let info = Plist::Dictionary(vec![
+            (
+                ENTRY_POINT_KEY.to_string(),
+                Plist::String("UIViewController-BYZ-38-t0r".to_string()),
+            ),
+            (
+                NIB_NAMES_KEY.to_string(),
+                Plist::Dictionary(vec![
+                    (
+                        "UIViewController-BYZ-38-t0r".to_string(),
+                        Plist::String("UIViewController-BYZ-38-t0r".to_string()),
+                    ),
+                    (
+                        "login".to_string(),
+                        Plist::String("UIViewController-BYZ-38-t0r".to_string()),
+                    ),
+                    (
+                        "settings".to_string(),
+                        Plist::String("settings-Abc-12-xyz".to_string()),
+                    ),
+                ]),
+            ),
+        ]);
+        fs::write(path.join("Info.plist"), bplist::to_buffer(&info)).unwrap();
+
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let array = builder.reserve_object();
+        let owner = builder.reserve_object();
+        let controller = builder.reserve_object();
+        let values = vec![builder.object_value("UINibTopLevelObjectsKey", array)];
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, values);
+        let values = vec![
+            builder.bool_value("NSInlinedValue", true),
+            builder.object_value("UINibEncoderEmptyKey", owner),
+            builder.object_value("UINibEncoderEmptyKey", controller),
+        ];
+        let class = builder.class("NSArray", &[]);
+        builder.set_object(array, class, values);
+        let class = builder.class("UIProxyObject", &[]);
+        builder.set_object(owner, class, vec![]);
+        let class = builder.class("LoginViewController", &[]);
+        builder.set_object(controller, class, vec![]);
+        let nib = NibFile::from_context(&builder.build().unwrap());
+
+        for name in [
+            "UIViewController-BYZ-38-t0r.nib",
+            "BYZ-38-t0r-view-8bC-Xf-vdC.nib",
+            "orphan.nib",
+        ] {
+            fs::write(path.join(name), nib.buffer()).unwrap();
+        }
+
+        let storyboard = Storyboard::open(&path);
+        fs::remove_dir_all(&path).unwrap();
+        let storyboard = storyboard.unwrap();
+
+        let initial = storyboard.initial_scene().unwrap();
+        assert_eq!("UIViewController-BYZ-38-t0r", initial.identifier);
+        assert_eq!(
+            Some("LoginViewController".to_string()),
+            initial.view_controller_class()
+        );
+        assert_eq!(
+            vec![path.join("BYZ-38-t0r-view-8bC-Xf-vdC.nib")],
+            initial
+                .view_nibs
+                .iter()
+                .map(|nib| nib.path.clone())
+                .collect::<Vec<PathBuf>>()
+        );
+
+        let login = &storyboard.scenes[1];
+        assert_eq!("login", login.identifier);
+        assert_eq!(
+            Some(path.join("UIViewController-BYZ-38-t0r.nib")),
+            login.nib.as_ref().map(|nib| nib.path.clone())
+        );
+
+        let settings = &storyboard.scenes[2];
+        assert_eq!("settings", settings.identifier);
+        assert!(settings.nib.is_none());
+        assert_eq!(1, storyboard.other_nibs.len());
+    }
+}
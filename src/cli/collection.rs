@@ -0,0 +1,308 @@
+use std::{io::Write, panic, path::Path, time::Instant};
+
+use crate::{
+    batch::{self, BatchOptions, OutputFormat, Status, Summary},
+    bundle::{
+        app::{Inventory, ItemKind},
+        nib::NibBundle,
+        storyboard::Storyboard,
+        OsVersion,
+    },
+    export::node::Node,
+    format::detect_format,
+};
+
+use super::{map, CliError, Command, Invocation, Opt, EXIT_INPUT, EXIT_OK, EXIT_PROBLEMS};
+
+pub const IDENTIFY: Command = Command {
+    name: "identify",
+    args: "<input>...",
+    summary: "Print the detected format of every input",
+    arity: (1, None),
+    formats: &["text", "json", "yaml"],
+    options: &[],
+    run: identify,
+};
+
+pub const BUNDLE: Command = Command {
+    name: "bundle",
+    args: "<input.nib>",
+    summary: "List the variants of a nib directory and mark the one UIKit loads",
+    arity: (1, Some(1)),
+    formats: &[],
+    options: &[
+        Opt {
+            name: "--ios",
+            value: Some("<version>"),
+            help: "Select for this iOS version instead of the newest",
+        },
+        Opt {
+            name: "--device",
+            value: Some("<name>"),
+            help: "Select for a device such as ipad",
+        },
+    ],
+    run: bundle,
+};
+
+pub const STORYBOARD: Command = Command {
+    name: "storyboard",
+    args: "<input.storyboardc>",
+    summary: "List the scenes of a compiled storyboard and their nibs",
+    arity: (1, Some(1)),
+    formats: &[],
+    options: &[],
+    run: storyboard,
+};
+
+pub const INVENTORY: Command = Command {
+    name: "inventory",
+    args: "<App.ipa | App.app>",
+    summary: "Decode every nib and storyboard of an app, exiting with 1 on errors",
+    arity: (1, Some(1)),
+    formats: &[],
+    options: &[],
+    run: inventory,
+};
+
+pub const BATCH: Command = Command {
+    name: "batch",
+    args: "<directory>",
+    summary: "Decode every nib below a directory in parallel, exiting with 1 on failures",
+    arity: (1, Some(1)),
+    // With --output, the format the decoded files are written in.
+    formats: &["json", "yaml", "tree"],
+    options: &[Opt {
+        name: "--jobs",
+        value: Some("<n>"),
+        help: "Number of worker threads, by default one per CPU",
+    }],
+    run: run_batch,
+};
+
+fn identify(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let mut rows = vec![];
+    let mut code = EXIT_OK;
+    for path in invocation.args.clone() {
+        match invocation.read(&path) {
+            Ok(buffer) => rows.push((path, Ok(detect_format(&buffer).to_string()))),
+            Err(error) => {
+                code = EXIT_INPUT;
+                rows.push((path, Err(error.message)));
+            }
+        }
+    }
+
+    let node = Node::Seq(
+        rows.iter()
+            .map(|(path, result)| {
+                let (key, value) = match result {
+                    Ok(format) => ("format", format),
+                    Err(error) => ("error", error),
+                };
+                map(vec![
+                    ("path", Node::String(path.to_owned())),
+                    (key, Node::String(value.to_owned())),
+                ])
+            })
+            .collect(),
+    );
+    invocation.report(&node, || {
+        rows.iter()
+            .map(|(path, result)| match result {
+                Ok(format) => format!("{}: {}\n", path, format),
+                Err(error) => format!("{}: {}\n", path, error),
+            })
+            .collect()
+    })?;
+    Ok(code)
+}
+
+fn bundle(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let version = match invocation.value("--ios") {
+        Some(version) => Some(OsVersion::parse(version).ok_or(CliError::usage(format!(
+            "--ios expects a version such as 13.0, not {}",
+            version
+        )))?),
+        None => None,
+    };
+    let device = invocation.value("--device").map(str::to_string);
+    let path = invocation.args[0].clone();
+
+    let bundle = NibBundle::open(Path::new(&path))
+        .map_err(|e| CliError::input(format!("Unable to read {}: {}", path, e)))?;
+    let selected = bundle.select(version.as_ref(), device.as_deref());
+    let mut text = String::new();
+    for variant in &bundle.variants {
+        let status = match variant.parse() {
+            Ok(context) => format!("{} objects", context.objects.len()),
+            Err(error) => format!("error: {}", error),
+        };
+        text.push_str(&format!(
+            "{} {:<28} {:<15} {:<8} {:<8} {}\n",
+            if selected.is_some_and(|s| std::ptr::eq(s, variant)) {
+                "*"
+            } else {
+                " "
+            },
+            variant.name,
+            format!("{:?}", variant.kind),
+            variant
+                .min_version
+                .as_ref()
+                .map(|v| format!("{}+", v))
+                .unwrap_or("-".to_string()),
+            variant.device.as_deref().unwrap_or("-"),
+            status
+        ));
+    }
+    invocation.emit(text.as_bytes())?;
+    Ok(EXIT_OK)
+}
+
+fn storyboard(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let path = invocation.args[0].clone();
+    let storyboard = Storyboard::open(Path::new(&path))
+        .map_err(|e| CliError::input(format!("Unable to read {}: {}", path, e)))?;
+
+    let mut text = String::new();
+    if let Some(version) = storyboard.version {
+        text.push_str(&format!("Storyboard version {}\n", version));
+    }
+    for scene in &storyboard.scenes {
+        let class = scene.view_controller_class().unwrap_or("?".to_string());
+        let nib = match &scene.nib {
+            Some(_) => format!("{}.nib", scene.nib_name),
+            None => format!("{}.nib (missing)", scene.nib_name),
+        };
+        text.push_str(&format!(
+            "{} {:<40} {:<28} {}\n",
+            if scene.is_initial { "*" } else { " " },
+            scene.identifier,
+            class,
+            nib
+        ));
+        for view_nib in &scene.view_nibs {
+            text.push_str(&format!("    {}\n", view_nib.path.display()));
+        }
+    }
+    for nib in &storyboard.other_nibs {
+        text.push_str(&format!("  (no scene) {}\n", nib.path.display()));
+    }
+    invocation.emit(text.as_bytes())?;
+    Ok(EXIT_OK)
+}
+
+fn inventory(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let path = invocation.args[0].clone();
+    let inventory = Inventory::open(Path::new(&path))
+        .map_err(|e| CliError::input(format!("Unable to read {}: {}", path, e)))?;
+
+    let mut text = String::new();
+    for item in &inventory.items {
+        text.push_str(&format!(
+            "{} [{}{}]\n",
+            item.path.display(),
+            match item.kind {
+                ItemKind::Nib => "nib",
+                ItemKind::Storyboard => "storyboard",
+            },
+            item.locale
+                .as_ref()
+                .map(|locale| format!(", {}", locale))
+                .unwrap_or_default()
+        ));
+        for file in &item.files {
+            let name = match file.path.strip_prefix(&item.path) {
+                Ok(name) if !name.as_os_str().is_empty() => name,
+                _ => Path::new(file.path.file_name().unwrap_or_default()),
+            };
+            text.push_str(&match &file.result {
+                Ok(objects) => format!("  ok    {} ({} objects)\n", name.display(), objects),
+                Err(error) => format!("  error {}: {}\n", name.display(), error),
+            });
+        }
+    }
+    let count = |kind| {
+        inventory
+            .items
+            .iter()
+            .filter(|item| item.kind == kind)
+            .count()
+    };
+    text.push_str(&format!(
+        "{} nibs, {} storyboards, {} files decoded, {} errors\n",
+        count(ItemKind::Nib),
+        count(ItemKind::Storyboard),
+        inventory.file_count() - inventory.error_count(),
+        inventory.error_count()
+    ));
+    invocation.emit(text.as_bytes())?;
+    Ok(if inventory.error_count() > 0 {
+        EXIT_PROBLEMS
+    } else {
+        EXIT_OK
+    })
+}
+
+fn run_batch(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let mut options = BatchOptions {
+        output: invocation.output.as_ref().map(Into::into),
+        ..Default::default()
+    };
+    if let Some(jobs) = invocation.number("--jobs")? {
+        options.jobs = jobs;
+    }
+    if let Some(format) = OutputFormat::parse(invocation.format) {
+        options.format = format;
+    }
+    let path = invocation.args[0].clone();
+
+    // Panics are reported per file instead of on stderr. Progress goes to
+    // standard output since --output names the directory decoded files are
+    // written to.
+    panic::set_hook(Box::new(|_| {}));
+    let start = Instant::now();
+    let stdout = &mut invocation.io.stdout;
+    let reports = batch::run(Path::new(&path), &options, |report| {
+        let path = report.path.display();
+        let _ = match &report.status {
+            Status::Ok { objects } => writeln!(stdout, "ok       {} ({} objects)", path, objects),
+            Status::Warnings { objects, warnings } => {
+                let _ = writeln!(stdout, "warning  {} ({} objects)", path, objects);
+                warnings
+                    .iter()
+                    .try_for_each(|warning| writeln!(stdout, "         {}", warning))
+            }
+            Status::Error {
+                offset: Some(offset),
+                reason,
+            } => writeln!(stdout, "error    {}: {} at offset {}", path, reason, offset),
+            Status::Error {
+                offset: None,
+                reason,
+            } => writeln!(stdout, "error    {}: {}", path, reason),
+            Status::Panic(message) => writeln!(stdout, "panic    {}: {}", path, message),
+        };
+    });
+    let _ = panic::take_hook();
+    let reports =
+        reports.map_err(|e| CliError::input(format!("Unable to read {}: {}", path, e)))?;
+
+    let summary = Summary::of(&reports);
+    let _ = writeln!(
+        invocation.io.stdout,
+        "{} files in {:.2}s: {} ok, {} with warnings, {} errors, {} panics",
+        summary.total(),
+        start.elapsed().as_secs_f64(),
+        summary.ok,
+        summary.warnings,
+        summary.errors,
+        summary.panics
+    );
+    Ok(if summary.failed() {
+        EXIT_PROBLEMS
+    } else {
+        EXIT_OK
+    })
+}
@@ -0,0 +1,317 @@
+use std::{fs, path::Path};
+
+use crate::{
+    convert::{encode, Encoding},
+    export::{
+        dot::{to_dot, DotOptions},
+        html::to_html,
+        json,
+        node::Node,
+        toml::to_toml,
+        yaml::to_yaml,
+    },
+    extract::{blobs, Kind},
+    format::{detect_format, Format},
+    search::object_paths,
+    xib::{compile::compile_xml, decompile::decompile},
+};
+
+use super::{decode, map, CliError, Command, Invocation, Opt, EXIT_OK};
+
+pub const CONVERT: Command = Command {
+    name: "convert",
+    args: "<input> [<output>]",
+    summary: "Re-encode a NIBArchive as a keyed archive, or the other way around",
+    arity: (1, Some(2)),
+    // The default depends on the input.
+    formats: &["auto", "nib", "keyed"],
+    options: &[],
+    run: convert,
+};
+
+pub const DOT: Command = Command {
+    name: "dot",
+    args: "<input> [<output.dot>]",
+    summary: "Export the object graph for Graphviz",
+    arity: (1, Some(2)),
+    formats: &[],
+    options: &[
+        Opt {
+            name: "--collapse-collections",
+            value: None,
+            help: "Draw collection items as children of the collection's owner",
+        },
+        Opt {
+            name: "--collapse-strings",
+            value: None,
+            help: "Show strings inside the label of the referencing object",
+        },
+        Opt {
+            name: "--root",
+            value: Some("<index>"),
+            help: "Start at another object than the root",
+        },
+        Opt {
+            name: "--depth",
+            value: Some("<n>"),
+            help: "Follow at most n references from the root",
+        },
+        Opt {
+            name: "--highlight-connections",
+            value: None,
+            help: "Highlight outlet and action connections",
+        },
+    ],
+    run: dot,
+};
+
+pub const HTML: Command = Command {
+    name: "html",
+    args: "<input> [<output.html>]",
+    summary: "Write a self-contained HTML report of the objects and their bytes",
+    arity: (1, Some(2)),
+    formats: &[],
+    options: &[],
+    run: html,
+};
+
+pub const YAML: Command = Command {
+    name: "yaml",
+    args: "<input> [<output.yaml>]",
+    summary: "Export the decoded objects as YAML, the same as dump --format yaml",
+    arity: (1, Some(2)),
+    formats: &[],
+    options: &[],
+    run: document,
+};
+
+pub const TOML: Command = Command {
+    name: "toml",
+    args: "<input> [<output.toml>]",
+    summary: "Export the decoded objects as TOML, the same as dump --format toml",
+    arity: (1, Some(2)),
+    formats: &[],
+    options: &[],
+    run: document,
+};
+
+pub const COMPILE: Command = Command {
+    name: "compile",
+    args: "<input.xib> [<output.nib>]",
+    summary: "Compile a XIB into a NIBArchive",
+    arity: (1, Some(2)),
+    formats: &[],
+    options: &[],
+    run: compile,
+};
+
+pub const DECOMPILE: Command = Command {
+    name: "decompile",
+    args: "<input> [<output.xib>]",
+    summary: "Regenerate an Interface Builder document from a compiled nib",
+    arity: (1, Some(2)),
+    formats: &[],
+    options: &[],
+    run: decompile_nib,
+};
+
+pub const EXTRACT: Command = Command {
+    name: "extract",
+    args: "<input> <directory>",
+    summary:
+        "Write every data value to a file named after its object and key, with a manifest.json",
+    arity: (2, Some(2)),
+    formats: &[],
+    options: &[Opt {
+        name: "--kind",
+        value: Some("<kinds>"),
+        help: "Comma separated kinds to extract: png, jpeg, pdf, bplist, nib, utf8 or data",
+    }],
+    run: extract,
+};
+
+// Binary output is only written to a file or pipe.
+fn check_binary_output(invocation: &Invocation) -> Result<(), CliError> {
+    if invocation.to_terminal() {
+        return Err(CliError::usage(
+            "Refusing to write binary output to a terminal, give an output path",
+        ));
+    }
+    Ok(())
+}
+
+fn convert(invocation: &mut Invocation) -> Result<i32, CliError> {
+    invocation.output_from_arg(1)?;
+    check_binary_output(invocation)?;
+    let path = invocation.args[0].clone();
+    let buffer = invocation.read_nib(&path)?;
+    let encoding = match invocation.format {
+        "nib" => Encoding::NibArchive,
+        "keyed" => Encoding::KeyedArchive,
+        _ => Encoding::of(&buffer).other(),
+    };
+    let context = decode(&path, buffer)?;
+    let output = encode(&context, encoding)
+        .map_err(|e| CliError::input(format!("Unable to encode {}: {}", path, e)))?;
+    invocation.emit(&output)?;
+    let message = format!(
+        "Converted {} to {:?} in {}",
+        path,
+        encoding,
+        invocation.destination()
+    );
+    invocation.status(message);
+    Ok(EXIT_OK)
+}
+
+fn dot(invocation: &mut Invocation) -> Result<i32, CliError> {
+    invocation.output_from_arg(1)?;
+    let options = DotOptions {
+        collapse_collections: invocation.flag("--collapse-collections"),
+        collapse_strings: invocation.flag("--collapse-strings"),
+        highlight_connections: invocation.flag("--highlight-connections"),
+        root: invocation.number("--root")?,
+        max_depth: invocation.number("--depth")?,
+    };
+    let path = invocation.args[0].clone();
+    let context = invocation.load(&path)?;
+    invocation.emit(to_dot(&context, &options).as_bytes())?;
+    exported(invocation, &path);
+    Ok(EXIT_OK)
+}
+
+fn html(invocation: &mut Invocation) -> Result<i32, CliError> {
+    invocation.output_from_arg(1)?;
+    let path = invocation.args[0].clone();
+    let context = invocation.load(&path)?;
+    invocation.emit(to_html(&context, &path).as_bytes())?;
+    exported(invocation, &path);
+    Ok(EXIT_OK)
+}
+
+fn document(invocation: &mut Invocation) -> Result<i32, CliError> {
+    invocation.output_from_arg(1)?;
+    let path = invocation.args[0].clone();
+    let context = invocation.load(&path)?;
+    let output = match invocation.command.name {
+        "yaml" => to_yaml(&context),
+        _ => to_toml(&context),
+    }
+    .map_err(|reason| CliError::problems(format!("Unable to export {}: {}", path, reason)))?;
+    invocation.emit(output.as_bytes())?;
+    exported(invocation, &path);
+    Ok(EXIT_OK)
+}
+
+fn exported(invocation: &mut Invocation, path: &str) {
+    if invocation.output.is_some() {
+        let message = format!("Exported {} to {}", path, invocation.destination());
+        invocation.status(message);
+    }
+}
+
+fn extract(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let kinds = match invocation.value("--kind") {
+        None => Kind::ALL.to_vec(),
+        Some(names) => names
+            .split(',')
+            .map(|name| {
+                Kind::from_name(name.trim()).ok_or_else(|| {
+                    CliError::usage(format!(
+                        "Unknown kind {}, expected png, jpeg, pdf, bplist, nib, utf8 or data",
+                        name
+                    ))
+                })
+            })
+            .collect::<Result<_, _>>()?,
+    };
+    let path = invocation.args[0].clone();
+    let directory = Path::new(&invocation.args[1]).to_path_buf();
+    let context = invocation.load(&path)?;
+    let paths = object_paths(&context);
+
+    let unable = |path: &Path, e: std::io::Error| {
+        CliError::output(format!("Unable to write {}: {}", path.display(), e))
+    };
+    fs::create_dir_all(&directory).map_err(|e| unable(&directory, e))?;
+    let mut manifest = vec![];
+    for blob in blobs(&context) {
+        if !kinds.contains(&blob.kind) {
+            continue;
+        }
+        let file = directory.join(&blob.file);
+        fs::write(&file, blob.bytes).map_err(|e| unable(&file, e))?;
+        let object_path = paths[blob.object]
+            .clone()
+            .unwrap_or(format!("#{}", blob.object));
+        manifest.push(map(vec![
+            ("file", Node::String(blob.file)),
+            ("object", Node::Integer(blob.object as i64)),
+            (
+                "class",
+                Node::String(context.class_name(blob.object).unwrap_or_default()),
+            ),
+            ("path", Node::String(object_path)),
+            ("key", Node::String(blob.key)),
+            ("kind", Node::String(blob.kind.name().to_string())),
+            ("length", Node::Integer(blob.bytes.len() as i64)),
+        ]));
+    }
+    let count = manifest.len();
+    let file = directory.join("manifest.json");
+    fs::write(&file, json::from_node(&Node::Seq(manifest))).map_err(|e| unable(&file, e))?;
+    invocation.status(format!(
+        "Extracted {} values from {} to {}",
+        count,
+        path,
+        directory.display()
+    ));
+    Ok(EXIT_OK)
+}
+
+fn compile(invocation: &mut Invocation) -> Result<i32, CliError> {
+    invocation.output_from_arg(1)?;
+    check_binary_output(invocation)?;
+    let path = invocation.args[0].clone();
+    let input = invocation.read(&path)?;
+    match detect_format(&input) {
+        Format::Xib => {}
+        format => {
+            return Err(CliError::input(format!(
+                "{} is a {}, not a XIB",
+                path, format
+            )))
+        }
+    }
+    let (output, warnings) = compile_xml(&String::from_utf8_lossy(&input))
+        .map_err(|e| CliError::input(format!("Unable to compile {}: {}", path, e)))?;
+    invocation.emit(&output)?;
+
+    for warning in &warnings {
+        invocation.warn(warning);
+    }
+    invocation.status(format!(
+        "Compiled {} with {} warnings",
+        path,
+        warnings.len()
+    ));
+    Ok(EXIT_OK)
+}
+
+fn decompile_nib(invocation: &mut Invocation) -> Result<i32, CliError> {
+    invocation.output_from_arg(1)?;
+    let path = invocation.args[0].clone();
+    let context = invocation.load(&path)?;
+    let decompilation = decompile(&context);
+    invocation.emit(decompilation.document.to_string().as_bytes())?;
+
+    for warning in &decompilation.warnings {
+        invocation.warn(warning);
+    }
+    invocation.status(format!(
+        "Decompiled {} with {} warnings",
+        path,
+        decompilation.warnings.len()
+    ));
+    Ok(EXIT_OK)
+}
@@ -0,0 +1,638 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    export::{
+        json::to_json,
+        node::Node,
+        toml::to_toml,
+        tree::{to_tree, TreeOptions},
+        yaml::to_yaml,
+    },
+    format::{detect_format, Format},
+    hexdump::{annotate, regions, RegionKind},
+    raw_model::{
+        nib::{NibFile, SectionKind, HEADER_SIZE},
+        raw_value::type_name,
+    },
+    stats::Stats,
+    validate::{self, Finding, Severity},
+};
+
+use super::{decode, map, CliError, Command, Invocation, Opt, EXIT_INPUT, EXIT_OK, EXIT_PROBLEMS};
+
+pub const INFO: Command = Command {
+    name: "info",
+    args: "<input>",
+    summary: "Print the format, header and section layout of a nib",
+    arity: (1, Some(1)),
+    formats: &["text", "json", "yaml"],
+    options: &[],
+    run: info,
+};
+
+pub const HEXDUMP: Command = Command {
+    name: "hexdump",
+    args: "<input>",
+    summary: "Print the bytes of a NIBArchive by header field and record, with gaps, exiting with 1 where it does not decode",
+    arity: (1, Some(1)),
+    formats: &["text", "json", "yaml"],
+    options: &[],
+    run: hexdump,
+};
+
+pub const DUMP: Command = Command {
+    name: "dump",
+    args: "<input>",
+    summary: "Print the decoded object graph",
+    arity: (1, Some(1)),
+    formats: &["json", "yaml", "toml"],
+    options: &[],
+    run: dump,
+};
+
+pub const TREE: Command = Command {
+    name: "tree",
+    args: "<input>",
+    summary: "Print the decoded objects as a tree, colored on a terminal",
+    arity: (1, Some(1)),
+    formats: &[],
+    options: &[
+        Opt {
+            name: "--depth",
+            value: Some("<n>"),
+            help: "Stop descending after n levels",
+        },
+        Opt {
+            name: "--root",
+            value: Some("<index>"),
+            help: "Start at another object than the root",
+        },
+        Opt {
+            name: "--no-collapse",
+            value: None,
+            help: "Print boilerplate objects such as UIColor and UIFont in full",
+        },
+        Opt {
+            name: "--color",
+            value: None,
+            help: "Always use colors",
+        },
+        Opt {
+            name: "--no-color",
+            value: None,
+            help: "Never use colors",
+        },
+    ],
+    run: tree,
+};
+
+pub const CLASSES: Command = Command {
+    name: "classes",
+    args: "<input>",
+    summary: "Count the objects of every class",
+    arity: (1, Some(1)),
+    formats: &["text", "json", "yaml"],
+    options: &[],
+    run: classes,
+};
+
+pub const KEYS: Command = Command {
+    name: "keys",
+    args: "<input>",
+    summary: "Count the values stored under every key",
+    arity: (1, Some(1)),
+    formats: &["text", "json", "yaml"],
+    options: &[],
+    run: keys,
+};
+
+pub const STATS: Command = Command {
+    name: "stats",
+    args: "<input>...",
+    summary: "Summarize classes, keys, value types and sizes across nibs and directories",
+    arity: (1, None),
+    formats: &["text", "json", "yaml"],
+    options: &[Opt {
+        name: "--top",
+        value: Some("<n>"),
+        help: "Print at most n classes, keys, value types and largest values, 20 by default",
+    }],
+    run: stats,
+};
+
+pub const VALIDATE: Command = Command {
+    name: "validate",
+    args: "<input>...",
+    summary: "Check indexes, value ranges, references and unused entries, exiting with 1 on errors",
+    arity: (1, None),
+    formats: &["text", "json", "yaml"],
+    options: &[Opt {
+        name: "--fail-on",
+        value: Some("<severity>"),
+        help: "Exit with 1 on findings of this severity or worse: error (the default), warning or note",
+    }],
+    run: validate,
+};
+
+fn info(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let path = invocation.args[0].clone();
+    let buffer = invocation.read_nib(&path)?;
+    let format = detect_format(&buffer);
+    let size = buffer.len();
+    // The header and section layout of a NIBArchive.
+    let layout = match format {
+        Format::NibArchive { .. } => {
+            let nib = NibFile::from_buffer(buffer.clone())
+                .and_then(|nib| Ok((nib.version(), nib.sections()?, nib.gaps()?, nib.trailing()?)))
+                .map_err(|e| CliError::input(format!("Unable to decode {}: {}", path, e)))?;
+            Some(nib)
+        }
+        _ => None,
+    };
+    let context = decode(&path, buffer)?;
+
+    let mut entries = vec![
+        ("path", Node::String(path.clone())),
+        ("format", Node::String(format.to_string())),
+        ("size", Node::Integer(size as i64)),
+    ];
+    if let Some((version, sections, gaps, trailing)) = &layout {
+        entries.push((
+            "header",
+            map(vec![
+                ("size", Node::Integer(HEADER_SIZE as i64)),
+                (
+                    "version",
+                    Node::Seq(vec![
+                        Node::Integer(version.0 as i64),
+                        Node::Integer(version.1 as i64),
+                    ]),
+                ),
+            ]),
+        ));
+        entries.push((
+            "sections",
+            Node::Seq(
+                sections
+                    .iter()
+                    .map(|section| {
+                        map(vec![
+                            ("name", Node::String(section.kind.name().to_string())),
+                            ("offset", Node::Integer(section.offset as i64)),
+                            ("count", Node::Integer(section.count as i64)),
+                            ("length", Node::Integer(section.length as i64)),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ));
+        entries.push((
+            "gaps",
+            Node::Seq(
+                gaps.iter()
+                    .map(|gap| {
+                        map(vec![
+                            ("offset", Node::Integer(gap.start as i64)),
+                            ("length", Node::Integer(gap.len() as i64)),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ));
+        entries.push(("trailing", Node::Integer(trailing.len() as i64)));
+    }
+    entries.extend([
+        ("objects", Node::Integer(context.objects.len() as i64)),
+        ("keys", Node::Integer(context.keys.len() as i64)),
+        ("values", Node::Integer(context.values.len() as i64)),
+        ("classes", Node::Integer(context.classes.len() as i64)),
+    ]);
+
+    invocation.report(&map(entries), || {
+        let mut text = format!(
+            "Path:     {}\nFormat:   {}\nSize:     {} bytes\n",
+            path, format, size
+        );
+        let Some((_, sections, gaps, trailing)) = &layout else {
+            text.push_str(&format!(
+                "Objects:  {}\nKeys:     {}\nValues:   {}\nClasses:  {}\n",
+                context.objects.len(),
+                context.keys.len(),
+                context.values.len(),
+                context.classes.len()
+            ));
+            return text;
+        };
+        text.push_str(&format!("Header:   {} bytes\n\n", HEADER_SIZE));
+        text.push_str("Section     Offset    Count    Bytes\n");
+        for section in sections {
+            text.push_str(&format!(
+                "{:<8} {:>9} {:>8} {:>8}\n",
+                section.kind.name(),
+                section.offset,
+                section.count,
+                section.length
+            ));
+        }
+        text.push('\n');
+        if gaps.is_empty() {
+            text.push_str("Gaps:     none\n");
+        }
+        for gap in gaps {
+            text.push_str(&format!(
+                "Gap:      {} bytes at {}..{}\n",
+                gap.len(),
+                gap.start,
+                gap.end
+            ));
+        }
+        text.push_str(&format!("Trailing: {} bytes\n", trailing.len()));
+        text
+    })?;
+    Ok(EXIT_OK)
+}
+
+fn hexdump(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let path = invocation.args[0].clone();
+    let buffer = invocation.read_nib(&path)?;
+    let regions = regions(&buffer);
+    let broken = regions
+        .iter()
+        .any(|region| matches!(region.kind, RegionKind::Invalid(_)));
+
+    let node = Node::Seq(
+        regions
+            .iter()
+            .map(|region| {
+                let mut entries = vec![
+                    ("offset", Node::Integer(region.range.start as i64)),
+                    ("length", Node::Integer(region.range.len() as i64)),
+                    ("region", Node::String(region.label())),
+                ];
+                if region.overlaps {
+                    entries.push(("overlaps", Node::Bool(true)));
+                }
+                if !region.fields.is_empty() {
+                    let fields = region
+                        .fields
+                        .iter()
+                        .map(|field| {
+                            map(vec![
+                                ("offset", Node::Integer(field.range.start as i64)),
+                                ("length", Node::Integer(field.range.len() as i64)),
+                                ("field", Node::String(field.name.to_string())),
+                                ("value", Node::String(field.value.clone())),
+                            ])
+                        })
+                        .collect();
+                    entries.push(("fields", Node::Seq(fields)));
+                }
+                map(entries)
+            })
+            .collect(),
+    );
+    invocation.report(&node, || annotate(&buffer))?;
+    Ok(if broken { EXIT_PROBLEMS } else { EXIT_OK })
+}
+
+fn dump(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let path = invocation.args[0].clone();
+    let context = invocation.load(&path)?;
+    let output = match invocation.format {
+        "yaml" => to_yaml(&context),
+        "toml" => to_toml(&context),
+        _ => to_json(&context),
+    }
+    .map_err(|reason| {
+        CliError::problems(format!(
+            "Unable to export {} as {}: {}",
+            path, invocation.format, reason
+        ))
+    })?;
+    invocation.emit(output.as_bytes())?;
+    Ok(EXIT_OK)
+}
+
+fn tree(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let mut options = TreeOptions {
+        color: invocation.to_terminal(),
+        max_depth: invocation.number("--depth")?,
+        ..Default::default()
+    };
+    if invocation.flag("--no-collapse") {
+        options.collapse.clear();
+    }
+    if invocation.flag("--color") {
+        options.color = true;
+    }
+    if invocation.flag("--no-color") {
+        options.color = false;
+    }
+    let root = invocation.number("--root")?.unwrap_or(0);
+
+    let path = invocation.args[0].clone();
+    let context = invocation.load(&path)?;
+    if root >= context.objects.len() && !context.objects.is_empty() {
+        return Err(CliError::usage(format!(
+            "--root {} is out of range, the archive has {} objects",
+            root,
+            context.objects.len()
+        )));
+    }
+    invocation.emit(to_tree(&context, root, &options).as_bytes())?;
+    Ok(EXIT_OK)
+}
+
+// Rows sorted by descending count, then by name.
+fn ranked(counts: BTreeMap<String, usize>) -> Vec<(String, usize)> {
+    let mut rows: Vec<(String, usize)> = counts.into_iter().collect();
+    rows.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    rows
+}
+
+fn counts_node(label: &str, rows: &[(String, usize)], count: &str) -> Node {
+    Node::Seq(
+        rows.iter()
+            .map(|(name, value)| {
+                map(vec![
+                    (label, Node::String(name.to_owned())),
+                    (count, Node::Integer(*value as i64)),
+                ])
+            })
+            .collect(),
+    )
+}
+
+fn counts_text(rows: &[(String, usize)]) -> String {
+    rows.iter()
+        .map(|(name, count)| format!("{:>7}  {}\n", count, name))
+        .collect()
+}
+
+fn counts_report(
+    invocation: &mut Invocation,
+    label: &str,
+    rows: &[(String, usize)],
+) -> Result<(), CliError> {
+    let node = counts_node(label, rows, "count");
+    invocation.report(&node, || counts_text(rows))
+}
+
+fn classes(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let path = invocation.args[0].clone();
+    let context = invocation.load(&path)?;
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for index in 0..context.objects.len() {
+        let class = context.class_name(index).unwrap_or("<unknown>".to_string());
+        *counts.entry(class).or_default() += 1;
+    }
+    counts_report(invocation, "class", &ranked(counts))?;
+    Ok(EXIT_OK)
+}
+
+fn keys(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let path = invocation.args[0].clone();
+    let context = invocation.load(&path)?;
+    // Unused keys are listed with a count of zero.
+    let mut counts: BTreeMap<String, usize> = context
+        .keys
+        .iter()
+        .map(|key| (String::from_utf8_lossy(&key.key_bytes).into_owned(), 0))
+        .collect();
+    for value in &context.values {
+        let key = context.key_name(value).unwrap_or("<unknown>".to_string());
+        *counts.entry(key).or_default() += 1;
+    }
+    counts_report(invocation, "key", &ranked(counts))?;
+    Ok(EXIT_OK)
+}
+
+fn stats(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let top: usize = invocation.number("--top")?.unwrap_or(20);
+    let mut code = EXIT_OK;
+
+    let files = invocation.inputs(0)?;
+    let mut stats = Stats {
+        largest_count: top,
+        ..Stats::default()
+    };
+    for path in &files {
+        let result = invocation.read_nib(path).and_then(|buffer| {
+            stats
+                .add(path, buffer)
+                .map_err(|e| CliError::input(format!("Unable to decode {}: {}", path, e)))
+        });
+        if let Err(error) = result {
+            invocation.warn(format!("{}, skipped", error.message));
+            code = EXIT_INPUT;
+        }
+    }
+
+    let classes = ranked(stats.classes.clone());
+    let keys = ranked(stats.keys.clone());
+    let types: Vec<(String, usize)> = ranked(
+        stats
+            .value_types
+            .iter()
+            .map(|(value_type, count)| (type_name(*value_type).to_string(), *count))
+            .collect(),
+    );
+    let sections: Vec<(String, usize)> = [
+        SectionKind::Objects,
+        SectionKind::Keys,
+        SectionKind::Values,
+        SectionKind::Classes,
+    ]
+    .iter()
+    .filter_map(|kind| {
+        let bytes = stats.sections.get(kind.name())?;
+        Some((kind.name().to_string(), *bytes))
+    })
+    .collect();
+
+    let node = map(vec![
+        ("files", Node::Integer(stats.files as i64)),
+        ("bytes", Node::Integer(stats.bytes as i64)),
+        ("objects", Node::Integer(stats.objects as i64)),
+        ("values", Node::Integer(stats.values as i64)),
+        ("values_per_object", Node::Float(stats.values_per_object())),
+        ("max_reference_depth", Node::Integer(stats.max_depth as i64)),
+        (
+            "deepest_file",
+            stats.deepest.clone().map_or(Node::Null, Node::String),
+        ),
+        ("section_bytes", counts_node("section", &sections, "bytes")),
+        ("classes", counts_node("class", &classes, "count")),
+        ("keys", counts_node("key", &keys, "count")),
+        ("value_types", counts_node("type", &types, "count")),
+        (
+            "largest",
+            Node::Seq(
+                stats
+                    .largest
+                    .iter()
+                    .map(|blob| {
+                        map(vec![
+                            ("file", Node::String(blob.file.clone())),
+                            ("object", Node::Integer(blob.object as i64)),
+                            ("key", Node::String(blob.key.clone())),
+                            ("length", Node::Integer(blob.length as i64)),
+                            ("string", Node::Bool(blob.is_string)),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ),
+    ]);
+    invocation.report(&node, || {
+        let mut text = format!(
+            "Files:   {} ({} bytes)\nObjects: {}\nValues:  {}\nValues per object: {:.2}\n",
+            stats.files,
+            stats.bytes,
+            stats.objects,
+            stats.values,
+            stats.values_per_object()
+        );
+        if let Some(file) = &stats.deepest {
+            text.push_str(&format!(
+                "Maximum reference depth: {} ({})\n",
+                stats.max_depth, file
+            ));
+        }
+        if !sections.is_empty() {
+            text.push_str("\nSection bytes:\n");
+            text.push_str(&counts_text(&sections));
+        }
+        for (title, rows) in [
+            ("Classes", &classes),
+            ("Keys", &keys),
+            ("Value types", &types),
+        ] {
+            if rows.is_empty() {
+                continue;
+            }
+            text.push_str(&format!("\n{}:\n", title));
+            text.push_str(&counts_text(&rows[..rows.len().min(top)]));
+            if rows.len() > top {
+                text.push_str(&format!("{:>7}  more\n", rows.len() - top));
+            }
+        }
+        if !stats.largest.is_empty() {
+            text.push_str("\nLargest strings and data:\n");
+        }
+        for blob in &stats.largest {
+            text.push_str(&format!(
+                "{:>7}  {:<6}  {} object {} {}\n",
+                blob.length,
+                if blob.is_string { "string" } else { "data" },
+                blob.file,
+                blob.object,
+                blob.key
+            ));
+        }
+        text
+    })?;
+    Ok(code)
+}
+
+fn validate(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let fail_on = match invocation.value("--fail-on") {
+        None => Severity::Error,
+        Some(name) => Severity::from_name(name).ok_or_else(|| {
+            CliError::usage(format!(
+                "--fail-on expects error, warning or note, not {}",
+                name
+            ))
+        })?,
+    };
+
+    let mut code = EXIT_OK;
+    let mut files = 0;
+    let mut findings: Vec<(String, Finding)> = vec![];
+    for path in invocation.inputs(0)? {
+        match invocation.load(&path) {
+            Ok(context) => {
+                files += 1;
+                findings.extend(
+                    validate::validate(&context)
+                        .into_iter()
+                        .map(|f| (path.clone(), f)),
+                );
+            }
+            Err(error) => {
+                invocation.warn(format!("{}, skipped", error.message));
+                code = EXIT_INPUT;
+            }
+        }
+    }
+    let count = |severity: Severity| {
+        findings
+            .iter()
+            .filter(|(_, finding)| finding.severity == severity)
+            .count()
+    };
+    let failed = findings
+        .iter()
+        .any(|(_, finding)| finding.severity >= fail_on);
+
+    let node = map(vec![
+        ("files", Node::Integer(files as i64)),
+        ("valid", Node::Bool(!failed)),
+        (
+            "counts",
+            map(Severity::ALL
+                .iter()
+                .map(|severity| (severity.name(), Node::Integer(count(*severity) as i64)))
+                .collect()),
+        ),
+        (
+            "findings",
+            Node::Seq(
+                findings
+                    .iter()
+                    .map(|(file, finding)| {
+                        let mut entries = vec![
+                            ("file", Node::String(file.clone())),
+                            (
+                                "severity",
+                                Node::String(finding.severity.name().to_string()),
+                            ),
+                            ("check", Node::String(finding.check.to_string())),
+                        ];
+                        if let Some((kind, index)) = finding.entry {
+                            entries.push(("table", Node::String(kind.name().to_string())));
+                            entries.push(("index", Node::Integer(index as i64)));
+                        }
+                        entries.push(("message", Node::String(finding.message.clone())));
+                        map(entries)
+                    })
+                    .collect(),
+            ),
+        ),
+    ]);
+    invocation.report(&node, || {
+        let mut text: String = findings
+            .iter()
+            .map(|(file, finding)| {
+                format!(
+                    "{}: {}: {} [{}]\n",
+                    file,
+                    finding.severity.name(),
+                    finding.message,
+                    finding.check
+                )
+            })
+            .collect();
+        let counts: Vec<String> = Severity::ALL
+            .iter()
+            .map(|severity| format!("{} {}s", count(*severity), severity.name()))
+            .collect();
+        text += &format!("{} files checked: {}\n", files, counts.join(", "));
+        text
+    })?;
+
+    if code == EXIT_OK && failed {
+        code = EXIT_PROBLEMS;
+    }
+    Ok(code)
+}
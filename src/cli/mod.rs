@@ -0,0 +1,629 @@
+use std::{
+    fs,
+    io::{Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use crate::{
+    batch::find_nibs,
+    bundle::nib::NibBundle,
+    data::Context,
+    export::{json, node::Node, yaml},
+    format::{detect_format, Format},
+};
+
+mod collection;
+mod export;
+mod inspect;
+mod search;
+mod shell;
+
+pub const PROGRAM: &str = "nib-parser";
+
+pub const EXIT_OK: i32 = 0;
+// The command ran and found problems, such as files that failed to decode.
+pub const EXIT_PROBLEMS: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+// An input could not be read or decoded.
+pub const EXIT_INPUT: i32 = 3;
+// The output could not be written.
+pub const EXIT_OUTPUT: i32 = 4;
+
+#[derive(Debug)]
+pub struct CliError {
+    pub code: i32,
+    pub message: String,
+}
+
+impl CliError {
+    pub fn usage(message: impl Into<String>) -> CliError {
+        CliError {
+            code: EXIT_USAGE,
+            message: message.into(),
+        }
+    }
+
+    pub fn input(message: impl Into<String>) -> CliError {
+        CliError {
+            code: EXIT_INPUT,
+            message: message.into(),
+        }
+    }
+
+    pub fn output(message: impl Into<String>) -> CliError {
+        CliError {
+            code: EXIT_OUTPUT,
+            message: message.into(),
+        }
+    }
+
+    pub fn problems(message: impl Into<String>) -> CliError {
+        CliError {
+            code: EXIT_PROBLEMS,
+            message: message.into(),
+        }
+    }
+}
+
+// The standard streams, which tests replace with buffers.
+pub struct Io<'a> {
+    pub stdin: &'a mut dyn Read,
+    pub stdout: &'a mut dyn Write,
+    pub stderr: &'a mut dyn Write,
+    // Whether standard output is a terminal, which enables colors.
+    pub terminal: bool,
+}
+
+pub struct Opt {
+    pub name: &'static str,
+    // Placeholder of the value the option takes, if any.
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+}
+
+pub struct Command {
+    pub name: &'static str,
+    pub args: &'static str,
+    pub summary: &'static str,
+    // Smallest and largest number of positional arguments.
+    pub arity: (usize, Option<usize>),
+    // What --format accepts, the default first. Empty when the command has
+    // a single kind of output.
+    pub formats: &'static [&'static str],
+    pub options: &'static [Opt],
+    run: fn(&mut Invocation) -> Result<i32, CliError>,
+}
+
+const GLOBAL_OPTIONS: &[Opt] = &[
+    Opt {
+        name: "--format",
+        value: Some("<format>"),
+        help: "Output format, one of the formats the command lists",
+    },
+    Opt {
+        name: "--output",
+        value: Some("<path>"),
+        help: "Write the output to a file instead of standard output",
+    },
+    Opt {
+        name: "--quiet",
+        value: None,
+        help: "Print no status messages or warnings",
+    },
+    Opt {
+        name: "--help",
+        value: None,
+        help: "Show help for the command",
+    },
+];
+
+const SHORT_OPTIONS: &[(&str, &str)] = &[
+    ("-f", "--format"),
+    ("-o", "--output"),
+    ("-q", "--quiet"),
+    ("-h", "--help"),
+    ("-i", "--ignore-case"),
+];
+
+const COMMANDS: &[Command] = &[
+    inspect::INFO,
+    inspect::HEXDUMP,
+    inspect::DUMP,
+    inspect::TREE,
+    inspect::CLASSES,
+    inspect::KEYS,
+    inspect::STATS,
+    inspect::VALIDATE,
+    search::GREP,
+    search::QUERY,
+    search::REFS,
+    search::PATH,
+    shell::SHELL,
+    export::CONVERT,
+    export::DOT,
+    export::HTML,
+    export::EXTRACT,
+    export::YAML,
+    export::TOML,
+    export::COMPILE,
+    export::DECOMPILE,
+    collection::IDENTIFY,
+    collection::BUNDLE,
+    collection::STORYBOARD,
+    collection::INVENTORY,
+    collection::BATCH,
+];
+
+// A parsed command line.
+pub struct Invocation<'a> {
+    pub command: &'static Command,
+    pub args: Vec<String>,
+    options: Vec<(&'static str, Option<String>)>,
+    pub format: &'static str,
+    pub output: Option<String>,
+    pub quiet: bool,
+    pub io: Io<'a>,
+}
+
+// Runs a command line, without the program name, and returns the exit code.
+// A path or option instead of a command dumps, as earlier versions did.
+pub fn run(args: &[String], mut io: Io) -> i32 {
+    let Some(name) = args.first() else {
+        let _ = write!(io.stderr, "{}", usage());
+        return finish(io, EXIT_USAGE);
+    };
+    if matches!(name.as_str(), "help" | "--help" | "-h") {
+        let code = help(args.get(1), &mut io);
+        return finish(io, code);
+    }
+
+    let (command, rest) = match find(name) {
+        Some(command) => (command, &args[1..]),
+        None if name.starts_with('-') || Path::new(name).exists() => (&inspect::DUMP, args),
+        None => {
+            let _ = writeln!(
+                io.stderr,
+                "error: Unknown command {}\nRun '{} help' for a list of commands.",
+                name, PROGRAM
+            );
+            return finish(io, EXIT_USAGE);
+        }
+    };
+    let mut invocation = match parse(command, rest, io) {
+        Ok(invocation) => invocation,
+        Err((error, io)) => {
+            let _ = writeln!(
+                io.stderr,
+                "error: {}\nRun '{} help {}' for usage.",
+                error.message, PROGRAM, command.name
+            );
+            return finish(io, error.code);
+        }
+    };
+
+    let code = if invocation.flag("--help") {
+        let _ = write!(invocation.io.stdout, "{}", command_usage(command));
+        EXIT_OK
+    } else {
+        match (command.run)(&mut invocation) {
+            Ok(code) => code,
+            Err(error) => {
+                let _ = writeln!(invocation.io.stderr, "error: {}", error.message);
+                if error.code == EXIT_USAGE {
+                    let _ = writeln!(
+                        invocation.io.stderr,
+                        "Run '{} help {}' for usage.",
+                        PROGRAM, command.name
+                    );
+                }
+                error.code
+            }
+        }
+    };
+    finish(invocation.io, code)
+}
+
+fn help(name: Option<&String>, io: &mut Io) -> i32 {
+    let Some(name) = name else {
+        let _ = write!(io.stdout, "{}", usage());
+        return EXIT_OK;
+    };
+    match find(name) {
+        Some(command) => {
+            let _ = write!(io.stdout, "{}", command_usage(command));
+            EXIT_OK
+        }
+        None => {
+            let _ = writeln!(io.stderr, "error: Unknown command {}", name);
+            EXIT_USAGE
+        }
+    }
+}
+
+fn finish(io: Io, code: i32) -> i32 {
+    let _ = io.stdout.flush();
+    code
+}
+
+fn find(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|command| command.name == name)
+}
+
+fn parse<'a>(
+    command: &'static Command,
+    args: &[String],
+    io: Io<'a>,
+) -> Result<Invocation<'a>, (CliError, Io<'a>)> {
+    let mut invocation = Invocation {
+        command,
+        args: vec![],
+        options: vec![],
+        format: command.formats.first().copied().unwrap_or("text"),
+        output: None,
+        quiet: false,
+        io,
+    };
+
+    let mut args = args.iter();
+    let mut positional_only = false;
+    while let Some(arg) = args.next() {
+        if positional_only || arg == "-" || !arg.starts_with('-') {
+            invocation.args.push(arg.to_owned());
+            continue;
+        }
+        if arg == "--" {
+            positional_only = true;
+            continue;
+        }
+
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let name = SHORT_OPTIONS
+            .iter()
+            .find(|(short, _)| *short == name)
+            .map_or(name, |(_, long)| long);
+        let Some(option) = GLOBAL_OPTIONS
+            .iter()
+            .chain(command.options)
+            .find(|option| option.name == name)
+        else {
+            let error = CliError::usage(format!("Unknown option {} for {}", arg, command.name));
+            return Err((error, invocation.io));
+        };
+
+        let value = match (option.value, inline) {
+            (None, None) => None,
+            (None, Some(_)) => {
+                let error = CliError::usage(format!("{} takes no value", option.name));
+                return Err((error, invocation.io));
+            }
+            (Some(_), Some(value)) => Some(value),
+            (Some(placeholder), None) => match args.next() {
+                Some(value) => Some(value.to_owned()),
+                None => {
+                    let error = CliError::usage(format!("{} expects {}", option.name, placeholder));
+                    return Err((error, invocation.io));
+                }
+            },
+        };
+        invocation.options.push((option.name, value));
+    }
+
+    if let Some(format) = invocation.value("--format").map(str::to_string) {
+        match command.formats.iter().find(|known| **known == format) {
+            Some(known) => invocation.format = known,
+            None if command.formats.is_empty() => {
+                let error = CliError::usage(format!("{} has no --format", command.name));
+                return Err((error, invocation.io));
+            }
+            None => {
+                let error = CliError::usage(format!(
+                    "Unknown format {}, expected {}",
+                    format,
+                    command.formats.join(", ")
+                ));
+                return Err((error, invocation.io));
+            }
+        }
+    }
+    invocation.output = invocation.value("--output").map(str::to_string);
+    invocation.quiet = invocation.flag("--quiet");
+
+    if !invocation.flag("--help") {
+        let (min, max) = command.arity;
+        if invocation.args.len() < min {
+            let error = CliError::usage(format!("Missing arguments, expected {}", command.args));
+            return Err((error, invocation.io));
+        }
+        if let Some(extra) = max.and_then(|max| invocation.args.get(max)) {
+            let error = CliError::usage(format!("Unexpected argument {}", extra));
+            return Err((error, invocation.io));
+        }
+    }
+    Ok(invocation)
+}
+
+fn usage() -> String {
+    let mut text = format!(
+        "Usage: {} <command> [options] <arguments>\n\nCommands:\n",
+        PROGRAM
+    );
+    for command in COMMANDS {
+        text.push_str(&format!("  {:<12} {}\n", command.name, command.summary));
+    }
+    text.push_str("\nOptions:\n");
+    text.push_str(&options_help(GLOBAL_OPTIONS));
+    text.push_str(&format!(
+        "\nInputs may be - for standard input. Run '{} help <command>' for the\n\
+         arguments, options and formats of a command.\n\n\
+         Exit codes: {} success, {} problems found, {} usage error, {} unreadable\n\
+         input, {} unwritable output.\n",
+        PROGRAM, EXIT_OK, EXIT_PROBLEMS, EXIT_USAGE, EXIT_INPUT, EXIT_OUTPUT
+    ));
+    text
+}
+
+fn command_usage(command: &Command) -> String {
+    let mut text = format!(
+        "Usage: {} {} {} [options]\n\n{}\n",
+        PROGRAM, command.name, command.args, command.summary
+    );
+    if !command.formats.is_empty() {
+        text.push_str(&format!(
+            "\nFormats: {} (default {})\n",
+            command.formats.join(", "),
+            command.formats[0]
+        ));
+    }
+    text.push_str("\nOptions:\n");
+    text.push_str(&options_help(command.options));
+    text.push_str(&options_help(GLOBAL_OPTIONS));
+    text
+}
+
+fn options_help(options: &[Opt]) -> String {
+    let mut text = String::new();
+    for option in options {
+        let short = SHORT_OPTIONS
+            .iter()
+            .find(|(_, long)| *long == option.name)
+            .map_or("   ".to_string(), |(short, _)| format!("{},", short));
+        let name = match option.value {
+            Some(value) => format!("{} {}", option.name, value),
+            None => option.name.to_string(),
+        };
+        text.push_str(&format!("  {} {:<24} {}\n", short, name, option.help));
+    }
+    text
+}
+
+impl Invocation<'_> {
+    pub fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|(option, _)| *option == name)
+    }
+
+    // The value of the last use of an option.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(option, _)| *option == name)
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    pub fn number<T: FromStr>(&self, name: &str) -> Result<Option<T>, CliError> {
+        match self.value(name) {
+            None => Ok(None),
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| CliError::usage(format!("{} expects a number, not {}", name, value))),
+        }
+    }
+
+    // Takes the output path from a positional argument, the way commands
+    // that always wrote a file were called.
+    pub fn output_from_arg(&mut self, position: usize) -> Result<(), CliError> {
+        if let Some(path) = self.args.get(position) {
+            if self.output.is_some() {
+                return Err(CliError::usage(
+                    "Both an output argument and --output given",
+                ));
+            }
+            self.output = Some(path.to_owned());
+        }
+        Ok(())
+    }
+
+    // Whether the output goes to a terminal rather than a file or pipe.
+    pub fn to_terminal(&self) -> bool {
+        self.io.terminal && self.output.as_deref().is_none_or(|path| path == "-")
+    }
+
+    // Reads a file, or standard input for "-".
+    pub fn read(&mut self, path: &str) -> Result<Vec<u8>, CliError> {
+        let mut buffer = vec![];
+        if path == "-" {
+            self.io
+                .stdin
+                .read_to_end(&mut buffer)
+                .map_err(|e| CliError::input(format!("Unable to read standard input: {}", e)))?;
+            return Ok(buffer);
+        }
+        fs::read(path).map_err(|e| CliError::input(format!("Unable to read {}: {}", path, e)))
+    }
+
+    // Reads a file in any format the crate decodes, or the variant of a .nib
+    // directory the newest iOS version would load.
+    pub fn read_nib(&mut self, path: &str) -> Result<Vec<u8>, CliError> {
+        if path == "-" || !Path::new(path).is_dir() {
+            return self.read(path);
+        }
+        if path.trim_end_matches('/').ends_with(".storyboardc") {
+            return Err(CliError::input(format!(
+                "{} is a compiled storyboard, list its scenes with storyboard",
+                path
+            )));
+        }
+        let bundle = NibBundle::open(Path::new(path))
+            .map_err(|e| CliError::input(format!("Unable to read {}: {}", path, e)))?;
+        match bundle.select(None, None) {
+            Some(variant) => Ok(variant.data.clone()),
+            None => Err(CliError::input(format!("{} contains no runtime nib", path))),
+        }
+    }
+
+    // The input paths from a position on, with directories other than
+    // compiled nibs replaced by the nibs below them.
+    pub fn inputs(&self, position: usize) -> Result<Vec<String>, CliError> {
+        let mut files = vec![];
+        for path in self.args.iter().skip(position) {
+            let is_nib = path.trim_end_matches('/').ends_with(".nib");
+            if Path::new(path).is_dir() && !is_nib {
+                let nibs = find_nibs(Path::new(path))
+                    .map_err(|e| CliError::input(format!("Unable to read {}: {}", path, e)))?;
+                files.extend(nibs.iter().map(|nib| nib.display().to_string()));
+            } else {
+                files.push(path.to_owned());
+            }
+        }
+        Ok(files)
+    }
+
+    pub fn load(&mut self, path: &str) -> Result<Context, CliError> {
+        let buffer = self.read_nib(path)?;
+        decode(path, buffer)
+    }
+
+    // Writes to the --output file, or standard output.
+    pub fn emit(&mut self, bytes: &[u8]) -> Result<(), CliError> {
+        match self.output.as_deref() {
+            None | Some("-") => self
+                .io
+                .stdout
+                .write_all(bytes)
+                .map_err(|e| CliError::output(format!("Unable to write output: {}", e))),
+            Some(path) => fs::write(path, bytes)
+                .map_err(|e| CliError::output(format!("Unable to write {}: {}", path, e))),
+        }
+    }
+
+    // Writes a report in the chosen format; `text` renders the text format.
+    pub fn report(&mut self, node: &Node, text: impl FnOnce() -> String) -> Result<(), CliError> {
+        let output = match self.format {
+            "json" => json::from_node(node),
+            "yaml" => yaml::from_node(node),
+            _ => text(),
+        };
+        self.emit(output.as_bytes())
+    }
+
+    // Tells what was written where, unless quiet or writing to stdout.
+    pub fn status(&mut self, message: impl AsRef<str>) {
+        if !self.quiet {
+            let _ = writeln!(self.io.stderr, "{}", message.as_ref());
+        }
+    }
+
+    pub fn warn(&mut self, message: impl AsRef<str>) {
+        if !self.quiet {
+            let _ = writeln!(self.io.stderr, "warning: {}", message.as_ref());
+        }
+    }
+
+    // Where the output went, for status messages.
+    pub fn destination(&self) -> &str {
+        match self.output.as_deref() {
+            None | Some("-") => "standard output",
+            Some(path) => path,
+        }
+    }
+}
+
+pub fn decode(path: &str, buffer: Vec<u8>) -> Result<Context, CliError> {
+    match detect_format(&buffer) {
+        Format::Zip => Err(CliError::input(format!(
+            "{} is a zip archive, list its nibs with inventory",
+            path
+        ))),
+        format => Context::from_buffer(buffer).map_err(|e| {
+            CliError::input(format!("Unable to decode {} as {}: {}", path, format, e))
+        }),
+    }
+}
+
+// Builds a map node from string keys.
+pub fn map(entries: Vec<(&str, Node)>) -> Node {
+    Node::Map {
+        anchor: None,
+        entries: entries
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cli::{run, Io, EXIT_INPUT, EXIT_OK, EXIT_USAGE},
+        raw_model::{builder::ContextBuilder, nib::NibFile},
+    };
+
+    fn invoke(args: &[&str], stdin: &[u8]) -> (i32, String, String) {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let (mut stdout, mut stderr) = (vec![], vec![]);
+        let code = run(
+            &args,
+            Io {
+                stdin: &mut &stdin[..],
+                stdout: &mut stdout,
+                stderr: &mut stderr,
+                terminal: false,
+            },
+        );
+        (
+            code,
+            String::from_utf8(stdout).unwrap(),
+            String::from_utf8(stderr).unwrap(),
+        )
+    }
+
+    #[test]
+    fn commands() {
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let title = builder.int_value("UITag", 7);
+        let class = builder.class("UIView", &[]);
+        builder.set_object(root, class, vec![title]);
+        let nib = NibFile::from_context(&builder.build().unwrap());
+
+        let (code, stdout, _) = invoke(&["info", "--format=json", "-"], nib.buffer());
+        assert_eq!(EXIT_OK, code);
+        assert!(stdout.contains("\"format\": \"NIBArchive (version 1.9)\""));
+        assert!(stdout.contains("\"objects\": 1"));
+
+        let (code, stdout, _) = invoke(&["classes", "-"], nib.buffer());
+        assert_eq!((EXIT_OK, "      1  UIView\n"), (code, stdout.as_str()));
+
+        // A path or - alone is dumped.
+        let (code, stdout, _) = invoke(&["-f", "yaml", "-"], nib.buffer());
+        assert_eq!(EXIT_OK, code);
+        assert!(stdout.contains("UITag: 7"));
+
+        let (code, _, stderr) = invoke(&["info", "--bogus", "-"], nib.buffer());
+        assert_eq!(EXIT_USAGE, code);
+        assert!(stderr.starts_with("error: Unknown option --bogus for info"));
+        assert_eq!(EXIT_USAGE, invoke(&["tree", "-f", "json", "-"], b"").0);
+        assert_eq!(EXIT_USAGE, invoke(&["info"], b"").0);
+        assert_eq!(EXIT_INPUT, invoke(&["info", "-"], b"NIBArchive").0);
+
+        let (code, stdout, _) = invoke(&["help", "tree"], b"");
+        assert_eq!(EXIT_OK, code);
+        assert!(stdout.starts_with("Usage: nib-parser tree <input> [options]"));
+        assert!(stdout.contains("--no-collapse"));
+    }
+}
@@ -0,0 +1,397 @@
+use crate::{
+    data::Context,
+    export::node::Node,
+    query::{self, Query, Scalar, Selected},
+    references::ReferenceIndex,
+    regex::Regex,
+    search::{grep, object_paths, Field, Match},
+};
+
+use super::{map, CliError, Command, Invocation, Opt, EXIT_INPUT, EXIT_OK, EXIT_PROBLEMS};
+
+pub const GREP: Command = Command {
+    name: "grep",
+    args: "<pattern> <input>...",
+    summary: "Search class names, keys and strings with a regular expression, exiting with 1 when nothing matches",
+    arity: (2, None),
+    formats: &["text", "json", "yaml"],
+    options: &[
+        Opt {
+            name: "--ignore-case",
+            value: None,
+            help: "Match letters regardless of case",
+        },
+        Opt {
+            name: "--only",
+            value: Some("<field>"),
+            help: "Search only class, key or string",
+        },
+    ],
+    run: run_grep,
+};
+
+pub const QUERY: Command = Command {
+    name: "query",
+    args: "<expression> <input>...",
+    summary: "Select objects and values with a path expression such as //UIButton[UITag=2]/UIFont, exiting with 1 when nothing is selected",
+    arity: (2, None),
+    formats: &["text", "json", "yaml"],
+    options: &[],
+    run: run_query,
+};
+
+pub const REFS: Command = Command {
+    name: "refs",
+    args: "<object> <input>",
+    summary: "List the objects referring to an object, with their key paths",
+    arity: (2, Some(2)),
+    formats: &["text", "json", "yaml"],
+    options: &[],
+    run: run_refs,
+};
+
+pub const PATH: Command = Command {
+    name: "path",
+    args: "<object> <input>",
+    summary: "Show the shortest chain of references from the root to an object, exiting with 1 when the root does not reach it",
+    arity: (2, Some(2)),
+    formats: &["text", "json", "yaml"],
+    options: &[],
+    run: run_path,
+};
+
+// Characters of context printed on each side of a match.
+const CONTEXT: usize = 30;
+
+const MATCH: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+
+fn run_grep(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let pattern = invocation.args[0].clone();
+    let regex = Regex::new(&pattern)
+        .map_err(|e| {
+            CliError::usage(format!(
+                "Invalid pattern {}: {} at {}",
+                pattern, e.reason, e.offset
+            ))
+        })?
+        .ignore_case(invocation.flag("--ignore-case"));
+    let only = match invocation.value("--only") {
+        None => None,
+        Some("class") => Some(Field::Class),
+        Some("key") => Some(Field::Key),
+        Some("string") => Some(Field::String),
+        Some(other) => {
+            return Err(CliError::usage(format!(
+                "--only expects class, key or string, not {}",
+                other
+            )))
+        }
+    };
+
+    let mut code = EXIT_OK;
+    let mut found: Vec<(String, Match)> = vec![];
+    for path in invocation.inputs(1)? {
+        match invocation.load(&path) {
+            Ok(context) => found.extend(
+                grep(&context, &regex)
+                    .into_iter()
+                    .filter(|m| only.is_none_or(|field| m.field == field))
+                    .map(|m| (path.clone(), m)),
+            ),
+            Err(error) => {
+                invocation.warn(format!("{}, skipped", error.message));
+                code = EXIT_INPUT;
+            }
+        }
+    }
+
+    let node = Node::Seq(
+        found
+            .iter()
+            .map(|(file, m)| {
+                map(vec![
+                    ("file", Node::String(file.clone())),
+                    ("object", Node::Integer(m.object as i64)),
+                    ("path", Node::String(m.path.clone())),
+                    ("field", Node::String(m.field.name().to_string())),
+                    ("text", Node::String(m.text.clone())),
+                    ("match", Node::String(m.text[m.range.clone()].to_string())),
+                ])
+            })
+            .collect(),
+    );
+    let color = invocation.to_terminal();
+    invocation.report(&node, || {
+        found
+            .iter()
+            .map(|(file, m)| {
+                format!(
+                    "{}: {}: {} {}\n",
+                    file,
+                    m.path,
+                    m.field.name(),
+                    excerpt(m, color)
+                )
+            })
+            .collect()
+    })?;
+
+    if code == EXIT_OK && found.is_empty() {
+        code = EXIT_PROBLEMS;
+    }
+    Ok(code)
+}
+
+fn run_query(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let expression = invocation.args[0].clone();
+    let query = Query::parse(&expression).map_err(|e| {
+        CliError::usage(format!(
+            "Invalid query {}: {} at {}",
+            expression, e.reason, e.offset
+        ))
+    })?;
+
+    let mut code = EXIT_OK;
+    // File, key path, object index and what the result holds.
+    let mut found: Vec<(String, String, Option<usize>, Scalar)> = vec![];
+    for path in invocation.inputs(1)? {
+        match invocation.load(&path) {
+            Ok(context) => {
+                let paths = object_paths(&context);
+                found.extend(query.select(&context).into_iter().map(|item| {
+                    let object = match item {
+                        Selected::Object(index) => Some(index),
+                        Selected::Value(_) => None,
+                    };
+                    (
+                        path.clone(),
+                        query::path(&context, &paths, item),
+                        object,
+                        query::scalar(&context, item),
+                    )
+                }))
+            }
+            Err(error) => {
+                invocation.warn(format!("{}, skipped", error.message));
+                code = EXIT_INPUT;
+            }
+        }
+    }
+
+    let node = Node::Seq(
+        found
+            .iter()
+            .map(|(file, path, object, scalar)| {
+                let mut entries = vec![
+                    ("file", Node::String(file.clone())),
+                    ("path", Node::String(path.clone())),
+                ];
+                if let Some(index) = object {
+                    entries.push(("object", Node::Integer(*index as i64)));
+                }
+                entries.push(match scalar {
+                    Scalar::Object(class) => ("class", Node::String(class.clone())),
+                    Scalar::Nil => ("value", Node::Null),
+                    Scalar::Bool(value) => ("value", Node::Bool(*value)),
+                    Scalar::Integer(value) => ("value", Node::Integer(*value)),
+                    Scalar::Float(value) => ("value", Node::Float(*value)),
+                    Scalar::String(text) => ("value", Node::String(text.clone())),
+                    Scalar::Bytes(length) => ("length", Node::Integer(*length as i64)),
+                });
+                map(entries)
+            })
+            .collect(),
+    );
+    invocation.report(&node, || {
+        found
+            .iter()
+            .map(|(file, path, object, scalar)| {
+                let text = match scalar {
+                    Scalar::Object(class) => class.clone(),
+                    Scalar::Nil => "nil".to_string(),
+                    Scalar::Bool(value) => value.to_string(),
+                    Scalar::Integer(value) => value.to_string(),
+                    Scalar::Float(value) => value.to_string(),
+                    Scalar::String(text) => format!("\"{}\"", text.escape_debug()),
+                    Scalar::Bytes(length) => format!("<{} bytes>", length),
+                };
+                match object {
+                    Some(index) => format!("{}: {}: {} #{}\n", file, path, text, index),
+                    None => format!("{}: {}: {}\n", file, path, text),
+                }
+            })
+            .collect()
+    })?;
+
+    if code == EXIT_OK && found.is_empty() {
+        code = EXIT_PROBLEMS;
+    }
+    Ok(code)
+}
+
+// The object index argument, checked against the archive.
+fn object_arg(invocation: &Invocation, context: &Context) -> Result<usize, CliError> {
+    let arg = &invocation.args[0];
+    let index: usize = arg
+        .trim_start_matches('#')
+        .parse()
+        .map_err(|_| CliError::usage(format!("Expected an object index, not {}", arg)))?;
+    if index >= context.objects.len() {
+        return Err(CliError::usage(format!(
+            "Object {} is out of range, the archive has {} objects",
+            index,
+            context.objects.len()
+        )));
+    }
+    Ok(index)
+}
+
+fn class_name(context: &Context, index: usize) -> String {
+    context.class_name(index).unwrap_or("<unknown>".to_string())
+}
+
+fn run_refs(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let path = invocation.args[1].clone();
+    let context = invocation.load(&path)?;
+    let object = object_arg(invocation, &context)?;
+    let index = ReferenceIndex::new(&context);
+    let paths = object_paths(&context);
+    let referrers = index.referrers(object);
+
+    let node = map(vec![
+        ("object", Node::Integer(object as i64)),
+        ("class", Node::String(class_name(&context, object))),
+        (
+            "referrers",
+            Node::Seq(
+                referrers
+                    .iter()
+                    .map(|reference| {
+                        map(vec![
+                            ("object", Node::Integer(reference.from as i64)),
+                            ("class", Node::String(class_name(&context, reference.from))),
+                            ("key", Node::String(reference.key.clone())),
+                            (
+                                "path",
+                                match &paths[reference.from] {
+                                    Some(path) => Node::String(path.clone()),
+                                    None => Node::Null,
+                                },
+                            ),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ),
+    ]);
+    invocation.report(&node, || {
+        let mut text = format!("#{} {}\n", object, class_name(&context, object));
+        if referrers.is_empty() {
+            text += "  no references\n";
+        }
+        for reference in referrers {
+            let path = paths[reference.from]
+                .clone()
+                .unwrap_or("unreachable".to_string());
+            text += &format!(
+                "  #{} {} {} ({})\n",
+                reference.from,
+                class_name(&context, reference.from),
+                reference.segment(),
+                path
+            );
+        }
+        text
+    })?;
+    Ok(EXIT_OK)
+}
+
+fn run_path(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let path = invocation.args[1].clone();
+    let context = invocation.load(&path)?;
+    let object = object_arg(invocation, &context)?;
+    let Some(references) = ReferenceIndex::new(&context).path(object) else {
+        invocation.report(&Node::Null, || {
+            format!(
+                "#{} {} is not reachable from the root\n",
+                object,
+                class_name(&context, object)
+            )
+        })?;
+        return Ok(EXIT_PROBLEMS);
+    };
+
+    let mut steps = vec![(None, 0)];
+    steps.extend(
+        references
+            .iter()
+            .map(|reference| (Some(reference), reference.to)),
+    );
+    let key_path: String = references
+        .iter()
+        .map(|reference| reference.segment())
+        .collect();
+    let node = map(vec![
+        ("path", Node::String(format!("root{}", key_path))),
+        (
+            "steps",
+            Node::Seq(
+                steps
+                    .iter()
+                    .map(|(reference, index)| {
+                        map(vec![
+                            (
+                                "key",
+                                match reference {
+                                    Some(reference) => Node::String(reference.key.clone()),
+                                    None => Node::Null,
+                                },
+                            ),
+                            ("object", Node::Integer(*index as i64)),
+                            ("class", Node::String(class_name(&context, *index))),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ),
+    ]);
+    invocation.report(&node, || {
+        let mut text = format!("root{}\n", key_path);
+        for (reference, index) in &steps {
+            let segment = match reference {
+                Some(reference) => reference.segment(),
+                None => "root".to_string(),
+            };
+            text += &format!(
+                "  {} -> #{} {}\n",
+                segment,
+                index,
+                class_name(&context, *index)
+            );
+        }
+        text
+    })?;
+    Ok(EXIT_OK)
+}
+
+// The match with some context, escaped and quoted.
+fn excerpt(m: &Match, color: bool) -> String {
+    let before = &m.text[..m.range.start];
+    let after = &m.text[m.range.end..];
+    let skip = before.chars().count().saturating_sub(CONTEXT);
+    let before: String = before.chars().skip(skip).collect();
+    let after_len = after.chars().count();
+    let after: String = after.chars().take(CONTEXT).collect();
+    let (start, end) = if color { (MATCH, RESET) } else { ("", "") };
+    format!(
+        "\"{}{}{}{}{}{}{}\"",
+        if skip > 0 { "..." } else { "" },
+        before.escape_debug(),
+        start,
+        m.text[m.range.clone()].escape_debug(),
+        end,
+        after.escape_debug(),
+        if after_len > CONTEXT { "..." } else { "" }
+    )
+}
@@ -0,0 +1,588 @@
+use std::io::{BufRead, BufReader};
+
+use crate::{
+    data::{is_string_class, Context, Data},
+    export::{hex_dump, printable, scalar_text},
+    plist::keyed_archive::EMPTY_KEY,
+    raw_model::{
+        raw_value::{type_name, RawValue, TYPE_DATA},
+        BufferView,
+    },
+    references::ReferenceIndex,
+    regex::Regex,
+    search::{grep, object_paths},
+};
+
+use super::{CliError, Command, Invocation, EXIT_OK};
+
+pub const SHELL: Command = Command {
+    name: "shell",
+    args: "<input>",
+    summary: "Explore the object graph with cd, ls, cat, refs, find and hexdump, reading commands from standard input",
+    arity: (1, Some(1)),
+    formats: &[],
+    options: &[],
+    run: run_shell,
+};
+
+const HELP: &str = "\
+cd [<path>]       go to an object, the root without a path
+ls [<path>]       list the values of an object with their types
+cat <path>        show a value, or an object and its values
+refs [<path>]     list the objects referring to an object
+find <pattern>    search class names, keys and strings
+hexdump <path>    show the bytes of a value, or the value records of an object
+pwd               show the key path of the current object
+back, forward     go through the objects visited before
+help              show this list
+exit              leave the shell
+
+Paths are keys separated by '/', such as UISubviews[0]/UIText, and may
+start with '/' for the root, '..' for the object before or #<n> for the
+object at index n.
+";
+
+fn run_shell(invocation: &mut Invocation) -> Result<i32, CliError> {
+    let path = invocation.args[0].clone();
+    if path == "-" {
+        return Err(CliError::usage(
+            "The shell reads commands from standard input, give the nib as a file",
+        ));
+    }
+    let context = invocation.load(&path)?;
+    let mut shell = Shell::new(&context);
+
+    // Prompts only make sense when someone is typing.
+    let prompt = invocation.io.terminal;
+    let io = &mut invocation.io;
+    let mut input = BufReader::new(&mut *io.stdin);
+    loop {
+        if prompt {
+            let _ = write!(io.stdout, "{}> ", shell.pwd());
+            let _ = io.stdout.flush();
+        }
+        let mut line = String::new();
+        let read = input
+            .read_line(&mut line)
+            .map_err(|e| CliError::input(format!("Unable to read standard input: {}", e)))?;
+        let line = line.trim();
+        if read == 0 || line == "exit" || line == "quit" {
+            break;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        let written = match shell.execute(line) {
+            Ok(output) => io.stdout.write_all(output.as_bytes()),
+            Err(message) => writeln!(io.stderr, "error: {}", message),
+        };
+        written.map_err(|e| CliError::output(format!("Unable to write output: {}", e)))?;
+    }
+    Ok(EXIT_OK)
+}
+
+// What a path leads to.
+enum Target {
+    Object(Vec<Step>),
+    // A value that is not an object reference, by its index in
+    // `Context::values`.
+    Value(usize),
+}
+
+// The key path segment an object was reached through, such as "/UIText",
+// "[0]" or "root", and its index.
+type Step = (String, usize);
+
+struct Shell<'a> {
+    context: &'a Context,
+    references: ReferenceIndex,
+    paths: Vec<Option<String>>,
+    location: Vec<Step>,
+    back: Vec<Vec<Step>>,
+    forward: Vec<Vec<Step>>,
+}
+
+impl<'a> Shell<'a> {
+    fn new(context: &'a Context) -> Shell<'a> {
+        Shell {
+            context,
+            references: ReferenceIndex::new(context),
+            paths: object_paths(context),
+            location: vec![("root".to_string(), 0)],
+            back: vec![],
+            forward: vec![],
+        }
+    }
+
+    fn pwd(&self) -> String {
+        self.location
+            .iter()
+            .map(|(segment, _)| segment.as_str())
+            .collect()
+    }
+
+    fn execute(&mut self, line: &str) -> Result<String, String> {
+        let words = words(line)?;
+        let command = words[0].as_str();
+        let arg = words.get(1).map(String::as_str);
+        if words.len() > 2 {
+            return Err(format!("{} takes at most one argument", command));
+        }
+        match (command, arg) {
+            ("help", None) => Ok(HELP.to_string()),
+            ("pwd", None) => Ok(format!("{}\n", self.pwd())),
+            ("cd", arg) => {
+                let location = match self.resolve(arg.unwrap_or("/"))? {
+                    Target::Object(location) => location,
+                    Target::Value(..) => return Err("Not an object, use cat".to_string()),
+                };
+                if location != self.location {
+                    let previous = std::mem::replace(&mut self.location, location);
+                    self.back.push(previous);
+                    self.forward.clear();
+                }
+                Ok(String::new())
+            }
+            ("back", None) | ("forward", None) => {
+                let (from, to) = match command {
+                    "back" => (&mut self.back, &mut self.forward),
+                    _ => (&mut self.forward, &mut self.back),
+                };
+                let location = from.pop().ok_or(format!("Nothing to go {} to", command))?;
+                to.push(std::mem::replace(&mut self.location, location));
+                Ok(format!("{}\n", self.pwd()))
+            }
+            ("ls", arg) => match self.resolve(arg.unwrap_or("."))? {
+                Target::Object(location) => Ok(self.list(current(&location))),
+                Target::Value(..) => Err("Not an object, use cat".to_string()),
+            },
+            ("cat", Some(arg)) => match self.resolve(arg)? {
+                Target::Object(location) => {
+                    let index = current(&location);
+                    match self.string(index) {
+                        Some(text) => Ok(format!("{}\n", text)),
+                        None => Ok(format!("{}\n{}", self.describe(index), self.list(index))),
+                    }
+                }
+                Target::Value(value) => {
+                    let raw = &self.context.values[value];
+                    Ok(match Data::from_scalar(raw) {
+                        Some(Data::Bytes(bytes)) => match printable(&bytes) {
+                            Some(text) => format!("{}\n", text),
+                            None => hex_dump(&bytes),
+                        },
+                        Some(data) => format!("{}\n", scalar_text(&data)),
+                        // Only references to objects that do not exist end
+                        // up here.
+                        None => match raw.object_reference() {
+                            Some(target) => format!("<missing object {}>\n", target),
+                            None => format!("<{}>\n", type_name(raw.value_type)),
+                        },
+                    })
+                }
+            },
+            ("refs", arg) => {
+                let index = match self.resolve(arg.unwrap_or("."))? {
+                    Target::Object(location) => current(&location),
+                    Target::Value(..) => return Err("Not an object".to_string()),
+                };
+                let referrers = self.references.referrers(index);
+                if referrers.is_empty() {
+                    return Ok("no references\n".to_string());
+                }
+                Ok(referrers
+                    .iter()
+                    .map(|reference| {
+                        let path = self.paths[reference.from]
+                            .clone()
+                            .unwrap_or(format!("#{}", reference.from));
+                        format!(
+                            "{}  {}{}\n",
+                            self.describe(reference.from),
+                            path,
+                            reference.segment()
+                        )
+                    })
+                    .collect())
+            }
+            ("find", Some(pattern)) => {
+                let regex = Regex::new(pattern)
+                    .map_err(|e| format!("Invalid pattern: {} at {}", e.reason, e.offset))?;
+                Ok(grep(self.context, &regex)
+                    .into_iter()
+                    .map(|m| format!("{}  {} {:?}\n", m.path, m.field.name(), m.text))
+                    .collect())
+            }
+            ("hexdump", Some(arg)) => match self.resolve(arg)? {
+                Target::Object(location) => {
+                    let records: Vec<u8> = self
+                        .values(current(&location))
+                        .into_iter()
+                        .flat_map(|(_, _, raw)| raw.to_bytes())
+                        .collect();
+                    Ok(hex_dump(&records))
+                }
+                Target::Value(value) => {
+                    let raw = &self.context.values[value];
+                    match raw.value_type {
+                        TYPE_DATA => Ok(hex_dump(&raw.data)),
+                        _ => Ok(hex_dump(&raw.to_bytes())),
+                    }
+                }
+            },
+            ("cat", None) | ("find", None) | ("hexdump", None) => {
+                Err(format!("{} expects an argument", command))
+            }
+            ("help" | "pwd" | "back" | "forward", Some(_)) => {
+                Err(format!("{} takes no argument", command))
+            }
+            _ => Err(format!("Unknown command {}, try help", command)),
+        }
+    }
+
+    // Values of an object with their index in the values table and their
+    // name, the key or the position for items of inlined collections.
+    fn values(&self, index: usize) -> Vec<(usize, String, &'a RawValue)> {
+        let start = self
+            .context
+            .objects
+            .get(index)
+            .and_then(|object| object.value_index.value().ok())
+            .unwrap_or_default() as usize;
+        let mut items = 0;
+        self.context
+            .named_values(index)
+            .into_iter()
+            .enumerate()
+            .map(|(position, (key, raw))| {
+                let name = match key == EMPTY_KEY {
+                    true => {
+                        items += 1;
+                        format!("[{}]", items - 1)
+                    }
+                    false => key,
+                };
+                (start + position, name, raw)
+            })
+            .collect()
+    }
+
+    fn target(&self, raw: &RawValue) -> Option<usize> {
+        raw.object_reference()
+            .map(|index| index as usize)
+            .filter(|index| *index < self.context.objects.len())
+    }
+
+    fn string(&self, index: usize) -> Option<String> {
+        let class = self.context.class_name(index)?;
+        let bytes = self.context.find_value(index, "NS.bytes")?;
+        match is_string_class(&class) {
+            true => printable(&bytes.data).map(str::to_string),
+            false => None,
+        }
+    }
+
+    fn describe(&self, index: usize) -> String {
+        let class = self
+            .context
+            .class_name(index)
+            .unwrap_or("<unknown>".to_string());
+        match self.string(index) {
+            Some(text) => format!("#{} {} {:?}", index, class, text),
+            None => format!("#{} {}", index, class),
+        }
+    }
+
+    fn list(&self, index: usize) -> String {
+        let values = self.values(index);
+        let width = values
+            .iter()
+            .map(|(_, name, _)| name.chars().count())
+            .max()
+            .unwrap_or_default();
+        values
+            .iter()
+            .map(|(_, name, raw)| {
+                let summary = match (self.target(raw), Data::from_scalar(raw)) {
+                    (Some(target), _) => format!("-> {}", self.describe(target)),
+                    (None, Some(data)) => scalar_text(&data),
+                    (None, None) => "<invalid>".to_string(),
+                };
+                format!(
+                    "{:<width$}  {:<6}  {}\n",
+                    name,
+                    type_name(raw.value_type),
+                    summary,
+                    width = width
+                )
+            })
+            .collect()
+    }
+
+    // The location of an object by the shortest path from the root, or the
+    // object alone when the root does not reach it.
+    fn location_of(&self, index: usize) -> Vec<Step> {
+        match self.references.path(index) {
+            Some(references) => std::iter::once(("root".to_string(), 0))
+                .chain(
+                    references
+                        .iter()
+                        .map(|reference| (reference.segment(), reference.to)),
+                )
+                .collect(),
+            None => vec![(format!("#{}", index), index)],
+        }
+    }
+
+    fn resolve(&self, path: &str) -> Result<Target, String> {
+        let mut location = self.location.clone();
+        let segments = segments(path);
+        for (position, segment) in segments.iter().enumerate() {
+            let last = position + 1 == segments.len();
+            let index = current(&location);
+            match segment.as_str() {
+                "/" => location = self.location_of(0),
+                "." => {}
+                ".." => {
+                    if location.len() > 1 {
+                        location.pop();
+                    }
+                }
+                _ if segment.trim_start_matches('#').parse::<usize>().is_ok() => {
+                    let target: usize = segment.trim_start_matches('#').parse().unwrap_or(0);
+                    if target >= self.context.objects.len() {
+                        return Err(format!(
+                            "Object {} is out of range, the archive has {} objects",
+                            target,
+                            self.context.objects.len()
+                        ));
+                    }
+                    location = self.location_of(target);
+                }
+                _ => {
+                    let name = match segment.starts_with('[') {
+                        true => segment.clone(),
+                        false => segment.trim_start_matches('/').to_string(),
+                    };
+                    let (value, _, raw) = self
+                        .values(index)
+                        .into_iter()
+                        .find(|(_, key, _)| *key == name)
+                        .ok_or(format!("{} has no value {}", self.describe(index), name))?;
+                    match self.target(raw) {
+                        Some(target) => {
+                            let segment = match name.starts_with('[') {
+                                true => name,
+                                false => format!("/{}", name),
+                            };
+                            location.push((segment, target))
+                        }
+                        None if last => return Ok(Target::Value(value)),
+                        None => match raw.object_reference() {
+                            Some(target) => {
+                                return Err(format!("{} refers to missing object {}", name, target))
+                            }
+                            None => return Err(format!("{} is not an object", name)),
+                        },
+                    }
+                }
+            }
+        }
+        Ok(Target::Object(location))
+    }
+}
+
+fn current(location: &[Step]) -> usize {
+    location.last().map_or(0, |(_, index)| *index)
+}
+
+// Splits a path into "/" for the root, keys and "[n]" items.
+fn segments(path: &str) -> Vec<String> {
+    let mut segments = vec![];
+    if path.starts_with('/') {
+        segments.push("/".to_string());
+    }
+    for part in path.split('/').filter(|part| !part.is_empty()) {
+        let mut rest = part;
+        while let Some(start) = rest[1..].find('[').map(|start| start + 1) {
+            segments.push(rest[..start].to_string());
+            rest = &rest[start..];
+        }
+        segments.push(rest.to_string());
+    }
+    segments
+}
+
+// Splits a command line into words, keeping double-quoted text together.
+fn words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let mut word = String::new();
+        if c == '"' {
+            loop {
+                match chars.next() {
+                    None => return Err("Unterminated quote".to_string()),
+                    Some('"') => break,
+                    Some('\\') => word.extend(chars.next()),
+                    Some(c) => word.push(c),
+                }
+            }
+        } else {
+            word.push(c);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                word.push(c);
+            }
+        }
+        words.push(word);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{cli::shell::Shell, raw_model::builder::ContextBuilder};
+
+    #[test]
+    fn navigation() {
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let top_level = builder.reserve_object();
+        let label = builder.reserve_object();
+        let text = builder.reserve_object();
+
+        let value = builder.object_value("UINibTopLevelObjectsKey", top_level);
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, vec![value]);
+        let values = vec![
+            builder.bool_value("NSInlinedValue", true),
+            builder.object_value("UINibEncoderEmptyKey", label),
+        ];
+        let class = builder.class("NSArray", &[]);
+        builder.set_object(top_level, class, values);
+        let values = vec![
+            builder.object_value("UIText", text),
+            builder.int_value("UITag", 7),
+            builder.bytes_value("UIBounds", vec![0, 1, 2]),
+        ];
+        let class = builder.class("UILabel", &[]);
+        builder.set_object(label, class, values);
+        let value = builder.bytes_value("NS.bytes", b"Hello".to_vec());
+        let class = builder.class("NSString", &[]);
+        builder.set_object(text, class, vec![value]);
+        let context = builder.build().unwrap();
+
+        let mut shell = Shell::new(&context);
+        assert_eq!(
+            Ok(String::new()),
+            shell.execute("cd UINibTopLevelObjectsKey[0]")
+        );
+        assert_eq!("root/UINibTopLevelObjectsKey[0]", shell.pwd());
+        assert_eq!(
+            Ok("UIText    object  -> #3 NSString \"Hello\"\n\
+                UITag     int8    7\n\
+                UIBounds  data    <3 bytes>\n"
+                .to_string()),
+            shell.execute("ls")
+        );
+        assert_eq!(Ok("Hello\n".to_string()), shell.execute("cat UIText"));
+        assert_eq!(Ok("7\n".to_string()), shell.execute("cat \"UITag\""));
+        assert_eq!(
+            Ok(format!("00000000  {:<47}  ...\n", "00 01 02")),
+            shell.execute("hexdump UIBounds")
+        );
+        assert_eq!(
+            Ok("#1 NSArray  root/UINibTopLevelObjectsKey[0]\n".to_string()),
+            shell.execute("refs")
+        );
+        assert_eq!(
+            Ok("root/UINibTopLevelObjectsKey[0]/UIText  string \"Hello\"\n".to_string()),
+            shell.execute("find ^Hel")
+        );
+
+        assert!(shell.execute("cd UITag").is_err());
+        assert!(shell.execute("cd #9").is_err());
+        shell.execute("cd #3").unwrap();
+        assert_eq!("root/UINibTopLevelObjectsKey[0]/UIText", shell.pwd());
+        shell.execute("cd ../..").unwrap();
+        assert_eq!("root/UINibTopLevelObjectsKey", shell.pwd());
+        assert_eq!(
+            Ok("root/UINibTopLevelObjectsKey[0]/UIText\n".to_string()),
+            shell.execute("back")
+        );
+        shell.execute("back").unwrap();
+        assert_eq!("root/UINibTopLevelObjectsKey[0]", shell.pwd());
+        assert!(shell.execute("back").is_ok());
+        assert!(shell.execute("back").is_err());
+        shell.execute("forward").unwrap();
+        assert_eq!("root/UINibTopLevelObjectsKey[0]", shell.pwd());
+        shell.execute("cd").unwrap();
+        assert_eq!("root", shell.pwd());
+    }
+
+    #[test]
+    fn malformed() {
+        let empty = ContextBuilder::new().build().unwrap();
+        let mut shell = Shell::new(&empty);
+        assert_eq!(Ok(String::new()), shell.execute("ls"));
+        assert_eq!(Ok("no references\n".to_string()), shell.execute("refs"));
+        assert_eq!(Ok(String::new()), shell.execute("find a"));
+        assert_eq!(
+            Err("Object 0 is out of range, the archive has 0 objects".to_string()),
+            shell.execute("cd #0")
+        );
+        assert_eq!(
+            Err("#0 <unknown> has no value x".to_string()),
+            shell.execute("hexdump x")
+        );
+
+        // A missing object, a value of an unknown type and a reference
+        // cycle.
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let values = vec![
+            builder.object_value("UIView", 5),
+            builder.int_value("UITag", 3),
+            builder.object_value("UISelf", root),
+        ];
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, values);
+        let mut context = builder.build().unwrap();
+        context.values[1].value_type = 11;
+
+        let mut shell = Shell::new(&context);
+        assert_eq!(
+            Ok("UIView  object  <invalid>\n\
+                UITag   unknown  <invalid>\n\
+                UISelf  object  -> #0 NSObject\n"
+                .to_string()),
+            shell.execute("ls")
+        );
+        assert_eq!(
+            Ok("<missing object 5>\n".to_string()),
+            shell.execute("cat UIView")
+        );
+        assert_eq!(
+            Err("UIView refers to missing object 5".to_string()),
+            shell.execute("cd UIView/UIFrame")
+        );
+        assert_eq!(Ok("<unknown>\n".to_string()), shell.execute("cat UITag"));
+        shell.execute("cd UISelf/UISelf").unwrap();
+        assert_eq!("root/UISelf/UISelf", shell.pwd());
+
+        for (line, error) in [
+            ("find (", "Invalid pattern: Unclosed group at 0"),
+            ("find \"abc", "Unterminated quote"),
+            ("frobnicate", "Unknown command frobnicate, try help"),
+            ("cat", "cat expects an argument"),
+            ("pwd x", "pwd takes no argument"),
+            ("ls a b", "ls takes at most one argument"),
+            ("forward", "Nothing to go forward to"),
+        ] {
+            assert_eq!(Err(error.to_string()), shell.execute(line));
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use crate::{
+    data::Context,
+    format::{detect_format, Format},
+    plist::keyed_archive,
+    raw_model::{nib::NibFile, ParseError},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    NibArchive,
+    KeyedArchive,
+}
+
+impl Encoding {
+    // Anything but a keyed archive is re-encoded as a keyed archive.
+    pub fn of(buffer: &[u8]) -> Encoding {
+        match detect_format(buffer) {
+            Format::KeyedArchive | Format::BinaryPlist | Format::XmlPlist => Encoding::KeyedArchive,
+            _ => Encoding::NibArchive,
+        }
+    }
+
+    pub fn other(&self) -> Encoding {
+        match self {
+            Encoding::NibArchive => Encoding::KeyedArchive,
+            Encoding::KeyedArchive => Encoding::NibArchive,
+        }
+    }
+}
+
+pub fn encode(context: &Context, encoding: Encoding) -> Result<Vec<u8>, ParseError> {
+    match encoding {
+        Encoding::NibArchive => Ok(NibFile::from_context(context).buffer().to_vec()),
+        Encoding::KeyedArchive => keyed_archive::to_buffer(context),
+    }
+}
+
+// Re-encodes a NIBArchive as a keyed archive or the other way around.
+pub fn convert(buffer: Vec<u8>) -> Result<(Encoding, Vec<u8>), ParseError> {
+    let encoding = Encoding::of(&buffer).other();
+    let context = Context::from_buffer(buffer)?;
+    Ok((encoding, encode(&context, encoding)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        convert::{convert, encode, Encoding},
+        data::{Class, Context},
+        display::Json,
+        raw_model::builder::ContextBuilder,
+    };
+
+    fn sample() -> Context {
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let label = builder.reserve_object();
+        let text = builder.reserve_object();
+        let array = builder.reserve_object();
+
+        let object_class = builder.class("NSObject", &[]);
+        let view_class = builder.class("UIView", &[]);
+        let label_class = builder.class("UILabel", &[view_class as i32]);
+        let string_class = builder.class("NSString", &[]);
+        let array_class = builder.class("NSArray", &[]);
+
+        let values = vec![
+            builder.object_value("UINibTopLevelObjectsKey", array),
+            builder.nil_value("UINibConnectionsKey"),
+        ];
+        builder.set_object(root, object_class, values);
+        let values = vec![
+            builder.object_value("UIText", text),
+            builder.int_value("UITag", 300),
+            builder.float_value("UIAlpha", 0.5),
+            builder.double_value("UIFontPointSize", 17.0),
+            builder.bool_value("UIHidden", false),
+            builder.int_value("UIOffset", -5),
+        ];
+        builder.set_object(label, label_class, values);
+        let values = vec![builder.bytes_value("NS.bytes", b"Hello".to_vec())];
+        builder.set_object(text, string_class, values);
+        let values = vec![
+            builder.bool_value("NSInlinedValue", true),
+            builder.object_value("UINibEncoderEmptyKey", label),
+            builder.object_value("UINibEncoderEmptyKey", text),
+        ];
+        builder.set_object(array, array_class, values);
+        builder.build().unwrap()
+    }
+
+    // Class names with their fallback chains resolved, since the class table
+    // is renumbered by a conversion.
+    fn class_chains(context: &Context) -> Vec<(String, Vec<String>)> {
+        let mut chains: Vec<_> = context
+            .classes
+            .iter()
+            .map(|raw| {
+                let class = Class::from(raw);
+                let fallbacks = class
+                    .extra_values
+                    .iter()
+                    .map(|e| Class::from(&context.classes[*e as usize]).name)
+                    .collect();
+                (class.name, fallbacks)
+            })
+            .collect();
+        chains.sort();
+        chains
+    }
+
+    fn without_extra_values(json: String) -> String {
+        let mut parts = json.split(r#", "extra_values": ["#);
+        let mut stripped = parts.next().unwrap_or_default().to_string();
+        for part in parts {
+            stripped.push_str(part.split_once(']').map_or(part, |(_, rest)| rest));
+        }
+        stripped
+    }
+
+    #[test]
+    fn nib_keyed_round_trip() {
+        let context = sample();
+        let nib = encode(&context, Encoding::NibArchive).unwrap();
+
+        let (encoding, keyed) = convert(nib.to_owned()).unwrap();
+        assert_eq!(Encoding::KeyedArchive, encoding);
+        assert!(keyed.starts_with(b"bplist00"));
+
+        let (encoding, round_trip) = convert(keyed).unwrap();
+        assert_eq!(Encoding::NibArchive, encoding);
+
+        let original = Context::from_buffer(nib).unwrap();
+        let converted = Context::from_buffer(round_trip).unwrap();
+        assert_eq!(original.objects.len(), converted.objects.len());
+        assert_eq!(class_chains(&original), class_chains(&converted));
+
+        assert_eq!(
+            without_extra_values(original.parse().unwrap().to_json()),
+            without_extra_values(converted.parse().unwrap().to_json())
+        );
+    }
+}
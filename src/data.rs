@@ -1,18 +1,185 @@
 use crate::{
-    cast::{cast_to_i32, cast_to_u32},
-    raw_model::{raw_class::RawClass, raw_key::RawKey, raw_object::RawObject, raw_value::RawValue},
+    cast::cast_to_i32,
+    format::{detect_format, Format},
+    plist::{self, ascii, bplist, keyed_archive, xml, Plist},
+    raw_model::{
+        nib::NibFile, raw_class::RawClass, raw_key::RawKey, raw_object::RawObject,
+        raw_value::RawValue, ParseError,
+    },
+    typedstream,
+    xib::compile::compile,
 };
 
+// Archives embedded in byte values, such as nibs inside other nibs or keyed
+// archives in user defined attributes, are decoded down to this many levels.
+pub const MAX_NESTING_DEPTH: usize = 4;
+// Classes whose objects hold their text as NS.bytes.
+const STRING_CLASSES: [&str; 5] = [
+    "NSString",
+    "NSMutableString",
+    "NSLocalizableString",
+    "__NSCFString",
+    "__NSCFConstantString",
+];
+// Object references resolved by Context::parse are followed down to this
+// many levels.
+const MAX_OBJECT_DEPTH: usize = 256;
+
 pub struct Context {
     pub objects: Vec<RawObject>,
     pub keys: Vec<RawKey>,
     pub values: Vec<RawValue>,
     pub classes: Vec<RawClass>,
+    // How deep this archive is embedded in other archives, 0 for a file.
+    pub depth: usize,
 }
 
 impl Context {
-    pub fn parse(&self) -> Object {
-        Object::from(self.objects.get(0).unwrap(), self)
+    pub fn from_nib(nib: &NibFile) -> Result<Context, ParseError> {
+        Ok(Context {
+            objects: nib.get_objects()?,
+            keys: nib.get_keys()?,
+            values: nib.get_values()?,
+            classes: nib.get_classes()?,
+            depth: 0,
+        })
+    }
+
+    // Decodes any archive format the crate reads: NIBArchive files, keyed
+    // archives as binary or XML plists, other property lists, typedstreams
+    // and XIB sources, which are compiled.
+    pub fn from_buffer(buffer: Vec<u8>) -> Result<Context, ParseError> {
+        match detect_format(&buffer) {
+            Format::KeyedArchive => keyed_archive::from_buffer(&buffer),
+            Format::BinaryPlist => plist_context(&bplist::from_buffer(&buffer)?),
+            Format::XmlPlist => plist_context(&xml::from_buffer(&buffer)?),
+            Format::AsciiPlist => plist::to_context(&ascii::from_buffer(&buffer)?),
+            Format::Typedstream { .. } => {
+                typedstream::to_context(&typedstream::from_buffer(&buffer)?)
+            }
+            Format::Xib => {
+                let text = String::from_utf8_lossy(&buffer);
+                Ok(compile(&crate::xib::xml::parse(&text)?)?.context)
+            }
+            format @ (Format::Storyboard | Format::Zip) => Err(ParseError::new(
+                0,
+                0,
+                format!("Decoding a {} is not supported", format),
+            )),
+            // Reported as an incorrect NIBArchive identifier.
+            Format::NibArchive { .. } | Format::Unknown => {
+                Context::from_nib(&NibFile::from_buffer(buffer)?)
+            }
+        }
+    }
+
+    // Decodes a byte value that is itself a complete NIBArchive, keyed
+    // archive, binary or XML property list or typedstream. Returns None for
+    // anything else and below the depth limit.
+    pub fn nested(&self, bytes: &[u8]) -> Option<Context> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return None;
+        }
+        match detect_format(bytes) {
+            Format::NibArchive { .. }
+            | Format::KeyedArchive
+            | Format::BinaryPlist
+            | Format::XmlPlist
+            | Format::Typedstream { .. } => {}
+            _ => return None,
+        }
+        let mut context = Context::from_buffer(bytes.to_vec()).ok()?;
+        if context.objects.is_empty() {
+            return None;
+        }
+        context.depth = self.depth + 1;
+        Some(context)
+    }
+
+    pub fn object_values(&self, object: &RawObject) -> Option<&[RawValue]> {
+        let start = object.value_index.value().ok()? as usize;
+        let end = start.checked_add(object.value_count.value().ok()? as usize)?;
+        self.values.get(start..end)
+    }
+
+    pub fn key_name(&self, value: &RawValue) -> Option<String> {
+        let key = self.keys.get(value.key_index.value().ok()? as usize)?;
+        Some(Key::from(key).string)
+    }
+
+    pub fn class_of(&self, object: &RawObject) -> Option<Class> {
+        let class = self
+            .classes
+            .get(object.class_index.value().ok()? as usize)?;
+        Some(Class::from(class))
+    }
+
+    pub fn class_name(&self, index: usize) -> Option<String> {
+        self.class_of(self.objects.get(index)?)
+            .map(|class| class.name)
+    }
+
+    // Values of the object at an index, paired with their key names.
+    pub fn named_values(&self, index: usize) -> Vec<(String, &RawValue)> {
+        self.objects
+            .get(index)
+            .and_then(|object| self.object_values(object))
+            .unwrap_or(&[])
+            .iter()
+            .map(|value| (self.key_name(value).unwrap_or_default(), value))
+            .collect()
+    }
+
+    pub fn find_value(&self, index: usize, key: &str) -> Option<&RawValue> {
+        self.named_values(index)
+            .into_iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+
+    // Reads a string stored either inline as UTF-8 bytes or as a reference
+    // to an NSString object.
+    pub fn string(&self, value: &RawValue) -> Option<String> {
+        match value.object_reference() {
+            Some(index) => {
+                let class = self.class_name(index as usize)?;
+                if !is_string_class(&class) {
+                    return None;
+                }
+                let bytes = self.find_value(index as usize, "NS.bytes")?;
+                String::from_utf8(bytes.data.to_owned()).ok()
+            }
+            None if value.value_type == 8 => String::from_utf8(value.data.to_owned()).ok(),
+            None => None,
+        }
+    }
+
+    // Element references of an inlined collection (NSArray, NSSet, ...).
+    pub fn collection_items(&self, index: usize) -> Vec<u32> {
+        self.named_values(index)
+            .into_iter()
+            .filter(|(name, _)| name == keyed_archive::EMPTY_KEY)
+            .filter_map(|(_, value)| value.object_reference())
+            .collect()
+    }
+
+    // The root object with every value and reference resolved.
+    pub fn parse(&self) -> Result<Object, String> {
+        let root = self.objects.first().ok_or("Archive has no objects")?;
+        Object::from(root, self)
+    }
+}
+
+pub fn is_string_class(class: &str) -> bool {
+    STRING_CLASSES.contains(&class)
+}
+
+// Property lists are unpacked as keyed archives when they are one.
+fn plist_context(plist: &Plist) -> Result<Context, ParseError> {
+    if plist.get("$archiver").is_some() {
+        keyed_archive::to_context(plist)
+    } else {
+        plist::to_context(plist)
     }
 }
 
@@ -23,32 +190,41 @@ pub struct Object {
 }
 
 impl Object {
-    pub fn from(raw: &RawObject, context: &Context) -> Object {
-        let raw_class = match context
+    pub fn from(raw: &RawObject, context: &Context) -> Result<Object, String> {
+        Object::at_depth(raw, context, 0)
+    }
+
+    // Object references are resolved into nested objects, so an archive with
+    // a reference cycle is rejected once it nests too deep.
+    fn at_depth(raw: &RawObject, context: &Context, depth: usize) -> Result<Object, String> {
+        if depth > MAX_OBJECT_DEPTH {
+            return Err(format!(
+                "Objects nest deeper than {} levels",
+                MAX_OBJECT_DEPTH
+            ));
+        }
+        let class_index = raw.class_index.value()?;
+        let raw_class = context
             .classes
-            .get(raw.class_index.value().unwrap() as usize)
-        {
-            Some(class) => class,
-            None => panic!(
-                "Unable to retrieve class at index {}",
-                raw.class_index.value().unwrap()
-            ),
-        };
+            .get(class_index as usize)
+            .ok_or_else(|| format!("Unable to retrieve class at index {}", class_index))?;
 
-        let start_value_index = raw.value_index.value().unwrap() as usize;
-        let end_value_index = start_value_index + raw.value_count.value().unwrap() as usize;
+        let start_value_index = raw.value_index.value()? as usize;
+        let end_value_index = start_value_index
+            .checked_add(raw.value_count.value()? as usize)
+            .ok_or("Value range overflows")?;
 
-        let values: Vec<Value> = (start_value_index..end_value_index)
+        let values = (start_value_index..end_value_index)
             .map(|index| match context.values.get(index) {
-                Some(raw_value) => Value::from(raw_value, context),
-                None => panic!("Did not find enough values {}", index),
+                Some(raw_value) => Value::at_depth(raw_value, context, depth),
+                None => Err(format!("Did not find enough values {}", index)),
             })
-            .collect();
+            .collect::<Result<Vec<Value>, String>>()?;
 
-        Object {
+        Ok(Object {
             class: Class::from(raw_class),
             values,
-        }
+        })
     }
 }
 
@@ -60,7 +236,7 @@ pub struct Key {
 impl Key {
     pub fn from(raw: &RawKey) -> Key {
         Key {
-            string: String::from_utf8(raw.key_bytes.to_owned()).unwrap(),
+            string: String::from_utf8_lossy(&raw.key_bytes).into_owned(),
         }
     }
 }
@@ -72,39 +248,44 @@ pub struct Value {
 }
 
 impl Value {
-    pub fn get_data(raw: &RawValue, context: &Context) -> Data {
+    pub fn get_data(raw: &RawValue, context: &Context) -> Result<Data, String> {
+        Value::data_at_depth(raw, context, 0)
+    }
+
+    fn data_at_depth(raw: &RawValue, context: &Context, depth: usize) -> Result<Data, String> {
         match raw.value_type {
-            0 => Data::Int8(i8::from_le_bytes(raw.data.to_owned().try_into().unwrap())),
-            1 => Data::Int16(i16::from_le_bytes(raw.data.to_owned().try_into().unwrap())),
-            2 => Data::Int32(i32::from_le_bytes(raw.data.to_owned().try_into().unwrap())),
-            3 => Data::Int64(i64::from_le_bytes(raw.data.to_owned().try_into().unwrap())),
-            4 => Data::Boolean(true),
-            5 => Data::Boolean(false),
-            6 => Data::Float(f32::from_le_bytes(raw.data.to_owned().try_into().unwrap())),
-            7 => Data::Double(f64::from_le_bytes(raw.data.to_owned().try_into().unwrap())),
-            8 => Data::Bytes(raw.data.to_owned()),
-            9 => Data::Nil,
-            10 => Data::Object(Object::from(
-                context
+            10 => {
+                let index = raw.object_reference().ok_or("Invalid object reference")?;
+                let object = context
                     .objects
-                    .get(cast_to_u32(&raw.data).unwrap() as usize)
-                    .unwrap(),
-                context,
-            )),
-            _ => panic!("Invalid parsed value type {}", raw.value_type),
+                    .get(index as usize)
+                    .ok_or_else(|| format!("Unable to retrieve object at index {}", index))?;
+                Ok(Data::Object(Object::at_depth(object, context, depth + 1)?))
+            }
+            8 => match context.nested(&raw.data) {
+                Some(nested) => Ok(Data::Archive(nested.parse()?)),
+                None => Ok(Data::Bytes(raw.data.to_owned())),
+            },
+            _ => Data::from_scalar(raw)
+                .ok_or_else(|| format!("Invalid parsed value type {}", raw.value_type)),
         }
     }
 
-    pub fn from(raw: &RawValue, context: &Context) -> Value {
-        let raw_key = match context.keys.get(raw.key_index.value().unwrap() as usize) {
-            Some(key) => key,
-            None => panic!("Unable to retrieve key at index {}", raw.key_index),
-        };
+    pub fn from(raw: &RawValue, context: &Context) -> Result<Value, String> {
+        Value::at_depth(raw, context, 0)
+    }
+
+    fn at_depth(raw: &RawValue, context: &Context, depth: usize) -> Result<Value, String> {
+        let key_index = raw.key_index.value()?;
+        let raw_key = context
+            .keys
+            .get(key_index as usize)
+            .ok_or_else(|| format!("Unable to retrieve key at index {}", key_index))?;
 
-        Value {
+        Ok(Value {
             key: Key::from(raw_key),
-            data: Value::get_data(raw, context),
-        }
+            data: Value::data_at_depth(raw, context, depth)?,
+        })
     }
 }
 
@@ -117,12 +298,11 @@ pub struct Class {
 impl Class {
     pub fn from(raw: &RawClass) -> Class {
         let extra_values: Vec<i32> = (0..raw.extra_values.len() / 4)
-            .map(|e| {
-                cast_to_i32(&raw.extra_values[(e * 4) as usize..((e * 4) + 4) as usize]).unwrap()
-            })
+            .map(|e| cast_to_i32(&raw.extra_values[e * 4..(e * 4) + 4]).unwrap())
             .collect();
+        let class_name = raw.class_name.strip_suffix(&[0]).unwrap_or(&raw.class_name);
         Class {
-            name: String::from_utf8(raw.class_name.to_owned()).unwrap(),
+            name: String::from_utf8_lossy(class_name).into_owned(),
             extra_values,
         }
     }
@@ -139,6 +319,8 @@ impl Class {
 // 8: data, varint , number of bytes as specified in varint
 // 9: nil
 // 10: object reference, 4 bytes uint32 LE coding an offset into the list of objects
+//
+// Data values that are archives themselves are decoded as Archive.
 #[derive(Debug)]
 pub enum Data {
     Boolean(bool),
@@ -151,4 +333,107 @@ pub enum Data {
     Bytes(Vec<u8>),
     Nil,
     Object(Object),
+    // Byte value holding an archive of its own, as its root object.
+    Archive(Object),
+}
+
+impl Data {
+    // Decodes every value type except object references, which need the
+    // context to be resolved.
+    pub fn from_scalar(raw: &RawValue) -> Option<Data> {
+        let data = match raw.value_type {
+            0 => Data::Int8(i8::from_le_bytes(raw.data.to_owned().try_into().ok()?)),
+            1 => Data::Int16(i16::from_le_bytes(raw.data.to_owned().try_into().ok()?)),
+            2 => Data::Int32(i32::from_le_bytes(raw.data.to_owned().try_into().ok()?)),
+            3 => Data::Int64(i64::from_le_bytes(raw.data.to_owned().try_into().ok()?)),
+            4 => Data::Boolean(true),
+            5 => Data::Boolean(false),
+            6 => Data::Float(f32::from_le_bytes(raw.data.to_owned().try_into().ok()?)),
+            7 => Data::Double(f64::from_le_bytes(raw.data.to_owned().try_into().ok()?)),
+            8 => Data::Bytes(raw.data.to_owned()),
+            9 => Data::Nil,
+            _ => return None,
+        };
+        Some(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::{is_string_class, Context, Data},
+        plist::{bplist, Plist},
+        raw_model::builder::ContextBuilder,
+    };
+
+    // A root object holding the bytes as its only value.
+    fn holding(bytes: Vec<u8>) -> Context {
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let values = vec![builder.bytes_value("UIData", bytes)];
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, values);
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn nested_binary_plist() {
+        let plist = Plist::Dictionary(vec![(
+            "UIColor".to_string(),
+            Plist::String("red".to_string()),
+        )]);
+        let context = holding(bplist::to_buffer(&plist));
+
+        let nested = context.nested(&context.values[0].data).unwrap();
+        assert_eq!(1, nested.depth);
+        assert_eq!(
+            Some("red".to_string()),
+            nested.string(nested.find_value(0, "UIColor").unwrap())
+        );
+        match &context.parse().unwrap().values[0].data {
+            Data::Archive(object) => assert_eq!("UIColor", object.values[0].key.string),
+            data => panic!("unexpected data {:?}", data),
+        }
+
+        // Byte values that are not archives stay bytes.
+        let context = holding(b"bplist00".to_vec());
+        assert!(context.nested(&context.values[0].data).is_none());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            Some("Archive has no objects".to_string()),
+            ContextBuilder::new().build().unwrap().parse().err()
+        );
+
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let values = vec![builder.object_value("UIView", 5)];
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, values);
+        assert_eq!(
+            Some("Unable to retrieve object at index 5".to_string()),
+            builder.build().unwrap().parse().err()
+        );
+
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let values = vec![builder.object_value("UIView", root)];
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, values);
+        assert_eq!(
+            Some("Objects nest deeper than 256 levels".to_string()),
+            builder.build().unwrap().parse().err()
+        );
+    }
+
+    #[test]
+    fn string_classes() {
+        assert!(is_string_class("NSString"));
+        assert!(is_string_class("NSLocalizableString"));
+        // Classes that merely have String in their name hold no NS.bytes text.
+        assert!(!is_string_class("NSAttributedString"));
+        assert!(!is_string_class("UIStringDrawingContext"));
+    }
 }
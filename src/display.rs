@@ -1,10 +1,10 @@
 use crate::data::{Class, Data, Key, Object, Value};
 
-pub trait JSON {
+pub trait Json {
     fn to_json(&self) -> String;
 }
 
-impl JSON for Object {
+impl Json for Object {
     fn to_json(&self) -> String {
         format!(
             "{{ \"class\": {}, {} }}",
@@ -18,21 +18,21 @@ impl JSON for Object {
     }
 }
 
-impl JSON for Key {
+impl Json for Key {
     fn to_json(&self) -> String {
-        format!("{}", self.string)
+        self.string.to_string()
     }
 }
 
-impl JSON for Value {
+impl Json for Value {
     fn to_json(&self) -> String {
         format!("\"{}\": {}", self.key.to_json(), self.data.to_json())
     }
 }
 
-impl JSON for Class {
+impl Json for Class {
     fn to_json(&self) -> String {
-        if self.extra_values.len() == 0 {
+        if self.extra_values.is_empty() {
             format!("\"{}\"", self.name)
         } else {
             format!(
@@ -43,13 +43,14 @@ impl JSON for Class {
     }
 }
 
-impl JSON for Data {
+impl Json for Data {
     fn to_json(&self) -> String {
         match self {
-            Data::Boolean(true) => format!("true"),
-            Data::Boolean(false) => format!("false"),
-            Data::Nil => format!("null"),
+            Data::Boolean(true) => "true".to_string(),
+            Data::Boolean(false) => "false".to_string(),
+            Data::Nil => "null".to_string(),
             Data::Object(o) => o.to_json(),
+            Data::Archive(o) => format!("{{ \"archive\": {} }}", o.to_json()),
             Data::Bytes(bytes) => match String::from_utf8(bytes.to_vec()) {
                 Ok(string) => format!("{{ \"bytes\": {:?}, \"string\": \"{}\" }}", bytes, string),
                 _ => format!("{{ \"bytes\": {:?} }}", bytes),
@@ -0,0 +1,308 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    data::{is_string_class, Context, Data},
+    plist::keyed_archive::{EMPTY_KEY, INLINED_VALUE_KEY},
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct DotOptions {
+    // Draw the items of inlined collections as direct children of the object
+    // holding the collection.
+    pub collapse_collections: bool,
+    // Show strings inside the label of the referencing object.
+    pub collapse_strings: bool,
+    // Object the graph starts from, the root object when unset.
+    pub root: Option<usize>,
+    // Maximum number of references followed from the root.
+    pub max_depth: Option<usize>,
+    pub highlight_connections: bool,
+}
+
+// Renders the objects reachable from the root as a Graphviz digraph. Nodes
+// are objects, edges are object references labeled with their key. Archives
+// embedded in byte values are drawn as clusters of their own.
+pub fn to_dot(context: &Context, options: &DotOptions) -> String {
+    let mut lines = vec![];
+    render(
+        context,
+        options,
+        options.root.unwrap_or(0),
+        "n",
+        &mut 0,
+        &mut lines,
+    );
+
+    let mut dot = String::from("digraph nib {\n    node [shape=box, fontname=\"Helvetica\"];\n");
+    for line in lines {
+        dot.push_str(&line);
+        dot.push('\n');
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+// Adds the nodes, then the edges, of the graph below `root`. Node names are
+// the object index after `prefix`; `archives` numbers nested archives.
+fn render(
+    context: &Context,
+    options: &DotOptions,
+    root: usize,
+    prefix: &str,
+    archives: &mut usize,
+    lines: &mut Vec<String>,
+) {
+    let mut graph = Graph {
+        context,
+        options,
+        depths: HashMap::new(),
+        queue: VecDeque::new(),
+    };
+    if root < context.objects.len() {
+        graph.visit(root, 0);
+    }
+
+    let mut nodes = vec![];
+    let mut edges = vec![];
+    while let Some(index) = graph.queue.pop_front() {
+        let depth = graph.depths[&index];
+        let limited = options.max_depth.is_some_and(|max| depth >= max);
+        let connection = options.highlight_connections && graph.is_connection(index);
+        let mut label = vec![format!(
+            "{} #{}",
+            context.class_name(index).unwrap_or_default(),
+            index
+        )];
+
+        for (key, target) in graph.references(index) {
+            if options.collapse_strings {
+                if let Some(string) = graph.string(target) {
+                    label.push(format!("{} = \"{}\"", key, truncate(&string, 40)));
+                    continue;
+                }
+            }
+            if limited {
+                continue;
+            }
+            graph.visit(target, depth + 1);
+            edges.push(format!(
+                "    {}{} -> {}{} [label=\"{}\"{}];",
+                prefix,
+                index,
+                prefix,
+                target,
+                escape(&key),
+                if connection {
+                    ", color=\"#bf9000\""
+                } else {
+                    ""
+                }
+            ));
+        }
+
+        for (key, value) in context.named_values(index) {
+            let Some(Data::Bytes(bytes)) = Data::from_scalar(value) else {
+                continue;
+            };
+            let Some(nested) = context.nested(&bytes).filter(|_| !limited) else {
+                continue;
+            };
+            *archives += 1;
+            let nested_prefix = format!("a{}n", archives);
+            let nested_options = DotOptions {
+                root: None,
+                max_depth: options.max_depth.map(|max| max - depth - 1),
+                ..options.clone()
+            };
+            nodes.push(format!(
+                "    subgraph cluster_a{} {{\n    label=\"{}\";\n    style=dashed;",
+                archives,
+                escape(&key)
+            ));
+            render(
+                &nested,
+                &nested_options,
+                0,
+                &nested_prefix,
+                archives,
+                &mut nodes,
+            );
+            nodes.push("    }".to_string());
+            edges.push(format!(
+                "    {}{} -> {}0 [label=\"{}\", style=dashed];",
+                prefix,
+                index,
+                nested_prefix,
+                escape(&key)
+            ));
+        }
+
+        let style = if connection {
+            ", style=filled, fillcolor=\"#ffd966\""
+        } else {
+            ""
+        };
+        let label: Vec<String> = label.iter().map(|line| escape(line)).collect();
+        nodes.push(format!(
+            "    {}{} [label=\"{}\"{}];",
+            prefix,
+            index,
+            label.join("\\n"),
+            style
+        ));
+    }
+    lines.extend(nodes);
+    lines.extend(edges);
+}
+
+struct Graph<'a> {
+    context: &'a Context,
+    options: &'a DotOptions,
+    depths: HashMap<usize, usize>,
+    queue: VecDeque<usize>,
+}
+
+impl Graph<'_> {
+    fn visit(&mut self, index: usize, depth: usize) {
+        if self.depths.contains_key(&index) {
+            return;
+        }
+        self.depths.insert(index, depth);
+        self.queue.push_back(index);
+    }
+
+    fn is_collection(&self, index: usize) -> bool {
+        self.context
+            .find_value(index, INLINED_VALUE_KEY)
+            .and_then(Data::from_scalar)
+            .is_some_and(|data| matches!(data, Data::Boolean(true)))
+    }
+
+    fn is_connection(&self, index: usize) -> bool {
+        self.context
+            .class_name(index)
+            .is_some_and(|class| class.starts_with("UIRuntime") && class.ends_with("Connection"))
+    }
+
+    fn string(&self, index: usize) -> Option<String> {
+        let class = self.context.class_name(index)?;
+        if !is_string_class(&class) {
+            return None;
+        }
+        let bytes = self.context.find_value(index, "NS.bytes")?;
+        Some(String::from_utf8_lossy(&bytes.data).into_owned())
+    }
+
+    // Outgoing references of an object with the key they are stored under.
+    // Collapsed collections are replaced by their items, keyed "key[n]".
+    fn references(&self, index: usize) -> Vec<(String, usize)> {
+        let mut references = vec![];
+        let mut position = 0;
+        for (key, value) in self.context.named_values(index) {
+            let Some(target) = value.object_reference().map(|t| t as usize) else {
+                continue;
+            };
+            if target >= self.context.objects.len() {
+                continue;
+            }
+            if self.options.collapse_collections
+                && key != EMPTY_KEY
+                && self.is_collection(target)
+                && target != index
+            {
+                for (position, item) in self.context.collection_items(target).iter().enumerate() {
+                    references.push((format!("{}[{}]", key, position), *item as usize));
+                }
+                continue;
+            }
+            let key = match key.as_str() {
+                EMPTY_KEY => {
+                    position += 1;
+                    format!("[{}]", position - 1)
+                }
+                _ => key,
+            };
+            references.push((key, target));
+        }
+        references
+    }
+}
+
+fn truncate(string: &str, length: usize) -> String {
+    match string.char_indices().nth(length) {
+        Some((end, _)) => format!("{}…", &string[..end]),
+        None => string.to_string(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        export::dot::{to_dot, DotOptions},
+        raw_model::builder::ContextBuilder,
+    };
+
+    fn context() -> crate::data::Context {
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let array = builder.reserve_object();
+        let view = builder.reserve_object();
+        let text = builder.reserve_object();
+        let connection = builder.reserve_object();
+
+        let values = vec![
+            builder.object_value("UINibTopLevelObjectsKey", array),
+            builder.object_value("UINibConnectionsKey", connection),
+        ];
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, values);
+        let values = vec![
+            builder.bool_value("NSInlinedValue", true),
+            builder.object_value("UINibEncoderEmptyKey", view),
+        ];
+        let class = builder.class("NSArray", &[]);
+        builder.set_object(array, class, values);
+        let values = vec![builder.object_value("UIText", text)];
+        let class = builder.class("UILabel", &[]);
+        builder.set_object(view, class, values);
+        let values = vec![builder.bytes_value("NS.bytes", b"Say \"hi\"".to_vec())];
+        let class = builder.class("NSString", &[]);
+        builder.set_object(text, class, values);
+        let values = vec![builder.object_value("UIDestination", view)];
+        let class = builder.class("UIRuntimeOutletConnection", &[]);
+        builder.set_object(connection, class, values);
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn full_graph() {
+        let dot = to_dot(&context(), &DotOptions::default());
+        assert!(dot.contains("n0 -> n1 [label=\"UINibTopLevelObjectsKey\"];"));
+        assert!(dot.contains("n1 -> n2 [label=\"[0]\"];"));
+        assert!(dot.contains("n2 [label=\"UILabel #2\"];"));
+        assert!(dot.contains("n2 -> n3 [label=\"UIText\"];"));
+    }
+
+    #[test]
+    fn collapsed_and_limited() {
+        let options = DotOptions {
+            collapse_collections: true,
+            collapse_strings: true,
+            max_depth: Some(1),
+            highlight_connections: true,
+            ..Default::default()
+        };
+        let dot = to_dot(&context(), &options);
+        assert!(dot.contains("n0 -> n2 [label=\"UINibTopLevelObjectsKey[0]\"];"));
+        assert!(dot.contains(r#"n2 [label="UILabel #2\nUIText = \"Say \"hi\"\""];"#));
+        assert!(dot.contains("n4 [label=\"UIRuntimeOutletConnection #4\", style=filled"));
+        assert!(!dot.contains("n1 "));
+        assert!(!dot.contains("n4 -> n2"));
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::{
+    data::{Context, Data},
+    raw_model::BufferView,
+};
+
+use super::{hex_dump, scalar_text};
+
+const STYLE: &str = r#"
+body { margin: 0; font: 13px -apple-system, Helvetica, sans-serif; display: flex; height: 100vh; }
+nav { width: 280px; overflow: auto; border-right: 1px solid #ddd; padding: 8px; background: #fafafa; }
+main { flex: 1; overflow: auto; padding: 8px 16px; }
+input { width: 100%; box-sizing: border-box; margin-bottom: 8px; }
+nav ul { list-style: none; padding-left: 0; margin: 0 0 12px 0; }
+nav li { padding: 1px 0; }
+details { margin-left: 16px; }
+summary { cursor: pointer; }
+table { border-collapse: collapse; margin: 4px 0 4px 16px; }
+td { padding: 1px 8px; vertical-align: top; border-bottom: 1px solid #eee; }
+pre { margin: 0; font: 11px Menlo, monospace; color: #555; }
+.class { font-weight: bold; }
+.type { color: #888; }
+.target { background: #ffd966; }
+"#;
+
+// Shown for objects whose class index is out of range.
+const INVALID_CLASS: &str = "<invalid class>";
+
+// Jumping to a reference opens every collapsed ancestor of the target first.
+const SCRIPT: &str = r##"
+function reveal(id) {
+  var target = document.getElementById(id);
+  if (!target) return;
+  for (var node = target; node; node = node.parentElement) {
+    if (node.tagName === 'DETAILS') node.open = true;
+  }
+  document.querySelectorAll('.target').forEach(function (n) { n.classList.remove('target'); });
+  target.classList.add('target');
+  target.scrollIntoView();
+}
+document.addEventListener('click', function (event) {
+  var link = event.target.closest('a[href^="#"]');
+  if (!link) return;
+  event.preventDefault();
+  reveal(link.getAttribute('href').slice(1));
+});
+document.getElementById('search').addEventListener('input', function () {
+  var query = this.value.toLowerCase();
+  document.querySelectorAll('nav li').forEach(function (item) {
+    item.style.display = item.textContent.toLowerCase().indexOf(query) < 0 ? 'none' : '';
+  });
+});
+"##;
+
+// Renders a standalone HTML page: an index of classes and keys on the side
+// and the object tree, with the raw record bytes of every value, next to it.
+pub fn to_html(context: &Context, title: &str) -> String {
+    let tree = SpanningTree::new(context);
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n", escape(title)));
+    html.push_str(&format!("<style>{}</style>\n</head>\n<body>\n", STYLE));
+
+    html.push_str("<nav>\n<input id=\"search\" placeholder=\"Search classes and keys\">\n");
+    html.push_str(&index(context));
+    html.push_str("</nav>\n<main>\n");
+    html.push_str(&format!(
+        "<h1>{}</h1>\n<p>{} objects, {} keys, {} values, {} classes</p>\n",
+        escape(title),
+        context.objects.len(),
+        context.keys.len(),
+        context.values.len(),
+        context.classes.len()
+    ));
+    tree.render_all(&mut html);
+    html.push_str(&format!(
+        "</main>\n<script>{}</script>\n</body>\n</html>\n",
+        SCRIPT
+    ));
+    html
+}
+
+fn index(context: &Context) -> String {
+    let mut classes: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    let mut keys: BTreeMap<String, BTreeSet<usize>> = BTreeMap::new();
+    for index in 0..context.objects.len() {
+        let class = context
+            .class_name(index)
+            .unwrap_or_else(|| INVALID_CLASS.to_string());
+        classes.entry(class).or_default().push(index);
+        for (key, _) in context.named_values(index) {
+            keys.entry(key).or_default().insert(index);
+        }
+    }
+
+    let mut html = String::from("<h3>Classes</h3>\n<ul>\n");
+    for (class, objects) in &classes {
+        html.push_str(&format!(
+            "<li><span class=\"class\">{}</span> ({}) {}</li>\n",
+            escape(class),
+            objects.len(),
+            links(objects.iter())
+        ));
+    }
+    html.push_str("</ul>\n<h3>Keys</h3>\n<ul>\n");
+    for (key, objects) in &keys {
+        html.push_str(&format!(
+            "<li>{} ({}) {}</li>\n",
+            escape(key),
+            objects.len(),
+            links(objects.iter())
+        ));
+    }
+    html.push_str("</ul>\n");
+    html
+}
+
+// Links to the first few objects of an index entry.
+fn links<'a>(objects: impl Iterator<Item = &'a usize>) -> String {
+    let objects: Vec<&usize> = objects.collect();
+    let mut links: Vec<String> = objects
+        .iter()
+        .take(20)
+        .map(|index| format!("<a href=\"#o{0}\">#{0}</a>", index))
+        .collect();
+    if objects.len() > 20 {
+        links.push("…".to_string());
+    }
+    links.join(" ")
+}
+
+// Every object is drawn once, under the object that first reaches it in a
+// breadth first walk from the root. Other references become links.
+struct SpanningTree<'a> {
+    context: &'a Context,
+    // Put before element ids so that archives nested in byte values, drawn
+    // inside the value's row, do not clash with their parent.
+    prefix: String,
+    children: Vec<Vec<usize>>,
+    roots: Vec<usize>,
+}
+
+impl<'a> SpanningTree<'a> {
+    fn new(context: &'a Context) -> SpanningTree<'a> {
+        SpanningTree::with_prefix(context, String::new())
+    }
+
+    fn with_prefix(context: &'a Context, prefix: String) -> SpanningTree<'a> {
+        let count = context.objects.len();
+        let mut children = vec![vec![]; count];
+        let mut seen = vec![false; count];
+        let mut roots = vec![];
+
+        for start in 0..count {
+            if seen[start] {
+                continue;
+            }
+            roots.push(start);
+            seen[start] = true;
+            let mut queue = VecDeque::from([start]);
+            while let Some(index) = queue.pop_front() {
+                for (_, value) in context.named_values(index) {
+                    let Some(target) = value.object_reference().map(|t| t as usize) else {
+                        continue;
+                    };
+                    if target < count && !seen[target] {
+                        seen[target] = true;
+                        children[index].push(target);
+                        queue.push_back(target);
+                    }
+                }
+            }
+        }
+
+        SpanningTree {
+            context,
+            prefix,
+            children,
+            roots,
+        }
+    }
+
+    fn render_all(&self, html: &mut String) {
+        for root in &self.roots {
+            self.render(*root, html);
+        }
+    }
+
+    fn render(&self, index: usize, html: &mut String) {
+        let context = self.context;
+        let class = context
+            .class_name(index)
+            .unwrap_or_else(|| INVALID_CLASS.to_string());
+        let record = context.objects[index].to_bytes();
+        html.push_str(&format!(
+            "<details id=\"{}o{}\"{}>\n<summary><span class=\"class\">{}</span> #{} <pre>{}</pre></summary>\n<table>\n",
+            self.prefix,
+            index,
+            if index == 0 { " open" } else { "" },
+            escape(&class),
+            index,
+            escape(hex_dump(&record).trim_end())
+        ));
+
+        for (position, (key, value)) in context.named_values(index).into_iter().enumerate() {
+            let data = Data::from_scalar(value);
+            let nested = match &data {
+                Some(Data::Bytes(bytes)) => context.nested(bytes),
+                _ => None,
+            };
+            let text = match value.object_reference() {
+                Some(target) if target as usize >= context.objects.len() => {
+                    escape(&format!("<missing object {}>", target))
+                }
+                Some(target) => format!(
+                    "<a href=\"#{0}o{1}\">#{1} {2}</a>",
+                    self.prefix,
+                    target,
+                    escape(&context.class_name(target as usize).unwrap_or_default())
+                ),
+                None if nested.is_some() => escape(&format!(
+                    "<archive of {}>",
+                    nested
+                        .as_ref()
+                        .and_then(|nested| nested.class_name(0))
+                        .unwrap_or_default()
+                )),
+                None => escape(
+                    &data
+                        .map(|data| scalar_text(&data))
+                        .unwrap_or_else(|| format!("<invalid type {}>", value.value_type)),
+                ),
+            };
+            html.push_str(&format!(
+                "<tr><td>{}</td><td class=\"type\">{}</td><td>{}</td><td><pre>{}</pre></td></tr>\n",
+                escape(&key),
+                value.value_type,
+                text,
+                escape(hex_dump(&value.to_bytes()).trim_end())
+            ));
+            if let Some(nested) = nested {
+                html.push_str("<tr><td colspan=\"4\">\n");
+                let prefix = format!("{}o{}.{}.", self.prefix, index, position);
+                SpanningTree::with_prefix(&nested, prefix).render_all(html);
+                html.push_str("</td></tr>\n");
+            }
+        }
+        html.push_str("</table>\n");
+
+        for child in &self.children[index] {
+            self.render(*child, html);
+        }
+        html.push_str("</details>\n");
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::Context,
+        export::html::to_html,
+        raw_model::{builder::ContextBuilder, raw_object::RawObject, raw_value::RawValue},
+    };
+
+    #[test]
+    fn report() {
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let view = builder.reserve_object();
+        let values = vec![
+            builder.object_value("UIView", view),
+            builder.bytes_value("UITitle", b"<b>".to_vec()),
+        ];
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, values);
+        // Points back at the root, which must become a link, not a subtree.
+        let values = vec![builder.object_value("UISuperview", root)];
+        let class = builder.class("UIView", &[]);
+        builder.set_object(view, class, values);
+
+        let html = to_html(&builder.build().unwrap(), "test.nib");
+        assert!(!html.contains("src="));
+        assert!(html.contains("<details id=\"o1\">"));
+        assert!(html.contains("<a href=\"#o0\">#0 NSObject</a>"));
+        assert!(html.contains("&quot;&lt;b&gt;&quot;"));
+        assert_eq!(2, html.matches("<details").count());
+        assert!(html
+            .contains("<li><span class=\"class\">UIView</span> (1) <a href=\"#o1\">#1</a></li>"));
+    }
+
+    #[test]
+    fn malformed() {
+        let html = to_html(&ContextBuilder::new().build().unwrap(), "empty.nib");
+        assert!(html.contains("<p>0 objects, 0 keys, 0 values, 0 classes</p>"));
+        assert!(!html.contains("<details"));
+
+        // A missing object, an unknown value type and an object whose class
+        // and values lie outside the tables.
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let values = vec![builder.object_value("UIView", 5)];
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, values);
+        let Context {
+            mut objects,
+            keys,
+            mut values,
+            classes,
+            ..
+        } = builder.build().unwrap();
+        values.push(RawValue::new(0, 11, vec![]));
+        objects.push(RawObject::new(9, 1, 1));
+        let broken = Context {
+            objects,
+            keys,
+            values,
+            classes,
+            depth: 0,
+        };
+
+        let html = to_html(&broken, "broken.nib");
+        assert!(html.contains("<td>&lt;missing object 5&gt;</td>"));
+        assert!(!html.contains("href=\"#o5\""));
+        assert!(html.contains("<span class=\"class\">&lt;invalid class&gt;</span> #1"));
+        assert!(html.contains("<td>&lt;invalid type 11&gt;</td>"));
+    }
+}
@@ -0,0 +1,160 @@
+use crate::data::Context;
+
+use super::node::{base64, build, Node};
+
+// Renders the objects below the root as JSON. Objects used more than once
+// carry an "$id" where they first appear and are {"$ref": id} afterwards.
+pub fn to_json(context: &Context) -> Result<String, String> {
+    Ok(from_node(&build(context, true)?))
+}
+
+// Renders a document, such as a report, as indented JSON. Anchored maps get
+// an "$id" entry and aliases become {"$ref": id}, since JSON has neither.
+// Binary values are base64 strings and non-finite floats null.
+pub fn from_node(root: &Node) -> String {
+    let mut json = String::new();
+    write(root, 0, &mut json);
+    json.push('\n');
+    json
+}
+
+fn write(node: &Node, depth: usize, json: &mut String) {
+    let indent = "  ".repeat(depth + 1);
+    match node {
+        Node::Null => json.push_str("null"),
+        Node::Bool(value) => json.push_str(&value.to_string()),
+        Node::Integer(value) => json.push_str(&value.to_string()),
+        Node::Float(value) if value.is_finite() => json.push_str(&format!("{:?}", value)),
+        Node::Float(_) => json.push_str("null"),
+        Node::String(value) => json.push_str(&string(value)),
+        Node::Bytes(bytes) => json.push_str(&string(&base64(bytes))),
+        Node::Alias(index) => json.push_str(&format!("{{\"$ref\": \"o{}\"}}", index)),
+        Node::Map { anchor, entries } => {
+            let mut members: Vec<(String, &Node)> = vec![];
+            let id = anchor.map(|anchor| Node::String(format!("o{}", anchor)));
+            if let Some(id) = &id {
+                members.push(("$id".to_string(), id));
+            }
+            members.extend(entries.iter().map(|(key, value)| (key.to_owned(), value)));
+            if members.is_empty() {
+                json.push_str("{}");
+                return;
+            }
+            json.push_str("{\n");
+            for (position, (key, value)) in members.iter().enumerate() {
+                json.push_str(&format!("{}{}: ", indent, string(key)));
+                write(value, depth + 1, json);
+                json.push_str(if position + 1 < members.len() {
+                    ",\n"
+                } else {
+                    "\n"
+                });
+            }
+            json.push_str(&format!("{}}}", "  ".repeat(depth)));
+        }
+        Node::Seq(items) => {
+            if items.is_empty() {
+                json.push_str("[]");
+                return;
+            }
+            json.push_str("[\n");
+            for (position, item) in items.iter().enumerate() {
+                json.push_str(&indent);
+                write(item, depth + 1, json);
+                json.push_str(if position + 1 < items.len() {
+                    ",\n"
+                } else {
+                    "\n"
+                });
+            }
+            json.push_str(&format!("{}]", "  ".repeat(depth)));
+        }
+    }
+}
+
+pub fn string(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        export::{
+            json::{from_node, to_json},
+            node::Node,
+        },
+        raw_model::builder::ContextBuilder,
+    };
+
+    #[test]
+    fn report() {
+        let node = Node::Map {
+            anchor: None,
+            entries: vec![
+                ("path".to_string(), Node::String("a \"b\".nib".to_string())),
+                (
+                    "counts".to_string(),
+                    Node::Seq(vec![Node::Integer(1), Node::Float(0.5), Node::Null]),
+                ),
+                ("empty".to_string(), Node::Seq(vec![])),
+                ("data".to_string(), Node::Bytes(vec![0, 1])),
+            ],
+        };
+        assert_eq!(
+            "{
+  \"path\": \"a \\\"b\\\".nib\",
+  \"counts\": [
+    1,
+    0.5,
+    null
+  ],
+  \"empty\": [],
+  \"data\": \"AAE=\"
+}
+",
+            from_node(&node)
+        );
+    }
+
+    #[test]
+    fn cycles() {
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let view = builder.reserve_object();
+        let values = vec![
+            builder.object_value("UISubviews", view),
+            builder.bytes_value("UIName", b"say \"hi\"".to_vec()),
+        ];
+        let class = builder.class("UIView", &[]);
+        builder.set_object(root, class, values);
+        let value = builder.object_value("UISuperview", root);
+        builder.set_object(view, class, vec![value]);
+
+        assert_eq!(
+            "{
+  \"$id\": \"o0\",
+  \"$class\": \"UIView\",
+  \"UIName\": \"say \\\"hi\\\"\",
+  \"UISubviews\": {
+    \"$class\": \"UIView\",
+    \"UISuperview\": {\"$ref\": \"o0\"}
+  }
+}
+",
+            to_json(&builder.build().unwrap()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,58 @@
+use crate::data::Data;
+
+pub mod dot;
+pub mod html;
+pub mod json;
+pub mod node;
+pub mod toml;
+pub mod tree;
+pub mod yaml;
+
+// Short human readable form of a scalar value. Byte values are shown as a
+// string when they are printable UTF-8.
+pub fn scalar_text(data: &Data) -> String {
+    match data {
+        Data::Boolean(value) => value.to_string(),
+        Data::Int8(value) => value.to_string(),
+        Data::Int16(value) => value.to_string(),
+        Data::Int32(value) => value.to_string(),
+        Data::Int64(value) => value.to_string(),
+        Data::Float(value) => value.to_string(),
+        Data::Double(value) => value.to_string(),
+        Data::Bytes(bytes) => match printable(bytes) {
+            Some(string) => format!("{:?}", string),
+            None => format!("<{} bytes>", bytes.len()),
+        },
+        Data::Nil => "nil".to_string(),
+        Data::Object(object) => format!("<{}>", object.class.name),
+        Data::Archive(object) => format!("<archive of {}>", object.class.name),
+    }
+}
+
+pub fn printable(bytes: &[u8]) -> Option<&str> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .filter(|string| !string.chars().any(|c| c.is_control() && !c.is_whitespace()))
+}
+
+// Classic 16 bytes per line hex dump with an ASCII column.
+pub fn hex_dump(bytes: &[u8]) -> String {
+    let mut dump = String::new();
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|b| match b {
+                0x20..=0x7e => *b as char,
+                _ => '.',
+            })
+            .collect();
+        dump.push_str(&format!(
+            "{:08x}  {:<47}  {}\n",
+            line * 16,
+            hex.join(" "),
+            ascii
+        ));
+    }
+    dump
+}
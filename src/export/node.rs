@@ -0,0 +1,252 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::{
+    data::{is_string_class, Context, Data},
+    plist::keyed_archive::{EMPTY_KEY, INLINED_VALUE_KEY},
+};
+
+use super::printable;
+
+// Key holding the class of an object in the exported documents.
+pub const CLASS_KEY: &str = "$class";
+// Key holding the items of an inlined collection.
+pub const ITEMS_KEY: &str = "$items";
+// Key holding the root object of an archive embedded in a byte value.
+pub const ARCHIVE_KEY: &str = "$archive";
+
+// Upper bound on the size of an expanded tree, shared objects are repeated
+// at every use when aliases are not available.
+const MAX_EXPANDED_NODES: usize = 1_000_000;
+
+// Format independent tree of the decoded objects, as written by the YAML and
+// TOML exports.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    // Objects referenced more than once carry the index of their object as
+    // anchor; later references become aliases.
+    Map {
+        anchor: Option<usize>,
+        entries: Vec<(String, Node)>,
+    },
+    Seq(Vec<Node>),
+    Alias(usize),
+}
+
+// Builds the tree below the root object. With `aliases`, shared objects are
+// written once and referenced afterwards; without, they are expanded at every
+// use and a cycle is an error.
+pub fn build(context: &Context, aliases: bool) -> Result<Node, String> {
+    build_from(context, aliases, 0).map(|(node, _)| node)
+}
+
+// Anchors of a nested archive are numbered after those of the archive that
+// embeds it, starting at `base`. Returns the tree and the next free anchor.
+fn build_from(context: &Context, aliases: bool, base: usize) -> Result<(Node, usize), String> {
+    if context.objects.is_empty() {
+        return Err("Archive has no objects".to_string());
+    }
+    let mut builder = Builder {
+        context,
+        aliases,
+        shared: HashSet::new(),
+        emitted: HashSet::new(),
+        path: HashSet::new(),
+        nodes: 0,
+        base,
+        next_base: base + context.objects.len(),
+    };
+    if aliases {
+        builder.shared = shared_objects(context);
+    }
+    let node = builder.object(0)?;
+    Ok((node, builder.next_base))
+}
+
+// Objects reached more than once when walking from the root.
+fn shared_objects(context: &Context) -> HashSet<usize> {
+    let mut uses: BTreeMap<usize, usize> = BTreeMap::from([(0, 1)]);
+    let mut stack = vec![0];
+    let mut seen = HashSet::from([0]);
+    while let Some(index) = stack.pop() {
+        for (_, value) in context.named_values(index) {
+            let Some(target) = value.object_reference().map(|t| t as usize) else {
+                continue;
+            };
+            if target >= context.objects.len() {
+                continue;
+            }
+            *uses.entry(target).or_default() += 1;
+            if seen.insert(target) {
+                stack.push(target);
+            }
+        }
+    }
+    uses.into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(index, _)| index)
+        .collect()
+}
+
+struct Builder<'a> {
+    context: &'a Context,
+    aliases: bool,
+    shared: HashSet<usize>,
+    emitted: HashSet<usize>,
+    path: HashSet<usize>,
+    nodes: usize,
+    base: usize,
+    next_base: usize,
+}
+
+impl Builder<'_> {
+    fn object(&mut self, index: usize) -> Result<Node, String> {
+        if self.aliases && self.emitted.contains(&index) {
+            return Ok(Node::Alias(self.base + index));
+        }
+        if !self.path.insert(index) {
+            return Err(format!(
+                "Object {} references itself through its children",
+                index
+            ));
+        }
+        self.nodes += 1;
+        if self.nodes > MAX_EXPANDED_NODES {
+            return Err(format!(
+                "Expanding shared objects exceeds {} nodes",
+                MAX_EXPANDED_NODES
+            ));
+        }
+        self.emitted.insert(index);
+
+        let node = self.object_node(index);
+        self.path.remove(&index);
+        // Strings are repeated at every use instead of aliased.
+        if let Ok(Node::String(_)) = node {
+            self.emitted.remove(&index);
+        }
+        node
+    }
+
+    fn object_node(&mut self, index: usize) -> Result<Node, String> {
+        let class = self.context.class_name(index).unwrap_or_default();
+        let mut values = self.context.named_values(index);
+        // Sorted before descending so anchors come before their aliases.
+        values.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        // Strings are written as their text.
+        if is_string_class(&class) && values.len() == 1 && values[0].0 == "NS.bytes" {
+            if let Some(string) = printable(&values[0].1.data) {
+                return Ok(Node::String(string.to_string()));
+            }
+        }
+
+        let inlined = values.iter().any(|(key, value)| {
+            key == INLINED_VALUE_KEY
+                && matches!(Data::from_scalar(value), Some(Data::Boolean(true)))
+        });
+        let mut entries: Vec<(String, Node)> = vec![];
+        let mut items = vec![];
+        for (key, value) in values {
+            if inlined && key == INLINED_VALUE_KEY {
+                continue;
+            }
+            let node = match value.object_reference() {
+                Some(target) if (target as usize) < self.context.objects.len() => {
+                    self.object(target as usize)?
+                }
+                Some(target) => {
+                    return Err(format!(
+                        "Object {} references missing object {}",
+                        index, target
+                    ))
+                }
+                None => match Data::from_scalar(value) {
+                    Some(Data::Bytes(bytes)) => match self.context.nested(&bytes) {
+                        Some(nested) => {
+                            let (root, next_base) =
+                                build_from(&nested, self.aliases, self.next_base)?;
+                            self.next_base = next_base;
+                            Node::Map {
+                                anchor: None,
+                                entries: vec![(ARCHIVE_KEY.to_string(), root)],
+                            }
+                        }
+                        None => scalar(Data::Bytes(bytes)),
+                    },
+                    Some(data) => scalar(data),
+                    None => {
+                        return Err(format!(
+                            "Object {} has invalid value type {}",
+                            index, value.value_type
+                        ))
+                    }
+                },
+            };
+            if inlined && key == EMPTY_KEY {
+                items.push(node);
+                continue;
+            }
+            // Repeated keys become a sequence at the position of the first.
+            match entries.iter_mut().find(|(existing, _)| *existing == key) {
+                Some((_, Node::Seq(existing))) => existing.push(node),
+                Some((_, existing)) => {
+                    let first = std::mem::replace(existing, Node::Null);
+                    *existing = Node::Seq(vec![first, node]);
+                }
+                None => entries.push((key, node)),
+            }
+        }
+
+        entries.insert(0, (CLASS_KEY.to_string(), Node::String(class)));
+        if inlined {
+            entries.push((ITEMS_KEY.to_string(), Node::Seq(items)));
+        }
+        Ok(Node::Map {
+            anchor: self.shared.contains(&index).then_some(self.base + index),
+            entries,
+        })
+    }
+}
+
+fn scalar(data: Data) -> Node {
+    match data {
+        Data::Boolean(value) => Node::Bool(value),
+        Data::Int8(value) => Node::Integer(value as i64),
+        Data::Int16(value) => Node::Integer(value as i64),
+        Data::Int32(value) => Node::Integer(value as i64),
+        Data::Int64(value) => Node::Integer(value),
+        Data::Float(value) => Node::Float(value as f64),
+        Data::Double(value) => Node::Float(value),
+        Data::Bytes(bytes) => match printable(&bytes) {
+            Some(string) => Node::String(string.to_string()),
+            None => Node::Bytes(bytes),
+        },
+        Data::Nil | Data::Object(_) | Data::Archive(_) => Node::Null,
+    }
+}
+
+// Standard base64 with padding, used for binary values.
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let value = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |value, (i, b)| value | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(value >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
@@ -0,0 +1,183 @@
+use crate::data::Context;
+
+use super::node::{base64, build, Node};
+
+// Renders the objects below the root as TOML. TOML has no aliases or null,
+// so shared objects are repeated at every use, and archives with cycles or
+// nil values are rejected.
+pub fn to_toml(context: &Context) -> Result<String, String> {
+    let root = build(context, false)?;
+    let Node::Map { entries, .. } = &root else {
+        return Err("The root object is not a table".to_string());
+    };
+    let mut toml = String::new();
+    write_table(entries, &mut vec![], &mut toml)?;
+    Ok(toml)
+}
+
+// Writes the plain keys of a table, followed by its sub-tables and arrays of
+// tables under their own headers.
+fn write_table(
+    entries: &[(String, Node)],
+    path: &mut Vec<String>,
+    toml: &mut String,
+) -> Result<(), String> {
+    let is_table = |node: &Node| matches!(node, Node::Map { .. });
+    let is_table_array = |node: &Node| matches!(node, Node::Seq(items) if !items.is_empty() && items.iter().all(is_table));
+
+    for (key, value) in entries {
+        if !is_table(value) && !is_table_array(value) {
+            path.push(key.to_owned());
+            toml.push_str(&format!("{} = {}\n", self::key(key), inline(value, path)?));
+            path.pop();
+        }
+    }
+    for (key, value) in entries {
+        path.push(key.to_owned());
+        match value {
+            Node::Map { entries, .. } => {
+                toml.push_str(&format!("\n[{}]\n", header(path)));
+                write_table(entries, path, toml)?;
+            }
+            Node::Seq(items) if is_table_array(value) => {
+                for item in items {
+                    if let Node::Map { entries, .. } = item {
+                        toml.push_str(&format!("\n[[{}]]\n", header(path)));
+                        write_table(entries, path, toml)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        path.pop();
+    }
+    Ok(())
+}
+
+fn inline(node: &Node, path: &[String]) -> Result<String, String> {
+    Ok(match node {
+        Node::Null | Node::Alias(_) => {
+            return Err(format!(
+                "TOML cannot represent the nil value at {}",
+                header(path)
+            ))
+        }
+        Node::Bool(value) => value.to_string(),
+        Node::Integer(value) => value.to_string(),
+        Node::Float(value) => match value {
+            v if v.is_nan() => "nan".to_string(),
+            v if v.is_infinite() && *v > 0.0 => "inf".to_string(),
+            v if v.is_infinite() => "-inf".to_string(),
+            v if v.fract() == 0.0 && v.abs() < 1e15 => format!("{:.1}", v),
+            v => v.to_string(),
+        },
+        Node::String(value) => string(value),
+        // TOML has no binary type.
+        Node::Bytes(bytes) => string(&base64(bytes)),
+        Node::Map { entries, .. } => {
+            let entries: Result<Vec<String>, String> = entries
+                .iter()
+                .map(|(k, v)| Ok(format!("{} = {}", key(k), inline(v, path)?)))
+                .collect();
+            format!("{{ {} }}", entries?.join(", "))
+        }
+        Node::Seq(items) => {
+            let items: Result<Vec<String>, String> =
+                items.iter().map(|item| inline(item, path)).collect();
+            format!("[{}]", items?.join(", "))
+        }
+    })
+}
+
+fn header(path: &[String]) -> String {
+    path.iter().map(|k| key(k)).collect::<Vec<_>>().join(".")
+}
+
+fn key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        key.to_string()
+    } else {
+        string(key)
+    }
+}
+
+fn string(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04X}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{export::toml::to_toml, raw_model::builder::ContextBuilder};
+
+    #[test]
+    fn tables_and_cycles() {
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let array = builder.reserve_object();
+        let view = builder.reserve_object();
+
+        let values = vec![builder.object_value("UINibTopLevelObjectsKey", array)];
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, values);
+        let values = vec![
+            builder.bool_value("NSInlinedValue", true),
+            builder.object_value("UINibEncoderEmptyKey", view),
+            builder.object_value("UINibEncoderEmptyKey", view),
+        ];
+        let class = builder.class("NSArray", &[]);
+        builder.set_object(array, class, values);
+        let values = vec![
+            builder.int_value("UITag", 7),
+            builder.bytes_value("UIText", b"a \"b\"".to_vec()),
+        ];
+        let class = builder.class("UIView", &[]);
+        builder.set_object(view, class, values);
+
+        let toml = to_toml(&builder.build().unwrap()).unwrap();
+        assert_eq!(
+            r#""$class" = "NSObject"
+
+[UINibTopLevelObjectsKey]
+"$class" = "NSArray"
+
+[[UINibTopLevelObjectsKey."$items"]]
+"$class" = "UIView"
+UITag = 7
+UIText = "a \"b\""
+
+[[UINibTopLevelObjectsKey."$items"]]
+"$class" = "UIView"
+UITag = 7
+UIText = "a \"b\""
+"#,
+            toml
+        );
+
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let values = vec![builder.object_value("UISelf", root)];
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, values);
+        assert_eq!(
+            Err("Object 0 references itself through its children".to_string()),
+            to_toml(&builder.build().unwrap())
+        );
+    }
+}
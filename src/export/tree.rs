@@ -0,0 +1,306 @@
+use std::collections::HashSet;
+
+use crate::{
+    data::{is_string_class, Context, Data},
+    plist::keyed_archive::{EMPTY_KEY, INLINED_VALUE_KEY},
+};
+
+use super::{printable, scalar_text};
+
+// Classes whose contents rarely matter when reading a nib; they are printed
+// on one line without their values unless collapsing is disabled.
+pub const BOILERPLATE_CLASSES: &[&str] = &[
+    "UIColor",
+    "UIFont",
+    "UIImageNibPlaceholder",
+    "NSLayoutConstraint",
+    "UIRuntimeOutletConnection",
+    "UIRuntimeEventConnection",
+];
+
+const RESET: &str = "\x1b[0m";
+const DIM: &str = "\x1b[2m";
+const CLASS: &str = "\x1b[1;36m";
+const KEY: &str = "\x1b[33m";
+const STRING: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[35m";
+
+#[derive(Debug, Clone)]
+pub struct TreeOptions {
+    // Levels of objects printed below the root, unlimited when unset.
+    pub max_depth: Option<usize>,
+    pub collapse: Vec<String>,
+    pub color: bool,
+    // Strings longer than this many characters are cut off.
+    pub max_string: usize,
+}
+
+impl Default for TreeOptions {
+    fn default() -> TreeOptions {
+        TreeOptions {
+            max_depth: None,
+            collapse: BOILERPLATE_CLASSES.iter().map(|c| c.to_string()).collect(),
+            color: false,
+            max_string: 60,
+        }
+    }
+}
+
+// Prints the objects below `root` as an indented tree, one value per line:
+//
+//   #0 NSObject
+//   ├── UINibTopLevelObjectsKey: #1 NSArray
+//   │   └── [0]: #2 UIView
+//   │       └── UIAlpha = 1
+//
+// Objects already printed are referred to by index instead of repeated.
+pub fn to_tree(context: &Context, root: usize, options: &TreeOptions) -> String {
+    let mut printer = Printer {
+        context,
+        options,
+        printed: HashSet::new(),
+        output: String::new(),
+    };
+    if root >= context.objects.len() {
+        return format!("No object {}\n", root);
+    }
+    let header = printer.object_label(root);
+    printer.output.push_str(&header);
+    printer.output.push('\n');
+    printer.printed.insert(root);
+    printer.children(root, "", 0);
+    printer.output
+}
+
+struct Printer<'a> {
+    context: &'a Context,
+    options: &'a TreeOptions,
+    printed: HashSet<usize>,
+    output: String,
+}
+
+impl Printer<'_> {
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.options.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn object_label(&self, index: usize) -> String {
+        format!(
+            "{} {}",
+            self.paint(DIM, &format!("#{}", index)),
+            self.paint(CLASS, &self.context.class_name(index).unwrap_or_default())
+        )
+    }
+
+    fn string(&self, string: &str) -> String {
+        let truncated = match string.char_indices().nth(self.options.max_string) {
+            Some((end, _)) => format!("{}…", &string[..end]),
+            None => string.to_string(),
+        };
+        self.paint(STRING, &format!("{:?}", truncated))
+    }
+
+    // Text of an NSString or NSNumber object, which are shown inline.
+    fn inline_object(&self, index: usize) -> Option<String> {
+        let class = self.context.class_name(index)?;
+        let values = self.context.named_values(index);
+        match (class.as_str(), values.as_slice()) {
+            (class, [(key, value)]) if is_string_class(class) && key == "NS.bytes" => {
+                printable(&value.data).map(|string| self.string(string))
+            }
+            ("NSNumber", [(_, value)]) => {
+                Data::from_scalar(value).map(|data| self.paint(NUMBER, &scalar_text(&data)))
+            }
+            _ => None,
+        }
+    }
+
+    fn children(&mut self, index: usize, prefix: &str, depth: usize) {
+        let values = self.context.named_values(index);
+        let inlined = values.iter().any(|(key, value)| {
+            key == INLINED_VALUE_KEY
+                && matches!(Data::from_scalar(value), Some(Data::Boolean(true)))
+        });
+        let values: Vec<_> = values
+            .into_iter()
+            .filter(|(key, _)| !(inlined && key == INLINED_VALUE_KEY))
+            .collect();
+
+        let mut position = 0;
+        for (i, (key, value)) in values.iter().enumerate() {
+            let last = i + 1 == values.len();
+            let branch = if last { "└── " } else { "├── " };
+            let key = if inlined && key == EMPTY_KEY {
+                position += 1;
+                format!("[{}]", position - 1)
+            } else {
+                key.to_owned()
+            };
+            let key = self.paint(KEY, &key);
+            let line_prefix = format!("{}{}", prefix, self.paint(DIM, branch));
+
+            let Some(target) = value.object_reference().map(|t| t as usize) else {
+                // Archives embedded in byte values are printed as subtrees.
+                let nested = match Data::from_scalar(value) {
+                    Some(Data::Bytes(bytes)) => self.context.nested(&bytes),
+                    _ => None,
+                };
+                if let Some(nested) = &nested {
+                    let mut printer = Printer {
+                        context: nested,
+                        options: self.options,
+                        printed: HashSet::from([0]),
+                        output: String::new(),
+                    };
+                    self.output.push_str(&format!(
+                        "{}{}: {} {}\n",
+                        line_prefix,
+                        key,
+                        self.paint(DIM, "archive"),
+                        printer.object_label(0)
+                    ));
+                    if self.options.max_depth.is_none_or(|max| depth < max) {
+                        let child_prefix = format!(
+                            "{}{}",
+                            prefix,
+                            self.paint(DIM, if last { "    " } else { "│   " })
+                        );
+                        printer.children(0, &child_prefix, depth + 1);
+                        self.output.push_str(&printer.output);
+                    }
+                    continue;
+                }
+                let text = match Data::from_scalar(value) {
+                    Some(Data::Bytes(bytes)) => match printable(&bytes) {
+                        Some(string) => self.string(string),
+                        None => self.paint(DIM, &format!("<{} bytes>", bytes.len())),
+                    },
+                    Some(data) => self.paint(NUMBER, &scalar_text(&data)),
+                    None => format!("<invalid type {}>", value.value_type),
+                };
+                self.output
+                    .push_str(&format!("{}{} = {}\n", line_prefix, key, text));
+                continue;
+            };
+
+            if let Some(text) = self.inline_object(target) {
+                self.output
+                    .push_str(&format!("{}{} = {}\n", line_prefix, key, text));
+                continue;
+            }
+            if target >= self.context.objects.len() {
+                self.output.push_str(&format!(
+                    "{}{}: <missing object {}>\n",
+                    line_prefix, key, target
+                ));
+                continue;
+            }
+
+            let label = self.object_label(target);
+            let class = self.context.class_name(target).unwrap_or_default();
+            let count = self.context.named_values(target).len();
+            let note = if self.printed.contains(&target) {
+                Some("(see above)".to_string())
+            } else if self.options.collapse.contains(&class) {
+                Some(format!("{{{} values}}", count))
+            } else if self.options.max_depth.is_some_and(|max| depth + 1 > max) && count > 0 {
+                Some(format!("{{{} values, depth limit}}", count))
+            } else {
+                None
+            };
+            match note {
+                Some(note) => self.output.push_str(&format!(
+                    "{}{}: {} {}\n",
+                    line_prefix,
+                    key,
+                    label,
+                    self.paint(DIM, &note)
+                )),
+                None => {
+                    self.output
+                        .push_str(&format!("{}{}: {}\n", line_prefix, key, label));
+                    self.printed.insert(target);
+                    let child_prefix = format!(
+                        "{}{}",
+                        prefix,
+                        self.paint(DIM, if last { "    " } else { "│   " })
+                    );
+                    self.children(target, &child_prefix, depth + 1);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        export::tree::{to_tree, TreeOptions},
+        raw_model::builder::ContextBuilder,
+    };
+
+    #[test]
+    fn tree() {
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let array = builder.reserve_object();
+        let view = builder.reserve_object();
+        let color = builder.reserve_object();
+        let text = builder.reserve_object();
+
+        let values = vec![
+            builder.object_value("UINibTopLevelObjectsKey", array),
+            builder.object_value("UINibObjectsKey", view),
+        ];
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, values);
+        let values = vec![
+            builder.bool_value("NSInlinedValue", true),
+            builder.object_value("UINibEncoderEmptyKey", view),
+        ];
+        let class = builder.class("NSArray", &[]);
+        builder.set_object(array, class, values);
+        let values = vec![
+            builder.object_value("UIBackgroundColor", color),
+            builder.object_value("UIText", text),
+            builder.int_value("UITag", 3),
+        ];
+        let class = builder.class("UILabel", &[]);
+        builder.set_object(view, class, values);
+        let values = vec![builder.float_value("UIWhite", 1.0)];
+        let class = builder.class("UIColor", &[]);
+        builder.set_object(color, class, values);
+        let values = vec![builder.bytes_value("NS.bytes", b"0123456789".to_vec())];
+        let class = builder.class("NSString", &[]);
+        builder.set_object(text, class, values);
+        let context = builder.build().unwrap();
+
+        let options = TreeOptions {
+            max_string: 4,
+            ..Default::default()
+        };
+        assert_eq!(
+            "#0 NSObject
+├── UINibTopLevelObjectsKey: #1 NSArray
+│   └── [0]: #2 UILabel
+│       ├── UIBackgroundColor: #3 UIColor {1 values}
+│       ├── UIText = \"0123…\"
+│       └── UITag = 3
+└── UINibObjectsKey: #2 UILabel (see above)
+",
+            to_tree(&context, 0, &options)
+        );
+
+        let options = TreeOptions {
+            max_depth: Some(1),
+            color: true,
+            ..Default::default()
+        };
+        let tree = to_tree(&context, 0, &options);
+        assert!(tree.contains("\x1b[1;36mUILabel\x1b[0m \x1b[2m{3 values, depth limit}\x1b[0m"));
+    }
+}
@@ -0,0 +1,243 @@
+use crate::data::Context;
+
+use super::node::{base64, build, Node};
+
+// Renders the objects below the root as YAML. Objects used more than once
+// are anchored as &oN where they first appear and aliased as *oN afterwards.
+pub fn to_yaml(context: &Context) -> Result<String, String> {
+    Ok(from_node(&build(context, true)?))
+}
+
+// Renders a document, such as a report, as YAML.
+pub fn from_node(root: &Node) -> String {
+    let mut yaml = match root {
+        Node::Map {
+            anchor: Some(anchor),
+            ..
+        } => format!("--- &o{}\n", anchor),
+        _ => String::from("---\n"),
+    };
+    match root {
+        Node::Map { .. } | Node::Seq(_) => write_block(root, 0, &mut yaml),
+        _ => {
+            yaml.push_str(&inline(root));
+            yaml.push('\n');
+        }
+    }
+    yaml
+}
+
+// Writes the contents of a mapping or sequence, one entry per line.
+fn write_block(node: &Node, depth: usize, yaml: &mut String) {
+    let indent = "  ".repeat(depth);
+    match node {
+        Node::Map { entries, .. } => {
+            for (key, value) in entries {
+                yaml.push_str(&format!("{}{}:", indent, string(key)));
+                write_value(value, depth, yaml);
+            }
+        }
+        Node::Seq(items) => {
+            for item in items {
+                yaml.push_str(&format!("{}-", indent));
+                write_value(item, depth, yaml);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Writes what follows a "key:" or "-" indicator.
+fn write_value(node: &Node, depth: usize, yaml: &mut String) {
+    match node {
+        Node::Map { anchor, entries } if !entries.is_empty() => {
+            if let Some(anchor) = anchor {
+                yaml.push_str(&format!(" &o{}", anchor));
+            }
+            yaml.push('\n');
+            write_block(node, depth + 1, yaml);
+        }
+        Node::Seq(items) if !items.is_empty() => {
+            yaml.push('\n');
+            write_block(node, depth + 1, yaml);
+        }
+        _ => {
+            yaml.push(' ');
+            yaml.push_str(&inline(node));
+            yaml.push('\n');
+        }
+    }
+}
+
+fn inline(node: &Node) -> String {
+    match node {
+        Node::Null => "null".to_string(),
+        Node::Bool(value) => value.to_string(),
+        Node::Integer(value) => value.to_string(),
+        Node::Float(value) => float(*value),
+        Node::String(value) => string(value),
+        Node::Bytes(bytes) => format!("!!binary {}", base64(bytes)),
+        Node::Map { anchor, .. } => match anchor {
+            Some(anchor) => format!("&o{} {{}}", anchor),
+            None => "{}".to_string(),
+        },
+        Node::Seq(_) => "[]".to_string(),
+        Node::Alias(index) => format!("*o{}", index),
+    }
+}
+
+fn float(value: f64) -> String {
+    match value {
+        v if v.is_nan() => ".nan".to_string(),
+        v if v.is_infinite() && v > 0.0 => ".inf".to_string(),
+        v if v.is_infinite() => "-.inf".to_string(),
+        v if v.fract() == 0.0 && v.abs() < 1e15 => format!("{:.1}", v),
+        v => v.to_string(),
+    }
+}
+
+// Strings are written as plain scalars unless YAML would read them as
+// something else, in which case they are double quoted.
+fn string(value: &str) -> String {
+    if is_plain(value) {
+        return value.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn is_plain(value: &str) -> bool {
+    const RESERVED: &[&str] = &[
+        "null", "Null", "NULL", "~", "true", "True", "TRUE", "false", "False", "FALSE", "yes",
+        "Yes", "YES", "no", "No", "NO", "on", "On", "ON", "off", "Off", "OFF", "y", "Y", "n", "N",
+    ];
+    let Some(first) = value.chars().next() else {
+        return false;
+    };
+    !(RESERVED.contains(&value)
+        || "-?:,[]{}#&*!|>'\"%@`".contains(first)
+        || first.is_whitespace()
+        || value.ends_with(char::is_whitespace)
+        || value.ends_with(':')
+        || value.contains(": ")
+        || value.contains(" #")
+        || value.chars().any(|c| c.is_control())
+        || looks_numeric(value))
+}
+
+fn looks_numeric(value: &str) -> bool {
+    let lower = value.to_ascii_lowercase();
+    value.parse::<f64>().is_ok()
+        || lower.starts_with("0x")
+        || lower.starts_with("0o")
+        || [".inf", "-.inf", "+.inf", ".nan"].contains(&lower.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::MAX_NESTING_DEPTH,
+        export::yaml::to_yaml,
+        raw_model::{builder::ContextBuilder, nib::NibFile},
+    };
+
+    #[test]
+    fn anchors_and_plain_strings() {
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let array = builder.reserve_object();
+        let view = builder.reserve_object();
+        let title = builder.reserve_object();
+
+        let values = vec![
+            builder.object_value("UINibTopLevelObjectsKey", array),
+            builder.object_value("UINibObjectsKey", view),
+        ];
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, values);
+        let values = vec![
+            builder.bool_value("NSInlinedValue", true),
+            builder.object_value("UINibEncoderEmptyKey", view),
+        ];
+        let class = builder.class("NSArray", &[]);
+        builder.set_object(array, class, values);
+        let values = vec![
+            builder.object_value("UITitle", title),
+            builder.double_value("UIAlpha", 1.0),
+            builder.bytes_value("UIIdentifier", b"true".to_vec()),
+            builder.object_value("UISuperview", root),
+        ];
+        let class = builder.class("UIView", &[]);
+        builder.set_object(view, class, values);
+        let values = vec![builder.bytes_value("NS.bytes", b"Log in".to_vec())];
+        let class = builder.class("NSString", &[]);
+        builder.set_object(title, class, values);
+
+        let yaml = to_yaml(&builder.build().unwrap()).unwrap();
+        assert_eq!(
+            "--- &o0
+$class: NSObject
+UINibObjectsKey: &o2
+  $class: UIView
+  UIAlpha: 1.0
+  UIIdentifier: \"true\"
+  UISuperview: *o0
+  UITitle: Log in
+UINibTopLevelObjectsKey:
+  $class: NSArray
+  $items:
+    - *o2
+",
+            yaml
+        );
+    }
+
+    #[test]
+    fn nested_archives() {
+        // Each archive holds the previous one as a byte value.
+        let mut bytes = b"payload".to_vec();
+        for _ in 0..=MAX_NESTING_DEPTH + 1 {
+            let mut builder = ContextBuilder::new();
+            let root = builder.reserve_object();
+            let values = vec![builder.bytes_value("UIData", bytes)];
+            let class = builder.class("UIView", &[]);
+            builder.set_object(root, class, values);
+            bytes = NibFile::from_context(&builder.build().unwrap())
+                .buffer()
+                .to_vec();
+        }
+
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let values = vec![builder.bytes_value("UIData", bytes)];
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, values);
+        let yaml = to_yaml(&builder.build().unwrap()).unwrap();
+
+        assert!(yaml.starts_with(
+            "---
+$class: NSObject
+UIData:
+  $archive:
+    $class: UIView
+    UIData:
+      $archive:
+"
+        ));
+        assert_eq!(MAX_NESTING_DEPTH, yaml.matches("$archive").count());
+        assert!(yaml.contains("UIData: !!binary TklCQXJjaGl2ZQ"));
+        assert!(!yaml.contains("payload"));
+    }
+}
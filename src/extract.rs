@@ -0,0 +1,190 @@
+use std::collections::HashSet;
+
+use crate::{
+    data::Context,
+    export::printable,
+    format::{detect_format, Format},
+    raw_model::raw_value::TYPE_DATA,
+};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const JPEG_SIGNATURE: &[u8] = b"\xff\xd8\xff";
+const PDF_SIGNATURE: &[u8] = b"%PDF-";
+
+// What a data value holds, judged by its leading bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Png,
+    Jpeg,
+    Pdf,
+    // Any binary plist, keyed archives included.
+    BinaryPlist,
+    NibArchive,
+    // UTF-8 without control characters other than whitespace.
+    Text,
+    Data,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 7] = [
+        Kind::Png,
+        Kind::Jpeg,
+        Kind::Pdf,
+        Kind::BinaryPlist,
+        Kind::NibArchive,
+        Kind::Text,
+        Kind::Data,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Png => "png",
+            Kind::Jpeg => "jpeg",
+            Kind::Pdf => "pdf",
+            Kind::BinaryPlist => "bplist",
+            Kind::NibArchive => "nib",
+            Kind::Text => "utf8",
+            Kind::Data => "data",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Kind> {
+        Kind::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Kind::Png => "png",
+            Kind::Jpeg => "jpg",
+            Kind::Pdf => "pdf",
+            Kind::BinaryPlist => "plist",
+            Kind::NibArchive => "nib",
+            Kind::Text => "txt",
+            Kind::Data => "bin",
+        }
+    }
+}
+
+pub fn sniff(bytes: &[u8]) -> Kind {
+    if bytes.starts_with(PNG_SIGNATURE) {
+        return Kind::Png;
+    }
+    if bytes.starts_with(JPEG_SIGNATURE) {
+        return Kind::Jpeg;
+    }
+    if bytes.starts_with(PDF_SIGNATURE) {
+        return Kind::Pdf;
+    }
+    match detect_format(bytes) {
+        Format::NibArchive { .. } => return Kind::NibArchive,
+        Format::BinaryPlist | Format::KeyedArchive => return Kind::BinaryPlist,
+        _ => {}
+    }
+    match printable(bytes) {
+        Some(_) => Kind::Text,
+        None => Kind::Data,
+    }
+}
+
+// A data value and the file name it is extracted to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blob<'a> {
+    pub object: usize,
+    pub key: String,
+    // Index of the value in `Context::values`.
+    pub value: usize,
+    pub kind: Kind,
+    pub bytes: &'a [u8],
+    // "<object>-<key>.<extension>", with characters other than letters,
+    // digits, '.', '-' and '_' in the key replaced, and a number appended
+    // when an object has the same key twice.
+    pub file: String,
+}
+
+// Every non-empty data value, in the order of the values table.
+pub fn blobs(context: &Context) -> Vec<Blob<'_>> {
+    let mut blobs = vec![];
+    let mut files = HashSet::new();
+    for index in 0..context.objects.len() {
+        let start = context.objects[index]
+            .value_index
+            .value()
+            .unwrap_or_default() as usize;
+        for (position, (key, value)) in context.named_values(index).into_iter().enumerate() {
+            if value.value_type != TYPE_DATA || value.data.is_empty() {
+                continue;
+            }
+            let kind = sniff(&value.data);
+            let stem: String = format!("{}-{}", index, key)
+                .chars()
+                .map(|c| match c.is_ascii_alphanumeric() || ".-_".contains(c) {
+                    true => c,
+                    false => '_',
+                })
+                .collect();
+            let mut file = format!("{}.{}", stem, kind.extension());
+            let mut copy = 1;
+            while !files.insert(file.clone()) {
+                copy += 1;
+                file = format!("{}-{}.{}", stem, copy, kind.extension());
+            }
+            blobs.push(Blob {
+                object: index,
+                key,
+                value: start + position,
+                kind,
+                bytes: &value.data,
+                file,
+            });
+        }
+    }
+    blobs
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        extract::{blobs, sniff, Kind},
+        raw_model::{builder::ContextBuilder, nib::NibFile},
+    };
+
+    #[test]
+    fn extract() {
+        let mut builder = ContextBuilder::new();
+        let image = builder.reserve_object();
+        let values = vec![
+            builder.bytes_value("UIImageData", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec()),
+            builder.bytes_value("UIImageData", b"\xff\xd8\xff\xe0".to_vec()),
+            builder.bytes_value("UIImageData", b"\x89PNG\r\n\x1a\n".to_vec()),
+            builder.bytes_value("UI Resource/Name", b"logo".to_vec()),
+            builder.bytes_value("UIEmpty", vec![]),
+            builder.int_value("UITag", 1),
+        ];
+        let class = builder.class("UIImage", &[]);
+        builder.set_object(image, class, values);
+        let context = builder.build().unwrap();
+
+        let nib = NibFile::from_context(&context);
+        assert_eq!(Kind::NibArchive, sniff(nib.buffer()));
+        assert_eq!(Kind::BinaryPlist, sniff(b"bplist00\xd0\x08"));
+        assert_eq!(Kind::Pdf, sniff(b"%PDF-1.7\n"));
+        assert_eq!(Kind::Text, sniff("Grüße\n".as_bytes()));
+        assert_eq!(Kind::Data, sniff(&[0, 0, 128, 63]));
+        assert_eq!(Some(Kind::BinaryPlist), Kind::from_name("bplist"));
+
+        let blobs = blobs(&context);
+        assert_eq!(
+            vec![
+                ("0-UIImageData.png", Kind::Png, 0),
+                ("0-UIImageData.jpg", Kind::Jpeg, 1),
+                ("0-UIImageData-2.png", Kind::Png, 2),
+                ("0-UI_Resource_Name.txt", Kind::Text, 3),
+            ],
+            blobs
+                .iter()
+                .map(|blob| (blob.file.as_str(), blob.kind, blob.value))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(b"logo", blobs[3].bytes);
+    }
+}
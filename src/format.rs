@@ -0,0 +1,183 @@
+use std::fmt::Display;
+
+use crate::{
+    cast::cast_to_u32,
+    plist::{ascii::is_ascii_plist, bplist, xml::is_xml_plist},
+    raw_model::nib::FILE_ID,
+    zip,
+};
+
+// Signatures of a NeXT typedstream, as written by NSArchiver. The order of
+// the words gives the byte order of the stream.
+pub const TYPEDSTREAM_LITTLE_ENDIAN: &[u8] = b"streamtyped";
+pub const TYPEDSTREAM_BIG_ENDIAN: &[u8] = b"typedstream";
+
+// How much of a text file is looked at to identify it.
+const XML_SNIFF_LENGTH: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    // The two header constants following the identifier, 1 and 9 in files
+    // written by current versions of ibtool.
+    NibArchive { version: (u32, u32) },
+    // A binary plist written by NSKeyedArchiver.
+    KeyedArchive,
+    // Any other binary plist.
+    BinaryPlist,
+    XmlPlist,
+    // An old-style OpenStep plist, such as classes.nib.
+    AsciiPlist,
+    Xib,
+    Storyboard,
+    Typedstream { big_endian: bool },
+    Zip,
+    Unknown,
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::NibArchive { version } => {
+                write!(f, "NIBArchive (version {}.{})", version.0, version.1)
+            }
+            Format::KeyedArchive => write!(f, "keyed archive (binary plist)"),
+            Format::BinaryPlist => write!(f, "binary plist"),
+            Format::XmlPlist => write!(f, "XML plist"),
+            Format::AsciiPlist => write!(f, "ASCII plist"),
+            Format::Xib => write!(f, "XIB"),
+            Format::Storyboard => write!(f, "storyboard source"),
+            Format::Typedstream { big_endian: false } => write!(f, "typedstream"),
+            Format::Typedstream { big_endian: true } => write!(f, "typedstream (big endian)"),
+            Format::Zip => write!(f, "zip archive"),
+            Format::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+// Identifies a file by its leading bytes.
+pub fn detect_format(buffer: &[u8]) -> Format {
+    if buffer.starts_with(FILE_ID.as_bytes()) {
+        return match buffer.get(10..18) {
+            Some(constants) => Format::NibArchive {
+                version: (
+                    cast_to_u32(&constants[..4]).unwrap_or_default(),
+                    cast_to_u32(&constants[4..]).unwrap_or_default(),
+                ),
+            },
+            // Truncated, but still meant to be a NIBArchive.
+            None => Format::NibArchive { version: (0, 0) },
+        };
+    }
+    if bplist::is_bplist(buffer) {
+        let keys = bplist::top_level_keys(buffer).unwrap_or_default();
+        return if keys.iter().any(|key| key == "$archiver") {
+            Format::KeyedArchive
+        } else {
+            Format::BinaryPlist
+        };
+    }
+    if zip::is_zip(buffer) {
+        return Format::Zip;
+    }
+    // A version byte, the length of the signature, then the signature.
+    if let [_, 11, signature @ ..] = buffer {
+        if signature.starts_with(TYPEDSTREAM_LITTLE_ENDIAN) {
+            return Format::Typedstream { big_endian: false };
+        }
+        if signature.starts_with(TYPEDSTREAM_BIG_ENDIAN) {
+            return Format::Typedstream { big_endian: true };
+        }
+    }
+
+    let head = &buffer[..buffer.len().min(XML_SNIFF_LENGTH)];
+    if is_xml_plist(head) {
+        return Format::XmlPlist;
+    }
+    if is_ascii_plist(head) {
+        return Format::AsciiPlist;
+    }
+    let text = String::from_utf8_lossy(head);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    if text.starts_with('<') {
+        // Interface Builder documents are typed "...CocoaTouch.XIB",
+        // "...CocoaTouch.Storyboard.XIB", "...Cocoa.XIB" and so on.
+        if let Some(start) = text.find("<document").or_else(|| text.find("<archive")) {
+            let tag = &text[start..];
+            let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+            if tag.contains(".Storyboard.XIB") {
+                return Format::Storyboard;
+            }
+            if tag.contains("InterfaceBuilder") {
+                return Format::Xib;
+            }
+        }
+    }
+    Format::Unknown
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        format::{detect_format, Format},
+        plist::{bplist, keyed_archive, Plist},
+        raw_model::{builder::ContextBuilder, nib::NibFile},
+    };
+
+    #[test]
+    fn formats() {
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, vec![]);
+        let context = builder.build().unwrap();
+
+        assert_eq!(
+            Format::NibArchive { version: (1, 9) },
+            detect_format(NibFile::from_context(&context).buffer())
+        );
+        assert_eq!(
+            Format::KeyedArchive,
+            detect_format(&keyed_archive::to_buffer(&context).unwrap())
+        );
+        assert_eq!(
+            Format::BinaryPlist,
+            detect_format(&bplist::to_buffer(&Plist::Boolean(true)))
+        );
+        // Only the key of the top dictionary counts, not strings elsewhere.
+        let mentions = Plist::Dictionary(vec![(
+            "name".to_string(),
+            Plist::Array(vec![
+                Plist::String("$archiver".to_string()),
+                Plist::String("NSKeyedArchiver".to_string()),
+            ]),
+        )]);
+        assert_eq!(
+            Format::BinaryPlist,
+            detect_format(&bplist::to_buffer(&mentions))
+        );
+        assert_eq!(
+            Format::XmlPlist,
+            detect_format(b"<?xml version=\"1.0\"?>\n<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"\">\n<plist version=\"1.0\"><true/></plist>")
+        );
+        assert_eq!(
+            Format::Xib,
+            detect_format(b"<?xml version=\"1.0\"?>\n<document type=\"com.apple.InterfaceBuilder3.CocoaTouch.XIB\" version=\"3.0\">")
+        );
+        assert_eq!(
+            Format::Storyboard,
+            detect_format(
+                b"<document type=\"com.apple.InterfaceBuilder3.CocoaTouch.Storyboard.XIB\">"
+            )
+        );
+        assert_eq!(
+            Format::Typedstream { big_endian: false },
+            detect_format(b"\x04\x0bstreamtyped\x81\xe8\x03")
+        );
+        assert_eq!(
+            Format::AsciiPlist,
+            detect_format(b"{\n    IBClasses = ();\n    IBVersion = 1;\n}")
+        );
+        assert_eq!(Format::Zip, detect_format(b"PK\x03\x04\x14\x00"));
+        assert_eq!(Format::Unknown, detect_format(b"<html>"));
+    }
+}
@@ -0,0 +1,553 @@
+use std::ops::Range;
+
+use crate::{
+    cast::cast_to_u32,
+    data::Data,
+    export::scalar_text,
+    raw_model::{
+        nib::{NibFile, SectionKind, FILE_ID, HEADER_SIZE},
+        raw_class::RawClass,
+        raw_key::RawKey,
+        raw_object::RawObject,
+        raw_value::{type_name, RawValue},
+        BufferView, ParseError,
+    },
+};
+
+// Header fields after the identifier, as read by `NibFile::from_buffer`.
+const HEADER_FIELDS: [&str; 10] = [
+    "version constant one",
+    "version constant two",
+    "object count",
+    "object offset",
+    "key count",
+    "key offset",
+    "value count",
+    "value offset",
+    "class count",
+    "class offset",
+];
+
+const BYTES_PER_LINE: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegionKind {
+    Header,
+    // A record of a section, by its index in the section.
+    Record(SectionKind, usize),
+    // Bytes no section covers, before the end of the last one.
+    Gap,
+    // Bytes after the end of the last section.
+    Trailing,
+    // Where a record or the header failed to decode, covering no bytes.
+    Invalid(String),
+}
+
+// Bytes of a record holding one field, with the field decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: &'static str,
+    pub range: Range<usize>,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub kind: RegionKind,
+    pub range: Range<usize>,
+    pub fields: Vec<Field>,
+    // Whether the region starts inside a record before it.
+    pub overlaps: bool,
+}
+
+impl Region {
+    pub fn label(&self) -> String {
+        match &self.kind {
+            RegionKind::Header => "header".to_string(),
+            RegionKind::Record(kind, index) => format!("{} #{}", record_name(*kind), index),
+            RegionKind::Gap => "gap".to_string(),
+            RegionKind::Trailing => "trailing bytes".to_string(),
+            RegionKind::Invalid(reason) => format!("error: {}", reason),
+        }
+    }
+}
+
+// Splits a NIBArchive into its header, records, gaps and trailing bytes, in
+// buffer order. Broken files are split as far as they can be: a section
+// stops at the first record that fails to decode, and the bytes after it
+// show up as gaps.
+pub fn regions(buffer: &[u8]) -> Vec<Region> {
+    let mut regions = vec![];
+    let header = header(buffer, &mut regions);
+    let field = |index: usize| header.get(index).copied().flatten();
+
+    let keys: Vec<(usize, RawKey)> =
+        walk(buffer, field(5), field(4), &mut regions, SectionKind::Keys);
+    let classes: Vec<(usize, RawClass)> = walk(
+        buffer,
+        field(9),
+        field(8),
+        &mut regions,
+        SectionKind::Classes,
+    );
+    let objects: Vec<(usize, RawObject)> = walk(
+        buffer,
+        field(3),
+        field(2),
+        &mut regions,
+        SectionKind::Objects,
+    );
+    let values: Vec<(usize, RawValue)> = walk(
+        buffer,
+        field(7),
+        field(6),
+        &mut regions,
+        SectionKind::Values,
+    );
+
+    let key_names: Vec<String> = keys
+        .iter()
+        .map(|(_, key)| String::from_utf8_lossy(&key.key_bytes).into_owned())
+        .collect();
+    let class_names: Vec<String> = classes
+        .iter()
+        .map(|(_, class)| {
+            String::from_utf8_lossy(&class.class_name)
+                .trim_end_matches('\0')
+                .to_string()
+        })
+        .collect();
+    let named = |names: &[String], index: Option<u32>| match index {
+        Some(index) => match names.get(index as usize) {
+            Some(name) => format!("{} ({})", index, name),
+            None => format!("{} (out of range)", index),
+        },
+        None => "invalid".to_string(),
+    };
+
+    for (index, (offset, key)) in keys.iter().enumerate() {
+        let length = key.size() - key.key_bytes.len();
+        let fields = vec![
+            varint("length", *offset, length, key.key_bytes.len().to_string()),
+            Field {
+                name: "name",
+                range: offset + length..offset + key.size(),
+                value: format!("{:?}", key_names[index]),
+            },
+        ];
+        regions.push(record(
+            SectionKind::Keys,
+            index,
+            *offset,
+            key.size(),
+            fields,
+        ));
+    }
+
+    for (index, (offset, class)) in classes.iter().enumerate() {
+        let extra = class.extra_values_count.size();
+        let length = class.size() - extra - class.extra_values.len() - class.class_name.len();
+        let mut fields = vec![
+            varint(
+                "name length",
+                *offset,
+                length,
+                class.class_name.len().to_string(),
+            ),
+            varint(
+                "extra value count",
+                offset + length,
+                extra,
+                (class.extra_values.len() / 4).to_string(),
+            ),
+        ];
+        let mut start = offset + length + extra;
+        if !class.extra_values.is_empty() {
+            let extras: Vec<String> = class
+                .extra_values
+                .chunks(4)
+                .map(|chunk| i32::from_le_bytes(chunk.try_into().unwrap_or([0; 4])).to_string())
+                .collect();
+            fields.push(Field {
+                name: "extra values",
+                range: start..start + class.extra_values.len(),
+                value: extras.join(", "),
+            });
+            start += class.extra_values.len();
+        }
+        fields.push(Field {
+            name: "name",
+            range: start..start + class.class_name.len(),
+            value: format!("{:?}", String::from_utf8_lossy(&class.class_name)),
+        });
+        regions.push(record(
+            SectionKind::Classes,
+            index,
+            *offset,
+            class.size(),
+            fields,
+        ));
+    }
+
+    for (index, (offset, object)) in objects.iter().enumerate() {
+        let mut start = *offset;
+        let mut fields = vec![];
+        for (name, value, text) in [
+            (
+                "class index",
+                &object.class_index,
+                named(&class_names, object.class_index.value().ok()),
+            ),
+            (
+                "value index",
+                &object.value_index,
+                display(object.value_index.value()),
+            ),
+            (
+                "value count",
+                &object.value_count,
+                display(object.value_count.value()),
+            ),
+        ] {
+            fields.push(varint(name, start, value.size(), text));
+            start += value.size();
+        }
+        regions.push(record(
+            SectionKind::Objects,
+            index,
+            *offset,
+            object.size(),
+            fields,
+        ));
+    }
+
+    for (index, (offset, value)) in values.iter().enumerate() {
+        let key_size = value.key_index.size();
+        let mut fields = vec![
+            varint(
+                "key index",
+                *offset,
+                key_size,
+                named(&key_names, value.key_index.value().ok()),
+            ),
+            Field {
+                name: "type",
+                range: offset + key_size..offset + key_size + 1,
+                value: format!("{} ({})", value.value_type, type_name(value.value_type)),
+            },
+        ];
+        let mut start = offset + key_size + 1;
+        let length = value.size() - key_size - 1 - value.data.len();
+        if length > 0 {
+            fields.push(varint(
+                "length",
+                start,
+                length,
+                value.data.len().to_string(),
+            ));
+            start += length;
+        }
+        if !value.data.is_empty() {
+            let text = match value.object_reference() {
+                Some(target) => format!("object #{}", target),
+                None => Data::from_scalar(value)
+                    .map(|data| scalar_text(&data))
+                    .unwrap_or_default(),
+            };
+            fields.push(Field {
+                name: "data",
+                range: start..start + value.data.len(),
+                value: text,
+            });
+        }
+        regions.push(record(
+            SectionKind::Values,
+            index,
+            *offset,
+            value.size(),
+            fields,
+        ));
+    }
+
+    sort(&mut regions);
+    let mut end = 0;
+    for region in &mut regions {
+        region.overlaps = !region.range.is_empty() && region.range.start < end;
+        end = end.max(region.range.end);
+    }
+
+    // Uncovered bytes, found in one pass over the sorted regions.
+    let mut uncovered = vec![];
+    let mut covered = 0;
+    for region in &regions {
+        if region.range.start > covered {
+            uncovered.push(covered..region.range.start);
+        }
+        covered = covered.max(region.range.end);
+    }
+    for range in uncovered {
+        regions.push(Region {
+            kind: RegionKind::Gap,
+            range,
+            fields: vec![],
+            overlaps: false,
+        });
+    }
+    if covered < buffer.len() {
+        regions.push(Region {
+            kind: RegionKind::Trailing,
+            range: covered..buffer.len(),
+            fields: vec![],
+            overlaps: false,
+        });
+    }
+    sort(&mut regions);
+    regions
+}
+
+// By offset, errors before a record starting at the same offset.
+fn sort(regions: &mut [Region]) {
+    regions.sort_by_key(|region| {
+        (
+            region.range.start,
+            !matches!(region.kind, RegionKind::Invalid(_)),
+        )
+    });
+}
+
+// Adds the header region and returns its ten numeric fields, None for those
+// the buffer is too short for.
+fn header(buffer: &[u8], regions: &mut Vec<Region>) -> Vec<Option<u32>> {
+    let mut fields = vec![];
+    let id_length = FILE_ID.len().min(buffer.len());
+    fields.push(Field {
+        name: "file identifier",
+        range: 0..id_length,
+        value: format!("{:?}", String::from_utf8_lossy(&buffer[..id_length])),
+    });
+    let mut numbers = vec![];
+    for (position, name) in HEADER_FIELDS.iter().enumerate() {
+        let start = FILE_ID.len() + position * 4;
+        let number = buffer
+            .get(start..start + 4)
+            .and_then(|bytes| cast_to_u32(bytes).ok());
+        if let Some(number) = number {
+            fields.push(Field {
+                name,
+                range: start..start + 4,
+                value: number.to_string(),
+            });
+        }
+        numbers.push(number);
+    }
+    let end = HEADER_SIZE.min(buffer.len());
+    if let Err(error) = NibFile::from_buffer(buffer[..end].to_vec()) {
+        regions.push(invalid(end.min(error.rel_offset), error.reason));
+    }
+    regions.push(Region {
+        kind: RegionKind::Header,
+        range: 0..fields.last().map_or(0, |field| field.range.end),
+        fields,
+        overlaps: false,
+    });
+    numbers
+}
+
+// Decodes the records of a section until the count is reached or one fails.
+fn walk<T: BufferView>(
+    buffer: &[u8],
+    offset: Option<u32>,
+    count: Option<u32>,
+    regions: &mut Vec<Region>,
+    kind: SectionKind,
+) -> Vec<(usize, T)> {
+    let (Some(offset), Some(count)) = (offset, count) else {
+        return vec![];
+    };
+    let name = record_name(kind);
+    let mut records = vec![];
+    let mut index = offset as usize;
+    // Every record takes at least one byte, so this ends at the end of the
+    // buffer whatever the count says.
+    while records.len() < count as usize {
+        if index >= buffer.len() {
+            let reason = format!("{} #{} is past the end of the file", name, records.len());
+            regions.push(invalid(buffer.len(), reason));
+            break;
+        }
+        match T::from_buffer(buffer, index) {
+            Ok(record) => {
+                let size = record.size();
+                records.push((index, record));
+                index += size;
+            }
+            Err(ParseError { reason, .. }) => {
+                let reason = format!("{} #{} does not decode: {}", name, records.len(), reason);
+                regions.push(invalid(index, reason));
+                break;
+            }
+        }
+    }
+    records
+}
+
+fn record_name(kind: SectionKind) -> &'static str {
+    match kind {
+        SectionKind::Objects => "object",
+        SectionKind::Keys => "key",
+        SectionKind::Values => "value",
+        SectionKind::Classes => "class",
+    }
+}
+
+fn invalid(offset: usize, reason: String) -> Region {
+    Region {
+        kind: RegionKind::Invalid(reason),
+        range: offset..offset,
+        fields: vec![],
+        overlaps: false,
+    }
+}
+
+fn record(
+    kind: SectionKind,
+    index: usize,
+    offset: usize,
+    size: usize,
+    fields: Vec<Field>,
+) -> Region {
+    Region {
+        kind: RegionKind::Record(kind, index),
+        range: offset..offset + size,
+        fields,
+        overlaps: false,
+    }
+}
+
+fn varint(name: &'static str, offset: usize, size: usize, value: String) -> Field {
+    Field {
+        name,
+        range: offset..offset + size,
+        value: format!("{} (varint, {} bytes)", value, size),
+    }
+}
+
+fn display(value: Result<u32, String>) -> String {
+    value.map_or("invalid".to_string(), |value| value.to_string())
+}
+
+// The regions as text: a line per region, then its bytes 16 per line with
+// the field they belong to.
+pub fn annotate(buffer: &[u8]) -> String {
+    let mut text = String::new();
+    for region in regions(buffer) {
+        if let RegionKind::Invalid(_) = region.kind {
+            text += &format!("{:08x}  {}\n", region.range.start, region.label());
+            continue;
+        }
+        let overlaps = match region.overlaps {
+            true => ", overlaps the record before",
+            false => "",
+        };
+        text += &format!(
+            "{:08x}  {} ({} bytes{})\n",
+            region.range.start,
+            region.label(),
+            region.range.len(),
+            overlaps
+        );
+        if region.fields.is_empty() {
+            text += &lines(buffer, region.range.clone(), "");
+        }
+        for field in &region.fields {
+            let label = format!("{}: {}", field.name, field.value);
+            text += &lines(buffer, field.range.clone(), &label);
+        }
+    }
+    text
+}
+
+fn lines(buffer: &[u8], range: Range<usize>, label: &str) -> String {
+    let bytes = &buffer[range.start.min(buffer.len())..range.end.min(buffer.len())];
+    let mut text = String::new();
+    for (line, chunk) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let label = if line == 0 { label } else { "" };
+        let line = format!(
+            "{:08x}    {:<47}  {}",
+            range.start + line * BYTES_PER_LINE,
+            hex.join(" "),
+            label
+        );
+        text += line.trim_end();
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        hexdump::{annotate, regions, RegionKind},
+        raw_model::{builder::ContextBuilder, nib::NibFile, nib::SectionKind},
+    };
+
+    #[test]
+    fn annotated() {
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let values = vec![
+            builder.int_value("UITag", 7),
+            builder.bytes_value("UIText", b"Hi".to_vec()),
+        ];
+        let class = builder.class("UIView", &[]);
+        builder.set_object(root, class, values);
+        let nib = NibFile::from_context(&builder.build().unwrap());
+
+        let mut buffer = nib.buffer().to_vec();
+        buffer.extend([0xff, 0xfe]);
+        let regions = regions(&buffer);
+        let kinds: Vec<RegionKind> = regions.iter().map(|region| region.kind.clone()).collect();
+        assert_eq!(
+            vec![
+                RegionKind::Header,
+                RegionKind::Record(SectionKind::Objects, 0),
+                RegionKind::Record(SectionKind::Keys, 0),
+                RegionKind::Record(SectionKind::Keys, 1),
+                RegionKind::Record(SectionKind::Values, 0),
+                RegionKind::Record(SectionKind::Values, 1),
+                RegionKind::Record(SectionKind::Classes, 0),
+                RegionKind::Trailing,
+            ],
+            kinds
+        );
+        let text = annotate(&buffer);
+        assert!(text.contains("class index: 0 (UIView) (varint, 1 bytes)"));
+        assert!(text.contains("key index: 1 (UIText) (varint, 1 bytes)"));
+        assert!(text.contains("length: 2 (varint, 1 bytes)"));
+        assert!(text.contains("data: \"Hi\""));
+        assert!(text.contains("trailing bytes (2 bytes)"));
+
+        // A value type that does not exist stops the values section; the
+        // rest of it is reported as a gap.
+        let value = regions
+            .iter()
+            .find(|region| region.kind == RegionKind::Record(SectionKind::Values, 0))
+            .unwrap();
+        buffer[value.fields[1].range.start] = 42;
+        let broken = super::regions(&buffer);
+        assert!(broken.iter().any(|region| region.kind
+            == RegionKind::Invalid("value #0 does not decode: Invalid data type 42".to_string())));
+        assert!(broken.iter().any(|region| region.kind == RegionKind::Gap));
+        assert!(broken
+            .iter()
+            .any(|region| region.kind == RegionKind::Record(SectionKind::Classes, 0)));
+
+        // Too short for a header.
+        let truncated = super::regions(&buffer[..20]);
+        assert_eq!(RegionKind::Header, truncated[0].kind);
+        assert_eq!(3, truncated[0].fields.len());
+        assert!(truncated.iter().any(|region| region.kind
+            == RegionKind::Invalid("Unexpected end of buffer: header is incomplete".to_string())));
+    }
+}
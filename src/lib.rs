@@ -0,0 +1,22 @@
+pub mod batch;
+pub mod bundle;
+pub mod cast;
+pub mod cli;
+pub mod convert;
+pub mod data;
+pub mod display;
+pub mod export;
+pub mod extract;
+pub mod format;
+pub mod hexdump;
+pub mod plist;
+pub mod query;
+pub mod raw_model;
+pub mod references;
+pub mod regex;
+pub mod search;
+pub mod stats;
+pub mod typedstream;
+pub mod validate;
+pub mod xib;
+pub mod zip;
@@ -0,0 +1,310 @@
+use crate::raw_model::ParseError;
+
+use super::Plist;
+
+// Reads an old-style (OpenStep) property list, as found in the classes.nib
+// and info.nib files of pre-keyed-archiving nibs. Every scalar is a string,
+// apart from <hex> data.
+pub fn from_buffer(buffer: &[u8]) -> Result<Plist, ParseError> {
+    let mut parser = Parser { buffer, index: 0 };
+    parser.skip_bom();
+    let plist = parser.value()?;
+    parser.skip_space()?;
+    if parser.index < buffer.len() {
+        return Err(parser.error("Unexpected content after the property list"));
+    }
+    Ok(plist)
+}
+
+// Whether a buffer starts like an old-style property list: an array, or a
+// dictionary whose first key is followed by "=". JSON objects use ":".
+pub fn is_ascii_plist(buffer: &[u8]) -> bool {
+    let mut parser = Parser { buffer, index: 0 };
+    parser.skip_bom();
+    if parser.skip_space().is_err() {
+        return false;
+    }
+    match parser.peek() {
+        Some(b'(') => true,
+        Some(b'{') => {
+            parser.index += 1;
+            if parser.skip_space().is_err() {
+                return false;
+            }
+            if parser.peek() == Some(b'}') {
+                return true;
+            }
+            parser.string().is_ok() && parser.skip_space().is_ok() && parser.peek() == Some(b'=')
+        }
+        _ => false,
+    }
+}
+
+struct Parser<'a> {
+    buffer: &'a [u8],
+    index: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, reason: impl Into<String>) -> ParseError {
+        let line = self.buffer[..self.index.min(self.buffer.len())]
+            .iter()
+            .filter(|byte| **byte == b'\n')
+            .count()
+            + 1;
+        ParseError::new(self.index, 0, format!("{} on line {}", reason.into(), line))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.buffer.get(self.index).copied()
+    }
+
+    fn skip_bom(&mut self) {
+        if self.buffer.starts_with("\u{feff}".as_bytes()) {
+            self.index = 3;
+        }
+    }
+
+    // Skips whitespace and // or /* */ comments.
+    fn skip_space(&mut self) -> Result<(), ParseError> {
+        loop {
+            match (self.peek(), self.buffer.get(self.index + 1)) {
+                (Some(byte), _) if byte.is_ascii_whitespace() => self.index += 1,
+                (Some(b'/'), Some(b'/')) => {
+                    while self.peek().is_some_and(|byte| byte != b'\n') {
+                        self.index += 1;
+                    }
+                }
+                (Some(b'/'), Some(b'*')) => {
+                    let start = self.index;
+                    self.index += 2;
+                    while !self.buffer[self.index..].starts_with(b"*/") {
+                        if self.index >= self.buffer.len() {
+                            self.index = start;
+                            return Err(self.error("Unterminated comment"));
+                        }
+                        self.index += 1;
+                    }
+                    self.index += 2;
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), ParseError> {
+        self.skip_space()?;
+        if self.peek() != Some(byte) {
+            return Err(self.error(format!("Expected '{}'", byte as char)));
+        }
+        self.index += 1;
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Plist, ParseError> {
+        self.skip_space()?;
+        match self.peek() {
+            Some(b'{') => self.dictionary(),
+            Some(b'(') => self.array(),
+            Some(b'<') => self.data(),
+            Some(_) => Ok(Plist::String(self.string()?)),
+            None => Err(self.error("Unexpected end of property list")),
+        }
+    }
+
+    fn dictionary(&mut self) -> Result<Plist, ParseError> {
+        self.index += 1;
+        let mut entries = vec![];
+        loop {
+            self.skip_space()?;
+            if self.peek() == Some(b'}') {
+                self.index += 1;
+                return Ok(Plist::Dictionary(entries));
+            }
+            let key = self.string()?;
+            self.expect(b'=')?;
+            let value = self.value()?;
+            self.expect(b';')?;
+            entries.push((key, value));
+        }
+    }
+
+    fn array(&mut self) -> Result<Plist, ParseError> {
+        self.index += 1;
+        let mut items = vec![];
+        loop {
+            self.skip_space()?;
+            if self.peek() == Some(b')') {
+                self.index += 1;
+                return Ok(Plist::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_space()?;
+            match self.peek() {
+                Some(b',') => self.index += 1,
+                Some(b')') => {}
+                _ => return Err(self.error("Expected ',' or ')'")),
+            }
+        }
+    }
+
+    fn data(&mut self) -> Result<Plist, ParseError> {
+        self.index += 1;
+        let mut bytes = vec![];
+        let mut high: Option<u8> = None;
+        loop {
+            let Some(byte) = self.peek() else {
+                return Err(self.error("Unterminated data"));
+            };
+            self.index += 1;
+            let nibble = match byte {
+                b'>' if high.is_none() => return Ok(Plist::Data(bytes)),
+                _ if byte.is_ascii_whitespace() => continue,
+                b'0'..=b'9' => byte - b'0',
+                b'a'..=b'f' => byte - b'a' + 10,
+                b'A'..=b'F' => byte - b'A' + 10,
+                _ => {
+                    self.index -= 1;
+                    return Err(self.error("Invalid hex digit in data"));
+                }
+            };
+            match high.take() {
+                Some(high) => bytes.push(high << 4 | nibble),
+                None => high = Some(nibble),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(quote @ (b'"' | b'\'')) => self.quoted(quote),
+            _ => {
+                let start = self.index;
+                while self
+                    .peek()
+                    .is_some_and(|byte| byte.is_ascii_alphanumeric() || b"_$+/:.-".contains(&byte))
+                {
+                    self.index += 1;
+                }
+                if self.index == start {
+                    return Err(self.error("Expected a string"));
+                }
+                Ok(String::from_utf8_lossy(&self.buffer[start..self.index]).into_owned())
+            }
+        }
+    }
+
+    fn quoted(&mut self, quote: u8) -> Result<String, ParseError> {
+        let start = self.index;
+        self.index += 1;
+        let mut bytes = vec![];
+        loop {
+            let Some(byte) = self.peek() else {
+                self.index = start;
+                return Err(self.error("Unterminated string"));
+            };
+            self.index += 1;
+            if byte == quote {
+                return Ok(String::from_utf8_lossy(&bytes).into_owned());
+            }
+            if byte != b'\\' {
+                bytes.push(byte);
+                continue;
+            }
+
+            let Some(escape) = self.peek() else {
+                continue;
+            };
+            self.index += 1;
+            match escape {
+                b'a' => bytes.push(0x07),
+                b'b' => bytes.push(0x08),
+                b'f' => bytes.push(0x0c),
+                b'n' => bytes.push(b'\n'),
+                b'r' => bytes.push(b'\r'),
+                b't' => bytes.push(b'\t'),
+                b'v' => bytes.push(0x0b),
+                b'0'..=b'7' => {
+                    // Up to three octal digits.
+                    let mut value = (escape - b'0') as u32;
+                    for _ in 0..2 {
+                        match self.peek() {
+                            Some(digit @ b'0'..=b'7') => {
+                                value = value * 8 + (digit - b'0') as u32;
+                                self.index += 1;
+                            }
+                            _ => break,
+                        }
+                    }
+                    bytes.push(value as u8);
+                }
+                b'U' | b'u' => {
+                    let digits = self.buffer.get(self.index..self.index + 4);
+                    let character = digits
+                        .and_then(|digits| std::str::from_utf8(digits).ok())
+                        .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                        .and_then(char::from_u32)
+                        .ok_or(self.error("Invalid \\U escape"))?;
+                    self.index += 4;
+                    let mut encoded = [0; 4];
+                    bytes.extend(character.encode_utf8(&mut encoded).as_bytes());
+                }
+                other => bytes.push(other),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::plist::{
+        ascii::{from_buffer, is_ascii_plist},
+        Plist,
+    };
+
+    #[test]
+    fn classes_nib() {
+        let text = b"/* Interface Builder classes */
+{
+    IBClasses = (
+        {CLASS = FirstResponder; LANGUAGE = ObjC; SUPERCLASS = NSObject; },
+        {
+            ACTIONS = {login = id; };
+            CLASS = \"Login\\tController\";
+            OUTLETS = {window = NSWindow; };
+        }
+    );
+    IBVersion = 1;
+    Icon = <0aff 10>; // trailing comment
+}
+";
+        assert!(is_ascii_plist(text));
+        assert!(!is_ascii_plist(b"{\"IBVersion\": 1}"));
+
+        let plist = from_buffer(text).unwrap();
+        let classes = plist.get("IBClasses").and_then(Plist::as_array).unwrap();
+        assert_eq!(2, classes.len());
+        assert_eq!(
+            Some("FirstResponder"),
+            classes[0].get("CLASS").and_then(Plist::as_str)
+        );
+        assert_eq!(
+            Some("Login\tController"),
+            classes[1].get("CLASS").and_then(Plist::as_str)
+        );
+        assert_eq!(
+            Some("id"),
+            classes[1]
+                .get("ACTIONS")
+                .and_then(|actions| actions.get("login"))
+                .and_then(Plist::as_str)
+        );
+        assert_eq!(
+            Some(&Plist::Data(vec![0x0a, 0xff, 0x10])),
+            plist.get("Icon")
+        );
+
+        let error = from_buffer(b"{\n  IBVersion = 1\n}").unwrap_err();
+        assert_eq!("Expected ';' on line 3", error.reason);
+    }
+}
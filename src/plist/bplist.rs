@@ -0,0 +1,642 @@
+use std::cell::Cell;
+
+use crate::raw_model::ParseError;
+
+use super::Plist;
+
+const MAGIC: &[u8] = b"bplist00";
+const TRAILER_SIZE: usize = 32;
+const MAX_DEPTH: usize = 512;
+// Upper bound on the objects decoded in total. Objects referenced more than
+// once are decoded at every use, so a small file can describe a huge tree.
+const MAX_NODES: usize = 1_000_000;
+
+struct Reader<'a> {
+    buffer: &'a [u8],
+    object_ref_size: usize,
+    offsets: Vec<usize>,
+    nodes: Cell<usize>,
+}
+
+pub fn is_bplist(buffer: &[u8]) -> bool {
+    buffer.starts_with(MAGIC)
+}
+
+pub fn from_buffer(buffer: &[u8]) -> Result<Plist, ParseError> {
+    let (reader, top_object) = reader(buffer)?;
+    reader.object(top_object, 0)
+}
+
+// The keys of the top object when it is a dictionary, without decoding the
+// values. Used to tell keyed archives from other binary plists.
+pub fn top_level_keys(buffer: &[u8]) -> Result<Vec<String>, ParseError> {
+    let (reader, top_object) = reader(buffer)?;
+    reader.keys(top_object)
+}
+
+// Reads the trailer and offset table, returning a reader for the objects
+// and the index of the top object.
+fn reader(buffer: &[u8]) -> Result<(Reader<'_>, usize), ParseError> {
+    if !is_bplist(buffer) {
+        return Err(ParseError::new(0, 0, "Incorrect binary plist identifier"));
+    }
+    if buffer.len() < MAGIC.len() + TRAILER_SIZE {
+        return Err(ParseError::new(
+            0,
+            buffer.len(),
+            "Unexpected end of buffer: binary plist trailer missing",
+        ));
+    }
+
+    let trailer = buffer.len() - TRAILER_SIZE;
+    let offset_int_size = buffer[trailer + 6] as usize;
+    let object_ref_size = buffer[trailer + 7] as usize;
+    let object_count = read_uint(buffer, trailer + 8, 8)? as usize;
+    let top_object = read_uint(buffer, trailer + 16, 8)? as usize;
+    let offset_table_offset = read_uint(buffer, trailer + 24, 8)? as usize;
+
+    if !(1..=8).contains(&offset_int_size) || !(1..=8).contains(&object_ref_size) {
+        return Err(ParseError::new(
+            trailer,
+            trailer + 6,
+            format!(
+                "Invalid binary plist integer sizes: offsets {}, references {}",
+                offset_int_size, object_ref_size
+            ),
+        ));
+    }
+
+    let offset_table_end = object_count
+        .checked_mul(offset_int_size)
+        .and_then(|size| size.checked_add(offset_table_offset));
+    if offset_table_end.is_none_or(|end| end > trailer) {
+        return Err(ParseError::new(
+            trailer,
+            trailer + 24,
+            "Binary plist offset table overflows into trailer",
+        ));
+    }
+
+    let offsets = (0..object_count)
+        .map(|index| {
+            read_uint(
+                buffer,
+                offset_table_offset + index * offset_int_size,
+                offset_int_size,
+            )
+            .map(|offset| offset as usize)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let reader = Reader {
+        buffer,
+        object_ref_size,
+        offsets,
+        nodes: Cell::new(0),
+    };
+    Ok((reader, top_object))
+}
+
+fn read_uint(buffer: &[u8], offset: usize, size: usize) -> Result<u64, ParseError> {
+    let bytes = offset
+        .checked_add(size)
+        .and_then(|end| buffer.get(offset..end))
+        .ok_or(ParseError::new(
+            offset,
+            offset,
+            "Unexpected end of buffer: integer overflows buffer",
+        ))?;
+    Ok(bytes
+        .iter()
+        .fold(0u64, |value, byte| (value << 8) | *byte as u64))
+}
+
+// Serializes a property list. Every node becomes its own object, numbered in
+// pre-order so the top object is always object 0.
+pub fn to_buffer(plist: &Plist) -> Vec<u8> {
+    let object_count = count_objects(plist);
+    let object_ref_size = uint_size((object_count as u64).saturating_sub(1));
+
+    let mut writer = Writer {
+        buffer: MAGIC.to_vec(),
+        object_ref_size,
+        offsets: vec![0; object_count],
+        next: 0,
+    };
+    writer.object(plist);
+
+    let offset_table_offset = writer.buffer.len();
+    let offset_int_size = uint_size(offset_table_offset as u64);
+    let mut buffer = writer.buffer;
+    for offset in writer.offsets {
+        write_uint(&mut buffer, offset as u64, offset_int_size);
+    }
+
+    buffer.extend([0; 6]);
+    buffer.push(offset_int_size as u8);
+    buffer.push(object_ref_size as u8);
+    write_uint(&mut buffer, object_count as u64, 8);
+    write_uint(&mut buffer, 0, 8);
+    write_uint(&mut buffer, offset_table_offset as u64, 8);
+    buffer
+}
+
+fn count_objects(plist: &Plist) -> usize {
+    match plist {
+        Plist::Array(items) => 1 + items.iter().map(count_objects).sum::<usize>(),
+        Plist::Dictionary(entries) => {
+            1 + entries
+                .iter()
+                .map(|(_, value)| 1 + count_objects(value))
+                .sum::<usize>()
+        }
+        _ => 1,
+    }
+}
+
+fn uint_size(value: u64) -> usize {
+    match value {
+        0..=0xFF => 1,
+        0x100..=0xFFFF => 2,
+        0x1_0000..=0xFFFF_FFFF => 4,
+        _ => 8,
+    }
+}
+
+fn write_uint(buffer: &mut Vec<u8>, value: u64, size: usize) {
+    buffer.extend(&value.to_be_bytes()[8 - size..]);
+}
+
+struct Writer {
+    buffer: Vec<u8>,
+    object_ref_size: usize,
+    offsets: Vec<usize>,
+    next: usize,
+}
+
+impl Writer {
+    fn object(&mut self, plist: &Plist) -> usize {
+        let index = self.next;
+        self.next += 1;
+
+        // Containers reference children that are written after them, so their
+        // indices are assigned up front and the bodies written afterwards.
+        match plist {
+            Plist::Array(items) => {
+                let children = self.child_indices(items.iter());
+                self.offsets[index] = self.buffer.len();
+                self.marker(0xA, items.len());
+                for child in &children {
+                    write_uint(&mut self.buffer, *child as u64, self.object_ref_size);
+                }
+                for item in items {
+                    self.object(item);
+                }
+            }
+            Plist::Dictionary(entries) => {
+                let keys: Vec<Plist> = entries
+                    .iter()
+                    .map(|(key, _)| Plist::String(key.to_owned()))
+                    .collect();
+                let mut children = vec![];
+                let mut next = self.next;
+                for (key, (_, value)) in keys.iter().zip(entries) {
+                    children.push((next, next + 1));
+                    next += count_objects(key) + count_objects(value);
+                }
+                self.offsets[index] = self.buffer.len();
+                self.marker(0xD, entries.len());
+                for (key, _) in &children {
+                    write_uint(&mut self.buffer, *key as u64, self.object_ref_size);
+                }
+                for (_, value) in &children {
+                    write_uint(&mut self.buffer, *value as u64, self.object_ref_size);
+                }
+                for (key, (_, value)) in keys.iter().zip(entries) {
+                    self.object(key);
+                    self.object(value);
+                }
+            }
+            _ => {
+                self.offsets[index] = self.buffer.len();
+                self.scalar(plist);
+            }
+        }
+        index
+    }
+
+    fn child_indices<'a>(&self, items: impl Iterator<Item = &'a Plist>) -> Vec<usize> {
+        let mut next = self.next;
+        items
+            .map(|item| {
+                let index = next;
+                next += count_objects(item);
+                index
+            })
+            .collect()
+    }
+
+    fn marker(&mut self, kind: u8, count: usize) {
+        if count < 0x0F {
+            self.buffer.push((kind << 4) | count as u8);
+        } else {
+            self.buffer.push((kind << 4) | 0x0F);
+            let size = uint_size(count as u64);
+            self.buffer.push(0x10 | size.trailing_zeros() as u8);
+            write_uint(&mut self.buffer, count as u64, size);
+        }
+    }
+
+    fn scalar(&mut self, plist: &Plist) {
+        match plist {
+            Plist::Null => self.buffer.push(0x00),
+            Plist::Boolean(false) => self.buffer.push(0x08),
+            Plist::Boolean(true) => self.buffer.push(0x09),
+            Plist::Integer { value, size } => {
+                // Integers narrower than 8 bytes are read back as unsigned,
+                // so negative values always take the full width.
+                let size = if *value < 0 {
+                    8
+                } else {
+                    uint_size(*value as u64).max((*size).min(8))
+                };
+                self.buffer.push(0x10 | size.trailing_zeros() as u8);
+                write_uint(&mut self.buffer, *value as u64, size);
+            }
+            Plist::Real { value, size: 4 } => {
+                self.buffer.push(0x22);
+                self.buffer.extend((*value as f32).to_be_bytes());
+            }
+            Plist::Real { value, .. } => {
+                self.buffer.push(0x23);
+                self.buffer.extend(value.to_be_bytes());
+            }
+            Plist::Date(value) => {
+                self.buffer.push(0x33);
+                self.buffer.extend(value.to_be_bytes());
+            }
+            Plist::Data(bytes) => {
+                self.marker(0x4, bytes.len());
+                self.buffer.extend(bytes);
+            }
+            Plist::String(string) if string.is_ascii() => {
+                self.marker(0x5, string.len());
+                self.buffer.extend(string.as_bytes());
+            }
+            Plist::String(string) => {
+                let units: Vec<u16> = string.encode_utf16().collect();
+                self.marker(0x6, units.len());
+                for unit in units {
+                    self.buffer.extend(unit.to_be_bytes());
+                }
+            }
+            Plist::Uid(uid) => {
+                let size = uint_size(*uid);
+                self.buffer.push(0x80 | (size - 1) as u8);
+                write_uint(&mut self.buffer, *uid, size);
+            }
+            Plist::Array(_) | Plist::Dictionary(_) => unreachable!("containers are not scalars"),
+        }
+    }
+}
+
+impl Reader<'_> {
+    // The keys of a dictionary, or none for any other object.
+    fn keys(&self, index: usize) -> Result<Vec<String>, ParseError> {
+        let offset = *self.offsets.get(index).ok_or(ParseError::new(
+            0,
+            0,
+            format!("Object reference {} out of range", index),
+        ))?;
+        let marker = *self.buffer.get(offset).ok_or(ParseError::new(
+            offset,
+            offset,
+            "Unexpected end of buffer: object marker missing",
+        ))?;
+        if marker >> 4 != 0xD {
+            return Ok(vec![]);
+        }
+        let (count, start) = self.count(offset, (marker & 0x0F) as usize)?;
+        let mut keys = vec![];
+        for i in 0..count {
+            if let Plist::String(key) = self.object(self.reference(offset, start, i)?, 1)? {
+                keys.push(key);
+            }
+        }
+        Ok(keys)
+    }
+
+    fn object(&self, index: usize, depth: usize) -> Result<Plist, ParseError> {
+        let offset = *self.offsets.get(index).ok_or(ParseError::new(
+            0,
+            0,
+            format!(
+                "Object reference {} out of range ({} objects)",
+                index,
+                self.offsets.len()
+            ),
+        ))?;
+        if depth > MAX_DEPTH {
+            return Err(ParseError::new(
+                offset,
+                offset,
+                "Binary plist nesting too deep",
+            ));
+        }
+        self.nodes.set(self.nodes.get() + 1);
+        if self.nodes.get() > MAX_NODES {
+            return Err(ParseError::new(
+                offset,
+                offset,
+                format!("Binary plist expands to more than {} objects", MAX_NODES),
+            ));
+        }
+
+        let marker = *self.buffer.get(offset).ok_or(ParseError::new(
+            offset,
+            offset,
+            "Unexpected end of buffer: object marker missing",
+        ))?;
+        let low = (marker & 0x0F) as usize;
+        let error = |rel_offset: usize, reason: String| ParseError::new(offset, rel_offset, reason);
+
+        match marker >> 4 {
+            0x0 => match marker {
+                0x00 => Ok(Plist::Null),
+                0x08 => Ok(Plist::Boolean(false)),
+                0x09 => Ok(Plist::Boolean(true)),
+                _ => Err(error(offset, format!("Invalid marker 0x{:02x}", marker))),
+            },
+            0x1 => {
+                let size = 1 << low;
+                let bytes = self.bytes(offset, offset + 1, size)?;
+                // Integers of 16 bytes only ever carry a 64 bit value in the low half.
+                let bytes = &bytes[size.saturating_sub(8)..];
+                let unsigned = bytes
+                    .iter()
+                    .fold(0u64, |value, byte| (value << 8) | *byte as u64);
+                Ok(Plist::Integer {
+                    value: unsigned as i64,
+                    size,
+                })
+            }
+            0x2 => {
+                let size = 1 << low;
+                let bytes = self.bytes(offset, offset + 1, size)?;
+                let value = match size {
+                    4 => f32::from_be_bytes(bytes.try_into().unwrap()) as f64,
+                    8 => f64::from_be_bytes(bytes.try_into().unwrap()),
+                    _ => return Err(error(offset, format!("Invalid real size {}", size))),
+                };
+                Ok(Plist::Real { value, size })
+            }
+            0x3 => {
+                let bytes = self.bytes(offset, offset + 1, 8)?;
+                Ok(Plist::Date(f64::from_be_bytes(bytes.try_into().unwrap())))
+            }
+            0x4 => {
+                let (count, start) = self.count(offset, low)?;
+                Ok(Plist::Data(self.bytes(offset, start, count)?.to_vec()))
+            }
+            0x5 => {
+                let (count, start) = self.count(offset, low)?;
+                let bytes = self.bytes(offset, start, count)?;
+                Ok(Plist::String(bytes.iter().map(|b| *b as char).collect()))
+            }
+            0x6 => {
+                let (count, start) = self.count(offset, low)?;
+                let size = count
+                    .checked_mul(2)
+                    .ok_or_else(|| error(start, format!("String length {} overflows", count)))?;
+                let bytes = self.bytes(offset, start, size)?;
+                let units: Vec<u16> = bytes
+                    .chunks(2)
+                    .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                    .collect();
+                String::from_utf16(&units)
+                    .map(Plist::String)
+                    .map_err(|e| error(start, format!("Invalid UTF-16 string: {}", e)))
+            }
+            0x8 => {
+                let bytes = self.bytes(offset, offset + 1, low + 1)?;
+                Ok(Plist::Uid(
+                    bytes
+                        .iter()
+                        .fold(0u64, |value, byte| (value << 8) | *byte as u64),
+                ))
+            }
+            0xA | 0xC => {
+                let (count, start) = self.count(offset, low)?;
+                let items = (0..count)
+                    .map(|i| self.object(self.reference(offset, start, i)?, depth + 1))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Plist::Array(items))
+            }
+            0xD => {
+                let (count, start) = self.count(offset, low)?;
+                let mut entries = vec![];
+                for i in 0..count {
+                    let key = match self.object(self.reference(offset, start, i)?, depth + 1)? {
+                        Plist::String(key) => key,
+                        other => {
+                            return Err(error(
+                                start,
+                                format!("Unsupported dictionary key {:?}", other),
+                            ))
+                        }
+                    };
+                    let value =
+                        self.object(self.reference(offset, start, count + i)?, depth + 1)?;
+                    entries.push((key, value));
+                }
+                Ok(Plist::Dictionary(entries))
+            }
+            _ => Err(error(offset, format!("Invalid marker 0x{:02x}", marker))),
+        }
+    }
+
+    // Returns the element count of a variable length object and the offset
+    // of its first element.
+    fn count(&self, offset: usize, low: usize) -> Result<(usize, usize), ParseError> {
+        if low != 0x0F {
+            return Ok((low, offset + 1));
+        }
+        let int_marker = *self.buffer.get(offset + 1).ok_or(ParseError::new(
+            offset,
+            offset + 1,
+            "Unexpected end of buffer: object length missing",
+        ))?;
+        if int_marker >> 4 != 0x1 {
+            return Err(ParseError::new(
+                offset,
+                offset + 1,
+                format!("Invalid object length marker 0x{:02x}", int_marker),
+            ));
+        }
+        let size = 1 << (int_marker & 0x0F);
+        let count = read_uint(self.buffer, offset + 2, size)? as usize;
+        Ok((count, offset + 2 + size))
+    }
+
+    fn reference(&self, offset: usize, start: usize, index: usize) -> Result<usize, ParseError> {
+        let position = index
+            .checked_mul(self.object_ref_size)
+            .and_then(|position| position.checked_add(start))
+            .ok_or(ParseError::new(
+                offset,
+                start,
+                format!("Object reference {} overflows buffer", index),
+            ))?;
+        read_uint(self.buffer, position, self.object_ref_size)
+            .map(|reference| reference as usize)
+            .map_err(|e| ParseError::new(offset, position, e.reason))
+    }
+
+    fn bytes(&self, offset: usize, start: usize, count: usize) -> Result<&[u8], ParseError> {
+        start
+            .checked_add(count)
+            .and_then(|end| self.buffer.get(start..end))
+            .ok_or(ParseError::new(
+                offset,
+                start,
+                "Unexpected end of buffer: object data overflows buffer",
+            ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::plist::{
+        bplist::{from_buffer, to_buffer},
+        Plist,
+    };
+
+    // { "a": [1, "b"], "u": UID(3) }
+    const SAMPLE: &[u8] = &[
+        0x62, 0x70, 0x6c, 0x69, 0x73, 0x74, 0x30, 0x30, // bplist00
+        0xD2, 0x01, 0x02, 0x03, 0x04, // dict, keys 1 2, values 3 4
+        0x51, 0x61, // "a"
+        0x51, 0x75, // "u"
+        0xA2, 0x05, 0x06, // array [5, 6]
+        0x80, 0x03, // UID 3
+        0x10, 0x01, // int 1
+        0x51, 0x62, // "b"
+        0x08, 0x0D, 0x0F, 0x11, 0x14, 0x16, 0x18, // offset table
+        0, 0, 0, 0, 0, 0, 1, 1, // trailer sizes
+        0, 0, 0, 0, 0, 0, 0, 7, // object count
+        0, 0, 0, 0, 0, 0, 0, 0, // top object
+        0, 0, 0, 0, 0, 0, 0, 0x1A, // offset table offset
+    ];
+
+    #[test]
+    fn simple_dictionary() {
+        let plist = from_buffer(SAMPLE).unwrap();
+        assert_eq!(
+            Some(&Plist::Array(vec![
+                Plist::Integer { value: 1, size: 1 },
+                Plist::String("b".to_string())
+            ])),
+            plist.get("a")
+        );
+        assert_eq!(Some(3), plist.get("u").and_then(Plist::as_uid));
+    }
+
+    #[test]
+    fn write_read_round_trip() {
+        let plist = Plist::Dictionary(vec![
+            (
+                "int".to_string(),
+                Plist::Integer {
+                    value: 300,
+                    size: 4,
+                },
+            ),
+            (
+                "negative".to_string(),
+                Plist::Integer { value: -2, size: 8 },
+            ),
+            (
+                "real".to_string(),
+                Plist::Real {
+                    value: 0.5,
+                    size: 4,
+                },
+            ),
+            ("utf16".to_string(), Plist::String("Héllo ✓".to_string())),
+            ("data".to_string(), Plist::Data(vec![0; 20])),
+            (
+                "array".to_string(),
+                Plist::Array((0..20).map(Plist::Uid).collect()),
+            ),
+            ("null".to_string(), Plist::Null),
+        ]);
+        assert_eq!(plist, from_buffer(&to_buffer(&plist)).unwrap());
+    }
+
+    #[test]
+    fn truncated_trailer() {
+        let plist = from_buffer(&SAMPLE[..20]);
+        assert!(plist.is_err_and(|x| x.offset == 0 && x.rel_offset == 20));
+    }
+
+    #[test]
+    fn overflowing_length() {
+        // An object with the given marker and a length of 2^63 + 1, whose
+        // first reference is to the string "k".
+        let plist = |marker: u8| {
+            let mut buffer = b"bplist00".to_vec();
+            buffer.extend([marker, 0x13, 0x80, 0, 0, 0, 0, 0, 0, 0x01]);
+            buffer.extend([0x00, 0x01]); // reference to object 1
+            buffer.extend([0x51, 0x6B]); // "k"
+            buffer.extend([0x08, 0x14]); // offset table
+            buffer.extend([0, 0, 0, 0, 0, 0, 1, 2]);
+            buffer.extend(2u64.to_be_bytes());
+            buffer.extend(0u64.to_be_bytes());
+            buffer.extend(22u64.to_be_bytes());
+            from_buffer(&buffer).unwrap_err()
+        };
+        let string = plist(0x6F);
+        assert_eq!(
+            (8, 18, "String length 9223372036854775809 overflows"),
+            (string.offset, string.rel_offset, string.reason.as_str())
+        );
+        // The reference to the first value lies 2^64 + 2 bytes past the keys.
+        let dictionary = plist(0xDF);
+        assert_eq!(
+            (
+                8,
+                18,
+                "Object reference 9223372036854775809 overflows buffer"
+            ),
+            (
+                dictionary.offset,
+                dictionary.rel_offset,
+                dictionary.reason.as_str()
+            )
+        );
+    }
+
+    #[test]
+    fn shared_objects() {
+        // 40 arrays that each hold the next one twice, ending in true.
+        let mut buffer = b"bplist00".to_vec();
+        let mut offsets = vec![];
+        for index in 1..=40u8 {
+            offsets.push(buffer.len() as u8);
+            buffer.extend([0xA2, index, index]);
+        }
+        offsets.push(buffer.len() as u8);
+        buffer.push(0x09);
+        let table = buffer.len() as u64;
+        buffer.extend(&offsets);
+        buffer.extend([0, 0, 0, 0, 0, 0, 1, 1]);
+        buffer.extend((offsets.len() as u64).to_be_bytes());
+        buffer.extend(0u64.to_be_bytes());
+        buffer.extend(table.to_be_bytes());
+
+        assert_eq!(
+            "Binary plist expands to more than 1000000 objects",
+            from_buffer(&buffer).unwrap_err().reason
+        );
+    }
+}
@@ -0,0 +1,637 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    data::{Class, Context, Data},
+    raw_model::{
+        builder::ContextBuilder,
+        raw_object::RawObject,
+        raw_value::{RawValue, TYPE_INT16, TYPE_INT32, TYPE_INT64, TYPE_INT8},
+        ParseError,
+    },
+};
+
+use super::{bplist, Plist};
+
+// Keys the NIB encoder uses for collections instead of NS.keys/NS.objects.
+pub const INLINED_VALUE_KEY: &str = "NSInlinedValue";
+pub const EMPTY_KEY: &str = "UINibEncoderEmptyKey";
+
+// Class given to the synthesized root object when $top holds more than a
+// single "root" entry, so the entries survive a round trip.
+pub const TOP_CLASS: &str = "$top";
+
+pub fn from_buffer(buffer: &[u8]) -> Result<Context, ParseError> {
+    to_context(&bplist::from_buffer(buffer)?)
+}
+
+pub fn to_buffer(context: &Context) -> Result<Vec<u8>, ParseError> {
+    Ok(bplist::to_buffer(&from_context(context)?))
+}
+
+// Unpacks an NSKeyedArchiver property list into the NIB object model. The
+// root object always ends up at index 0, followed by the objects reachable
+// from it in breadth first order and finally any unreferenced objects.
+pub fn to_context(archive: &Plist) -> Result<Context, ParseError> {
+    let objects = archive
+        .get("$objects")
+        .and_then(Plist::as_array)
+        .ok_or(structure_error("Keyed archive has no $objects array"))?;
+    let top = archive
+        .get("$top")
+        .and_then(Plist::as_dictionary)
+        .ok_or(structure_error("Keyed archive has no $top dictionary"))?;
+
+    let mut unpacker = Unpacker {
+        objects,
+        builder: ContextBuilder::new(),
+        indices: HashMap::new(),
+        queue: VecDeque::new(),
+    };
+
+    match top {
+        [(key, Plist::Uid(uid))] if key == "root" && *uid != 0 => {
+            unpacker.reference(*uid)?;
+        }
+        _ => {
+            let index = unpacker.builder.reserve_object();
+            let class_index = unpacker.builder.class(TOP_CLASS, &[]);
+            let mut values = vec![];
+            for (key, value) in top {
+                unpacker.value(key, value, &mut values)?;
+            }
+            unpacker.builder.set_object(index, class_index, values);
+        }
+    }
+    unpacker.drain()?;
+
+    for (uid, object) in objects.iter().enumerate().skip(1) {
+        if object.get("$classname").is_none() && !unpacker.indices.contains_key(&(uid as u64)) {
+            unpacker.reference(uid as u64)?;
+            unpacker.drain()?;
+        }
+    }
+
+    unpacker.builder.build().map_err(structure_error)
+}
+
+// Packs the NIB object model into an NSKeyedArchiver property list, the
+// inverse of to_context. Every object keeps its own $objects entry so shared
+// references stay shared.
+pub fn from_context(context: &Context) -> Result<Plist, ParseError> {
+    let root = context
+        .objects
+        .first()
+        .ok_or(structure_error("Context has no root object"))?;
+    let synthetic_top = context.class_of(root).is_some_and(|c| c.name == TOP_CLASS);
+
+    // $objects[0] is always the $null marker.
+    let mut uids: Vec<Option<u64>> = vec![];
+    let mut next_uid = 1;
+    for index in 0..context.objects.len() {
+        if synthetic_top && index == 0 {
+            uids.push(None);
+        } else {
+            uids.push(Some(next_uid));
+            next_uid += 1;
+        }
+    }
+
+    let mut packer = Packer {
+        context,
+        uids,
+        class_uids: HashMap::new(),
+        classes: vec![],
+        next_class_uid: next_uid,
+    };
+
+    let mut objects = vec![Plist::String("$null".to_string())];
+    for (index, object) in context.objects.iter().enumerate() {
+        if packer.uids[index].is_some() {
+            objects.push(packer.object(index, object)?);
+        }
+    }
+
+    let top = if synthetic_top {
+        let values = context
+            .object_values(root)
+            .ok_or(structure_error("Root object values out of range"))?;
+        packer.entries(values, false, false)?
+    } else {
+        vec![("root".to_string(), Plist::Uid(packer.uid(0)?))]
+    };
+
+    objects.extend(packer.classes);
+
+    Ok(Plist::Dictionary(vec![
+        (
+            "$version".to_string(),
+            Plist::Integer {
+                value: 100000,
+                size: 4,
+            },
+        ),
+        (
+            "$archiver".to_string(),
+            Plist::String("NSKeyedArchiver".to_string()),
+        ),
+        ("$top".to_string(), Plist::Dictionary(top)),
+        ("$objects".to_string(), Plist::Array(objects)),
+    ]))
+}
+
+struct Packer<'a> {
+    context: &'a Context,
+    uids: Vec<Option<u64>>,
+    class_uids: HashMap<usize, u64>,
+    classes: Vec<Plist>,
+    next_class_uid: u64,
+}
+
+impl Packer<'_> {
+    fn uid(&self, index: usize) -> Result<u64, ParseError> {
+        self.uids
+            .get(index)
+            .copied()
+            .flatten()
+            .ok_or(structure_error(format!(
+                "Reference to object {} cannot be archived",
+                index
+            )))
+    }
+
+    fn object(&mut self, index: usize, object: &RawObject) -> Result<Plist, ParseError> {
+        let class = self
+            .context
+            .class_of(object)
+            .ok_or(structure_error(format!(
+                "Object {} has an invalid class",
+                index
+            )))?;
+        let values = self
+            .context
+            .object_values(object)
+            .ok_or(structure_error(format!(
+                "Object {} values out of range",
+                index
+            )))?;
+
+        if let [value] = values {
+            let key = self.context.key_name(value).unwrap_or_default();
+            let data = Data::from_scalar(value);
+            let plain = match (class.name.as_str(), key.as_str(), data) {
+                ("NSString", "NS.bytes", Some(Data::Bytes(bytes))) => {
+                    String::from_utf8(bytes).ok().map(Plist::String)
+                }
+                ("NSData", "NS.bytes", Some(Data::Bytes(bytes))) => Some(Plist::Data(bytes)),
+                ("NSNumber", "NS.intval" | "NS.dblval" | "NS.boolval", Some(data)) => {
+                    Some(scalar(&data))
+                }
+                _ => None,
+            };
+            if let Some(plain) = plain {
+                return Ok(plain);
+            }
+        }
+
+        let class_index = object.class_index.value().unwrap_or_default() as usize;
+        let mut entries = vec![(
+            "$class".to_string(),
+            Plist::Uid(self.class_uid(class_index)?),
+        )];
+        let dictionary = class.name.contains("Dictionary");
+        entries.extend(self.entries(values, true, dictionary)?);
+        Ok(Plist::Dictionary(entries))
+    }
+
+    fn class_uid(&mut self, class_index: usize) -> Result<u64, ParseError> {
+        if let Some(uid) = self.class_uids.get(&class_index) {
+            return Ok(*uid);
+        }
+        let class = self
+            .context
+            .classes
+            .get(class_index)
+            .map(Class::from)
+            .ok_or(structure_error(format!(
+                "Invalid class index {}",
+                class_index
+            )))?;
+
+        let mut chain = vec![Plist::String(class.name.to_owned())];
+        for extra_value in &class.extra_values {
+            if let Some(fallback) = usize::try_from(*extra_value)
+                .ok()
+                .and_then(|index| self.context.classes.get(index))
+            {
+                chain.push(Plist::String(Class::from(fallback).name));
+            }
+        }
+
+        let uid = self.next_class_uid;
+        self.next_class_uid += 1;
+        self.class_uids.insert(class_index, uid);
+        self.classes.push(Plist::Dictionary(vec![
+            ("$classes".to_string(), Plist::Array(chain)),
+            ("$classname".to_string(), Plist::String(class.name)),
+        ]));
+        Ok(uid)
+    }
+
+    // Repeated keys are folded into arrays. Inlined collections are turned
+    // back into NS.objects (and NS.keys for dictionaries) at the position of
+    // their NSInlinedValue marker.
+    fn entries(
+        &mut self,
+        values: &[RawValue],
+        collections: bool,
+        dictionary: bool,
+    ) -> Result<Vec<(String, Plist)>, ParseError> {
+        let mut entries: Vec<(String, Plist)> = vec![];
+        let mut collection: Option<(usize, Vec<Plist>)> = None;
+
+        for value in values {
+            let key = self.context.key_name(value).ok_or(structure_error(format!(
+                "Invalid key index {}",
+                value.key_index
+            )))?;
+            let plist = self.value(value)?;
+
+            if collections && key == INLINED_VALUE_KEY && plist == Plist::Boolean(true) {
+                collection.get_or_insert((entries.len(), vec![]));
+                continue;
+            }
+            if let (Some((_, items)), EMPTY_KEY) = (collection.as_mut(), key.as_str()) {
+                items.push(plist);
+                continue;
+            }
+
+            match entries.iter_mut().find(|(existing, _)| *existing == key) {
+                Some((_, Plist::Array(items))) => items.push(plist),
+                Some((_, existing)) => {
+                    let first = std::mem::replace(existing, Plist::Null);
+                    *existing = Plist::Array(vec![first, plist]);
+                }
+                None => entries.push((key, plist)),
+            }
+        }
+
+        if let Some((position, items)) = collection {
+            if dictionary {
+                let (keys, objects): (Vec<_>, Vec<_>) = items
+                    .chunks(2)
+                    .map(|pair| {
+                        (
+                            pair[0].to_owned(),
+                            pair.get(1).cloned().unwrap_or(Plist::Uid(0)),
+                        )
+                    })
+                    .unzip();
+                entries.insert(position, ("NS.objects".to_string(), Plist::Array(objects)));
+                entries.insert(position, ("NS.keys".to_string(), Plist::Array(keys)));
+            } else {
+                entries.insert(position, ("NS.objects".to_string(), Plist::Array(items)));
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn value(&self, value: &RawValue) -> Result<Plist, ParseError> {
+        if let Some(reference) = value.object_reference() {
+            return Ok(Plist::Uid(self.uid(reference as usize)?));
+        }
+        match Data::from_scalar(value) {
+            Some(data) => Ok(scalar(&data)),
+            None => Err(structure_error(format!(
+                "Invalid value type {}",
+                value.value_type
+            ))),
+        }
+    }
+}
+
+fn scalar(data: &Data) -> Plist {
+    match data {
+        Data::Boolean(boolean) => Plist::Boolean(*boolean),
+        Data::Int8(value) => Plist::Integer {
+            value: *value as i64,
+            size: 1,
+        },
+        Data::Int16(value) => Plist::Integer {
+            value: *value as i64,
+            size: 2,
+        },
+        Data::Int32(value) => Plist::Integer {
+            value: *value as i64,
+            size: 4,
+        },
+        Data::Int64(value) => Plist::Integer {
+            value: *value,
+            size: 8,
+        },
+        Data::Float(value) => Plist::Real {
+            value: *value as f64,
+            size: 4,
+        },
+        Data::Double(value) => Plist::Real {
+            value: *value,
+            size: 8,
+        },
+        Data::Bytes(bytes) => Plist::Data(bytes.to_owned()),
+        Data::Nil | Data::Object(_) | Data::Archive(_) => Plist::Uid(0),
+    }
+}
+
+fn structure_error(reason: impl Into<String>) -> ParseError {
+    ParseError::new(0, 0, reason)
+}
+
+struct Unpacker<'a> {
+    objects: &'a [Plist],
+    builder: ContextBuilder,
+    indices: HashMap<u64, u32>,
+    queue: VecDeque<(u64, u32)>,
+}
+
+impl<'a> Unpacker<'a> {
+    fn drain(&mut self) -> Result<(), ParseError> {
+        while let Some((uid, index)) = self.queue.pop_front() {
+            self.object(uid, index)?;
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, uid: u64) -> Result<&'a Plist, ParseError> {
+        self.objects
+            .get(uid as usize)
+            .ok_or(structure_error(format!(
+                "UID {} out of range ({} objects)",
+                uid,
+                self.objects.len()
+            )))
+    }
+
+    // Returns the object index for a UID, queueing it for unpacking when it
+    // is seen for the first time.
+    fn reference(&mut self, uid: u64) -> Result<u32, ParseError> {
+        self.lookup(uid)?;
+        if let Some(index) = self.indices.get(&uid) {
+            return Ok(*index);
+        }
+        let index = self.builder.reserve_object();
+        self.indices.insert(uid, index);
+        self.queue.push_back((uid, index));
+        Ok(index)
+    }
+
+    fn object(&mut self, uid: u64, index: u32) -> Result<(), ParseError> {
+        let object = self.lookup(uid)?;
+        let mut values = vec![];
+
+        let class_index = match object {
+            Plist::Dictionary(entries) => {
+                let class_uid = object
+                    .get("$class")
+                    .and_then(Plist::as_uid)
+                    .ok_or(structure_error(format!("Object {} has no $class", uid)))?;
+                let class_index = self.class(class_uid)?;
+
+                for (key, value) in entries {
+                    match key.as_str() {
+                        "$class" | "NS.keys" => {}
+                        "NS.objects" => self.collection(object, &mut values)?,
+                        _ => self.value(key, value, &mut values)?,
+                    }
+                }
+                class_index
+            }
+            Plist::String(string) => {
+                values.push(
+                    self.builder
+                        .bytes_value("NS.bytes", string.as_bytes().to_vec()),
+                );
+                self.builder.class("NSString", &[])
+            }
+            Plist::Data(bytes) => {
+                values.push(self.builder.bytes_value("NS.bytes", bytes.to_owned()));
+                self.builder.class("NSData", &[])
+            }
+            Plist::Integer { .. } => {
+                self.value("NS.intval", object, &mut values)?;
+                self.builder.class("NSNumber", &[])
+            }
+            Plist::Real { .. } => {
+                self.value("NS.dblval", object, &mut values)?;
+                self.builder.class("NSNumber", &[])
+            }
+            Plist::Boolean(_) => {
+                self.value("NS.boolval", object, &mut values)?;
+                self.builder.class("NSNumber", &[])
+            }
+            _ => {
+                return Err(structure_error(format!(
+                    "Unsupported archived object {}: {:?}",
+                    uid, object
+                )))
+            }
+        };
+
+        self.builder.set_object(index, class_index, values);
+        Ok(())
+    }
+
+    // Registers the class described by a $classes dictionary. Every class in
+    // the fallback chain becomes an extra value indexing the class table.
+    fn class(&mut self, uid: u64) -> Result<u32, ParseError> {
+        let class = self.lookup(uid)?;
+        let name = class
+            .get("$classname")
+            .and_then(Plist::as_str)
+            .ok_or(structure_error(format!("Class {} has no $classname", uid)))?
+            .to_string();
+        let fallbacks: Vec<String> = class
+            .get("$classes")
+            .and_then(Plist::as_array)
+            .unwrap_or(&[])
+            .iter()
+            .filter_map(Plist::as_str)
+            .filter(|fallback| *fallback != name)
+            .map(str::to_string)
+            .collect();
+
+        let extra_values: Vec<i32> = fallbacks
+            .iter()
+            .map(|fallback| self.builder.class(fallback, &[]) as i32)
+            .collect();
+        Ok(self.builder.class(&name, &extra_values))
+    }
+
+    // NS.keys/NS.objects pairs are stored the way the NIB encoder stores
+    // collections: an inlined marker followed by one empty key per element.
+    fn collection(
+        &mut self,
+        object: &'a Plist,
+        values: &mut Vec<RawValue>,
+    ) -> Result<(), ParseError> {
+        let items = object
+            .get("NS.objects")
+            .and_then(Plist::as_array)
+            .unwrap_or(&[]);
+        let keys = object.get("NS.keys").and_then(Plist::as_array);
+
+        values.push(self.builder.bool_value(INLINED_VALUE_KEY, true));
+        for (position, item) in items.iter().enumerate() {
+            if let Some(key) = keys.and_then(|keys| keys.get(position)) {
+                self.value(EMPTY_KEY, key, values)?;
+            }
+            self.value(EMPTY_KEY, item, values)?;
+        }
+        Ok(())
+    }
+
+    fn value(
+        &mut self,
+        key: &str,
+        value: &Plist,
+        values: &mut Vec<RawValue>,
+    ) -> Result<(), ParseError> {
+        let raw = match value {
+            Plist::Null | Plist::Uid(0) => self.builder.nil_value(key),
+            Plist::Uid(uid) => {
+                let index = self.reference(*uid)?;
+                self.builder.object_value(key, index)
+            }
+            Plist::Boolean(boolean) => self.builder.bool_value(key, *boolean),
+            Plist::Integer { value, size } => integer_value(&mut self.builder, key, *value, *size),
+            Plist::Real { value, size: 4 } => self.builder.float_value(key, *value as f32),
+            Plist::Real { value, .. } | Plist::Date(value) => {
+                self.builder.double_value(key, *value)
+            }
+            Plist::Data(bytes) => self.builder.bytes_value(key, bytes.to_owned()),
+            Plist::String(string) => self.builder.bytes_value(key, string.as_bytes().to_vec()),
+            Plist::Array(items) => {
+                for item in items {
+                    self.value(key, item, values)?;
+                }
+                return Ok(());
+            }
+            Plist::Dictionary(_) => {
+                return Err(structure_error(format!(
+                    "Unsupported inline dictionary for key {}",
+                    key
+                )))
+            }
+        };
+        values.push(raw);
+        Ok(())
+    }
+}
+
+// Keeps the encoded width of the integer where the value fits in it, so a
+// NIB value type survives a round trip through a keyed archive.
+fn integer_value(builder: &mut ContextBuilder, key: &str, value: i64, size: usize) -> RawValue {
+    match size {
+        1 if i8::try_from(value).is_ok() => {
+            builder.value(key, TYPE_INT8, (value as i8).to_le_bytes().to_vec())
+        }
+        2 if i16::try_from(value).is_ok() => {
+            builder.value(key, TYPE_INT16, (value as i16).to_le_bytes().to_vec())
+        }
+        4 if i32::try_from(value).is_ok() => {
+            builder.value(key, TYPE_INT32, (value as i32).to_le_bytes().to_vec())
+        }
+        1 | 2 | 4 => builder.int_value(key, value),
+        _ => builder.value(key, TYPE_INT64, value.to_le_bytes().to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::Data,
+        plist::{keyed_archive::to_context, Plist},
+    };
+
+    fn string(s: &str) -> Plist {
+        Plist::String(s.to_string())
+    }
+
+    fn dict(entries: Vec<(&str, Plist)>) -> Plist {
+        Plist::Dictionary(
+            entries
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn unpack_root_with_string_and_array() {
+        let archive = dict(vec![
+            ("$archiver", string("NSKeyedArchiver")),
+            ("$top", dict(vec![("root", Plist::Uid(1))])),
+            (
+                "$objects",
+                Plist::Array(vec![
+                    string("$null"),
+                    dict(vec![
+                        ("$class", Plist::Uid(2)),
+                        ("UIText", Plist::Uid(3)),
+                        (
+                            "UITag",
+                            Plist::Integer {
+                                value: 300,
+                                size: 2,
+                            },
+                        ),
+                        ("UISubviews", Plist::Uid(4)),
+                        ("UIMissing", Plist::Uid(0)),
+                    ]),
+                    dict(vec![
+                        ("$classname", string("UILabel")),
+                        (
+                            "$classes",
+                            Plist::Array(vec![string("UILabel"), string("UIView")]),
+                        ),
+                    ]),
+                    string("Hello"),
+                    dict(vec![
+                        ("$class", Plist::Uid(5)),
+                        ("NS.objects", Plist::Array(vec![Plist::Uid(3)])),
+                    ]),
+                    dict(vec![("$classname", string("NSArray"))]),
+                ]),
+            ),
+        ]);
+
+        let context = to_context(&archive).unwrap();
+        assert_eq!(3, context.objects.len());
+
+        let root = context.parse().unwrap();
+        assert_eq!("UILabel", root.class.name);
+        assert_eq!(1, root.class.extra_values.len());
+        assert_eq!("UIText", root.values[0].key.string);
+        match &root.values[0].data {
+            Data::Object(text) => {
+                assert_eq!("NSString", text.class.name);
+                assert!(matches!(&text.values[0].data, Data::Bytes(b) if b == b"Hello"));
+            }
+            other => panic!("Unexpected data {:?}", other),
+        }
+        assert!(matches!(root.values[1].data, Data::Int16(300)));
+        match &root.values[2].data {
+            Data::Object(array) => {
+                assert_eq!("NSInlinedValue", array.values[0].key.string);
+                assert_eq!("UINibEncoderEmptyKey", array.values[1].key.string);
+            }
+            other => panic!("Unexpected data {:?}", other),
+        }
+        assert!(matches!(root.values[3].data, Data::Nil));
+    }
+
+    #[test]
+    fn missing_objects() {
+        let archive = dict(vec![("$top", dict(vec![]))]);
+        assert!(to_context(&archive).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use crate::{
+    data::Context,
+    raw_model::{builder::ContextBuilder, raw_value::RawValue, ParseError},
+};
+
+use keyed_archive::{EMPTY_KEY, INLINED_VALUE_KEY, TOP_CLASS};
+
+pub mod ascii;
+pub mod bplist;
+pub mod keyed_archive;
+pub mod xml;
+
+// Decoded property list object. Integers and reals keep the byte width they
+// were encoded with so the NIB value types can be reconstructed from them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Plist {
+    Null,
+    Boolean(bool),
+    Integer { value: i64, size: usize },
+    Real { value: f64, size: usize },
+    Date(f64),
+    Data(Vec<u8>),
+    String(String),
+    Uid(u64),
+    Array(Vec<Plist>),
+    Dictionary(Vec<(String, Plist)>),
+}
+
+impl Plist {
+    pub fn get(&self, key: &str) -> Option<&Plist> {
+        match self {
+            Plist::Dictionary(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Plist::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_uid(&self) -> Option<u64> {
+        match self {
+            Plist::Uid(uid) => Some(*uid),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Plist]> {
+        match self {
+            Plist::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_dictionary(&self) -> Option<&[(String, Plist)]> {
+        match self {
+            Plist::Dictionary(entries) => Some(entries),
+            _ => None,
+        }
+    }
+}
+
+// Reads a binary, XML or old-style ASCII property list.
+pub fn from_buffer(buffer: &[u8]) -> Result<Plist, ParseError> {
+    if bplist::is_bplist(buffer) {
+        bplist::from_buffer(buffer)
+    } else if ascii::is_ascii_plist(buffer) {
+        ascii::from_buffer(buffer)
+    } else {
+        xml::from_buffer(buffer)
+    }
+}
+
+// Maps a property list that is not a keyed archive into the NIB object
+// model. Dictionaries become NSDictionary objects keyed by their own keys,
+// arrays become inlined NSArray collections. A scalar root is wrapped in a
+// $top object.
+pub fn to_context(plist: &Plist) -> Result<Context, ParseError> {
+    let mut builder = ContextBuilder::new();
+    match plist {
+        Plist::Dictionary(_) | Plist::Array(_) => {
+            collection(&mut builder, plist);
+        }
+        _ => {
+            let class = builder.class(TOP_CLASS, &[]);
+            let index = builder.reserve_object();
+            let mut values = vec![];
+            value(&mut builder, "root", plist, &mut values);
+            builder.set_object(index, class, values);
+        }
+    }
+    builder
+        .build()
+        .map_err(|reason| ParseError::new(0, 0, reason))
+}
+
+fn collection(builder: &mut ContextBuilder, plist: &Plist) -> u32 {
+    let index = builder.reserve_object();
+    let mut values = vec![];
+    let class = match plist {
+        Plist::Dictionary(entries) => {
+            for (key, item) in entries {
+                value(builder, key, item, &mut values);
+            }
+            builder.class("NSDictionary", &[])
+        }
+        Plist::Array(items) => {
+            values.push(builder.bool_value(INLINED_VALUE_KEY, true));
+            for item in items {
+                value(builder, EMPTY_KEY, item, &mut values);
+            }
+            builder.class("NSArray", &[])
+        }
+        _ => unreachable!("Only collections become objects"),
+    };
+    builder.set_object(index, class, values);
+    index
+}
+
+fn value(builder: &mut ContextBuilder, key: &str, plist: &Plist, values: &mut Vec<RawValue>) {
+    let raw = match plist {
+        Plist::Null | Plist::Uid(_) => builder.nil_value(key),
+        Plist::Boolean(boolean) => builder.bool_value(key, *boolean),
+        Plist::Integer { value, .. } => builder.int_value(key, *value),
+        Plist::Real { value, .. } | Plist::Date(value) => builder.double_value(key, *value),
+        Plist::Data(bytes) => builder.bytes_value(key, bytes.to_owned()),
+        Plist::String(string) => builder.bytes_value(key, string.as_bytes().to_vec()),
+        Plist::Array(_) | Plist::Dictionary(_) => {
+            let index = collection(builder, plist);
+            builder.object_value(key, index)
+        }
+    };
+    values.push(raw);
+}
@@ -0,0 +1,189 @@
+use crate::{
+    raw_model::ParseError,
+    xib::xml::{self, Element},
+};
+
+use super::Plist;
+
+// Seconds between the Unix epoch and the Core Foundation reference date
+// (2001-01-01), which dates are stored relative to.
+const REFERENCE_DATE: i64 = 978_307_200;
+
+pub fn is_xml_plist(buffer: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(&buffer[..buffer.len().min(256)]);
+    let head = head.trim_start_matches('\u{feff}').trim_start();
+    head.starts_with("<?xml") && head.contains("<!DOCTYPE plist") || head.starts_with("<plist")
+}
+
+pub fn from_buffer(buffer: &[u8]) -> Result<Plist, ParseError> {
+    let text = std::str::from_utf8(buffer)
+        .map_err(|e| ParseError::new(e.valid_up_to(), 0, "XML property list is not UTF-8"))?;
+    let root = xml::parse(text)?;
+    if root.name != "plist" {
+        return Err(error(
+            &root,
+            format!("Expected <plist>, found <{}>", root.name),
+        ));
+    }
+    match root.children.as_slice() {
+        [value] => value_of(value),
+        _ => Err(error(&root, "<plist> must contain exactly one value")),
+    }
+}
+
+fn error(element: &Element, reason: impl Into<String>) -> ParseError {
+    ParseError::new(0, 0, format!("{} on line {}", reason.into(), element.line))
+}
+
+fn value_of(element: &Element) -> Result<Plist, ParseError> {
+    let text = element.text.as_deref().unwrap_or_default();
+    let plist = match element.name.as_str() {
+        "true" => Plist::Boolean(true),
+        "false" => Plist::Boolean(false),
+        "string" => Plist::String(text.to_string()),
+        "integer" => {
+            let value = text
+                .trim()
+                .parse::<i64>()
+                .map_err(|_| error(element, format!("Invalid integer {:?}", text)))?;
+            Plist::Integer { value, size: 8 }
+        }
+        "real" => {
+            let value = text
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| error(element, format!("Invalid real {:?}", text)))?;
+            Plist::Real { value, size: 8 }
+        }
+        "data" => {
+            Plist::Data(base64_decode(text).ok_or(error(element, "Invalid base64 in <data>"))?)
+        }
+        "date" => Plist::Date(
+            parse_date(text.trim()).ok_or(error(element, format!("Invalid date {:?}", text)))?,
+        ),
+        "array" => Plist::Array(
+            element
+                .children
+                .iter()
+                .map(value_of)
+                .collect::<Result<_, _>>()?,
+        ),
+        "dict" => {
+            let mut entries = vec![];
+            let mut children = element.children.iter();
+            while let Some(key) = children.next() {
+                if key.name != "key" {
+                    return Err(error(key, format!("Expected <key>, found <{}>", key.name)));
+                }
+                let value = children.next().ok_or(error(key, "<key> without a value"))?;
+                entries.push((key.text.clone().unwrap_or_default(), value_of(value)?));
+            }
+            // Keyed archives written as XML store UIDs as CF$UID dictionaries.
+            match entries.as_slice() {
+                [(key, Plist::Integer { value, .. })] if key == "CF$UID" && *value >= 0 => {
+                    Plist::Uid(*value as u64)
+                }
+                _ => Plist::Dictionary(entries),
+            }
+        }
+        name => {
+            return Err(error(
+                element,
+                format!("Unknown property list element <{}>", name),
+            ))
+        }
+    };
+    Ok(plist)
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in text.chars().filter(|c| !c.is_whitespace()) {
+        let value = match c {
+            'A'..='Z' => c as u32 - 'A' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 26,
+            '0'..='9' => c as u32 - '0' as u32 + 52,
+            '+' => 62,
+            '/' => 63,
+            '=' => break,
+            _ => return None,
+        };
+        buffer = buffer << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+// Parses "YYYY-MM-DDTHH:MM:SSZ" into seconds since the reference date.
+fn parse_date(text: &str) -> Option<f64> {
+    let text = text.strip_suffix('Z')?;
+    let (date, time) = text.split_once('T')?;
+    let date: Vec<i64> = date
+        .split('-')
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    let time: Vec<i64> = time
+        .split(':')
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    let ([year, month, day], [hour, minute, second]) = (date.as_slice(), time.as_slice()) else {
+        return None;
+    };
+
+    // Days from civil, proleptic Gregorian calendar.
+    let year = if *month <= 2 { year - 1 } else { *year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = days * 86_400 + hour * 3_600 + minute * 60 + second;
+    Some((seconds - REFERENCE_DATE) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::plist::{xml::from_buffer, Plist};
+
+    #[test]
+    fn xml_plist() {
+        let plist = from_buffer(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>name</key><string>a &amp; b</string>
+    <key>items</key><array><integer>-3</integer><true/><real>1.5</real></array>
+    <key>data</key><data>AAEC/w==</data>
+    <key>date</key><date>2001-01-02T00:00:00Z</date>
+    <key>uid</key><dict><key>CF$UID</key><integer>7</integer></dict>
+</dict>
+</plist>"#,
+        )
+        .unwrap();
+
+        assert_eq!(Some("a & b"), plist.get("name").and_then(Plist::as_str));
+        assert_eq!(
+            Some(&Plist::Array(vec![
+                Plist::Integer { value: -3, size: 8 },
+                Plist::Boolean(true),
+                Plist::Real {
+                    value: 1.5,
+                    size: 8
+                },
+            ])),
+            plist.get("items")
+        );
+        assert_eq!(Some(&Plist::Data(vec![0, 1, 2, 255])), plist.get("data"));
+        assert_eq!(Some(&Plist::Date(86_400.0)), plist.get("date"));
+        assert_eq!(Some(7), plist.get("uid").and_then(Plist::as_uid));
+    }
+}
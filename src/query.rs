@@ -0,0 +1,748 @@
+use std::collections::HashSet;
+
+use crate::{
+    data::{is_string_class, Context, Data},
+    export::printable,
+    plist::keyed_archive::{EMPTY_KEY, INLINED_VALUE_KEY},
+    raw_model::ParseError,
+    regex::Regex,
+};
+
+// A path expression selecting objects and values, evaluated directly over
+// the tables of a `Context`:
+//
+//   /Key            values under Key of the current objects
+//   //Class         the current objects and everything they reference, of
+//                   the given class
+//   *               any key, or any class after //
+//   [n], [-1], [*]  item n, counted from the end when negative, or every
+//                   item of an inlined collection; other results by position
+//   [Key/Key]       results where the key path exists
+//   [Key op value]  results where a value at the key path compares to a
+//                   string, number, true, false or nil with =, !=, <, <=, >,
+//                   >= or ~= (regular expression match)
+//   [.="text"]      the result itself, [@class="UILabel"] its class name
+//
+// For example //UIButton[UIAccessibilityIdentifier="login"]/UIFont. Keys
+// with other characters than letters, digits and _ . $ : - are quoted.
+#[derive(Debug, Clone)]
+pub struct Query {
+    steps: Vec<Step>,
+}
+
+// An object, or a value that is not an object reference, by its index in
+// the context's tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Selected {
+    Object(usize),
+    Value(usize),
+}
+
+// What a selected object or value holds, for comparisons and display.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scalar {
+    Nil,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    // Data that is not UTF-8, by length.
+    Bytes(usize),
+    // An object other than a string, by class name.
+    Object(String),
+}
+
+#[derive(Debug, Clone)]
+struct Step {
+    descendant: bool,
+    // None for *.
+    name: Option<String>,
+    predicates: Vec<Predicate>,
+}
+
+#[derive(Debug, Clone)]
+enum Predicate {
+    Index(i64),
+    All,
+    Condition {
+        operand: Operand,
+        comparison: Option<(Op, Literal)>,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Itself,
+    Class,
+    Path(Vec<Option<String>>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Matches,
+}
+
+#[derive(Debug, Clone)]
+enum Literal {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Regex(Regex),
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Query, ParseError> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            index: 0,
+        };
+        let mut steps = vec![];
+        parser.skip_space();
+        // A query not starting with / is relative to the root.
+        if parser.peek().is_some_and(|c| c != '/') {
+            steps.push(parser.step(false)?);
+        }
+        while parser.eat('/') {
+            let descendant = parser.eat('/');
+            steps.push(parser.step(descendant)?);
+        }
+        parser.skip_space();
+        if parser.index < parser.chars.len() {
+            return Err(parser.error("Expected '/'"));
+        }
+        if steps.is_empty() {
+            return Err(parser.error("Empty query"));
+        }
+        Ok(Query { steps })
+    }
+
+    // The results in the order they are reached from the root, without
+    // duplicates.
+    pub fn select(&self, context: &Context) -> Vec<Selected> {
+        if context.objects.is_empty() {
+            return vec![];
+        }
+        let mut current = vec![Selected::Object(0)];
+        for step in &self.steps {
+            let mut seen = HashSet::new();
+            let mut next = vec![];
+            for item in &current {
+                let mut candidates = match step.descendant {
+                    true => descendants(context, *item)
+                        .into_iter()
+                        .filter(|item| match (&step.name, item) {
+                            (None, _) => true,
+                            (Some(name), Selected::Object(index)) => {
+                                context.class_name(*index).as_ref() == Some(name)
+                            }
+                            _ => false,
+                        })
+                        .collect(),
+                    false => children(context, *item, step.name.as_deref()),
+                };
+                for predicate in &step.predicates {
+                    candidates = predicate.apply(context, candidates);
+                }
+                next.extend(candidates.into_iter().filter(|item| seen.insert(*item)));
+            }
+            current = next;
+        }
+        current
+    }
+}
+
+// Selects with a query given as text.
+pub fn select(context: &Context, query: &str) -> Result<Vec<Selected>, ParseError> {
+    Ok(Query::parse(query)?.select(context))
+}
+
+pub fn scalar(context: &Context, item: Selected) -> Scalar {
+    match item {
+        Selected::Object(index) => {
+            let class = context.class_name(index).unwrap_or_default();
+            if is_string_class(&class) {
+                let text = context
+                    .find_value(index, "NS.bytes")
+                    .and_then(|bytes| String::from_utf8(bytes.data.clone()).ok());
+                if let Some(text) = text {
+                    return Scalar::String(text);
+                }
+            }
+            Scalar::Object(class)
+        }
+        Selected::Value(index) => {
+            let Some(data) = context.values.get(index).and_then(Data::from_scalar) else {
+                return Scalar::Nil;
+            };
+            match data {
+                Data::Boolean(value) => Scalar::Bool(value),
+                Data::Int8(value) => Scalar::Integer(value as i64),
+                Data::Int16(value) => Scalar::Integer(value as i64),
+                Data::Int32(value) => Scalar::Integer(value as i64),
+                Data::Int64(value) => Scalar::Integer(value),
+                Data::Float(value) => Scalar::Float(value as f64),
+                Data::Double(value) => Scalar::Float(value),
+                Data::Bytes(bytes) => match printable(&bytes) {
+                    Some(text) => Scalar::String(text.to_string()),
+                    None => Scalar::Bytes(bytes.len()),
+                },
+                _ => Scalar::Nil,
+            }
+        }
+    }
+}
+
+// The key path of a result, given the paths of `search::object_paths`.
+// Values are named after the object holding them; objects the root does
+// not reach are written as "#<index>".
+pub fn path(context: &Context, paths: &[Option<String>], item: Selected) -> String {
+    let object_path = |index: usize| {
+        paths
+            .get(index)
+            .cloned()
+            .flatten()
+            .unwrap_or(format!("#{}", index))
+    };
+    match item {
+        Selected::Object(index) => object_path(index),
+        Selected::Value(value) => {
+            let owner = context.objects.iter().position(|object| {
+                let start = object.value_index.value().unwrap_or_default() as usize;
+                let count = object.value_count.value().unwrap_or_default() as usize;
+                (start..start + count).contains(&value)
+            });
+            let key = context
+                .values
+                .get(value)
+                .and_then(|raw| context.key_name(raw))
+                .unwrap_or_default();
+            match owner {
+                Some(owner) => format!("{}/{}", object_path(owner), key),
+                None => format!("#value{}", value),
+            }
+        }
+    }
+}
+
+// Values of an object with their index in the values table and key name.
+fn values(context: &Context, index: usize) -> Vec<(usize, String)> {
+    let Some(object) = context.objects.get(index) else {
+        return vec![];
+    };
+    let start = object.value_index.value().unwrap_or_default() as usize;
+    context
+        .named_values(index)
+        .into_iter()
+        .enumerate()
+        .map(|(position, (key, _))| (start + position, key))
+        .collect()
+}
+
+fn resolve(context: &Context, value: usize) -> Option<Selected> {
+    let raw = context.values.get(value)?;
+    match raw.object_reference() {
+        Some(target) if (target as usize) < context.objects.len() => {
+            Some(Selected::Object(target as usize))
+        }
+        Some(_) => None,
+        None => Some(Selected::Value(value)),
+    }
+}
+
+fn children(context: &Context, item: Selected, key: Option<&str>) -> Vec<Selected> {
+    let Selected::Object(index) = item else {
+        return vec![];
+    };
+    values(context, index)
+        .into_iter()
+        .filter(|(_, name)| key.is_none_or(|key| key == name))
+        .filter_map(|(value, _)| resolve(context, value))
+        .collect()
+}
+
+// The item and every object it reaches, depth first.
+fn descendants(context: &Context, item: Selected) -> Vec<Selected> {
+    let Selected::Object(root) = item else {
+        return vec![item];
+    };
+    let mut seen = vec![false; context.objects.len()];
+    let mut found = vec![];
+    let mut stack = vec![root];
+    while let Some(index) = stack.pop() {
+        if std::mem::replace(&mut seen[index], true) {
+            continue;
+        }
+        found.push(Selected::Object(index));
+        let targets: Vec<usize> = values(context, index)
+            .into_iter()
+            .filter_map(|(value, _)| match resolve(context, value) {
+                Some(Selected::Object(target)) => Some(target),
+                _ => None,
+            })
+            .collect();
+        stack.extend(targets.into_iter().rev());
+    }
+    found
+}
+
+fn is_collection(context: &Context, item: Selected) -> bool {
+    match item {
+        Selected::Object(index) => values(context, index)
+            .iter()
+            .any(|(_, key)| key == EMPTY_KEY || key == INLINED_VALUE_KEY),
+        Selected::Value(_) => false,
+    }
+}
+
+impl Predicate {
+    fn apply(&self, context: &Context, items: Vec<Selected>) -> Vec<Selected> {
+        let position = |n: i64, len: usize| match n < 0 {
+            true => len.checked_sub(n.unsigned_abs() as usize),
+            false => Some(n as usize),
+        };
+        let count = items.len();
+        match self {
+            Predicate::Index(n) => items
+                .into_iter()
+                .enumerate()
+                .filter_map(|(index, item)| {
+                    if is_collection(context, item) {
+                        let items = children(context, item, Some(EMPTY_KEY));
+                        return position(*n, items.len()).and_then(|n| items.get(n).copied());
+                    }
+                    (position(*n, count) == Some(index)).then_some(item)
+                })
+                .collect(),
+            Predicate::All => items
+                .into_iter()
+                .flat_map(|item| match is_collection(context, item) {
+                    true => children(context, item, Some(EMPTY_KEY)),
+                    false => vec![item],
+                })
+                .collect(),
+            Predicate::Condition {
+                operand,
+                comparison,
+            } => items
+                .into_iter()
+                .filter(|item| {
+                    let scalars: Vec<Scalar> = match operand {
+                        Operand::Itself => vec![scalar(context, *item)],
+                        Operand::Class => match item {
+                            Selected::Object(index) => context
+                                .class_name(*index)
+                                .map(Scalar::String)
+                                .into_iter()
+                                .collect(),
+                            Selected::Value(_) => vec![],
+                        },
+                        Operand::Path(keys) => {
+                            let mut current = vec![*item];
+                            for key in keys {
+                                current = current
+                                    .into_iter()
+                                    .flat_map(|item| children(context, item, key.as_deref()))
+                                    .collect();
+                            }
+                            current
+                                .into_iter()
+                                .map(|item| scalar(context, item))
+                                .collect()
+                        }
+                    };
+                    match comparison {
+                        None => !scalars.is_empty(),
+                        Some((op, literal)) => {
+                            scalars.iter().any(|scalar| compare(scalar, *op, literal))
+                        }
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+fn compare(scalar: &Scalar, op: Op, literal: &Literal) -> bool {
+    let number = match scalar {
+        Scalar::Integer(value) => Some(*value as f64),
+        Scalar::Float(value) => Some(*value),
+        _ => None,
+    };
+    let ordering = match (scalar, literal) {
+        (_, Literal::Number(expected)) => number.and_then(|value| value.partial_cmp(expected)),
+        (Scalar::String(text), Literal::String(expected)) => Some(text.as_str().cmp(expected)),
+        (Scalar::Bool(value), Literal::Bool(expected)) => Some(value.cmp(expected)),
+        (Scalar::Nil, Literal::Nil) => Some(std::cmp::Ordering::Equal),
+        (_, Literal::Regex(regex)) => {
+            let text = match scalar {
+                Scalar::String(text) | Scalar::Object(text) => text.clone(),
+                Scalar::Integer(value) => value.to_string(),
+                Scalar::Float(value) => value.to_string(),
+                Scalar::Bool(value) => value.to_string(),
+                Scalar::Nil => "nil".to_string(),
+                Scalar::Bytes(_) => return false,
+            };
+            return regex.is_match(&text);
+        }
+        _ => None,
+    };
+    match (op, ordering) {
+        (Op::Ne, None) => true,
+        (_, None) => false,
+        (Op::Eq, Some(ordering)) => ordering.is_eq(),
+        (Op::Ne, Some(ordering)) => ordering.is_ne(),
+        (Op::Lt, Some(ordering)) => ordering.is_lt(),
+        (Op::Le, Some(ordering)) => ordering.is_le(),
+        (Op::Gt, Some(ordering)) => ordering.is_gt(),
+        (Op::Ge, Some(ordering)) => ordering.is_ge(),
+        (Op::Matches, Some(_)) => false,
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+}
+
+impl Parser {
+    fn error(&self, reason: impl Into<String>) -> ParseError {
+        ParseError::new(self.index, 0, reason)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.index += 1;
+            return true;
+        }
+        false
+    }
+
+    fn skip_space(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.index += 1;
+        }
+    }
+
+    fn step(&mut self, descendant: bool) -> Result<Step, ParseError> {
+        let name = self.name()?;
+        let mut predicates = vec![];
+        while self.eat('[') {
+            self.skip_space();
+            predicates.push(self.predicate()?);
+            self.skip_space();
+            if !self.eat(']') {
+                return Err(self.error("Expected ']'"));
+            }
+        }
+        Ok(Step {
+            descendant,
+            name,
+            predicates,
+        })
+    }
+
+    // A key or class name, or None for *.
+    fn name(&mut self) -> Result<Option<String>, ParseError> {
+        if self.eat('*') {
+            return Ok(None);
+        }
+        if self.peek() == Some('"') {
+            return self.string().map(Some);
+        }
+        let start = self.index;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_alphanumeric() || "_.$:-".contains(c))
+        {
+            self.index += 1;
+        }
+        if self.index == start {
+            return Err(self.error("Expected a name"));
+        }
+        Ok(Some(self.chars[start..self.index].iter().collect()))
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        let start = self.index;
+        self.index += 1;
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                None => {
+                    self.index = start;
+                    return Err(self.error("Unterminated string"));
+                }
+                Some('"') => {
+                    self.index += 1;
+                    return Ok(text);
+                }
+                Some('\\') => {
+                    self.index += 1;
+                    if let Some(c) = self.peek() {
+                        text.push(c);
+                        self.index += 1;
+                    }
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.index += 1;
+                }
+            }
+        }
+    }
+
+    fn predicate(&mut self) -> Result<Predicate, ParseError> {
+        if self.eat('*') {
+            return Ok(Predicate::All);
+        }
+        if self.peek().is_some_and(|c| c.is_ascii_digit() || c == '-') {
+            let start = self.index;
+            self.index += 1;
+            while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                self.index += 1;
+            }
+            let text: String = self.chars[start..self.index].iter().collect();
+            return text.parse().map(Predicate::Index).map_err(|_| {
+                self.index = start;
+                self.error("Invalid index")
+            });
+        }
+
+        let operand = if self.eat('.') {
+            Operand::Itself
+        } else if self.eat('@') {
+            match self.name()? {
+                Some(name) if name == "class" => Operand::Class,
+                _ => return Err(self.error("Expected @class")),
+            }
+        } else {
+            let mut keys = vec![self.name()?];
+            while self.eat('/') {
+                keys.push(self.name()?);
+            }
+            Operand::Path(keys)
+        };
+
+        self.skip_space();
+        let op = match (self.peek(), self.chars.get(self.index + 1)) {
+            (Some('!'), Some('=')) => Op::Ne,
+            (Some('~'), Some('=')) => Op::Matches,
+            (Some('<'), Some('=')) => Op::Le,
+            (Some('>'), Some('=')) => Op::Ge,
+            (Some('='), _) => Op::Eq,
+            (Some('<'), _) => Op::Lt,
+            (Some('>'), _) => Op::Gt,
+            _ => {
+                return Ok(Predicate::Condition {
+                    operand,
+                    comparison: None,
+                })
+            }
+        };
+        self.index += match op {
+            Op::Eq | Op::Lt | Op::Gt => 1,
+            _ => 2,
+        };
+        self.skip_space();
+        let literal = self.literal()?;
+        let literal = match (op, literal) {
+            (Op::Matches, Literal::String(pattern)) => {
+                let start = self.index;
+                Literal::Regex(Regex::new(&pattern).map_err(|e| {
+                    ParseError::new(start, e.offset, format!("Invalid pattern: {}", e.reason))
+                })?)
+            }
+            (Op::Matches, _) => return Err(self.error("~= expects a quoted pattern")),
+            (_, literal) => literal,
+        };
+        Ok(Predicate::Condition {
+            operand,
+            comparison: Some((op, literal)),
+        })
+    }
+
+    fn literal(&mut self) -> Result<Literal, ParseError> {
+        if self.peek() == Some('"') {
+            return self.string().map(Literal::String);
+        }
+        let start = self.index;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_alphanumeric() || "+-.".contains(c))
+        {
+            self.index += 1;
+        }
+        let word: String = self.chars[start..self.index].iter().collect();
+        match word.as_str() {
+            "true" => Ok(Literal::Bool(true)),
+            "false" => Ok(Literal::Bool(false)),
+            "nil" => Ok(Literal::Nil),
+            _ => word.parse().map(Literal::Number).map_err(|_| {
+                self.index = start;
+                self.error("Expected a string, number, true, false or nil")
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        query::{scalar, select, Query, Scalar, Selected},
+        raw_model::builder::ContextBuilder,
+    };
+
+    #[test]
+    fn queries() {
+        // Root -> top-level array of two buttons, each with an identifier
+        // and a font.
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let top_level = builder.reserve_object();
+        let buttons = [builder.reserve_object(), builder.reserve_object()];
+        let fonts = [builder.reserve_object(), builder.reserve_object()];
+
+        let value = builder.object_value("UINibTopLevelObjectsKey", top_level);
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, vec![value]);
+        let mut values = vec![builder.bool_value("NSInlinedValue", true)];
+        for button in buttons {
+            values.push(builder.object_value("UINibEncoderEmptyKey", button));
+        }
+        let class = builder.class("NSArray", &[]);
+        builder.set_object(top_level, class, values);
+        for (position, identifier) in ["login", "logout"].iter().enumerate() {
+            let values = vec![
+                builder.bytes_value("UIAccessibilityIdentifier", identifier.as_bytes().to_vec()),
+                builder.int_value("UITag", position as i64 + 1),
+                builder.object_value("UIFont", fonts[position]),
+            ];
+            let class = builder.class("UIButton", &[]);
+            builder.set_object(buttons[position], class, values);
+            let values = vec![builder.double_value("UIFontPointSize", 12.0 + position as f64)];
+            let class = builder.class("UIFont", &[]);
+            builder.set_object(fonts[position], class, values);
+        }
+        let context = builder.build().unwrap();
+
+        let objects = |query: &str| -> Vec<u32> {
+            select(&context, query)
+                .unwrap()
+                .into_iter()
+                .filter_map(|item| match item {
+                    Selected::Object(index) => Some(index as u32),
+                    Selected::Value(_) => None,
+                })
+                .collect()
+        };
+        assert_eq!(
+            vec![fonts[0]],
+            objects("//UIButton[UIAccessibilityIdentifier=\"login\"]/UIFont")
+        );
+        assert_eq!(buttons.to_vec(), objects("//UIButton"));
+        assert_eq!(vec![buttons[1]], objects("/UINibTopLevelObjectsKey[-1]"));
+        assert_eq!(buttons.to_vec(), objects("UINibTopLevelObjectsKey[*]"));
+        assert_eq!(
+            vec![buttons[1]],
+            objects("//*[UITag>=2][@class=\"UIButton\"]")
+        );
+        assert_eq!(
+            vec![buttons[0]],
+            objects("//UIButton[UIFont/UIFontPointSize<12.5]")
+        );
+        assert_eq!(
+            vec![buttons[1]],
+            objects("//UIButton[UIAccessibilityIdentifier~=\"out$\"]")
+        );
+        assert!(objects("//UIButton[UIMissing]").is_empty());
+
+        let values = select(
+            &context,
+            "//UIButton/UIAccessibilityIdentifier[.!=\"login\"]",
+        )
+        .unwrap();
+        assert_eq!(
+            vec![Scalar::String("logout".to_string())],
+            values
+                .iter()
+                .map(|item| scalar(&context, *item))
+                .collect::<Vec<_>>()
+        );
+
+        for (query, reason) in [
+            ("//UIButton[", "Expected a name"),
+            (
+                "//UIButton[UITag=]",
+                "Expected a string, number, true, false or nil",
+            ),
+            ("//UIButton]", "Expected '/'"),
+            ("//UIButton[.~=\"(\"]", "Invalid pattern: Unclosed group"),
+        ] {
+            assert_eq!(reason, Query::parse(query).unwrap_err().reason);
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        let empty = ContextBuilder::new().build().unwrap();
+        assert!(select(&empty, "//*").unwrap().is_empty());
+
+        // A view that is its own superview, a reference to a missing object
+        // and a value of an unknown type.
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let view = builder.reserve_object();
+        let value = builder.object_value("UIView", view);
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, vec![value]);
+        let values = vec![
+            builder.object_value("UISuperview", view),
+            builder.object_value("UIWindow", 7),
+            builder.int_value("UITag", 3),
+        ];
+        let class = builder.class("UIView", &[]);
+        builder.set_object(view, class, values);
+        let mut context = builder.build().unwrap();
+        context.values[3].value_type = 11;
+
+        let objects = |query: &str| select(&context, query).unwrap();
+        // Cycles are walked once, missing objects are skipped.
+        assert_eq!(vec![Selected::Object(1)], objects("//UIView"));
+        assert_eq!(
+            vec![Selected::Object(0), Selected::Object(1)],
+            objects("//*")
+        );
+        assert!(objects("/UIView/UIWindow").is_empty());
+        assert!(objects("/UIView/UIWindow/*").is_empty());
+        assert!(objects("/UIView[UITag=3]").is_empty());
+        assert_eq!(
+            vec![Scalar::Nil],
+            objects("/UIView/UITag")
+                .iter()
+                .map(|item| scalar(&context, *item))
+                .collect::<Vec<_>>()
+        );
+        assert!(objects("/UIView/UISuperview/UISuperview[9223372036854775807]").is_empty());
+
+        for (query, reason) in [
+            ("", "Empty query"),
+            ("//", "Expected a name"),
+            ("[1]", "Expected a name"),
+            ("/UIView[99999999999999999999]", "Invalid index"),
+            ("/UIView[\"unterminated]", "Unterminated string"),
+        ] {
+            assert_eq!(reason, Query::parse(query).unwrap_err().reason);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use crate::data::Context;
+
+use super::{
+    raw_class::RawClass,
+    raw_key::RawKey,
+    raw_object::RawObject,
+    raw_value::{
+        RawValue, TYPE_DATA, TYPE_DOUBLE, TYPE_FALSE, TYPE_FLOAT, TYPE_INT16, TYPE_INT32,
+        TYPE_INT64, TYPE_INT8, TYPE_NIL, TYPE_OBJECT, TYPE_TRUE,
+    },
+};
+
+// Assembles the raw tables of a Context. Objects can be reserved before their
+// contents are known so references may point forward; the value ranges are
+// laid out in object order when the context is built.
+#[derive(Default)]
+pub struct ContextBuilder {
+    objects: Vec<Option<(u32, Vec<RawValue>)>>,
+    keys: Vec<RawKey>,
+    key_indices: HashMap<String, u32>,
+    classes: Vec<RawClass>,
+    class_indices: HashMap<(String, Vec<i32>), u32>,
+}
+
+impl ContextBuilder {
+    pub fn new() -> ContextBuilder {
+        ContextBuilder::default()
+    }
+
+    pub fn key(&mut self, name: &str) -> u32 {
+        if let Some(index) = self.key_indices.get(name) {
+            return *index;
+        }
+        let index = self.keys.len() as u32;
+        self.keys.push(RawKey::new(name.as_bytes().to_vec()));
+        self.key_indices.insert(name.to_string(), index);
+        index
+    }
+
+    pub fn class(&mut self, name: &str, extra_values: &[i32]) -> u32 {
+        let id = (name.to_string(), extra_values.to_vec());
+        if let Some(index) = self.class_indices.get(&id) {
+            return *index;
+        }
+        let index = self.classes.len() as u32;
+        // Class names are stored NUL terminated.
+        let class_name = [name.as_bytes(), &[0]].concat();
+        self.classes.push(RawClass::new(class_name, extra_values));
+        self.class_indices.insert(id, index);
+        index
+    }
+
+    pub fn object_count(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn reserve_object(&mut self) -> u32 {
+        self.objects.push(None);
+        (self.objects.len() - 1) as u32
+    }
+
+    pub fn set_object(&mut self, index: u32, class_index: u32, values: Vec<RawValue>) {
+        self.objects[index as usize] = Some((class_index, values));
+    }
+
+    pub fn push_object(&mut self, class_index: u32, values: Vec<RawValue>) -> u32 {
+        let index = self.reserve_object();
+        self.set_object(index, class_index, values);
+        index
+    }
+
+    pub fn value(&mut self, key: &str, value_type: u8, data: Vec<u8>) -> RawValue {
+        RawValue::new(self.key(key), value_type, data)
+    }
+
+    // Uses the smallest integer type able to hold the value.
+    pub fn int_value(&mut self, key: &str, value: i64) -> RawValue {
+        if let Ok(value) = i8::try_from(value) {
+            self.value(key, TYPE_INT8, value.to_le_bytes().to_vec())
+        } else if let Ok(value) = i16::try_from(value) {
+            self.value(key, TYPE_INT16, value.to_le_bytes().to_vec())
+        } else if let Ok(value) = i32::try_from(value) {
+            self.value(key, TYPE_INT32, value.to_le_bytes().to_vec())
+        } else {
+            self.value(key, TYPE_INT64, value.to_le_bytes().to_vec())
+        }
+    }
+
+    pub fn bool_value(&mut self, key: &str, value: bool) -> RawValue {
+        let value_type = if value { TYPE_TRUE } else { TYPE_FALSE };
+        self.value(key, value_type, vec![])
+    }
+
+    pub fn float_value(&mut self, key: &str, value: f32) -> RawValue {
+        self.value(key, TYPE_FLOAT, value.to_le_bytes().to_vec())
+    }
+
+    pub fn double_value(&mut self, key: &str, value: f64) -> RawValue {
+        self.value(key, TYPE_DOUBLE, value.to_le_bytes().to_vec())
+    }
+
+    pub fn bytes_value(&mut self, key: &str, bytes: Vec<u8>) -> RawValue {
+        self.value(key, TYPE_DATA, bytes)
+    }
+
+    pub fn nil_value(&mut self, key: &str) -> RawValue {
+        self.value(key, TYPE_NIL, vec![])
+    }
+
+    pub fn object_value(&mut self, key: &str, object_index: u32) -> RawValue {
+        self.value(key, TYPE_OBJECT, object_index.to_le_bytes().to_vec())
+    }
+
+    pub fn build(self) -> Result<Context, String> {
+        let mut objects = vec![];
+        let mut values = vec![];
+
+        for (index, object) in self.objects.into_iter().enumerate() {
+            let (class_index, object_values) =
+                object.ok_or(format!("Object {} was reserved but never set", index))?;
+            objects.push(RawObject::new(
+                class_index,
+                values.len() as u32,
+                object_values.len() as u32,
+            ));
+            values.extend(object_values);
+        }
+
+        Ok(Context {
+            objects,
+            keys: self.keys,
+            values,
+            classes: self.classes,
+            depth: 0,
+        })
+    }
+}
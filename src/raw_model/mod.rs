@@ -1,3 +1,6 @@
+use std::fmt::Display;
+
+pub mod builder;
 pub mod nib;
 pub mod raw_class;
 pub mod raw_key;
@@ -7,14 +10,37 @@ pub mod varint;
 
 #[derive(Debug)]
 pub struct ParseError {
-    offset: usize,
-    rel_offset: usize,
-    reason: String,
+    pub offset: usize,
+    pub rel_offset: usize,
+    pub reason: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (offset {}, at {})",
+            self.reason, self.offset, self.rel_offset
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    pub fn new(offset: usize, rel_offset: usize, reason: impl Into<String>) -> ParseError {
+        ParseError {
+            offset,
+            rel_offset,
+            reason: reason.into(),
+        }
+    }
 }
 
 pub trait BufferView {
     fn size(&self) -> usize;
-    fn from_buffer(buffer: &Vec<u8>, offset: usize) -> Result<Self, ParseError>
+    fn to_bytes(&self) -> Vec<u8>;
+    fn from_buffer(buffer: &[u8], offset: usize) -> Result<Self, ParseError>
     where
         Self: Sized;
 }
@@ -1,15 +1,57 @@
-use std::fmt::Display;
+use std::{fmt::Display, ops::Range};
 
-use crate::cast::cast_to_u32;
+use crate::{cast::cast_to_u32, data::Context};
 
 use super::{
     raw_class::RawClass, raw_key::RawKey, raw_object::RawObject, raw_value::RawValue, BufferView,
     ParseError,
 };
 
+pub const FILE_ID: &str = "NIBArchive";
+pub const HEADER_SIZE: usize = 50;
+// Header constants written by current versions of ibtool.
+const CONSTANT_ONE: u32 = 1;
+const CONSTANT_TWO: u32 = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionKind {
+    Objects,
+    Keys,
+    Values,
+    Classes,
+}
+
+impl SectionKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SectionKind::Objects => "objects",
+            SectionKind::Keys => "keys",
+            SectionKind::Values => "values",
+            SectionKind::Classes => "classes",
+        }
+    }
+}
+
+// Where a table lies in the buffer: the offset and count from the header,
+// and the number of bytes its entries take up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Section {
+    pub kind: SectionKind,
+    pub offset: usize,
+    pub count: usize,
+    pub length: usize,
+}
+
+impl Section {
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.length
+    }
+}
+
 #[derive(Debug)]
 pub struct NibFile {
-    version: String,
+    // The two header constants following the identifier.
+    version: (u32, u32),
     object_count: u32,
     object_offset: u32,
     key_count: u32,
@@ -23,7 +65,7 @@ pub struct NibFile {
 
 impl Display for NibFile {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "File {{\n  Buffer size: {}\n  Version: {},\n  Objects: [{}; {}],\n  Keys: [{}; {}],\n  Values: [{}; {}],\n  Classes: [{}; {}],\n}}", self.buffer.len(), self.version, self.object_offset, self.object_count, self.key_offset, self.key_count, self.value_offset, self.value_count, self.class_offset, self.class_count)
+        write!(f, "File {{\n  Buffer size: {}\n  Version: {}.{},\n  Objects: [{}; {}],\n  Keys: [{}; {}],\n  Values: [{}; {}],\n  Classes: [{}; {}],\n}}", self.buffer.len(), self.version.0, self.version.1, self.object_offset, self.object_count, self.key_offset, self.key_count, self.value_offset, self.value_count, self.class_offset, self.class_count)
     }
 }
 
@@ -41,21 +83,9 @@ impl NibFile {
                     reason: "Keys memory overflowed into values".to_string(),
                 });
             }
-            match RawKey::from_buffer(&self.buffer, index) {
-                Ok(key) => {
-                    index += key.size();
-                    keys.push(key);
-                },
-                Err(e) => {
-                    println!(
-                        "Partial key extraction, {} of {} retrieved: {:?}",
-                        keys.len(),
-                        self.key_count,
-                        keys
-                    );
-                    return Err(e);
-                }
-            }
+            let key = RawKey::from_buffer(&self.buffer, index)?;
+            index += key.size();
+            keys.push(key);
         }
 
         Ok(keys)
@@ -74,21 +104,9 @@ impl NibFile {
                     reason: "Objects memory overflowed into keys".to_string(),
                 });
             }
-            match RawObject::from_buffer(&self.buffer, index) {
-                Ok(object) => {
-                    index += object.size();
-                    objects.push(object);
-                },
-                Err(e) => {
-                    println!(
-                        "Partial object extraction, {} of {} retrieved: {:?}",
-                        objects.len(),
-                        self.object_count,
-                        objects
-                    );
-                    return Err(e);
-                }
-            }
+            let object = RawObject::from_buffer(&self.buffer, index)?;
+            index += object.size();
+            objects.push(object);
         }
 
         Ok(objects)
@@ -107,21 +125,9 @@ impl NibFile {
                     reason: "Values memory overflowed into classes".to_string(),
                 });
             }
-            match RawValue::from_buffer(&self.buffer, index) {
-                Ok(value) => {
-                    index += value.size();
-                    values.push(value);
-                },
-                Err(e) => {
-                    println!(
-                        "Partial value extraction, {} of {} retrieved: {:?}",
-                        values.len(),
-                        self.value_count,
-                        values
-                    );
-                    return Err(e);
-                }
-            }
+            let value = RawValue::from_buffer(&self.buffer, index)?;
+            index += value.size();
+            values.push(value);
         }
 
         Ok(values)
@@ -133,39 +139,34 @@ impl NibFile {
         let mut classes = vec![];
 
         while classes.len() < self.class_count as usize {
-            if index >= self.buffer.len() as usize {
+            if index >= self.buffer.len() {
                 return Err(ParseError {
                     offset: index,
                     rel_offset: index,
                     reason: "Classes memory overflowed out of buffer".to_string(),
                 });
             }
-            match RawClass::from_buffer(&self.buffer, index) {
-                Ok(class) => {
-                    index += class.size();
-                    classes.push(class);
-                }
-                Err(e) => {
-                    println!(
-                        "Partial class extraction, {} of {} retrieved: {:?}",
-                        classes.len(),
-                        self.class_count,
-                        classes
-                    );
-                    return Err(e);
-                }
-            }
+            let class = RawClass::from_buffer(&self.buffer, index)?;
+            index += class.size();
+            classes.push(class);
         }
 
         Ok(classes)
     }
 
     pub fn from_buffer(buffer: Vec<u8>) -> Result<NibFile, ParseError> {
+        if buffer.len() < HEADER_SIZE {
+            return Err(ParseError::new(
+                0,
+                buffer.len(),
+                "Unexpected end of buffer: header is incomplete",
+            ));
+        }
         let file_id = String::from_utf8(buffer[0..=9].to_vec());
 
         match file_id {
             Ok(id) => {
-                if id != "NIBArchive" {
+                if id != FILE_ID {
                     return Err(ParseError {
                         offset: 0,
                         rel_offset: 0,
@@ -184,7 +185,7 @@ impl NibFile {
 
         let constant_one = cast_to_u32(&buffer[10..=13]).map_err(cast_error(10))?;
         let constant_two = cast_to_u32(&buffer[14..=17]).map_err(cast_error(14))?;
-        let version: String = format!("{}.{}", constant_one, constant_two);
+        let version = (constant_one, constant_two);
 
         let object_count = cast_to_u32(&buffer[18..=21]).map_err(cast_error(18))?;
         let object_offset = cast_to_u32(&buffer[22..=25]).map_err(cast_error(22))?;
@@ -212,6 +213,127 @@ impl NibFile {
     }
 }
 
+impl NibFile {
+    // Lays out the raw tables of a context as objects, keys, values and
+    // classes, in the order the section getters expect them.
+    pub fn from_context(context: &Context) -> NibFile {
+        fn section<T: BufferView>(items: &[T]) -> Vec<u8> {
+            items.iter().flat_map(|item| item.to_bytes()).collect()
+        }
+
+        let objects = section(&context.objects);
+        let keys = section(&context.keys);
+        let values = section(&context.values);
+        let classes = section(&context.classes);
+
+        let object_offset = HEADER_SIZE as u32;
+        let key_offset = object_offset + objects.len() as u32;
+        let value_offset = key_offset + keys.len() as u32;
+        let class_offset = value_offset + values.len() as u32;
+
+        let mut nib = NibFile {
+            version: (CONSTANT_ONE, CONSTANT_TWO),
+            object_count: context.objects.len() as u32,
+            object_offset,
+            key_count: context.keys.len() as u32,
+            key_offset,
+            value_count: context.values.len() as u32,
+            value_offset,
+            class_count: context.classes.len() as u32,
+            class_offset,
+            buffer: vec![],
+        };
+
+        let mut buffer = FILE_ID.as_bytes().to_vec();
+        for field in [
+            CONSTANT_ONE,
+            CONSTANT_TWO,
+            nib.object_count,
+            nib.object_offset,
+            nib.key_count,
+            nib.key_offset,
+            nib.value_count,
+            nib.value_offset,
+            nib.class_count,
+            nib.class_offset,
+        ] {
+            buffer.extend(field.to_le_bytes());
+        }
+        buffer.extend(objects);
+        buffer.extend(keys);
+        buffer.extend(values);
+        buffer.extend(classes);
+
+        nib.buffer = buffer;
+        nib
+    }
+
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    // The two constants following the identifier, 1 and 9 for current
+    // versions of ibtool.
+    pub fn version(&self) -> (u32, u32) {
+        self.version
+    }
+
+    // The four tables in header order. Fails like the getters when an entry
+    // cannot be decoded.
+    pub fn sections(&self) -> Result<Vec<Section>, ParseError> {
+        Ok(vec![
+            self.section::<RawObject>(SectionKind::Objects, self.object_offset, self.object_count)?,
+            self.section::<RawKey>(SectionKind::Keys, self.key_offset, self.key_count)?,
+            self.section::<RawValue>(SectionKind::Values, self.value_offset, self.value_count)?,
+            self.section::<RawClass>(SectionKind::Classes, self.class_offset, self.class_count)?,
+        ])
+    }
+
+    fn section<T: BufferView>(
+        &self,
+        kind: SectionKind,
+        offset: u32,
+        count: u32,
+    ) -> Result<Section, ParseError> {
+        let mut index = offset as usize;
+        for _ in 0..count {
+            index += T::from_buffer(&self.buffer, index)?.size();
+        }
+        Ok(Section {
+            kind,
+            offset: offset as usize,
+            count: count as usize,
+            length: index - offset as usize,
+        })
+    }
+
+    // Bytes between the header and the end of the last section that belong
+    // to no section.
+    pub fn gaps(&self) -> Result<Vec<Range<usize>>, ParseError> {
+        let mut sections = self.sections()?;
+        sections.sort_by_key(|section| section.offset);
+        let mut gaps = vec![];
+        let mut end = HEADER_SIZE;
+        for section in &sections {
+            if section.offset > end {
+                gaps.push(end..section.offset);
+            }
+            end = end.max(section.range().end);
+        }
+        Ok(gaps)
+    }
+
+    // Bytes after the end of the last section.
+    pub fn trailing(&self) -> Result<Range<usize>, ParseError> {
+        let end = self
+            .sections()?
+            .iter()
+            .map(|section| section.range().end)
+            .fold(HEADER_SIZE, usize::max);
+        Ok(end.min(self.buffer.len())..self.buffer.len())
+    }
+}
+
 fn cast_error(rel_offset: usize) -> impl Fn(String) -> ParseError {
     move |s| ParseError {
         offset: 0,
@@ -219,3 +341,55 @@ fn cast_error(rel_offset: usize) -> impl Fn(String) -> ParseError {
         reason: s,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::raw_model::{
+        builder::ContextBuilder,
+        nib::{NibFile, SectionKind, HEADER_SIZE},
+    };
+
+    #[test]
+    fn layout() {
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let tag = builder.int_value("UITag", 7);
+        let class = builder.class("UIView", &[]);
+        builder.set_object(root, class, vec![tag]);
+        let nib = NibFile::from_context(&builder.build().unwrap());
+        assert_eq!((1, 9), nib.version());
+
+        // Two padding bytes after the header and three at the end.
+        let mut buffer = nib.buffer()[..HEADER_SIZE].to_vec();
+        for field in [22, 30, 38, 46] {
+            let offset = u32::from_le_bytes(buffer[field..field + 4].try_into().unwrap());
+            buffer[field..field + 4].copy_from_slice(&(offset + 2).to_le_bytes());
+        }
+        buffer.extend([0, 0]);
+        buffer.extend(&nib.buffer()[HEADER_SIZE..]);
+        buffer.extend([1, 2, 3]);
+        let nib = NibFile::from_buffer(buffer).unwrap();
+
+        let sections = nib.sections().unwrap();
+        let kinds: Vec<SectionKind> = sections.iter().map(|section| section.kind).collect();
+        assert_eq!(
+            vec![
+                SectionKind::Objects,
+                SectionKind::Keys,
+                SectionKind::Values,
+                SectionKind::Classes
+            ],
+            kinds
+        );
+        // One object with a class index, value index and value count.
+        assert_eq!(
+            (52, 1, 3),
+            (sections[0].offset, sections[0].count, sections[0].length)
+        );
+        // A length byte and "UITag".
+        assert_eq!(6, sections[1].length);
+        assert_eq!(vec![50..52], nib.gaps().unwrap());
+        let end = nib.buffer().len();
+        assert_eq!(end - 3..end, nib.trailing().unwrap());
+    }
+}
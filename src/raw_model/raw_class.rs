@@ -32,12 +32,22 @@ impl BufferView for RawClass {
             + self.class_name.len()
     }
 
-    fn from_buffer(buffer: &Vec<u8>, offset: usize) -> Result<Self, super::ParseError> {
+    fn to_bytes(&self) -> Vec<u8> {
+        [
+            self.class_name_length.bytes(),
+            self.extra_values_count.bytes(),
+            &self.extra_values,
+            &self.class_name,
+        ]
+        .concat()
+    }
+
+    fn from_buffer(buffer: &[u8], offset: usize) -> Result<Self, super::ParseError> {
         let mut rel_offset = offset;
-        let class_name_length = VarInt::from_buffer(&buffer, rel_offset)?;
+        let class_name_length = VarInt::from_buffer(buffer, rel_offset)?;
         rel_offset += class_name_length.size();
 
-        let extra_values_count = VarInt::from_buffer(&buffer, rel_offset)?;
+        let extra_values_count = VarInt::from_buffer(buffer, rel_offset)?;
         rel_offset += extra_values_count.size();
 
         let extra_values_count_value =
@@ -90,6 +100,17 @@ impl BufferView for RawClass {
     }
 }
 
+impl RawClass {
+    pub fn new(class_name: Vec<u8>, extra_values: &[i32]) -> RawClass {
+        RawClass {
+            class_name_length: VarInt::new(class_name.len() as u32),
+            extra_values_count: VarInt::new(extra_values.len() as u32),
+            extra_values: extra_values.iter().flat_map(|e| e.to_le_bytes()).collect(),
+            class_name,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -11,9 +11,13 @@ impl BufferView for RawKey {
         self.key_length.size() + self.key_bytes.len()
     }
 
-    fn from_buffer(buffer: &Vec<u8>, offset: usize) -> Result<Self, super::ParseError> {
+    fn to_bytes(&self) -> Vec<u8> {
+        [self.key_length.bytes(), &self.key_bytes].concat()
+    }
+
+    fn from_buffer(buffer: &[u8], offset: usize) -> Result<Self, super::ParseError> {
         let mut rel_offset = offset;
-        let key_length = VarInt::from_buffer(&buffer, rel_offset)?;
+        let key_length = VarInt::from_buffer(buffer, rel_offset)?;
         rel_offset += key_length.size();
 
         let key_length_value = match key_length.value() {
@@ -37,10 +41,19 @@ impl BufferView for RawKey {
 
         let key_bytes = buffer[rel_offset..(rel_offset + key_length_value)].to_vec();
 
-        return Ok(RawKey {
+        Ok(RawKey {
             key_length,
             key_bytes,
-        });
+        })
+    }
+}
+
+impl RawKey {
+    pub fn new(key_bytes: Vec<u8>) -> RawKey {
+        RawKey {
+            key_length: VarInt::new(key_bytes.len() as u32),
+            key_bytes,
+        }
     }
 }
 
@@ -50,7 +63,7 @@ mod tests {
 
     #[test]
     fn simple_string() {
-        let raw_key = RawKey::from_buffer(&vec![0, 0, 133, 72, 101, 108, 108, 111], 2).unwrap();
+        let raw_key = RawKey::from_buffer(&[0, 0, 133, 72, 101, 108, 108, 111], 2).unwrap();
         assert_eq!(6, raw_key.size());
         assert_eq!(1, raw_key.key_length.size());
         assert_eq!(5, raw_key.key_length.value().unwrap());
@@ -59,13 +72,13 @@ mod tests {
 
     #[test]
     fn too_big_string() {
-        let raw_key = RawKey::from_buffer(&vec![135, 21, 10], 0);
+        let raw_key = RawKey::from_buffer(&[135, 21, 10], 0);
         assert!(raw_key.is_err_and(|x| { x.offset == 0 && x.rel_offset == 1 }));
     }
 
     #[test]
     fn string_size_too_long() {
-        let raw_key = RawKey::from_buffer(&vec![135, 2, 3, 1], 1);
-        assert!(raw_key.is_err_and(|x| { x.offset == 1 && x.rel_offset == 2 }));
+        let raw_key = RawKey::from_buffer(&[135, 2, 3, 1], 1);
+        assert!(raw_key.is_err_and(|x| { x.offset == 1 && x.rel_offset == 4 }));
     }
 }
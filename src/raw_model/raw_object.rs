@@ -12,21 +12,40 @@ impl BufferView for RawObject {
         self.class_index.size() + self.value_index.size() + self.value_count.size()
     }
 
-    fn from_buffer(buffer: &Vec<u8>, offset: usize) -> Result<Self, super::ParseError> {
+    fn to_bytes(&self) -> Vec<u8> {
+        [
+            self.class_index.bytes(),
+            self.value_index.bytes(),
+            self.value_count.bytes(),
+        ]
+        .concat()
+    }
+
+    fn from_buffer(buffer: &[u8], offset: usize) -> Result<Self, super::ParseError> {
         let mut rel_offset = offset;
 
-        let class_index = VarInt::from_buffer(&buffer, rel_offset)?;
+        let class_index = VarInt::from_buffer(buffer, rel_offset)?;
         rel_offset += class_index.size();
 
-        let value_index = VarInt::from_buffer(&buffer, rel_offset)?;
+        let value_index = VarInt::from_buffer(buffer, rel_offset)?;
         rel_offset += value_index.size();
 
-        let value_count = VarInt::from_buffer(&buffer, rel_offset)?;
+        let value_count = VarInt::from_buffer(buffer, rel_offset)?;
 
-        return Ok(RawObject {
+        Ok(RawObject {
             class_index,
             value_index,
             value_count,
-        });
+        })
+    }
+}
+
+impl RawObject {
+    pub fn new(class_index: u32, value_index: u32, value_count: u32) -> RawObject {
+        RawObject {
+            class_index: VarInt::new(class_index),
+            value_index: VarInt::new(value_index),
+            value_count: VarInt::new(value_count),
+        }
     }
 }
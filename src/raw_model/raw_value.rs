@@ -11,7 +11,36 @@ use super::{varint::VarInt, BufferView};
 // 8: data, varint , number of bytes as specified in varint
 // 9: nil
 // 10: object reference, 4 bytes uint32 LE coding an offset into the list of objects
-fn data_type_size(data_type: u8) -> Option<i8> {
+pub const TYPE_INT8: u8 = 0;
+pub const TYPE_INT16: u8 = 1;
+pub const TYPE_INT32: u8 = 2;
+pub const TYPE_INT64: u8 = 3;
+pub const TYPE_TRUE: u8 = 4;
+pub const TYPE_FALSE: u8 = 5;
+pub const TYPE_FLOAT: u8 = 6;
+pub const TYPE_DOUBLE: u8 = 7;
+pub const TYPE_DATA: u8 = 8;
+pub const TYPE_NIL: u8 = 9;
+pub const TYPE_OBJECT: u8 = 10;
+
+pub fn type_name(value_type: u8) -> &'static str {
+    match value_type {
+        TYPE_INT8 => "int8",
+        TYPE_INT16 => "int16",
+        TYPE_INT32 => "int32",
+        TYPE_INT64 => "int64",
+        TYPE_TRUE => "true",
+        TYPE_FALSE => "false",
+        TYPE_FLOAT => "float",
+        TYPE_DOUBLE => "double",
+        TYPE_DATA => "data",
+        TYPE_NIL => "nil",
+        TYPE_OBJECT => "object",
+        _ => "unknown",
+    }
+}
+
+pub fn data_type_size(data_type: u8) -> Option<i8> {
     let size = match data_type {
         8 => -1,
         4 | 5 | 9 => 0,
@@ -37,13 +66,23 @@ impl BufferView for RawValue {
         self.key_index.size() + 1 + self.data.len() + self.extra_size
     }
 
-    fn from_buffer(buffer: &Vec<u8>, offset: usize) -> Result<Self, super::ParseError>
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.key_index.to_bytes();
+        bytes.push(self.value_type);
+        if self.extra_size > 0 {
+            bytes.extend(VarInt::new(self.data.len() as u32).bytes());
+        }
+        bytes.extend(&self.data);
+        bytes
+    }
+
+    fn from_buffer(buffer: &[u8], offset: usize) -> Result<Self, super::ParseError>
     where
         Self: Sized,
     {
         let mut rel_offset = offset;
 
-        let key_index = VarInt::from_buffer(&buffer, rel_offset)?;
+        let key_index = VarInt::from_buffer(buffer, rel_offset)?;
         rel_offset += key_index.size();
 
         let value_type = buffer
@@ -58,14 +97,14 @@ impl BufferView for RawValue {
         rel_offset += 1;
 
         let value_size = data_type_size(value_type).ok_or(super::ParseError {
-            offset: offset,
-            rel_offset: rel_offset,
+            offset,
+            rel_offset,
             reason: format!("Invalid data type {}", value_type),
         })?;
 
         let mut extra_size = 0;
         let data: Vec<u8> = if value_size < 0 {
-            let var_value_size = VarInt::from_buffer(&buffer, rel_offset)?;
+            let var_value_size = VarInt::from_buffer(buffer, rel_offset)?;
             extra_size = var_value_size.size();
 
             rel_offset += var_value_size.size();
@@ -99,11 +138,34 @@ impl BufferView for RawValue {
             buffer[rel_offset..rel_offset + value_size as usize].to_vec()
         };
 
-        return Ok(RawValue {
+        Ok(RawValue {
             key_index,
             value_type,
             data,
             extra_size,
-        });
+        })
+    }
+}
+
+impl RawValue {
+    // Index of the referenced object for type 10 values.
+    pub fn object_reference(&self) -> Option<u32> {
+        match self.value_type {
+            TYPE_OBJECT => Some(u32::from_le_bytes(self.data.to_owned().try_into().ok()?)),
+            _ => None,
+        }
+    }
+
+    pub fn new(key_index: u32, value_type: u8, data: Vec<u8>) -> RawValue {
+        let extra_size = match data_type_size(value_type) {
+            Some(size) if size < 0 => VarInt::new(data.len() as u32).size(),
+            _ => 0,
+        };
+        RawValue {
+            key_index: VarInt::new(key_index),
+            value_type,
+            data,
+            extra_size,
+        }
     }
 }
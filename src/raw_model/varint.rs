@@ -1,7 +1,5 @@
 use std::fmt::{Debug, Display};
 
-use crate::cast::cast_to_u32;
-
 use super::BufferView;
 
 pub struct VarInt {
@@ -28,7 +26,11 @@ impl BufferView for VarInt {
         self.bytes.len()
     }
 
-    fn from_buffer(buffer: &Vec<u8>, offset: usize) -> Result<VarInt, super::ParseError> {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.bytes.to_owned()
+    }
+
+    fn from_buffer(buffer: &[u8], offset: usize) -> Result<VarInt, super::ParseError> {
         let mut rel_offset = offset;
         let mut finished = false;
         let mut bytes: Vec<u8> = vec![];
@@ -43,6 +45,8 @@ impl BufferView for VarInt {
         }
 
         if !finished {
+            // No byte with the high bit set before the buffer ended, point at
+            // the end of the buffer where the last byte was expected.
             return Err(super::ParseError {
                 offset,
                 rel_offset,
@@ -55,30 +59,38 @@ impl BufferView for VarInt {
 }
 
 impl VarInt {
-    fn decode_bytes(&self) -> Vec<u8> {
-        let mut decoded_bytes: Vec<u8> = vec![{ 0 }];
-
-        for (index, byte) in self.bytes.iter().enumerate() {
-            let value = byte & 0x7F;
-            let filled = ((index * 7) % 8) as u32;
-
-            let fill_position = decoded_bytes.len() - 1;
-            decoded_bytes[fill_position] =
-                decoded_bytes[fill_position] | (value.checked_shl(filled).unwrap_or(0));
-            if filled > 1 {
-                decoded_bytes.push(value >> (7 - (8 - filled)));
+    pub fn new(value: u32) -> VarInt {
+        let mut bytes = vec![];
+        let mut remaining = value;
+        loop {
+            let byte = (remaining & 0x7F) as u8;
+            remaining >>= 7;
+            if remaining == 0 {
+                bytes.push(byte | 0x80);
+                break;
             }
+            bytes.push(byte);
         }
+        VarInt { bytes }
+    }
 
-        decoded_bytes
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
     }
 
     pub fn value(&self) -> Result<u32, String> {
-        let mut bytes = self.decode_bytes();
-        while bytes.len() < 4 {
-            bytes.push(0);
+        let mut value: u64 = 0;
+        for (index, byte) in self.bytes.iter().enumerate() {
+            let shift = index * 7;
+            if shift >= 32 {
+                return Err(format!(
+                    "VarInt of {} bytes overflows u32",
+                    self.bytes.len()
+                ));
+            }
+            value |= ((byte & 0x7F) as u64) << shift;
         }
-        cast_to_u32(&bytes)
+        u32::try_from(value).map_err(|e| format!("Failed to parse to u32: {:?}", e))
     }
 }
 
@@ -88,21 +100,38 @@ mod tests {
 
     #[test]
     fn simple_onebyte_var() {
-        let var_int = VarInt::from_buffer(&vec![146], 0).unwrap();
+        let var_int = VarInt::from_buffer(&[146], 0).unwrap();
         assert_eq!(1, var_int.size());
         assert_eq!(18, var_int.value().unwrap());
     }
 
     #[test]
     fn simple_twobytes_var() {
-        let var_int = VarInt::from_buffer(&vec![127, 129], 0).unwrap();
+        let var_int = VarInt::from_buffer(&[127, 129], 0).unwrap();
         assert_eq!(2, var_int.size());
         assert_eq!(255, var_int.value().unwrap());
     }
 
+    #[test]
+    fn simple_threebytes_var() {
+        let var_int = VarInt::from_buffer(&[0, 0, 129], 0).unwrap();
+        assert_eq!(3, var_int.size());
+        assert_eq!(16_384, var_int.value().unwrap());
+    }
+
     #[test]
     fn invalid_var_int() {
-        let var_int = VarInt::from_buffer(&vec![0, 130, 127, 125], 2);
-        assert!(var_int.is_err_and(|x| { x.offset == 2 && x.rel_offset == 3 }));
+        let var_int = VarInt::from_buffer(&[0, 130, 127, 125], 2);
+        assert!(var_int.is_err_and(|x| { x.offset == 2 && x.rel_offset == 4 }));
+    }
+
+    #[test]
+    fn multibyte_round_trip() {
+        for value in [0, 127, 128, 256, 300, 16_384, 1_000_000, u32::MAX] {
+            let encoded = VarInt::new(value);
+            let decoded = VarInt::from_buffer(encoded.bytes(), 0).unwrap();
+            assert_eq!(encoded.size(), decoded.size());
+            assert_eq!(value, decoded.value().unwrap());
+        }
     }
 }
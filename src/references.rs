@@ -0,0 +1,171 @@
+use std::collections::VecDeque;
+
+use crate::{data::Context, plist::keyed_archive::EMPTY_KEY};
+
+// A value of one object referring to another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub from: usize,
+    pub to: usize,
+    pub key: String,
+    // Index of the value in `Context::values`.
+    pub value: usize,
+    // Position among the items of an inlined collection.
+    pub item: Option<usize>,
+}
+
+impl Reference {
+    // The step this reference adds to a key path, "/Key" or "[item]".
+    pub fn segment(&self) -> String {
+        match self.item {
+            Some(item) => format!("[{}]", item),
+            None => format!("/{}", self.key),
+        }
+    }
+}
+
+// Incoming references of every object, and the reference each object is
+// first reached through from the root.
+#[derive(Debug, Clone, Default)]
+pub struct ReferenceIndex {
+    incoming: Vec<Vec<Reference>>,
+    parents: Vec<Option<Reference>>,
+}
+
+impl ReferenceIndex {
+    pub fn new(context: &Context) -> ReferenceIndex {
+        let count = context.objects.len();
+        let mut outgoing: Vec<Vec<Reference>> = vec![vec![]; count];
+        let mut incoming: Vec<Vec<Reference>> = vec![vec![]; count];
+        for (from, references) in outgoing.iter_mut().enumerate() {
+            let start = context.objects[from]
+                .value_index
+                .value()
+                .unwrap_or_default() as usize;
+            let mut items = 0;
+            for (position, (key, value)) in context.named_values(from).into_iter().enumerate() {
+                let item = (key == EMPTY_KEY).then(|| {
+                    items += 1;
+                    items - 1
+                });
+                let Some(to) = value.object_reference().map(|to| to as usize) else {
+                    continue;
+                };
+                if to >= count {
+                    continue;
+                }
+                let reference = Reference {
+                    from,
+                    to,
+                    key,
+                    value: start + position,
+                    item,
+                };
+                incoming[to].push(reference.clone());
+                references.push(reference);
+            }
+        }
+
+        // Breadth first, in value order, like `search::object_paths`.
+        let mut parents: Vec<Option<Reference>> = vec![None; count];
+        let mut reached = vec![false; count];
+        let mut queue = VecDeque::new();
+        if count > 0 {
+            reached[0] = true;
+            queue.push_back(0);
+        }
+        while let Some(index) = queue.pop_front() {
+            for reference in &outgoing[index] {
+                if !std::mem::replace(&mut reached[reference.to], true) {
+                    parents[reference.to] = Some(reference.clone());
+                    queue.push_back(reference.to);
+                }
+            }
+        }
+        ReferenceIndex { incoming, parents }
+    }
+
+    // References to an object, in the order of the values table.
+    pub fn referrers(&self, object: usize) -> &[Reference] {
+        self.incoming.get(object).map_or(&[], Vec::as_slice)
+    }
+
+    // The references leading from the root to an object on the shortest
+    // way, empty for the root itself. None when the root does not reach it.
+    pub fn path(&self, object: usize) -> Option<Vec<Reference>> {
+        if object >= self.incoming.len() {
+            return None;
+        }
+        let mut path = vec![];
+        let mut current = object;
+        while current != 0 {
+            let reference = self.parents[current].clone()?;
+            current = reference.from;
+            path.push(reference);
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        raw_model::builder::ContextBuilder, references::ReferenceIndex, search::object_paths,
+    };
+
+    #[test]
+    fn references() {
+        // A controller kept by the top-level objects and by an outlet of the
+        // view, which is also reachable through the controller.
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let top_level = builder.reserve_object();
+        let controller = builder.reserve_object();
+        let view = builder.reserve_object();
+        let stray = builder.reserve_object();
+
+        let value = builder.object_value("UINibTopLevelObjectsKey", top_level);
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, vec![value]);
+        let values = vec![
+            builder.bool_value("NSInlinedValue", true),
+            builder.object_value("UINibEncoderEmptyKey", controller),
+        ];
+        let class = builder.class("NSArray", &[]);
+        builder.set_object(top_level, class, values);
+        let value = builder.object_value("UIView", view);
+        let class = builder.class("UIViewController", &[]);
+        builder.set_object(controller, class, vec![value]);
+        let value = builder.object_value("delegate", controller);
+        let class = builder.class("UIView", &[]);
+        builder.set_object(view, class, vec![value]);
+        builder.set_object(stray, class, vec![]);
+        let context = builder.build().unwrap();
+
+        let index = ReferenceIndex::new(&context);
+        assert_eq!(
+            vec![
+                (top_level as usize, "[0]".to_string()),
+                (view as usize, "/delegate".to_string())
+            ],
+            index
+                .referrers(controller as usize)
+                .iter()
+                .map(|reference| (reference.from, reference.segment()))
+                .collect::<Vec<_>>()
+        );
+        assert!(index.referrers(stray as usize).is_empty());
+
+        let path = index.path(view as usize).unwrap();
+        let segments: String = path.iter().map(|reference| reference.segment()).collect();
+        assert_eq!("/UINibTopLevelObjectsKey[0]/UIView", segments);
+        assert_eq!(
+            object_paths(&context)[view as usize],
+            Some(format!("root{}", segments))
+        );
+        assert_eq!(Some(vec![]), index.path(root as usize));
+        assert_eq!(None, index.path(stray as usize));
+        assert_eq!(None, index.path(99));
+    }
+}
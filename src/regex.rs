@@ -0,0 +1,630 @@
+use std::ops::Range;
+
+use crate::raw_model::ParseError;
+
+// A small regular expression engine for searching decoded strings. It
+// supports literals, ".", classes such as [a-z] and [^0-9], the \d \w \s
+// escapes and their negations, ^ and $ anchors, \b word boundaries, groups
+// with alternation, and greedy or lazy *, +, ? and {m,n} quantifiers.
+// Patterns compile to a program that runs as a Pike VM, in time linear in
+// the text and without recursion, so large strings are safe to search.
+#[derive(Debug, Clone)]
+pub struct Regex {
+    program: Vec<Instruction>,
+    ignore_case: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Empty,
+    Char(char),
+    // Any character but a newline.
+    Any,
+    Class(Class),
+    Assert(Assertion),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Class {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Assertion {
+    Start,
+    End,
+    // \b, or \B when false.
+    WordBoundary(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Instruction {
+    Char(char),
+    Any,
+    Class(Class),
+    Assert(Assertion),
+    // Continue at both targets, preferring the first.
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+// Nested groups allowed in a pattern, which bounds the recursion of the
+// parser and compiler.
+const MAX_NESTING: usize = 64;
+// Instructions a pattern may compile to, since {m,n} copies its operand.
+const MAX_PROGRAM: usize = 10_000;
+// Largest count allowed in {m,n}, as in RE2.
+const MAX_REPEAT: usize = 1000;
+
+const DIGIT: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+const SPACE: &[(char, char)] = &[('\t', '\r'), (' ', ' ')];
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, ParseError> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            index: 0,
+            depth: 0,
+        };
+        let node = parser.alternate()?;
+        if parser.index < parser.chars.len() {
+            return Err(parser.error("Unmatched ')'"));
+        }
+
+        let mut program = vec![];
+        compile(&node, &mut program)?;
+        program.push(Instruction::Match);
+        if program.len() > MAX_PROGRAM {
+            return Err(too_large());
+        }
+        Ok(Regex {
+            program,
+            ignore_case: false,
+        })
+    }
+
+    pub fn ignore_case(mut self, ignore_case: bool) -> Regex {
+        self.ignore_case = ignore_case;
+        self
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find(text).is_some()
+    }
+
+    // Byte range of the leftmost match. Of the matches starting there, the
+    // one the greedy and lazy quantifiers prefer wins, as in Perl.
+    pub fn find(&self, text: &str) -> Option<Range<usize>> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut offsets: Vec<usize> = text.char_indices().map(|(offset, _)| offset).collect();
+        offsets.push(text.len());
+
+        // Threads in priority order, as (instruction, start of the match).
+        let mut current: Vec<(usize, usize)> = vec![];
+        let mut next: Vec<(usize, usize)> = vec![];
+        // Generation in which each instruction was last added, so every
+        // instruction runs at most once per position.
+        let mut seen = vec![usize::MAX; self.program.len()];
+        let mut found = None;
+
+        for at in 0..=chars.len() {
+            if found.is_none() {
+                self.add(&mut current, &mut seen, (0, at), &chars, at);
+            }
+            if current.is_empty() && found.is_some() {
+                break;
+            }
+            for &(pc, start) in &current {
+                let accepts = match &self.program[pc] {
+                    Instruction::Char(c) => chars.get(at).is_some_and(|x| self.same(*x, *c)),
+                    Instruction::Any => chars.get(at).is_some_and(|x| *x != '\n'),
+                    Instruction::Class(class) => {
+                        chars.get(at).is_some_and(|x| self.in_class(*x, class))
+                    }
+                    Instruction::Match => {
+                        // Threads after this one have lower priority.
+                        found = Some(start..at);
+                        break;
+                    }
+                    _ => false,
+                };
+                if accepts {
+                    self.add(&mut next, &mut seen, (pc + 1, start), &chars, at + 1);
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+        found.map(|range| offsets[range.start]..offsets[range.end])
+    }
+
+    // Adds a thread and everything it reaches without consuming a character.
+    fn add(
+        &self,
+        threads: &mut Vec<(usize, usize)>,
+        seen: &mut [usize],
+        thread: (usize, usize),
+        text: &[char],
+        at: usize,
+    ) {
+        let mut stack = vec![thread];
+        while let Some((pc, start)) = stack.pop() {
+            if seen[pc] == at {
+                continue;
+            }
+            seen[pc] = at;
+            match &self.program[pc] {
+                Instruction::Jump(target) => stack.push((*target, start)),
+                Instruction::Split(first, second) => {
+                    stack.push((*second, start));
+                    stack.push((*first, start));
+                }
+                Instruction::Assert(assertion) => {
+                    if holds(*assertion, text, at) {
+                        stack.push((pc + 1, start));
+                    }
+                }
+                _ => threads.push((pc, start)),
+            }
+        }
+    }
+
+    fn same(&self, a: char, b: char) -> bool {
+        a == b || (self.ignore_case && a.to_lowercase().eq(b.to_lowercase()))
+    }
+
+    fn in_class(&self, c: char, class: &Class) -> bool {
+        let contains = |c: char| {
+            class
+                .ranges
+                .iter()
+                .any(|(low, high)| (*low..=*high).contains(&c))
+        };
+        let contained = contains(c)
+            || (self.ignore_case
+                && (c.to_lowercase().any(contains) || c.to_uppercase().any(contains)));
+        contained != class.negated
+    }
+}
+
+fn holds(assertion: Assertion, text: &[char], at: usize) -> bool {
+    match assertion {
+        Assertion::Start => at == 0,
+        Assertion::End => at == text.len(),
+        Assertion::WordBoundary(boundary) => {
+            let word = |index: Option<usize>| {
+                index
+                    .and_then(|index| text.get(index))
+                    .is_some_and(|c| c.is_alphanumeric() || *c == '_')
+            };
+            (word(at.checked_sub(1)) != word(Some(at))) == boundary
+        }
+    }
+}
+
+fn too_large() -> ParseError {
+    ParseError::new(0, 0, "Pattern is too large")
+}
+
+// Fails as soon as the program grows past MAX_PROGRAM, on patterns such as
+// (a{1000}){1000}.
+fn compile(node: &Node, program: &mut Vec<Instruction>) -> Result<(), ParseError> {
+    if program.len() > MAX_PROGRAM {
+        return Err(too_large());
+    }
+    match node {
+        Node::Empty => {}
+        Node::Char(c) => program.push(Instruction::Char(*c)),
+        Node::Any => program.push(Instruction::Any),
+        Node::Class(class) => program.push(Instruction::Class(class.clone())),
+        Node::Assert(assertion) => program.push(Instruction::Assert(*assertion)),
+        Node::Concat(nodes) => {
+            for node in nodes {
+                compile(node, program)?;
+            }
+        }
+        Node::Alternate(options) => {
+            // Each option but the last is tried through a split and jumps to
+            // the end when it matched.
+            let mut jumps = vec![];
+            for (position, option) in options.iter().enumerate() {
+                if position + 1 == options.len() {
+                    compile(option, program)?;
+                    break;
+                }
+                let split = program.len();
+                program.push(Instruction::Split(split + 1, 0));
+                compile(option, program)?;
+                jumps.push(program.len());
+                program.push(Instruction::Jump(0));
+                let after = program.len();
+                program[split] = Instruction::Split(split + 1, after);
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Instruction::Jump(end);
+            }
+        }
+        Node::Repeat {
+            node,
+            min,
+            max,
+            greedy,
+        } => {
+            for _ in 0..*min {
+                let before = program.len();
+                compile(node, program)?;
+                if program.len() > MAX_PROGRAM {
+                    return Err(too_large());
+                }
+                // Copies of an operand that matches only the empty string,
+                // such as (), change nothing.
+                if program.len() == before {
+                    break;
+                }
+            }
+            let split = |program: &mut Vec<Instruction>, at: usize, body: usize, exit: usize| {
+                program[at] = match greedy {
+                    true => Instruction::Split(body, exit),
+                    false => Instruction::Split(exit, body),
+                };
+            };
+            match max {
+                None => {
+                    let start = program.len();
+                    program.push(Instruction::Match);
+                    compile(node, program)?;
+                    program.push(Instruction::Jump(start));
+                    let exit = program.len();
+                    split(program, start, start + 1, exit);
+                }
+                Some(max) => {
+                    let mut splits = vec![];
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Instruction::Match);
+                        compile(node, program)?;
+                        if program.len() > MAX_PROGRAM {
+                            return Err(too_large());
+                        }
+                    }
+                    let exit = program.len();
+                    for at in splits {
+                        split(program, at, at + 1, exit);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn error(&self, reason: impl Into<String>) -> ParseError {
+        ParseError::new(self.index, 0, reason)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.index += 1;
+            return true;
+        }
+        false
+    }
+
+    fn alternate(&mut self) -> Result<Node, ParseError> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING {
+            return Err(self.error("Pattern is nested too deeply"));
+        }
+        let mut options = vec![self.concat()?];
+        while self.eat('|') {
+            options.push(self.concat()?);
+        }
+        self.depth -= 1;
+        Ok(match options.len() {
+            1 => options.pop().unwrap_or(Node::Empty),
+            _ => Node::Alternate(options),
+        })
+    }
+
+    fn concat(&mut self) -> Result<Node, ParseError> {
+        let mut nodes = vec![];
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.pop().unwrap_or(Node::Empty),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn quantified(&mut self, mut node: Node) -> Result<Node, ParseError> {
+        let mut repeated = false;
+        loop {
+            let start = self.index;
+            let (min, max) = match self.peek() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => match self.bounds() {
+                    Some(bounds) => bounds,
+                    None => return Ok(node),
+                },
+                _ => return Ok(node),
+            };
+            if self.index == start {
+                self.index += 1;
+            }
+            if matches!(node, Node::Assert(_)) {
+                self.index = start;
+                return Err(self.error("Nothing to repeat"));
+            }
+            if repeated {
+                self.index = start;
+                return Err(self.error("Nested quantifiers need a group"));
+            }
+            if max.is_some_and(|max| max < min) {
+                self.index = start;
+                return Err(self.error("Repetition range is reversed"));
+            }
+            if max.unwrap_or(min) > MAX_REPEAT {
+                self.index = start;
+                return Err(self.error(format!("Repetition count exceeds {}", MAX_REPEAT)));
+            }
+            let greedy = !self.eat('?');
+            node = Node::Repeat {
+                node: Box::new(node),
+                min,
+                max,
+                greedy,
+            };
+            repeated = true;
+        }
+    }
+
+    // Parses {n}, {n,} or {n,m}. Anything else leaves the "{" a literal.
+    fn bounds(&mut self) -> Option<(usize, Option<usize>)> {
+        let start = self.index;
+        self.index += 1;
+        let number = |parser: &mut Parser| {
+            let from = parser.index;
+            while parser.peek().is_some_and(|c| c.is_ascii_digit()) {
+                parser.index += 1;
+            }
+            // Counts too large for usize are still counts, and rejected later.
+            let digits: String = parser.chars[from..parser.index].iter().collect();
+            match digits.is_empty() {
+                true => None,
+                false => Some(digits.parse::<usize>().unwrap_or(usize::MAX)),
+            }
+        };
+        let bounds = number(self).and_then(|min| {
+            if self.eat('}') {
+                return Some((min, Some(min)));
+            }
+            if !self.eat(',') {
+                return None;
+            }
+            let max = number(self);
+            self.eat('}').then_some((min, max))
+        });
+        if bounds.is_none() {
+            self.index = start;
+        }
+        bounds
+    }
+
+    fn atom(&mut self) -> Result<Node, ParseError> {
+        let start = self.index;
+        let Some(c) = self.peek() else {
+            return Err(self.error("Unexpected end of pattern"));
+        };
+        self.index += 1;
+        match c {
+            '(' => {
+                // Groups never capture, so (?:...) is the same as (...).
+                if self.chars[self.index..].starts_with(&['?', ':']) {
+                    self.index += 2;
+                }
+                let node = self.alternate()?;
+                if !self.eat(')') {
+                    self.index = start;
+                    return Err(self.error("Unclosed group"));
+                }
+                Ok(node)
+            }
+            '[' => self.class(start),
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::Assert(Assertion::Start)),
+            '$' => Ok(Node::Assert(Assertion::End)),
+            '*' | '+' | '?' => {
+                self.index = start;
+                Err(self.error("Nothing to repeat"))
+            }
+            '\\' => match self.escape()? {
+                Escape::Char(c) => Ok(Node::Char(c)),
+                Escape::Class(ranges, negated) => Ok(Node::Class(Class {
+                    ranges: ranges.to_vec(),
+                    negated,
+                })),
+                Escape::WordBoundary(boundary) => {
+                    Ok(Node::Assert(Assertion::WordBoundary(boundary)))
+                }
+            },
+            c => Ok(Node::Char(c)),
+        }
+    }
+
+    fn escape(&mut self) -> Result<Escape, ParseError> {
+        let Some(c) = self.peek() else {
+            return Err(self.error("Pattern ends with a backslash"));
+        };
+        self.index += 1;
+        Ok(match c {
+            'd' => Escape::Class(DIGIT, false),
+            'D' => Escape::Class(DIGIT, true),
+            'w' => Escape::Class(WORD, false),
+            'W' => Escape::Class(WORD, true),
+            's' => Escape::Class(SPACE, false),
+            'S' => Escape::Class(SPACE, true),
+            'b' => Escape::WordBoundary(true),
+            'B' => Escape::WordBoundary(false),
+            'n' => Escape::Char('\n'),
+            'r' => Escape::Char('\r'),
+            't' => Escape::Char('\t'),
+            '0' => Escape::Char('\0'),
+            c if c.is_ascii_alphanumeric() => {
+                self.index -= 2;
+                return Err(self.error(format!("Unknown escape \\{}", c)));
+            }
+            c => Escape::Char(c),
+        })
+    }
+
+    fn class(&mut self, start: usize) -> Result<Node, ParseError> {
+        let negated = self.eat('^');
+        let mut ranges = vec![];
+        let mut first = true;
+        loop {
+            let Some(c) = self.peek() else {
+                self.index = start;
+                return Err(self.error("Unclosed character class"));
+            };
+            self.index += 1;
+            if c == ']' && !first {
+                return Ok(Node::Class(Class { ranges, negated }));
+            }
+            first = false;
+
+            let low = match c {
+                '\\' => match self.escape()? {
+                    Escape::Char(c) => c,
+                    Escape::Class(class, false) => {
+                        ranges.extend(class);
+                        continue;
+                    }
+                    _ => {
+                        self.index -= 2;
+                        return Err(self.error("Escape not supported in a character class"));
+                    }
+                },
+                c => c,
+            };
+            // A "-" before the closing "]" is a literal.
+            if self.peek() == Some('-') && self.chars.get(self.index + 1) != Some(&']') {
+                self.index += 1;
+                let high = match self.peek() {
+                    Some('\\') => {
+                        self.index += 1;
+                        match self.escape()? {
+                            Escape::Char(c) => c,
+                            _ => return Err(self.error("Invalid range in character class")),
+                        }
+                    }
+                    Some(c) => {
+                        self.index += 1;
+                        c
+                    }
+                    None => continue,
+                };
+                if high < low {
+                    return Err(self.error("Range in character class is reversed"));
+                }
+                ranges.push((low, high));
+            } else {
+                ranges.push((low, low));
+            }
+        }
+    }
+}
+
+enum Escape {
+    Char(char),
+    Class(&'static [(char, char)], bool),
+    WordBoundary(bool),
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::regex::Regex;
+
+    #[test]
+    fn matching() {
+        let find = |pattern: &str, text: &str| {
+            let range = Regex::new(pattern).unwrap().find(text)?;
+            Some(text[range].to_string())
+        };
+        assert_eq!(
+            Some("login"),
+            find("log(in|out)", "the login button").as_deref()
+        );
+        assert_eq!(
+            Some("UIButton"),
+            find(r"\bUI\w+", "AUIView UIButton").as_deref()
+        );
+        assert_eq!(Some("a1b22"), find("[a-c][0-9]+b2{2}", "xa1b22").as_deref());
+        assert_eq!(Some("<b>"), find("<.+?>", "<b>bold</b>").as_deref());
+        assert_eq!(Some("<b>bold</b>"), find("<.+>", "<b>bold</b>").as_deref());
+        assert_eq!(None, find("^Label$", "Labels"));
+        assert_eq!(Some("é"), find("[^a-z ]", "caf é").as_deref());
+        assert_eq!(Some("x{,2}"), find("x{,2}", "x{,2}").as_deref());
+        assert_eq!(Some(""), find("(a*)*", "b").as_deref());
+        assert_eq!(Some("aaa"), find("(a|ab)(c|bcd)?a*", "aaa").as_deref());
+        assert_eq!(Some("abcd"), find("(a|ab)(c|bcd)", "abcd").as_deref());
+        assert_eq!(Some("xx"), find("x{2,3}?", "xxxx").as_deref());
+        assert_eq!(Some("x"), find("((){1000}){1000}x", "ax").as_deref());
+
+        // Long strings do not exhaust the stack.
+        let long = "a".repeat(200_000) + "b";
+        assert_eq!(Some(0..long.len()), Regex::new("a*b$").unwrap().find(&long));
+        assert_eq!(Some("a.b"), find(r"a\.b", "axb a.b").as_deref());
+
+        let regex = Regex::new("hello [w]orld").unwrap().ignore_case(true);
+        assert_eq!(Some(2..13), regex.find("> HELLO World"));
+
+        for (pattern, reason) in [
+            ("a(b", "Unclosed group"),
+            ("a)", "Unmatched ')'"),
+            ("*a", "Nothing to repeat"),
+            ("a**", "Nested quantifiers need a group"),
+            ("(a{100}){200}", "Pattern is too large"),
+            ("a{99999999999}", "Repetition count exceeds 1000"),
+            ("(){99999999999}x", "Repetition count exceeds 1000"),
+            (
+                "a{2,99999999999999999999999}",
+                "Repetition count exceeds 1000",
+            ),
+            ("[z-a]", "Range in character class is reversed"),
+            (r"\q", "Unknown escape \\q"),
+        ] {
+            assert_eq!(reason, Regex::new(pattern).unwrap_err().reason);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use std::{collections::VecDeque, ops::Range};
+
+use crate::{
+    data::{is_string_class, Context},
+    plist::keyed_archive::EMPTY_KEY,
+    raw_model::raw_value::TYPE_DATA,
+    regex::Regex,
+};
+
+// What part of an object a match was found in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Class,
+    Key,
+    String,
+}
+
+impl Field {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::Class => "class",
+            Field::Key => "key",
+            Field::String => "string",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub object: usize,
+    // Key path of the object, or of the value for keys and strings stored in
+    // a value.
+    pub path: String,
+    pub field: Field,
+    pub text: String,
+    // Byte range of the match in `text`.
+    pub range: Range<usize>,
+}
+
+// The shortest key path from the root to every object, such as
+// "root/UINibTopLevelObjectsKey[0]/UISubviews[2]/UIText". Items of inlined
+// collections are numbered after the key of the collection. Objects the root
+// does not reach have no path.
+pub fn object_paths(context: &Context) -> Vec<Option<String>> {
+    let mut paths: Vec<Option<String>> = vec![None; context.objects.len()];
+    if context.objects.is_empty() {
+        return paths;
+    }
+    paths[0] = Some("root".to_string());
+    let mut queue = VecDeque::from([0]);
+    while let Some(index) = queue.pop_front() {
+        let path = paths[index].clone().unwrap_or_default();
+        let mut item = 0;
+        for (key, value) in context.named_values(index) {
+            let segment = if key == EMPTY_KEY {
+                item += 1;
+                format!("{}[{}]", path, item - 1)
+            } else {
+                format!("{}/{}", path, key)
+            };
+            let Some(target) = value.object_reference().map(|t| t as usize) else {
+                continue;
+            };
+            if paths.get(target) == Some(&None) {
+                paths[target] = Some(segment);
+                queue.push_back(target);
+            }
+        }
+    }
+    paths
+}
+
+// Searches class names, key names and strings. Matches come in the order
+// objects are reached from the root; unreachable objects follow with paths
+// of the form "#<index>".
+pub fn grep(context: &Context, regex: &Regex) -> Vec<Match> {
+    let paths = object_paths(context);
+    let mut order: Vec<usize> = (0..context.objects.len()).collect();
+    order.sort_by_key(|index| {
+        (
+            paths[*index].is_none(),
+            paths[*index]
+                .as_ref()
+                .map(|path| path.matches(['/', '[']).count()),
+        )
+    });
+
+    let mut matches = vec![];
+    for index in order {
+        let path = paths[index].clone().unwrap_or(format!("#{}", index));
+        let mut found = |path: &str, field: Field, text: String| {
+            if let Some(range) = regex.find(&text) {
+                matches.push(Match {
+                    object: index,
+                    path: path.to_string(),
+                    field,
+                    text,
+                    range,
+                });
+            }
+        };
+
+        let class = context.class_name(index).unwrap_or_default();
+        let is_string = is_string_class(&class);
+        found(&path, Field::Class, class);
+        let mut item = 0;
+        for (key, value) in context.named_values(index) {
+            let value_path = if key == EMPTY_KEY {
+                item += 1;
+                format!("{}[{}]", path, item - 1)
+            } else {
+                format!("{}/{}", path, key)
+            };
+            found(&value_path, Field::Key, key.clone());
+            if value.value_type != TYPE_DATA {
+                continue;
+            }
+            // The bytes of an NSString are the string the object stands for.
+            if let Ok(text) = String::from_utf8(value.data.clone()) {
+                let path = if is_string && key == "NS.bytes" {
+                    &path
+                } else {
+                    &value_path
+                };
+                found(path, Field::String, text);
+            }
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        raw_model::builder::ContextBuilder,
+        regex::Regex,
+        search::{grep, object_paths, Field},
+    };
+
+    #[test]
+    fn search() {
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let top_level = builder.reserve_object();
+        let label = builder.reserve_object();
+        let text = builder.reserve_object();
+        let orphan = builder.reserve_object();
+
+        let value = builder.object_value("UINibTopLevelObjectsKey", top_level);
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, vec![value]);
+        let values = vec![
+            builder.bool_value("NSInlinedValue", true),
+            builder.object_value("UINibEncoderEmptyKey", root),
+            builder.object_value("UINibEncoderEmptyKey", label),
+        ];
+        let class = builder.class("NSArray", &[]);
+        builder.set_object(top_level, class, values);
+        let value = builder.object_value("UIText", text);
+        let class = builder.class("UILabel", &[]);
+        builder.set_object(label, class, vec![value]);
+        let value = builder.bytes_value("NS.bytes", b"Log in".to_vec());
+        let class = builder.class("NSString", &[]);
+        builder.set_object(text, class, vec![value]);
+        let value = builder.bytes_value("UIText", b"Log out".to_vec());
+        let class = builder.class("UILabel", &[]);
+        builder.set_object(orphan, class, vec![value]);
+        let context = builder.build().unwrap();
+
+        assert_eq!(
+            Some("root/UINibTopLevelObjectsKey[1]/UIText"),
+            object_paths(&context)[text as usize].as_deref()
+        );
+
+        let regex = Regex::new("log (in|out)").unwrap().ignore_case(true);
+        let matches = grep(&context, &regex);
+        assert_eq!(
+            vec![
+                (
+                    "root/UINibTopLevelObjectsKey[1]/UIText",
+                    Field::String,
+                    "Log in"
+                ),
+                ("#4/UIText", Field::String, "Log out"),
+            ],
+            matches
+                .iter()
+                .map(|m| (m.path.as_str(), m.field, &m.text[m.range.clone()]))
+                .collect::<Vec<_>>()
+        );
+
+        let matches = grep(&context, &Regex::new("^UI(Label|Text)$").unwrap());
+        assert_eq!(4, matches.len());
+        assert_eq!(
+            ("root/UINibTopLevelObjectsKey[1]", Field::Class),
+            (matches[0].path.as_str(), matches[0].field)
+        );
+    }
+}
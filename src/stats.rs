@@ -0,0 +1,232 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::{
+    data::{is_string_class, Context},
+    format::{detect_format, Format},
+    raw_model::{nib::NibFile, raw_value::TYPE_DATA, ParseError},
+};
+
+// Number of strings and data values kept in `Stats::largest` by default.
+pub const LARGEST: usize = 10;
+
+// A string or data value, for the list of the largest ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blob {
+    pub file: String,
+    pub object: usize,
+    pub key: String,
+    pub length: usize,
+    // Whether the value holds the bytes of an NSString.
+    pub is_string: bool,
+}
+
+// Figures summed over any number of archives.
+#[derive(Debug)]
+pub struct Stats {
+    pub files: usize,
+    pub bytes: usize,
+    pub objects: usize,
+    pub values: usize,
+    // Objects per class name.
+    pub classes: BTreeMap<String, usize>,
+    // Values per key name. Keys no value uses are counted with zero.
+    pub keys: BTreeMap<String, usize>,
+    // Values per `RawValue::value_type`.
+    pub value_types: BTreeMap<u8, usize>,
+    // Bytes per section name, from NIBArchive inputs only.
+    pub sections: BTreeMap<&'static str, usize>,
+    // Largest first, at most `largest_count`.
+    pub largest: Vec<Blob>,
+    pub largest_count: usize,
+    // Longest chain of references needed to reach an object from the root,
+    // and the file it is in.
+    pub max_depth: usize,
+    pub deepest: Option<String>,
+}
+
+impl Default for Stats {
+    fn default() -> Stats {
+        Stats {
+            files: 0,
+            bytes: 0,
+            objects: 0,
+            values: 0,
+            classes: BTreeMap::new(),
+            keys: BTreeMap::new(),
+            value_types: BTreeMap::new(),
+            sections: BTreeMap::new(),
+            largest: vec![],
+            largest_count: LARGEST,
+            max_depth: 0,
+            deepest: None,
+        }
+    }
+}
+
+impl Stats {
+    // Decodes a buffer in any supported format and adds it.
+    pub fn add(&mut self, file: &str, buffer: Vec<u8>) -> Result<(), ParseError> {
+        let size = buffer.len();
+        let mut sections = vec![];
+        if let Format::NibArchive { .. } = detect_format(&buffer) {
+            sections = NibFile::from_buffer(buffer.clone())?.sections()?;
+        }
+        let context = Context::from_buffer(buffer)?;
+
+        for section in sections {
+            *self.sections.entry(section.kind.name()).or_default() += section.length;
+        }
+        self.bytes += size;
+        self.add_context(file, &context);
+        Ok(())
+    }
+
+    pub fn add_context(&mut self, file: &str, context: &Context) {
+        self.files += 1;
+        self.objects += context.objects.len();
+        self.values += context.values.len();
+
+        for key in &context.keys {
+            let name = String::from_utf8_lossy(&key.key_bytes).into_owned();
+            self.keys.entry(name).or_default();
+        }
+        for value in &context.values {
+            let key = context.key_name(value).unwrap_or("<unknown>".to_string());
+            *self.keys.entry(key).or_default() += 1;
+            *self.value_types.entry(value.value_type).or_default() += 1;
+        }
+
+        for index in 0..context.objects.len() {
+            let class = context.class_name(index).unwrap_or("<unknown>".to_string());
+            let is_string = is_string_class(&class);
+            *self.classes.entry(class).or_default() += 1;
+
+            for (key, value) in context.named_values(index) {
+                if value.value_type == TYPE_DATA {
+                    self.add_blob(Blob {
+                        file: file.to_string(),
+                        object: index,
+                        key,
+                        length: value.data.len(),
+                        is_string,
+                    });
+                }
+            }
+        }
+
+        let depth = reference_depth(context);
+        if depth > self.max_depth || self.deepest.is_none() {
+            self.max_depth = depth;
+            self.deepest = Some(file.to_string());
+        }
+    }
+
+    fn add_blob(&mut self, blob: Blob) {
+        if self.largest.len() >= self.largest_count
+            && self
+                .largest
+                .last()
+                .is_some_and(|smallest| smallest.length >= blob.length)
+        {
+            return;
+        }
+        let position = self
+            .largest
+            .partition_point(|other| other.length >= blob.length);
+        self.largest.insert(position, blob);
+        self.largest.truncate(self.largest_count);
+    }
+
+    pub fn values_per_object(&self) -> f64 {
+        if self.objects == 0 {
+            return 0.0;
+        }
+        self.values as f64 / self.objects as f64
+    }
+}
+
+// Number of references on the longest of the shortest paths from the root to
+// each object it reaches.
+pub fn reference_depth(context: &Context) -> usize {
+    if context.objects.is_empty() {
+        return 0;
+    }
+    let mut depths: Vec<Option<usize>> = vec![None; context.objects.len()];
+    depths[0] = Some(0);
+    let mut queue = VecDeque::from([0]);
+    let mut max = 0;
+    while let Some(index) = queue.pop_front() {
+        let depth = depths[index].unwrap_or_default();
+        max = max.max(depth);
+        for (_, value) in context.named_values(index) {
+            let Some(target) = value.object_reference().map(|t| t as usize) else {
+                continue;
+            };
+            if depths.get(target) == Some(&None) {
+                depths[target] = Some(depth + 1);
+                queue.push_back(target);
+            }
+        }
+    }
+    max
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        raw_model::{builder::ContextBuilder, nib::NibFile, raw_value::TYPE_OBJECT},
+        stats::{reference_depth, Stats},
+    };
+
+    #[test]
+    fn aggregate() {
+        // A view with a label whose text is a string, and a cycle back to the
+        // view.
+        let mut builder = ContextBuilder::new();
+        let view = builder.reserve_object();
+        let label = builder.reserve_object();
+        let text = builder.reserve_object();
+        let subview = builder.object_value("UISubviews", label);
+        let view_class = builder.class("UIView", &[]);
+        builder.set_object(view, view_class, vec![subview]);
+        let values = vec![
+            builder.object_value("UIText", text),
+            builder.object_value("UISuperview", view),
+        ];
+        let label_class = builder.class("UILabel", &[]);
+        builder.set_object(label, label_class, values);
+        let bytes = builder.bytes_value("NS.bytes", b"Hello".to_vec());
+        let string_class = builder.class("NSString", &[]);
+        builder.set_object(text, string_class, vec![bytes]);
+        let context = builder.build().unwrap();
+        assert_eq!(2, reference_depth(&context));
+
+        let nib = NibFile::from_context(&context);
+        let mut stats = Stats::default();
+        stats.add("a.nib", nib.buffer().to_vec()).unwrap();
+        stats.add("b.nib", nib.buffer().to_vec()).unwrap();
+        assert!(stats.add("c.nib", b"NIBArchive".to_vec()).is_err());
+
+        assert_eq!((2, 6, 8), (stats.files, stats.objects, stats.values));
+        assert_eq!(Some(&2), stats.classes.get("UILabel"));
+        assert_eq!(Some(&2), stats.keys.get("UISuperview"));
+        assert_eq!(Some(&6), stats.value_types.get(&TYPE_OBJECT));
+        assert_eq!(Some(&(2 * 9)), stats.sections.get("objects"));
+        assert_eq!(2, stats.largest.len());
+        assert_eq!(5, stats.largest[0].length);
+        assert!(stats.largest[0].is_string);
+
+        let mut top = Stats {
+            largest_count: 1,
+            ..Stats::default()
+        };
+        top.add("a.nib", nib.buffer().to_vec()).unwrap();
+        top.add("b.nib", nib.buffer().to_vec()).unwrap();
+        assert_eq!(1, top.largest.len());
+        assert_eq!(
+            (2, Some("a.nib")),
+            (stats.max_depth, stats.deepest.as_deref())
+        );
+        assert!((stats.values_per_object() - 4.0 / 3.0).abs() < 1e-9);
+    }
+}
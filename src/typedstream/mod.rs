@@ -0,0 +1,868 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    data::Context,
+    format::{TYPEDSTREAM_BIG_ENDIAN, TYPEDSTREAM_LITTLE_ENDIAN},
+    plist::keyed_archive::{EMPTY_KEY, INLINED_VALUE_KEY, TOP_CLASS},
+    raw_model::{builder::ContextBuilder, raw_value::RawValue, ParseError},
+};
+
+// Only streams written by NSArchiver and NeXTSTEP 3 or later are read.
+const STREAMER_VERSION: u8 = 4;
+
+// One byte integers from -128 to -111 are tags rather than values.
+const TAG_INTEGER_2: i8 = -127;
+const TAG_INTEGER_4: i8 = -126;
+const TAG_FLOATING_POINT: i8 = -125;
+const TAG_NEW: i8 = -124;
+const TAG_NIL: i8 = -123;
+const TAG_END_OF_OBJECT: i8 = -122;
+const LAST_TAG: i8 = -111;
+// Shared strings and objects are referred to by numbers counted from here.
+const FIRST_REFERENCE: i64 = -110;
+
+// Objects nested deeper than this are rejected instead of overflowing the
+// stack.
+const MAX_OBJECT_DEPTH: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Nil,
+    Integer(i64),
+    Float(f32),
+    Double(f64),
+    // C strings, atoms, selectors, class names and unshared strings.
+    String(Vec<u8>),
+    // An array of chars.
+    Bytes(Vec<u8>),
+    // Index into Typedstream::objects.
+    Object(usize),
+    Array(Vec<Value>),
+    // Fields are paired with their type encoding.
+    Struct {
+        name: String,
+        fields: Vec<(String, Value)>,
+    },
+}
+
+// Values written by one call to encodeValuesOfObjCTypes:, each paired with
+// its part of the type encoding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    pub encoding: String,
+    pub values: Vec<(String, Value)>,
+}
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    pub version: i64,
+    // Index into Typedstream::classes.
+    pub superclass: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    // Index into Typedstream::classes.
+    pub class: usize,
+    // Written by encodeWithCoder: of every class, superclasses first.
+    pub groups: Vec<Group>,
+}
+
+// A NeXT typedstream, as written by NSArchiver into the objects.nib of
+// nibs that predate keyed archiving.
+#[derive(Debug)]
+pub struct Typedstream {
+    pub big_endian: bool,
+    pub system_version: i64,
+    pub classes: Vec<Class>,
+    pub objects: Vec<Instance>,
+    // Groups written at the top level, usually just the root object.
+    pub top: Vec<Group>,
+}
+
+impl Typedstream {
+    // A class followed by its superclasses.
+    pub fn class_chain(&self, class: usize) -> Vec<&Class> {
+        let mut chain = vec![];
+        let mut next = Some(class);
+        while let Some(class) = next.and_then(|index| self.classes.get(index)) {
+            // Guard against a class table that loops.
+            if chain.len() > self.classes.len() {
+                break;
+            }
+            chain.push(class);
+            next = class.superclass;
+        }
+        chain
+    }
+}
+
+pub fn from_buffer(buffer: &[u8]) -> Result<Typedstream, ParseError> {
+    let mut reader = Reader {
+        buffer,
+        index: 0,
+        big_endian: false,
+        depth: 0,
+        strings: vec![],
+        shared: vec![],
+        classes: vec![],
+        objects: vec![],
+    };
+
+    let version = reader.byte()?;
+    if version != STREAMER_VERSION {
+        return Err(ParseError::new(
+            0,
+            0,
+            format!("Unsupported typedstream version {}", version),
+        ));
+    }
+    let head = reader.head()?;
+    let signature = reader.unshared_string(head)?;
+    reader.big_endian = match signature.as_slice() {
+        TYPEDSTREAM_LITTLE_ENDIAN => false,
+        TYPEDSTREAM_BIG_ENDIAN => true,
+        _ => return Err(ParseError::new(1, 0, "Invalid typedstream signature")),
+    };
+    let head = reader.head()?;
+    let system_version = reader.integer(head, true)?;
+
+    let mut top = vec![];
+    while reader.index < buffer.len() {
+        let head = reader.head()?;
+        top.push(reader.group(head)?);
+    }
+
+    Ok(Typedstream {
+        big_endian: reader.big_endian,
+        system_version,
+        classes: reader.classes,
+        objects: reader.objects,
+        top,
+    })
+}
+
+// Entries of the table shared references point into.
+enum Shared {
+    Object(usize),
+    Class(usize),
+    CString(Vec<u8>),
+}
+
+struct Reader<'a> {
+    buffer: &'a [u8],
+    index: usize,
+    big_endian: bool,
+    depth: usize,
+    strings: Vec<Vec<u8>>,
+    shared: Vec<Shared>,
+    classes: Vec<Class>,
+    objects: Vec<Instance>,
+}
+
+impl Reader<'_> {
+    fn error(&self, reason: impl Into<String>) -> ParseError {
+        ParseError::new(self.index, 0, reason)
+    }
+
+    fn take(&mut self, length: usize) -> Result<&[u8], ParseError> {
+        let bytes = self
+            .buffer
+            .get(self.index..self.index.saturating_add(length))
+            .ok_or(self.error("Unexpected end of typedstream"))?;
+        self.index += length;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, ParseError> {
+        Ok(self.take(1)?[0])
+    }
+
+    // The first byte of an integer, or a tag.
+    fn head(&mut self) -> Result<i8, ParseError> {
+        Ok(self.byte()? as i8)
+    }
+
+    fn fixed<const N: usize>(&mut self) -> Result<[u8; N], ParseError> {
+        let mut bytes: [u8; N] = self.take(N)?.try_into().unwrap();
+        if self.big_endian {
+            bytes.reverse();
+        }
+        Ok(bytes)
+    }
+
+    fn integer(&mut self, head: i8, signed: bool) -> Result<i64, ParseError> {
+        Ok(match head {
+            TAG_INTEGER_2 => {
+                let value = i16::from_le_bytes(self.fixed()?);
+                if signed {
+                    value as i64
+                } else {
+                    value as u16 as i64
+                }
+            }
+            TAG_INTEGER_4 => {
+                let value = i32::from_le_bytes(self.fixed()?);
+                if signed {
+                    value as i64
+                } else {
+                    value as u32 as i64
+                }
+            }
+            _ if head <= LAST_TAG => {
+                self.index -= 1;
+                return Err(self.error(format!("Expected an integer, found tag {}", head)));
+            }
+            _ if signed => head as i64,
+            _ => head as u8 as i64,
+        })
+    }
+
+    fn real(&mut self, double: bool) -> Result<f64, ParseError> {
+        let head = self.head()?;
+        if head != TAG_FLOATING_POINT {
+            return Ok(self.integer(head, true)? as f64);
+        }
+        Ok(if double {
+            f64::from_le_bytes(self.fixed()?)
+        } else {
+            f32::from_le_bytes(self.fixed()?) as f64
+        })
+    }
+
+    fn reference(&mut self, head: i8) -> Result<usize, ParseError> {
+        let number = self.integer(head, true)?;
+        if number < FIRST_REFERENCE {
+            return Err(self.error(format!("Invalid reference {}", number)));
+        }
+        Ok((number - FIRST_REFERENCE) as usize)
+    }
+
+    fn unshared_string(&mut self, head: i8) -> Result<Vec<u8>, ParseError> {
+        let length = self.integer(head, false)?;
+        Ok(self.take(length as usize)?.to_vec())
+    }
+
+    fn shared_string(&mut self, head: i8) -> Result<Option<Vec<u8>>, ParseError> {
+        match head {
+            TAG_NIL => Ok(None),
+            TAG_NEW => {
+                let head = self.head()?;
+                let string = self.unshared_string(head)?;
+                self.strings.push(string.clone());
+                Ok(Some(string))
+            }
+            _ => {
+                let reference = self.reference(head)?;
+                match self.strings.get(reference) {
+                    Some(string) => Ok(Some(string.clone())),
+                    None => Err(self.error(format!("Invalid string reference {}", reference))),
+                }
+            }
+        }
+    }
+
+    fn c_string(&mut self) -> Result<Value, ParseError> {
+        let head = self.head()?;
+        match head {
+            TAG_NIL => Ok(Value::Nil),
+            TAG_NEW => {
+                let head = self.head()?;
+                let string = self.shared_string(head)?.unwrap_or_default();
+                self.shared.push(Shared::CString(string.clone()));
+                Ok(Value::String(string))
+            }
+            _ => match self
+                .reference(head)
+                .map(|reference| self.shared.get(reference))?
+            {
+                Some(Shared::CString(string)) => Ok(Value::String(string.clone())),
+                _ => Err(self.error("Invalid C string reference")),
+            },
+        }
+    }
+
+    fn class(&mut self, head: i8) -> Result<Option<usize>, ParseError> {
+        match head {
+            TAG_NIL => Ok(None),
+            TAG_NEW => {
+                let head = self.head()?;
+                let name = self
+                    .shared_string(head)?
+                    .ok_or(self.error("Class has no name"))?;
+                let head = self.head()?;
+                let version = self.integer(head, true)?;
+                let index = self.classes.len();
+                self.classes.push(Class {
+                    name: String::from_utf8_lossy(&name).into_owned(),
+                    version,
+                    superclass: None,
+                });
+                self.shared.push(Shared::Class(index));
+
+                let head = self.head()?;
+                self.classes[index].superclass = self.class(head)?;
+                Ok(Some(index))
+            }
+            _ => match self
+                .reference(head)
+                .map(|reference| self.shared.get(reference))?
+            {
+                Some(Shared::Class(index)) => Ok(Some(*index)),
+                _ => Err(self.error("Invalid class reference")),
+            },
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, ParseError> {
+        let head = self.head()?;
+        match head {
+            TAG_NIL => Ok(Value::Nil),
+            TAG_NEW => {
+                if self.depth >= MAX_OBJECT_DEPTH {
+                    return Err(self.error("Objects are nested too deeply"));
+                }
+                self.depth += 1;
+
+                // Registered before its contents, which may refer back to it.
+                let index = self.objects.len();
+                self.objects.push(Instance {
+                    class: 0,
+                    groups: vec![],
+                });
+                self.shared.push(Shared::Object(index));
+
+                let head = self.head()?;
+                let class = self.class(head)?.ok_or(self.error("Object has no class"))?;
+                let mut groups = vec![];
+                loop {
+                    let head = self.head()?;
+                    if head == TAG_END_OF_OBJECT {
+                        break;
+                    }
+                    groups.push(self.group(head)?);
+                }
+                self.objects[index] = Instance { class, groups };
+
+                self.depth -= 1;
+                Ok(Value::Object(index))
+            }
+            _ => match self
+                .reference(head)
+                .map(|reference| self.shared.get(reference))?
+            {
+                Some(Shared::Object(index)) => Ok(Value::Object(*index)),
+                _ => Err(self.error("Invalid object reference")),
+            },
+        }
+    }
+
+    fn group(&mut self, head: i8) -> Result<Group, ParseError> {
+        let encoding = self
+            .shared_string(head)?
+            .ok_or(self.error("Missing type encoding"))?;
+        let encoding = String::from_utf8_lossy(&encoding).into_owned();
+        let mut values = vec![];
+        let mut position = 0;
+        while position < encoding.len() {
+            let start = position;
+            if let Some(value) = self.typed(encoding.as_bytes(), &mut position)? {
+                values.push((encoding[start..position].to_string(), value));
+            }
+        }
+        Ok(Group { encoding, values })
+    }
+
+    // Reads the value of the type starting at `position` in an encoding and
+    // moves past it. Returns None for types that write nothing.
+    fn typed(
+        &mut self,
+        encoding: &[u8],
+        position: &mut usize,
+    ) -> Result<Option<Value>, ParseError> {
+        let code = encoding[*position];
+        *position += 1;
+        let value = match code {
+            b'c' | b's' | b'i' | b'l' | b'q' => {
+                let head = self.head()?;
+                Value::Integer(self.integer(head, true)?)
+            }
+            b'C' | b'S' | b'I' | b'L' | b'Q' => {
+                let head = self.head()?;
+                Value::Integer(self.integer(head, false)?)
+            }
+            b'f' => Value::Float(self.real(false)? as f32),
+            b'd' => Value::Double(self.real(true)?),
+            b'*' => self.c_string()?,
+            b'%' | b':' => {
+                let head = self.head()?;
+                self.shared_string(head)?.map_or(Value::Nil, Value::String)
+            }
+            b'+' => {
+                let head = self.head()?;
+                Value::String(self.unshared_string(head)?)
+            }
+            b'@' => self.object()?,
+            b'#' => {
+                let head = self.head()?;
+                match self.class(head)? {
+                    Some(class) => Value::String(self.classes[class].name.as_bytes().to_vec()),
+                    None => Value::Nil,
+                }
+            }
+            b'!' => return Ok(None),
+            // Type qualifiers such as const.
+            b'r' | b'n' | b'N' | b'o' | b'O' | b'R' | b'V' if *position < encoding.len() => {
+                return self.typed(encoding, position)
+            }
+            // The value pointed to is written in place of the pointer.
+            b'^' if *position < encoding.len() => return self.typed(encoding, position),
+            b'[' => {
+                let digits = encoding[*position..]
+                    .iter()
+                    .take_while(|byte| byte.is_ascii_digit())
+                    .count();
+                let count: usize = std::str::from_utf8(&encoding[*position..*position + digits])
+                    .unwrap()
+                    .parse()
+                    .map_err(|_| self.error("Array encoding has no length"))?;
+                *position += digits;
+                let element = *position;
+                let end =
+                    type_end(encoding, element).ok_or(self.error("Invalid array encoding"))?;
+                if encoding.get(end) != Some(&b']') {
+                    return Err(self.error("Invalid array encoding"));
+                }
+                *position = end + 1;
+
+                if matches!(&encoding[element..end], b"c" | b"C") {
+                    Value::Bytes(self.take(count)?.to_vec())
+                } else {
+                    let mut items = vec![];
+                    for _ in 0..count {
+                        if let Some(item) = self.typed(encoding, &mut element.clone())? {
+                            items.push(item);
+                        }
+                    }
+                    Value::Array(items)
+                }
+            }
+            b'{' => {
+                let end = type_end(encoding, *position - 1)
+                    .ok_or(self.error("Invalid struct encoding"))?;
+                let body = &encoding[*position..end - 1];
+                let (name, mut field) = match body.iter().position(|byte| *byte == b'=') {
+                    Some(equals) => (&body[..equals], *position + equals + 1),
+                    None => (body, end - 1),
+                };
+                let mut fields = vec![];
+                while field < end - 1 {
+                    let start = field;
+                    if let Some(value) = self.typed(encoding, &mut field)? {
+                        let encoding = String::from_utf8_lossy(&encoding[start..field]);
+                        fields.push((encoding.into_owned(), value));
+                    }
+                }
+                *position = end;
+                Value::Struct {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    fields,
+                }
+            }
+            _ => {
+                return Err(self.error(format!(
+                    "Unsupported type encoding {:?}",
+                    String::from_utf8_lossy(encoding)
+                )))
+            }
+        };
+        Ok(Some(value))
+    }
+}
+
+// The position just past the type starting at `start`.
+fn type_end(encoding: &[u8], start: usize) -> Option<usize> {
+    match encoding.get(start)? {
+        b'r' | b'n' | b'N' | b'o' | b'O' | b'R' | b'V' | b'^' => type_end(encoding, start + 1),
+        b'[' => {
+            let mut position = start + 1;
+            while encoding.get(position)?.is_ascii_digit() {
+                position += 1;
+            }
+            let end = type_end(encoding, position)?;
+            (encoding.get(end) == Some(&b']')).then_some(end + 1)
+        }
+        b'{' => {
+            let mut position = start + 1;
+            while !matches!(encoding.get(position)?, b'=' | b'}') {
+                position += 1;
+            }
+            if encoding[position] == b'=' {
+                position += 1;
+                while *encoding.get(position)? != b'}' {
+                    position = type_end(encoding, position)?;
+                }
+            }
+            Some(position + 1)
+        }
+        _ => Some(start + 1),
+    }
+}
+
+// Maps a typedstream into the NIB object model. Every object keeps its class
+// with the superclasses as extra class values, gets one "<Class>.version"
+// value per class and one value per ivar, keyed by its position and type
+// encoding such as "0:@" or "3:i". Strings, data and collections are stored
+// the way the NIB encoder stores them. The root object, or a $top object
+// holding the top level values, ends up at index 0.
+pub fn to_context(stream: &Typedstream) -> Result<Context, ParseError> {
+    let mut unpacker = Unpacker {
+        stream,
+        builder: ContextBuilder::new(),
+        indices: HashMap::new(),
+        queue: VecDeque::new(),
+    };
+
+    let top: Vec<&(String, Value)> = stream.top.iter().flat_map(|group| &group.values).collect();
+    match top.as_slice() {
+        [(_, Value::Object(root))] => {
+            unpacker.reference(*root);
+        }
+        _ => {
+            let index = unpacker.builder.reserve_object();
+            let class = unpacker.builder.class(TOP_CLASS, &[]);
+            let mut values = vec![];
+            for (position, (encoding, value)) in top.iter().enumerate() {
+                unpacker.value(&ivar_key(position, encoding), value, &mut values);
+            }
+            unpacker.builder.set_object(index, class, values);
+        }
+    }
+    unpacker.drain()?;
+
+    for object in 0..stream.objects.len() {
+        if !unpacker.indices.contains_key(&object) {
+            unpacker.reference(object);
+            unpacker.drain()?;
+        }
+    }
+
+    unpacker
+        .builder
+        .build()
+        .map_err(|reason| ParseError::new(0, 0, reason))
+}
+
+fn ivar_key(position: usize, encoding: &str) -> String {
+    // Structs are named rather than spelled out.
+    let encoding = match encoding.strip_prefix('{') {
+        Some(body) => body.split(['=', '}']).next().unwrap_or_default(),
+        None => encoding,
+    };
+    format!("{}:{}", position, encoding)
+}
+
+struct Unpacker<'a> {
+    stream: &'a Typedstream,
+    builder: ContextBuilder,
+    indices: HashMap<usize, u32>,
+    queue: VecDeque<(usize, u32)>,
+}
+
+impl Unpacker<'_> {
+    fn drain(&mut self) -> Result<(), ParseError> {
+        while let Some((object, index)) = self.queue.pop_front() {
+            self.object(object, index)?;
+        }
+        Ok(())
+    }
+
+    fn reference(&mut self, object: usize) -> u32 {
+        if let Some(index) = self.indices.get(&object) {
+            return *index;
+        }
+        let index = self.builder.reserve_object();
+        self.indices.insert(object, index);
+        self.queue.push_back((object, index));
+        index
+    }
+
+    fn object(&mut self, object: usize, index: u32) -> Result<(), ParseError> {
+        let stream = self.stream;
+        let instance = &stream.objects[object];
+        let chain = stream.class_chain(instance.class);
+        let name = chain.first().map_or("", |class| class.name.as_str());
+        let superclasses: Vec<i32> = chain[1.min(chain.len())..]
+            .iter()
+            .map(|class| self.builder.class(&class.name, &[]) as i32)
+            .collect();
+        let class_index = self.builder.class(name, &superclasses);
+
+        let mut values = vec![];
+        for class in &chain {
+            values.push(
+                self.builder
+                    .int_value(&format!("{}.version", class.name), class.version),
+            );
+        }
+
+        let ivars: Vec<&(String, Value)> = instance
+            .groups
+            .iter()
+            .flat_map(|group| &group.values)
+            .collect();
+        // Collections start with their element count, which must match the
+        // elements that follow. Otherwise the ivars are kept as they are.
+        let items = match ivars.as_slice() {
+            [(_, Value::Integer(count)), items @ ..] => {
+                let count = usize::try_from(*count).map_err(|_| {
+                    ParseError::new(
+                        0,
+                        0,
+                        format!("Object {} has an invalid count {}", object, count),
+                    )
+                })?;
+                Some((count, items))
+            }
+            _ => None,
+        };
+        match (name, ivars.as_slice(), items) {
+            ("NSString" | "NSMutableString", [(_, Value::String(bytes))], _)
+            | ("NSData" | "NSMutableData", [(_, Value::Integer(_)), (_, Value::Bytes(bytes))], _) =>
+            {
+                values.push(self.builder.bytes_value("NS.bytes", bytes.to_owned()));
+            }
+            ("NSArray" | "NSMutableArray" | "NSSet" | "NSMutableSet", _, Some((count, items)))
+                if count == items.len() =>
+            {
+                self.collection(items, &mut values);
+            }
+            ("NSDictionary" | "NSMutableDictionary", _, Some((count, items)))
+                if count.checked_mul(2) == Some(items.len()) =>
+            {
+                self.collection(items, &mut values);
+            }
+            _ => {
+                for (position, (encoding, value)) in ivars.iter().enumerate() {
+                    self.value(&ivar_key(position, encoding), value, &mut values);
+                }
+            }
+        }
+
+        self.builder.set_object(index, class_index, values);
+        Ok(())
+    }
+
+    // Elements, or alternating keys and values, the way the NIB encoder
+    // stores collections.
+    fn collection(&mut self, items: &[&(String, Value)], values: &mut Vec<RawValue>) {
+        values.push(self.builder.bool_value(INLINED_VALUE_KEY, true));
+        for (_, item) in items {
+            self.value(EMPTY_KEY, item, values);
+        }
+    }
+
+    fn value(&mut self, key: &str, value: &Value, values: &mut Vec<RawValue>) {
+        let raw = match value {
+            Value::Nil => self.builder.nil_value(key),
+            Value::Integer(integer) => self.builder.int_value(key, *integer),
+            Value::Float(float) => self.builder.float_value(key, *float),
+            Value::Double(double) => self.builder.double_value(key, *double),
+            Value::String(bytes) | Value::Bytes(bytes) => {
+                self.builder.bytes_value(key, bytes.to_owned())
+            }
+            Value::Object(object) => {
+                let index = self.reference(*object);
+                self.builder.object_value(key, index)
+            }
+            Value::Array(items) => {
+                for item in items {
+                    self.value(key, item, values);
+                }
+                return;
+            }
+            Value::Struct { name, fields } => {
+                let mut field_values = vec![];
+                for (position, (encoding, field)) in fields.iter().enumerate() {
+                    self.value(&ivar_key(position, encoding), field, &mut field_values);
+                }
+                let class = self.builder.class(name, &[]);
+                let index = self.builder.push_object(class, field_values);
+                self.builder.object_value(key, index)
+            }
+        };
+        values.push(raw);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::{Context, Data},
+        plist::keyed_archive::INLINED_VALUE_KEY,
+        typedstream::{from_buffer, to_context, Class, Group, Instance, Typedstream, Value},
+    };
+
+    // A length prefixed string.
+    fn string(text: &str) -> Vec<u8> {
+        [&[text.len() as u8], text.as_bytes()].concat()
+    }
+
+    #[test]
+    fn objects_nib() {
+        let new = 0x84;
+        let stream = [
+            vec![4],
+            string("streamtyped"),
+            vec![0x81, 0xe8, 0x03],
+            // Top level "@", a new LoginController : NSObject, version 3.
+            vec![new],
+            string("@"),
+            vec![new, new, new],
+            string("LoginController"),
+            vec![3, new, new],
+            string("NSObject"),
+            vec![0, 0x85],
+            // "@", a new NSString, whose superclass is shared reference 2.
+            vec![0x92, new, new, new],
+            string("NSString"),
+            vec![1, 0x94, new],
+            string("+"),
+            string("Log in"),
+            vec![0x86],
+            vec![new],
+            string("i"),
+            vec![0x81, 0xe8, 0x03],
+            vec![new],
+            string("{_NSPoint=ff}"),
+            [vec![0x83], 1.5f32.to_le_bytes().to_vec(), vec![2]].concat(),
+            vec![new],
+            string("[3c]"),
+            vec![1, 2, 3],
+            // "@", the controller itself.
+            vec![0x92, 0x92],
+            vec![new],
+            string("*"),
+            vec![new, new],
+            string("hi"),
+            vec![0x86],
+        ]
+        .concat();
+
+        let typedstream = from_buffer(&stream).unwrap();
+        assert_eq!(1000, typedstream.system_version);
+        assert_eq!(2, typedstream.objects.len());
+        assert_eq!(
+            vec![("NSString", 1), ("NSObject", 0)],
+            typedstream
+                .class_chain(typedstream.objects[1].class)
+                .iter()
+                .map(|class| (class.name.as_str(), class.version))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            (
+                "{_NSPoint=ff}".to_string(),
+                Value::Struct {
+                    name: "_NSPoint".to_string(),
+                    fields: vec![
+                        ("f".to_string(), Value::Float(1.5)),
+                        ("f".to_string(), Value::Float(2.0))
+                    ]
+                }
+            ),
+            typedstream.objects[0].groups[2].values[0]
+        );
+
+        let context = to_context(&typedstream).unwrap();
+        let values = |context: &Context, index: usize| -> Vec<(String, String)> {
+            context
+                .named_values(index)
+                .into_iter()
+                .map(|(key, value)| {
+                    let text = match value.object_reference() {
+                        Some(target) => format!("#{}", target),
+                        None => match Data::from_scalar(value) {
+                            Some(Data::Bytes(bytes)) => String::from_utf8_lossy(&bytes).into(),
+                            data => format!("{:?}", data.unwrap()),
+                        },
+                    };
+                    (key, text)
+                })
+                .collect()
+        };
+        let pairs = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        };
+
+        assert_eq!(Some("LoginController".to_string()), context.class_name(0));
+        assert_eq!(
+            pairs(&[
+                ("LoginController.version", "Int8(3)"),
+                ("NSObject.version", "Int8(0)"),
+                ("0:@", "#1"),
+                ("1:i", "Int16(1000)"),
+                ("2:_NSPoint", "#2"),
+                ("3:[3c]", "\u{1}\u{2}\u{3}"),
+                ("4:@", "#0"),
+                ("5:*", "hi"),
+            ]),
+            values(&context, 0)
+        );
+        assert_eq!(
+            pairs(&[
+                ("NSString.version", "Int8(1)"),
+                ("NSObject.version", "Int8(0)"),
+                ("NS.bytes", "Log in"),
+            ]),
+            values(&context, 1)
+        );
+        assert_eq!(
+            pairs(&[("0:f", "Float(1.5)"), ("1:f", "Float(2.0)")]),
+            values(&context, 2)
+        );
+    }
+
+    #[test]
+    fn invalid_count() {
+        let array = |count: i64| Typedstream {
+            big_endian: false,
+            system_version: 1000,
+            classes: vec![Class {
+                name: "NSArray".to_string(),
+                version: 0,
+                superclass: None,
+            }],
+            objects: vec![Instance {
+                class: 0,
+                groups: vec![
+                    Group {
+                        encoding: "i".to_string(),
+                        values: vec![("i".to_string(), Value::Integer(count))],
+                    },
+                    Group {
+                        encoding: "@".to_string(),
+                        values: vec![("@".to_string(), Value::Nil)],
+                    },
+                ],
+            }],
+            top: vec![Group {
+                encoding: "@".to_string(),
+                values: vec![("@".to_string(), Value::Object(0))],
+            }],
+        };
+
+        let context = to_context(&array(1)).unwrap();
+        assert!(context.find_value(0, INLINED_VALUE_KEY).is_some());
+        // A count that does not match keeps the ivars as they are.
+        let context = to_context(&array(2)).unwrap();
+        assert!(context.find_value(0, INLINED_VALUE_KEY).is_none());
+
+        let error = to_context(&array(-1)).err().unwrap();
+        assert_eq!("Object 0 has an invalid count -1", error.reason);
+    }
+}
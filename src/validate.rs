@@ -0,0 +1,355 @@
+use crate::{
+    data::{Class, Context},
+    raw_model::{nib::SectionKind, raw_value::TYPE_OBJECT},
+    references::ReferenceIndex,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    // Harmless, but not something ibtool writes.
+    Note,
+    // Decodes, but probably not what the author meant.
+    Warning,
+    // Decoding has to guess or drop something.
+    Error,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 3] = [Severity::Error, Severity::Warning, Severity::Note];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Note => "note",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Severity> {
+        Severity::ALL
+            .into_iter()
+            .find(|severity| severity.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    // Short name of the check, such as "object-reference".
+    pub check: &'static str,
+    // The table entry the finding is about, if any.
+    pub entry: Option<(SectionKind, usize)>,
+    pub message: String,
+}
+
+// Checks the tables of an archive against each other:
+//
+//   error    class-index          object with a class index out of range
+//   error    key-index            value with a key index out of range
+//   error    value-range          object whose values run past the table
+//   error    object-reference     reference to an object that does not exist
+//   error    value-type           value of an unknown type
+//   error    no-objects           archive without objects
+//   warning  overlapping-values   objects sharing values
+//   warning  unreferenced-object  object other than the root nothing refers to
+//   warning  unreachable-object   object only referred to by unreachable ones
+//   note     unused-key           key no value uses
+//   note     unused-class         class no object uses, not even as fallback
+pub fn validate(context: &Context) -> Vec<Finding> {
+    let mut findings = vec![];
+    let mut add = |severity, check, entry, message: String| {
+        findings.push(Finding {
+            severity,
+            check,
+            entry,
+            message,
+        })
+    };
+    let object = |index: usize| Some((SectionKind::Objects, index));
+
+    if context.objects.is_empty() {
+        add(
+            Severity::Error,
+            "no-objects",
+            None,
+            "Archive has no objects".to_string(),
+        );
+    }
+
+    let mut used_classes = vec![false; context.classes.len()];
+    for (index, raw) in context.objects.iter().enumerate() {
+        match raw.class_index.value() {
+            Ok(class) if (class as usize) < context.classes.len() => {
+                used_classes[class as usize] = true
+            }
+            class => add(
+                Severity::Error,
+                "class-index",
+                object(index),
+                format!(
+                    "Object {} has class index {}, the archive has {} classes",
+                    index,
+                    class.map_or("invalid".to_string(), |class| class.to_string()),
+                    context.classes.len()
+                ),
+            ),
+        }
+    }
+    // Fallback classes of a used class, decoded when the class itself is
+    // missing at runtime, are used as well.
+    let mut pending: Vec<usize> = (0..used_classes.len())
+        .filter(|index| used_classes[*index])
+        .collect();
+    while let Some(index) = pending.pop() {
+        for fallback in Class::from(&context.classes[index]).extra_values {
+            let Ok(fallback) = usize::try_from(fallback) else {
+                continue;
+            };
+            if fallback < used_classes.len() && !used_classes[fallback] {
+                used_classes[fallback] = true;
+                pending.push(fallback);
+            }
+        }
+    }
+
+    // Value ranges, checked for bounds and then for overlaps in start order.
+    let mut ranges = vec![];
+    for (index, raw) in context.objects.iter().enumerate() {
+        let start = raw.value_index.value().unwrap_or(u32::MAX) as usize;
+        let count = raw.value_count.value().unwrap_or(u32::MAX) as usize;
+        match start.checked_add(count) {
+            Some(end) if end <= context.values.len() => {
+                if count > 0 {
+                    ranges.push((start, end, index));
+                }
+            }
+            _ => add(
+                Severity::Error,
+                "value-range",
+                object(index),
+                format!(
+                    "Values of object {} start at {} and number {}, the archive has {} values",
+                    index,
+                    start,
+                    count,
+                    context.values.len()
+                ),
+            ),
+        }
+    }
+    ranges.sort();
+    let mut furthest: Option<(usize, usize)> = None;
+    for (start, end, index) in ranges {
+        if let Some((previous_end, previous)) = furthest {
+            if start < previous_end {
+                add(
+                    Severity::Warning,
+                    "overlapping-values",
+                    object(index),
+                    format!(
+                        "Values of object {} overlap those of object {}",
+                        index, previous
+                    ),
+                );
+            }
+        }
+        if furthest.is_none_or(|(previous_end, _)| end > previous_end) {
+            furthest = Some((end, index));
+        }
+    }
+
+    let mut used_keys = vec![false; context.keys.len()];
+    for (index, value) in context.values.iter().enumerate() {
+        let entry = Some((SectionKind::Values, index));
+        match value.key_index.value() {
+            Ok(key) if (key as usize) < context.keys.len() => used_keys[key as usize] = true,
+            key => add(
+                Severity::Error,
+                "key-index",
+                entry,
+                format!(
+                    "Value {} has key index {}, the archive has {} keys",
+                    index,
+                    key.map_or("invalid".to_string(), |key| key.to_string()),
+                    context.keys.len()
+                ),
+            ),
+        }
+        if value.value_type > TYPE_OBJECT {
+            add(
+                Severity::Error,
+                "value-type",
+                entry,
+                format!("Value {} has unknown type {}", index, value.value_type),
+            );
+        }
+        if let Some(target) = value.object_reference() {
+            if target as usize >= context.objects.len() {
+                add(
+                    Severity::Error,
+                    "object-reference",
+                    entry,
+                    format!(
+                        "Value {} references missing object {}, the archive has {} objects",
+                        index,
+                        target,
+                        context.objects.len()
+                    ),
+                );
+            }
+        }
+    }
+
+    let references = ReferenceIndex::new(context);
+    for index in 1..context.objects.len() {
+        if references.referrers(index).is_empty() {
+            add(
+                Severity::Warning,
+                "unreferenced-object",
+                object(index),
+                format!("Object {} is not referenced by any object", index),
+            );
+        } else if references.path(index).is_none() {
+            add(
+                Severity::Warning,
+                "unreachable-object",
+                object(index),
+                format!(
+                    "Object {} is only referenced by objects the root does not reach",
+                    index
+                ),
+            );
+        }
+    }
+
+    for (index, used) in used_keys.iter().enumerate() {
+        if !used {
+            let name = String::from_utf8_lossy(&context.keys[index].key_bytes).into_owned();
+            add(
+                Severity::Note,
+                "unused-key",
+                Some((SectionKind::Keys, index)),
+                format!("Key {} ({}) is not used by any value", index, name),
+            );
+        }
+    }
+    for (index, used) in used_classes.iter().enumerate() {
+        if !used {
+            let name = String::from_utf8_lossy(&context.classes[index].class_name)
+                .trim_end_matches('\0')
+                .to_string();
+            add(
+                Severity::Note,
+                "unused-class",
+                Some((SectionKind::Classes, index)),
+                format!("Class {} ({}) is not used by any object", index, name),
+            );
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::Context,
+        raw_model::{
+            builder::ContextBuilder, nib::SectionKind, raw_object::RawObject, raw_value::RawValue,
+        },
+        validate::{validate, Severity},
+    };
+
+    #[test]
+    fn findings() {
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let view = builder.reserve_object();
+        let island = builder.reserve_object();
+        let value = builder.object_value("UISubviews", view);
+        let class = builder.class("UIView", &[]);
+        builder.set_object(root, class, vec![value]);
+        builder.set_object(view, class, vec![]);
+        let value = builder.object_value("UISuperview", island);
+        builder.set_object(island, class, vec![value]);
+        builder.key("UIUnused");
+        builder.class("UIUnused", &[]);
+        let context = builder.build().unwrap();
+
+        let checks = |context: &Context| -> Vec<(Severity, &'static str)> {
+            validate(context)
+                .into_iter()
+                .map(|finding| (finding.severity, finding.check))
+                .collect()
+        };
+        assert_eq!(
+            vec![
+                (Severity::Warning, "unreachable-object"),
+                (Severity::Note, "unused-key"),
+                (Severity::Note, "unused-class"),
+            ],
+            checks(&context)
+        );
+
+        // Break every index.
+        let Context {
+            mut objects,
+            keys,
+            mut values,
+            classes,
+            ..
+        } = context;
+        objects.push(RawObject::new(9, 0, 1));
+        objects.push(RawObject::new(0, 2, 5));
+        values.push(RawValue::new(7, 10, 42u32.to_le_bytes().to_vec()));
+        values.push(RawValue::new(0, 11, vec![]));
+        let broken = Context {
+            objects,
+            keys,
+            values,
+            classes,
+            depth: 0,
+        };
+        let findings = validate(&broken);
+        assert_eq!(
+            vec![
+                (Severity::Error, "class-index"),
+                (Severity::Error, "value-range"),
+                (Severity::Warning, "overlapping-values"),
+                (Severity::Error, "key-index"),
+                (Severity::Error, "object-reference"),
+                (Severity::Error, "value-type"),
+                (Severity::Warning, "unreachable-object"),
+                (Severity::Warning, "unreferenced-object"),
+                (Severity::Warning, "unreferenced-object"),
+                (Severity::Note, "unused-key"),
+                (Severity::Note, "unused-class"),
+            ],
+            checks(&broken)
+        );
+        assert_eq!(Some((SectionKind::Objects, 3)), findings[0].entry);
+        assert_eq!(
+            "Value 2 references missing object 42, the archive has 5 objects",
+            findings[4].message
+        );
+    }
+
+    #[test]
+    fn fallback_classes() {
+        // UIView is only named as the fallback of CustomView, and UIResponder
+        // as the fallback of UIView.
+        let mut builder = ContextBuilder::new();
+        let responder = builder.class("UIResponder", &[]) as i32;
+        let view = builder.class("UIView", &[responder]) as i32;
+        let custom = builder.class("CustomView", &[view]);
+        builder.class("UIUnused", &[responder]);
+        let root = builder.reserve_object();
+        builder.set_object(root, custom, vec![]);
+
+        let findings = validate(&builder.build().unwrap());
+        assert_eq!(1, findings.len());
+        assert_eq!(
+            "Class 3 (UIUnused) is not used by any object",
+            findings[0].message
+        );
+    }
+}
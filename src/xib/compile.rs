@@ -0,0 +1,960 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    data::Context,
+    plist::keyed_archive::{EMPTY_KEY, INLINED_VALUE_KEY},
+    raw_model::{builder::ContextBuilder, nib::NibFile, raw_value::RawValue, ParseError},
+};
+
+use super::{
+    class_for_element, is_view_element,
+    xml::{self, Element},
+    AUTORESIZING, BORDER_STYLES, BUTTON_TYPES, CONTENT_MODES, CONTROL_EVENTS, CONTROL_STATES,
+    HORIZONTAL_ALIGNMENTS, LAYOUT_ATTRIBUTES, LINE_BREAK_MODES, TEXT_ALIGNMENTS,
+    VERTICAL_ALIGNMENTS,
+};
+
+pub struct Compilation {
+    pub context: Context,
+    // Elements of the document that are not represented in the nib.
+    pub warnings: Vec<String>,
+}
+
+// Compiles XIB source into NIBArchive bytes.
+pub fn compile_xml(text: &str) -> Result<(Vec<u8>, Vec<String>), ParseError> {
+    let compilation = compile(&xml::parse(text)?)?;
+    let nib = NibFile::from_context(&compilation.context);
+    Ok((nib.buffer().to_vec(), compilation.warnings))
+}
+
+// Builds the object graph UIKit expects when loading a nib from an Interface
+// Builder document. An element or attribute the compiler does not know is
+// skipped with a warning naming it and its line.
+pub fn compile(document: &Element) -> Result<Compilation, ParseError> {
+    if document.name != "document" {
+        return Err(ParseError::new(
+            0,
+            0,
+            format!(
+                "Expected a <document> root element, found <{}>",
+                document.name
+            ),
+        ));
+    }
+    let objects = document.child("objects").ok_or(ParseError::new(
+        0,
+        0,
+        "Document has no <objects> element",
+    ))?;
+
+    let mut compiler = Compiler {
+        builder: ContextBuilder::new(),
+        defined_ids: HashSet::new(),
+        indices: HashMap::new(),
+        set: HashSet::new(),
+        objects: vec![],
+        connections: vec![],
+        key_value_pairs: vec![],
+        warnings: vec![],
+    };
+    collect_ids(objects, &mut compiler.defined_ids);
+
+    let root = compiler.builder.reserve_object();
+    let mut top_level = vec![];
+    for child in &objects.children {
+        if let Some(index) = compiler.object(child, None) {
+            top_level.push(index);
+        }
+    }
+
+    let mut values = vec![];
+    for (key, items) in [
+        ("UINibTopLevelObjectsKey", top_level),
+        ("UINibObjectsKey", compiler.objects.clone()),
+        ("UINibConnectionsKey", compiler.connections.clone()),
+        ("UINibVisibleWindowsKey", vec![]),
+        ("UINibAccessibilityConfigurationsKey", vec![]),
+        ("UINibKeyValuePairsKey", compiler.key_value_pairs.clone()),
+    ] {
+        let array = compiler.array("NSArray", &items);
+        values.push(compiler.builder.object_value(key, array));
+    }
+    let class = compiler.builder.class("NSObject", &[]);
+    compiler.builder.set_object(root, class, values);
+    compiler.fill_unset();
+
+    let context = compiler
+        .builder
+        .build()
+        .map_err(|e| ParseError::new(0, 0, e))?;
+    Ok(Compilation {
+        context,
+        warnings: compiler.warnings,
+    })
+}
+
+// Attributes that identify or place an element, or that only the editor of
+// Interface Builder uses.
+const EDITOR_ATTRIBUTES: &[&str] = &[
+    "id",
+    "key",
+    "customClass",
+    "customModule",
+    "customModuleProvider",
+    "userLabel",
+    "sceneMemberID",
+    "fixedFrame",
+    "misplaced",
+    "ambiguous",
+    "placeholderIntrinsicWidth",
+    "placeholderIntrinsicHeight",
+];
+
+fn collect_ids(element: &Element, ids: &mut HashSet<String>) {
+    for child in &element.children {
+        if let Some(id) = child.get_attr("id") {
+            ids.insert(id.to_string());
+        }
+        collect_ids(child, ids);
+    }
+}
+
+struct Compiler {
+    builder: ContextBuilder,
+    // Every id declared in the document, so references can be checked
+    // before the referenced element is compiled.
+    defined_ids: HashSet<String>,
+    indices: HashMap<String, u32>,
+    set: HashSet<u32>,
+    objects: Vec<u32>,
+    connections: Vec<u32>,
+    key_value_pairs: Vec<u32>,
+    warnings: Vec<String>,
+}
+
+impl Compiler {
+    fn unsupported(&mut self, element: &Element, parent: &Element) {
+        self.warnings.push(format!(
+            "Line {}: <{}> in <{}> is not supported",
+            element.line, element.name, parent.name
+        ));
+    }
+
+    fn unsupported_attribute(&mut self, element: &Element, name: &str, value: &str) {
+        self.warnings.push(format!(
+            "Line {}: {}=\"{}\" of <{}> is not supported",
+            element.line, name, value, element.name
+        ));
+    }
+
+    // Warns about the attributes of an element that are neither in `known`
+    // nor editor attributes.
+    fn check_attributes(&mut self, element: &Element, known: &[&str]) {
+        for (name, value) in &element.attributes {
+            if !known.contains(&name.as_str()) && !EDITOR_ATTRIBUTES.contains(&name.as_str()) {
+                self.unsupported_attribute(element, name, value);
+            }
+        }
+    }
+
+    // Object index of an id, reserved on first use.
+    fn index(&mut self, id: &str) -> u32 {
+        if let Some(index) = self.indices.get(id) {
+            return *index;
+        }
+        let index = self.builder.reserve_object();
+        self.indices.insert(id.to_string(), index);
+        index
+    }
+
+    // Index of the object an attribute refers to, if the id exists.
+    fn reference(&mut self, element: &Element, attribute: &str) -> Option<u32> {
+        let id = element.get_attr(attribute)?;
+        if !self.defined_ids.contains(id) {
+            self.warnings.push(format!(
+                "Line {}: {}=\"{}\" of <{}> refers to an unknown id",
+                element.line, attribute, id, element.name
+            ));
+            return None;
+        }
+        Some(self.index(id))
+    }
+
+    fn set_object(&mut self, index: u32, class: u32, values: Vec<RawValue>) {
+        self.set.insert(index);
+        self.builder.set_object(index, class, values);
+    }
+
+    // Objects referenced by id whose element was skipped still need an entry.
+    fn fill_unset(&mut self) {
+        let mut unset: Vec<(&String, &u32)> = self
+            .indices
+            .iter()
+            .filter(|(_, index)| !self.set.contains(index))
+            .collect();
+        unset.sort_by_key(|(_, index)| **index);
+        let unset: Vec<(String, u32)> = unset
+            .into_iter()
+            .map(|(id, index)| (id.to_owned(), *index))
+            .collect();
+
+        for (id, index) in unset {
+            self.warnings.push(format!(
+                "Object {} is referenced but its element was not compiled, using an empty NSObject",
+                id
+            ));
+            let class = self.builder.class("NSObject", &[]);
+            self.set_object(index, class, vec![]);
+        }
+    }
+
+    fn array(&mut self, class: &str, items: &[u32]) -> u32 {
+        let mut values = vec![self.builder.bool_value(INLINED_VALUE_KEY, true)];
+        for item in items {
+            values.push(self.builder.object_value(EMPTY_KEY, *item));
+        }
+        let class = self.builder.class(class, &[]);
+        self.builder.push_object(class, values)
+    }
+
+    fn string(&mut self, key: &str, string: &str) -> RawValue {
+        let values = vec![self
+            .builder
+            .bytes_value("NS.bytes", string.as_bytes().to_vec())];
+        let class = self.builder.class("NSString", &[]);
+        let index = self.builder.push_object(class, values);
+        self.builder.object_value(key, index)
+    }
+
+    // Class of an element, with the UIKit class as fallback of a custom class.
+    fn class(&mut self, element: &Element, base: &str) -> u32 {
+        match element.get_attr("customClass") {
+            Some(custom) if custom != base => {
+                let base = self.builder.class(base, &[]);
+                self.builder.class(custom, &[base as i32])
+            }
+            _ => self.builder.class(base, &[]),
+        }
+    }
+
+    fn object(&mut self, element: &Element, superview: Option<u32>) -> Option<u32> {
+        let index = match element.get_attr("id") {
+            Some(id) => self.index(id),
+            None => self.builder.reserve_object(),
+        };
+        let mut values = vec![];
+
+        let class = match element.name.as_str() {
+            "placeholder" => {
+                self.check_attributes(element, &["placeholderIdentifier"]);
+                let identifier = element
+                    .get_attr("placeholderIdentifier")
+                    .unwrap_or_default();
+                values.push(self.string("UIProxiedObjectIdentifier", identifier));
+                self.builder.class("UIProxyObject", &[])
+            }
+            "customObject" => {
+                self.check_attributes(element, &[]);
+                self.class(element, "NSObject")
+            }
+            name => match class_for_element(name) {
+                Some(base) => self.class(element, base),
+                None => {
+                    self.warnings.push(format!(
+                        "Line {}: <{}> is not supported",
+                        element.line, element.name
+                    ));
+                    // Keep the slot so references to it stay valid.
+                    let class = self.builder.class("NSObject", &[]);
+                    self.set_object(index, class, vec![]);
+                    return None;
+                }
+            },
+        };
+
+        let view = is_view_element(&element.name);
+        if view {
+            self.view_attributes(element, &mut values);
+            if let Some(superview) = superview {
+                values.push(self.builder.object_value("UISuperview", superview));
+            }
+        } else if class_for_element(&element.name).is_some() {
+            self.check_attributes(element, &[]);
+        }
+        for child in &element.children {
+            match (child.name.as_str(), child.get_attr("key")) {
+                ("connections", _) => self.connections(child, index),
+                ("userDefinedRuntimeAttributes", _) => self.runtime_attributes(child, index),
+                (_, _) if view && self.view_child(element, child, index, &mut values) => {}
+                ("view", Some("view")) if !view => {
+                    if let Some(view) = self.object(child, None) {
+                        values.push(self.builder.object_value("UIView", view));
+                    }
+                }
+                _ => self.unsupported(child, element),
+            }
+        }
+
+        self.set_object(index, class, values);
+        self.objects.push(index);
+        Some(index)
+    }
+
+    fn view_attributes(&mut self, element: &Element, values: &mut Vec<RawValue>) {
+        for (attribute, value) in &element.attributes {
+            let yes = value == "YES";
+            let raw = match attribute.as_str() {
+                "contentMode" => position(CONTENT_MODES, value)
+                    .map(|mode| self.builder.int_value("UIContentMode", mode)),
+                "opaque" if !yes => Some(self.builder.bool_value("UIOpaque", false)),
+                "clipsSubviews" => Some(self.builder.bool_value("UIClipsToBounds", yes)),
+                "hidden" => Some(self.builder.bool_value("UIHidden", yes)),
+                "alpha" => number(value).map(|alpha| self.builder.double_value("UIAlpha", alpha)),
+                "tag" => value
+                    .parse()
+                    .ok()
+                    .map(|tag| self.builder.int_value("UITag", tag)),
+                "userInteractionEnabled" if !yes => {
+                    Some(self.builder.bool_value("UIUserInteractionDisabled", true))
+                }
+                "multipleTouchEnabled" => {
+                    Some(self.builder.bool_value("UIMultipleTouchEnabled", yes))
+                }
+                "autoresizesSubviews" => Some(self.builder.bool_value("UIAutoresizeSubviews", yes)),
+                "translatesAutoresizingMaskIntoConstraints" if !yes => {
+                    Some(self.builder.bool_value(
+                        "UIViewDoesNotTranslateAutoresizingMaskIntoConstraints",
+                        true,
+                    ))
+                }
+                "contentHorizontalAlignment" => {
+                    position(HORIZONTAL_ALIGNMENTS, value).map(|alignment| {
+                        self.builder
+                            .int_value("UIContentHorizontalAlignment", alignment)
+                    })
+                }
+                "contentVerticalAlignment" => {
+                    position(VERTICAL_ALIGNMENTS, value).map(|alignment| {
+                        self.builder
+                            .int_value("UIContentVerticalAlignment", alignment)
+                    })
+                }
+                "enabled" => Some(self.builder.bool_value("UIEnabled", yes)),
+                "selected" => Some(self.builder.bool_value("UISelected", yes)),
+                "highlighted" => Some(self.builder.bool_value("UIHighlighted", yes)),
+                "text" => Some(self.string("UIText", value)),
+                "placeholder" => Some(self.string("UIPlaceholder", value)),
+                "numberOfLines" => value
+                    .parse()
+                    .ok()
+                    .map(|lines| self.builder.int_value("UINumberOfLines", lines)),
+                "textAlignment" => position(TEXT_ALIGNMENTS, value)
+                    .map(|alignment| self.builder.int_value("UITextAlignment", alignment)),
+                "borderStyle" => position(BORDER_STYLES, value)
+                    .map(|style| self.builder.int_value("UIBorderStyle", style)),
+                "lineBreakMode" => position(LINE_BREAK_MODES, value)
+                    .map(|mode| self.builder.int_value("UILineBreakMode", mode)),
+                "buttonType" => {
+                    let value = if value == "roundedRect" {
+                        "system"
+                    } else {
+                        value
+                    };
+                    position(BUTTON_TYPES, value)
+                        .map(|kind| self.builder.int_value("UIButtonType", kind))
+                }
+                "on" => Some(self.builder.bool_value("UISwitchOn", yes)),
+                "value" | "minValue" | "maxValue" => number(value).map(|number| {
+                    let key = match attribute.as_str() {
+                        "value" => "UIValue",
+                        "minValue" => "UIMinValue",
+                        _ => "UIMaxValue",
+                    };
+                    self.builder.float_value(key, number as f32)
+                }),
+                "image" => {
+                    let image = self.image(value);
+                    Some(self.builder.object_value("UIImage", image))
+                }
+                name if EDITOR_ATTRIBUTES.contains(&name) => None,
+                name => {
+                    self.unsupported_attribute(element, name, value);
+                    None
+                }
+            };
+            values.extend(raw);
+        }
+    }
+
+    // Children specific to views. Returns false for elements it does not know.
+    fn view_child(
+        &mut self,
+        parent: &Element,
+        child: &Element,
+        index: u32,
+        values: &mut Vec<RawValue>,
+    ) -> bool {
+        match (child.name.as_str(), child.get_attr("key")) {
+            ("rect", Some("frame")) => {
+                self.check_attributes(child, &["x", "y", "width", "height"]);
+                let [x, y, width, height] =
+                    ["x", "y", "width", "height"].map(|a| child.get_attr(a).and_then(number));
+                let (x, y) = (x.unwrap_or(0.0), y.unwrap_or(0.0));
+                let (width, height) = (width.unwrap_or(0.0), height.unwrap_or(0.0));
+                values.push(
+                    self.builder
+                        .bytes_value("UIBounds", geometry(&[0.0, 0.0, width, height])),
+                );
+                values.push(
+                    self.builder
+                        .bytes_value("UICenter", geometry(&[x + width / 2.0, y + height / 2.0])),
+                );
+            }
+            ("autoresizingMask", _) => {
+                let names: Vec<&str> = AUTORESIZING.iter().map(|(_, name)| *name).collect();
+                self.check_attributes(child, &names);
+                let mask = AUTORESIZING
+                    .iter()
+                    .filter(|(_, name)| child.get_attr(name) == Some("YES"))
+                    .fold(0, |mask, (bit, _)| mask | bit);
+                values.push(self.builder.int_value("UIAutoresizingMask", mask));
+            }
+            ("subviews", _) => {
+                let mut subviews = vec![];
+                for subview in &child.children {
+                    if !is_view_element(&subview.name) {
+                        self.unsupported(subview, child);
+                        continue;
+                    }
+                    subviews.extend(self.object(subview, Some(index)));
+                }
+                let array = self.array("NSMutableArray", &subviews);
+                values.push(self.builder.object_value("UISubviews", array));
+            }
+            ("constraints", _) => {
+                let mut constraints = vec![];
+                for constraint in &child.children {
+                    if constraint.name != "constraint" {
+                        self.unsupported(constraint, child);
+                        continue;
+                    }
+                    constraints.push(self.constraint(constraint, index));
+                }
+                let array = self.array("NSMutableArray", &constraints);
+                values.push(
+                    self.builder
+                        .object_value("UIViewAutolayoutConstraints", array),
+                );
+            }
+            ("color", Some(key @ ("backgroundColor" | "textColor"))) => {
+                let key = match key {
+                    "backgroundColor" => "UIBackgroundColor",
+                    _ => "UITextColor",
+                };
+                if let Some(color) = self.color(child) {
+                    values.push(self.builder.object_value(key, color));
+                }
+            }
+            ("fontDescription", _) => {
+                if let Some(font) = self.font(child) {
+                    values.push(self.builder.object_value("UIFont", font));
+                }
+            }
+            ("state", _) if parent.name == "button" => {
+                // All states go into one dictionary, built at the first of them.
+                let states: Vec<&Element> = parent
+                    .children
+                    .iter()
+                    .filter(|c| c.name == "state")
+                    .collect();
+                if std::ptr::eq(states[0], child) {
+                    let content = self.button_content(&states);
+                    values.push(
+                        self.builder
+                            .object_value("UIButtonStatefulContent", content),
+                    );
+                }
+            }
+            // Layout helpers Interface Builder keeps for the editor only.
+            ("viewLayoutGuide" | "freeformSimulatedSizeMetrics", _) => {}
+            _ => return false,
+        }
+        true
+    }
+
+    fn button_content(&mut self, states: &[&Element]) -> u32 {
+        let mut items = vec![];
+        for state in states {
+            let key = state.get_attr("key").unwrap_or("normal");
+            let Some((value, _)) = CONTROL_STATES.iter().find(|(_, name)| *name == key) else {
+                self.warnings.push(format!(
+                    "Line {}: button state {} is not supported",
+                    state.line, key
+                ));
+                continue;
+            };
+            self.check_attributes(state, &["title", "image"]);
+            let number = vec![self.builder.int_value("NS.intval", *value)];
+            let class = self.builder.class("NSNumber", &[]);
+            items.push(self.builder.push_object(class, number));
+
+            let mut values = vec![];
+            if let Some(title) = state.get_attr("title") {
+                values.push(self.string("UITitle", title));
+            }
+            if let Some(image) = state.get_attr("image") {
+                let image = self.image(image);
+                values.push(self.builder.object_value("UIImage", image));
+            }
+            for child in &state.children {
+                match (child.name.as_str(), child.get_attr("key")) {
+                    ("color", Some("titleColor")) => {
+                        if let Some(color) = self.color(child) {
+                            values.push(self.builder.object_value("UITitleColor", color));
+                        }
+                    }
+                    _ => self.unsupported(child, state),
+                }
+            }
+            let class = self.builder.class("UIButtonContent", &[]);
+            items.push(self.builder.push_object(class, values));
+        }
+        let mut values = vec![self.builder.bool_value(INLINED_VALUE_KEY, true)];
+        for item in items {
+            values.push(self.builder.object_value(EMPTY_KEY, item));
+        }
+        let class = self.builder.class("NSMutableDictionary", &[]);
+        self.builder.push_object(class, values)
+    }
+
+    fn image(&mut self, name: &str) -> u32 {
+        let values = vec![self.string("UIResourceName", name)];
+        let class = self.builder.class("UIImageNibPlaceholder", &[]);
+        self.builder.push_object(class, values)
+    }
+
+    fn color(&mut self, element: &Element) -> Option<u32> {
+        // Colors are always written in the sRGB or gray space they name.
+        self.check_attributes(
+            element,
+            &[
+                "systemColor",
+                "red",
+                "green",
+                "blue",
+                "white",
+                "alpha",
+                "colorSpace",
+                "customColorSpace",
+            ],
+        );
+        let attribute = |name: &str| element.get_attr(name).and_then(number);
+        let alpha = attribute("alpha").unwrap_or(1.0) as f32;
+        let mut values = vec![];
+
+        if let Some(name) = element.get_attr("systemColor") {
+            values.push(self.string("UISystemColorName", name));
+        } else if let (Some(red), Some(green), Some(blue)) =
+            (attribute("red"), attribute("green"), attribute("blue"))
+        {
+            values.push(self.builder.float_value("UIRed", red as f32));
+            values.push(self.builder.float_value("UIGreen", green as f32));
+            values.push(self.builder.float_value("UIBlue", blue as f32));
+            values.push(self.builder.float_value("UIAlpha", alpha));
+            values.push(self.builder.int_value("UIColorComponentCount", 4));
+        } else if let Some(white) = attribute("white") {
+            values.push(self.builder.float_value("UIWhite", white as f32));
+            values.push(self.builder.float_value("UIAlpha", alpha));
+            values.push(self.builder.int_value("UIColorComponentCount", 2));
+        } else {
+            self.warnings.push(format!(
+                "Line {}: <color> without red/green/blue, white or systemColor is not supported",
+                element.line
+            ));
+            return None;
+        }
+        let class = self.builder.class("UIColor", &[]);
+        Some(self.builder.push_object(class, values))
+    }
+
+    fn font(&mut self, element: &Element) -> Option<u32> {
+        self.check_attributes(element, &["pointSize", "type", "name", "family"]);
+        let size = element
+            .get_attr("pointSize")
+            .and_then(number)
+            .unwrap_or(17.0);
+        let mut values = vec![self.builder.double_value("UIFontPointSize", size)];
+
+        match (element.get_attr("type"), element.get_attr("name")) {
+            (Some(kind @ ("system" | "boldSystem" | "italicSystem")), _) => {
+                let (name, traits) = match kind {
+                    "boldSystem" => (".SFUI-Semibold", 2),
+                    "italicSystem" => (".SFUI-RegularItalic", 1),
+                    _ => (".SFUI-Regular", 0),
+                };
+                values.push(self.string("UIFontName", name));
+                values.push(self.builder.bool_value("UISystemFont", true));
+                values.push(self.builder.int_value("UIFontTraits", traits));
+            }
+            (None, Some(name)) => {
+                values.push(self.string("UIFontName", name));
+                values.push(self.builder.bool_value("UISystemFont", false));
+                values.push(self.builder.int_value("UIFontTraits", 0));
+            }
+            _ => {
+                self.warnings.push(format!(
+                    "Line {}: <fontDescription> must have a system type or a font name",
+                    element.line
+                ));
+                return None;
+            }
+        }
+        let class = self.builder.class("UIFont", &[]);
+        Some(self.builder.push_object(class, values))
+    }
+
+    fn constraint(&mut self, element: &Element, owner: u32) -> u32 {
+        self.check_attributes(
+            element,
+            &[
+                "firstItem",
+                "firstAttribute",
+                "secondItem",
+                "secondAttribute",
+                "relation",
+                "multiplier",
+                "constant",
+                "priority",
+                "identifier",
+            ],
+        );
+        let index = match element.get_attr("id") {
+            Some(id) => self.index(id),
+            None => self.builder.reserve_object(),
+        };
+        let first = self.reference(element, "firstItem").unwrap_or(owner);
+        let attribute = |name: &str| {
+            element
+                .get_attr(name)
+                .and_then(|value| position(LAYOUT_ATTRIBUTES, value))
+                .unwrap_or(0)
+        };
+
+        let mut values = vec![
+            self.builder.object_value("NSFirstItem", first),
+            self.builder
+                .int_value("NSFirstAttribute", attribute("firstAttribute")),
+        ];
+        if let Some(second) = self.reference(element, "secondItem") {
+            values.push(self.builder.object_value("NSSecondItem", second));
+            values.push(
+                self.builder
+                    .int_value("NSSecondAttribute", attribute("secondAttribute")),
+            );
+        }
+        match element.get_attr("relation") {
+            Some("lessThanOrEqual") => values.push(self.builder.int_value("NSRelation", -1)),
+            Some("greaterThanOrEqual") => values.push(self.builder.int_value("NSRelation", 1)),
+            _ => {}
+        }
+        if let Some(multiplier) = element.get_attr("multiplier").and_then(ratio) {
+            values.push(self.builder.double_value("NSMultiplier", multiplier));
+        }
+        if let Some(constant) = element.get_attr("constant").and_then(number) {
+            values.push(self.builder.double_value("NSConstant", constant));
+        }
+        if let Some(priority) = element.get_attr("priority").and_then(|p| p.parse().ok()) {
+            values.push(self.builder.int_value("NSPriority", priority));
+        }
+        if let Some(identifier) = element.get_attr("identifier") {
+            values.push(self.string("NSLayoutIdentifier", identifier));
+        }
+        values.push(self.builder.bool_value("NSShouldBeArchived", true));
+
+        let class = self.builder.class("NSLayoutConstraint", &[]);
+        self.set_object(index, class, values);
+        self.objects.push(index);
+        index
+    }
+
+    fn connections(&mut self, element: &Element, source: u32) {
+        for connection in &element.children {
+            let (class, label, known) = match connection.name.as_str() {
+                "outlet" => (
+                    "UIRuntimeOutletConnection",
+                    "property",
+                    &["property", "destination"][..],
+                ),
+                "outletCollection" => (
+                    "UIRuntimeOutletCollectionConnection",
+                    "property",
+                    &["property", "destination"][..],
+                ),
+                "action" => (
+                    "UIRuntimeEventConnection",
+                    "selector",
+                    &["selector", "destination", "eventType"][..],
+                ),
+                _ => {
+                    self.unsupported(connection, element);
+                    continue;
+                }
+            };
+            self.check_attributes(connection, known);
+            let Some(destination) = self.reference(connection, "destination") else {
+                continue;
+            };
+            let Some(label) = connection.get_attr(label) else {
+                self.warnings.push(format!(
+                    "Line {}: <{}> has no {}",
+                    connection.line, connection.name, label
+                ));
+                continue;
+            };
+
+            let mut values = vec![
+                self.string("UILabel", label),
+                self.builder.object_value("UISource", source),
+                self.builder.object_value("UIDestination", destination),
+            ];
+            if connection.name == "action" {
+                let event = connection.get_attr("eventType").unwrap_or("touchUpInside");
+                let Some((mask, _)) = CONTROL_EVENTS.iter().find(|(_, name)| *name == event) else {
+                    self.warnings.push(format!(
+                        "Line {}: event type {} is not supported",
+                        connection.line, event
+                    ));
+                    continue;
+                };
+                values.push(self.builder.int_value("UIEventMask", *mask as i64));
+            }
+            let class = self.builder.class(class, &[]);
+            let index = self.builder.push_object(class, values);
+            self.connections.push(index);
+        }
+    }
+
+    fn runtime_attributes(&mut self, element: &Element, object: u32) {
+        for attribute in &element.children {
+            let key_path = attribute.get_attr("keyPath");
+            if attribute.name != "userDefinedRuntimeAttribute" || key_path.is_none() {
+                self.unsupported(attribute, element);
+                continue;
+            }
+            self.check_attributes(attribute, &["type", "keyPath", "value"]);
+            let value = match attribute.get_attr("type") {
+                Some("string") => {
+                    Some(self.string("UIValue", attribute.get_attr("value").unwrap_or_default()))
+                }
+                Some("boolean") => {
+                    let value = attribute.get_attr("value") == Some("YES");
+                    let values = vec![self.builder.bool_value("NS.boolval", value)];
+                    let class = self.builder.class("NSNumber", &[]);
+                    let number = self.builder.push_object(class, values);
+                    Some(self.builder.object_value("UIValue", number))
+                }
+                Some("number") => {
+                    let values = match (
+                        attribute.keyed_child("value").map(|v| v.name.as_str()),
+                        attribute
+                            .keyed_child("value")
+                            .and_then(|v| v.get_attr("value")),
+                    ) {
+                        (Some("integer"), Some(value)) => value
+                            .parse()
+                            .ok()
+                            .map(|value| vec![self.builder.int_value("NS.intval", value)]),
+                        (Some("real"), Some(value)) => number(value)
+                            .map(|value| vec![self.builder.double_value("NS.dblval", value)]),
+                        _ => None,
+                    };
+                    values.map(|values| {
+                        let class = self.builder.class("NSNumber", &[]);
+                        let number = self.builder.push_object(class, values);
+                        self.builder.object_value("UIValue", number)
+                    })
+                }
+                Some("color") => attribute
+                    .keyed_child("value")
+                    .and_then(|color| self.color(color))
+                    .map(|color| self.builder.object_value("UIValue", color)),
+                _ => None,
+            };
+            let Some(value) = value else {
+                self.warnings.push(format!(
+                    "Line {}: runtime attribute {} of type {} is not supported",
+                    attribute.line,
+                    key_path.unwrap_or_default(),
+                    attribute.get_attr("type").unwrap_or("<none>")
+                ));
+                continue;
+            };
+
+            let values = vec![
+                self.builder.object_value("UIObject", object),
+                self.string("UIKeyPath", key_path.unwrap_or_default()),
+                value,
+            ];
+            let class = self.builder.class("UINibKeyValuePair", &[]);
+            let index = self.builder.push_object(class, values);
+            self.key_value_pairs.push(index);
+        }
+    }
+}
+
+fn position(table: &[&str], value: &str) -> Option<i64> {
+    table
+        .iter()
+        .position(|name| *name == value)
+        .map(|index| index as i64)
+}
+
+fn number(value: &str) -> Option<f64> {
+    value.parse().ok()
+}
+
+// Multipliers are written either as a number or as a "width:height" ratio.
+fn ratio(value: &str) -> Option<f64> {
+    match value.split_once(':') {
+        Some((numerator, denominator)) => Some(number(numerator)? / number(denominator)?),
+        None => number(value),
+    }
+}
+
+// Points and rects are stored as doubles behind a type byte.
+fn geometry(components: &[f64]) -> Vec<u8> {
+    let mut bytes = vec![crate::raw_model::raw_value::TYPE_DOUBLE];
+    for component in components {
+        bytes.extend(component.to_le_bytes());
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::Context,
+        xib::{compile::compile_xml, decompile::decompile, xml},
+    };
+
+    const XIB: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<document type="com.apple.InterfaceBuilder3.CocoaTouch.XIB" version="3.0">
+    <objects>
+        <placeholder placeholderIdentifier="IBFilesOwner" id="-1" customClass="LoginController">
+            <connections>
+                <outlet property="button" destination="btn-01-abc"/>
+            </connections>
+        </placeholder>
+        <view contentMode="scaleToFill" id="vew-01-abc">
+            <rect key="frame" x="0.0" y="0.0" width="320" height="480"/>
+            <subviews>
+                <button contentMode="scaleToFill" buttonType="roundedRect" lineBreakMode="middleTruncation" id="btn-01-abc" customClass="FancyButton">
+                    <rect key="frame" x="10" y="20" width="100" height="40"/>
+                    <state key="normal" title="Log in">
+                        <color key="titleColor" white="0.0" alpha="1" colorSpace="custom"/>
+                    </state>
+                    <userDefinedRuntimeAttributes>
+                        <userDefinedRuntimeAttribute type="number" keyPath="layer.cornerRadius">
+                            <integer key="value" value="4"/>
+                        </userDefinedRuntimeAttribute>
+                    </userDefinedRuntimeAttributes>
+                    <connections>
+                        <action selector="login:" destination="-1" eventType="touchUpInside" id="act-01-abc"/>
+                    </connections>
+                </button>
+                <mapView id="map-01-abc"/>
+            </subviews>
+            <constraints>
+                <constraint firstItem="btn-01-abc" firstAttribute="top" secondItem="vew-01-abc" secondAttribute="top" constant="20" id="cst-01-abc"/>
+            </constraints>
+        </view>
+    </objects>
+</document>
+"#;
+
+    #[test]
+    fn compile_and_decompile() {
+        let (nib, warnings) = compile_xml(XIB).unwrap();
+        assert_eq!(
+            vec!["Line 26: <mapView> in <subviews> is not supported".to_string()],
+            warnings
+        );
+
+        let context = Context::from_buffer(nib).unwrap();
+        let decompiled = decompile(&context);
+        assert_eq!(Vec::<String>::new(), decompiled.warnings);
+
+        let xml = xml::parse(&decompiled.document.to_string()).unwrap();
+        let objects = xml.child("objects").unwrap();
+        let owner = &objects.children[0];
+        assert_eq!(Some("-1"), owner.get_attr("id"));
+        assert_eq!(
+            Some("button"),
+            owner.child("connections").unwrap().children[0].get_attr("property")
+        );
+
+        let view = &objects.children[1];
+        let button = &view.child("subviews").unwrap().children[0];
+        assert_eq!("button", button.name);
+        assert_eq!(Some("FancyButton"), button.get_attr("customClass"));
+        assert_eq!(Some("system"), button.get_attr("buttonType"));
+        assert_eq!(Some("middleTruncation"), button.get_attr("lineBreakMode"));
+        let frame = button.keyed_child("frame").unwrap();
+        assert_eq!(Some("10.0"), frame.get_attr("x"));
+        assert_eq!(Some("40.0"), frame.get_attr("height"));
+        assert_eq!(
+            Some("Log in"),
+            button.child("state").unwrap().get_attr("title")
+        );
+        let action = &button.child("connections").unwrap().children[0];
+        assert_eq!(Some("login:"), action.get_attr("selector"));
+        assert_eq!(Some("-1"), action.get_attr("destination"));
+        let attribute = &button
+            .child("userDefinedRuntimeAttributes")
+            .unwrap()
+            .children[0];
+        assert_eq!(Some("layer.cornerRadius"), attribute.get_attr("keyPath"));
+
+        let constraint = &view.child("constraints").unwrap().children[0];
+        assert_eq!(Some("top"), constraint.get_attr("firstAttribute"));
+        assert_eq!(Some("20.0"), constraint.get_attr("constant"));
+    }
+
+    #[test]
+    fn unknown_destination() {
+        let xib = r#"<document><objects>
+            <placeholder placeholderIdentifier="IBFilesOwner" id="-1">
+                <connections><outlet property="view" destination="nope"/></connections>
+            </placeholder>
+        </objects></document>"#;
+        let (_, warnings) = compile_xml(xib).unwrap();
+        assert_eq!(
+            vec!["Line 3: destination=\"nope\" of <outlet> refers to an unknown id".to_string()],
+            warnings
+        );
+    }
+
+    #[test]
+    fn unsupported_attributes() {
+        let xib = r#"<document><objects>
+            <stackView axis="vertical" misplaced="YES" id="stk-01-abc">
+                <subviews>
+                    <button buttonType="system" lineBreakMode="middleTruncation" id="btn-01-abc">
+                        <fontDescription key="fontDescription" type="system" weight="semibold" pointSize="15"/>
+                        <state key="normal" title="Go">
+                            <color key="titleColor" white="0.0" alpha="1" colorSpace="custom"/>
+                        </state>
+                    </button>
+                </subviews>
+            </stackView>
+        </objects></document>"#;
+        let (_, warnings) = compile_xml(xib).unwrap();
+        assert_eq!(
+            vec![
+                "Line 2: axis=\"vertical\" of <stackView> is not supported".to_string(),
+                "Line 5: weight=\"semibold\" of <fontDescription> is not supported".to_string(),
+            ],
+            warnings
+        );
+    }
+}
@@ -0,0 +1,1051 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::data::{Context, Data};
+
+use super::{
+    element_for_class, format_number, ib_id, is_view_element, xml::Element, AUTORESIZING,
+    BORDER_STYLES, BUTTON_TYPES, CONTENT_MODES, CONTROL_EVENTS, CONTROL_STATES,
+    HORIZONTAL_ALIGNMENTS, LAYOUT_ATTRIBUTES, LINE_BREAK_MODES, TEXT_ALIGNMENTS,
+    VERTICAL_ALIGNMENTS,
+};
+
+// Keys of the root object a UIKit nib is built around.
+const ROOT_KEYS: &[&str] = &[
+    "UINibTopLevelObjectsKey",
+    "UINibObjectsKey",
+    "UINibConnectionsKey",
+    "UINibVisibleWindowsKey",
+    "UINibAccessibilityConfigurationsKey",
+    "UINibKeyValuePairsKey",
+    "UINibTraitStorageListsKey",
+];
+
+pub struct Decompilation {
+    pub document: Element,
+    // Objects and keys that could not be expressed in the document.
+    pub warnings: Vec<String>,
+}
+
+// Regenerates an Interface Builder document from a UIKit nib. Everything the
+// decompiler reads is tracked, so values it cannot model end up as warnings
+// instead of disappearing.
+pub fn decompile(context: &Context) -> Decompilation {
+    let mut decompiler = Decompiler {
+        context,
+        ids: HashMap::new(),
+        handled: HashSet::new(),
+        visited: BTreeSet::new(),
+        elements: HashSet::new(),
+        connections: HashMap::new(),
+        runtime_attributes: HashMap::new(),
+        system_colors: BTreeSet::new(),
+        images: BTreeSet::new(),
+        autolayout: false,
+        warnings: vec![],
+    };
+
+    let mut objects = Element::new("objects");
+    if context.objects.is_empty() {
+        decompiler.warnings.push("Nib has no objects".to_string());
+    } else {
+        decompiler.visited.insert(0);
+        for key in ROOT_KEYS {
+            decompiler.handle(0, key);
+        }
+        let top_level = decompiler.items(0, "UINibTopLevelObjectsKey");
+        if decompiler.reference(0, "UINibTopLevelObjectsKey").is_none() {
+            decompiler
+                .warnings
+                .push("Root object has no UINibTopLevelObjectsKey, not a UIKit nib".to_string());
+        }
+
+        decompiler.assign_placeholder_ids(&top_level);
+        decompiler.collect_connections();
+        decompiler.collect_runtime_attributes();
+
+        for index in top_level {
+            objects.children.extend(decompiler.element(index));
+        }
+
+        for index in decompiler.items(0, "UINibObjectsKey") {
+            if !decompiler.visited.contains(&index) {
+                decompiler.warnings.push(format!(
+                    "Object {} ({}) is not reachable from the top level objects and was not decompiled",
+                    index,
+                    decompiler.class_name(index)
+                ));
+            }
+        }
+        for index in decompiler.items(0, "UINibVisibleWindowsKey") {
+            decompiler.visited.insert(index);
+        }
+        decompiler.report_unhandled();
+    }
+
+    let mut document = Element::new("document")
+        .attr("type", "com.apple.InterfaceBuilder3.CocoaTouch.XIB")
+        .attr("version", "3.0")
+        .attr("toolsVersion", "21701")
+        .attr("targetRuntime", "iOS.CocoaTouch")
+        .attr("propertyAccessControl", "none")
+        .attr(
+            "useAutolayout",
+            if decompiler.autolayout { "YES" } else { "NO" },
+        )
+        .attr("useTraitCollections", "YES")
+        .attr("colorMatched", "YES");
+    document.push(
+        Element::new("device")
+            .attr("id", "retina6_1")
+            .attr("orientation", "portrait")
+            .attr("appearance", "light"),
+    );
+    let mut dependencies = Element::new("dependencies");
+    dependencies.push(
+        Element::new("plugIn")
+            .attr(
+                "identifier",
+                "com.apple.InterfaceBuilder.IBCocoaTouchPlugin",
+            )
+            .attr("version", "21679"),
+    );
+    dependencies.push(
+        Element::new("capability")
+            .attr("name", "documents saved in the Xcode 8 format")
+            .attr("minToolsVersion", "8.0"),
+    );
+    document.push(dependencies);
+    document.push(objects);
+
+    if !decompiler.system_colors.is_empty() || !decompiler.images.is_empty() {
+        let mut resources = Element::new("resources");
+        for image in &decompiler.images {
+            resources.push(
+                Element::new("image")
+                    .attr("name", image)
+                    .attr("width", "16")
+                    .attr("height", "16"),
+            );
+        }
+        for color in &decompiler.system_colors {
+            resources.push(Element::new("systemColor").attr("name", color));
+        }
+        document.push(resources);
+    }
+
+    Decompilation {
+        document,
+        warnings: decompiler.warnings,
+    }
+}
+
+struct Decompiler<'a> {
+    context: &'a Context,
+    ids: HashMap<usize, String>,
+    handled: HashSet<(usize, String)>,
+    visited: BTreeSet<usize>,
+    // Objects already written as an element, so cycles end.
+    elements: HashSet<usize>,
+    connections: HashMap<usize, Vec<Element>>,
+    runtime_attributes: HashMap<usize, Vec<Element>>,
+    system_colors: BTreeSet<String>,
+    images: BTreeSet<String>,
+    autolayout: bool,
+    warnings: Vec<String>,
+}
+
+impl Decompiler<'_> {
+    fn class_name(&self, index: usize) -> String {
+        self.context
+            .class_name(index)
+            .unwrap_or_else(|| "<invalid class>".to_string())
+    }
+
+    // Class followed by its fallback classes.
+    fn class_chain(&self, index: usize) -> Vec<String> {
+        let Some(class) = self
+            .context
+            .objects
+            .get(index)
+            .and_then(|object| self.context.class_of(object))
+        else {
+            return vec![];
+        };
+        let mut chain = vec![class.name];
+        for extra_value in class.extra_values {
+            if let Some(fallback) = usize::try_from(extra_value)
+                .ok()
+                .and_then(|e| self.context.classes.get(e))
+            {
+                chain.push(crate::data::Class::from(fallback).name);
+            }
+        }
+        chain
+    }
+
+    fn id(&mut self, index: usize) -> String {
+        self.ids
+            .entry(index)
+            .or_insert_with(|| ib_id(index))
+            .clone()
+    }
+
+    fn handle(&mut self, index: usize, key: &str) {
+        self.handled.insert((index, key.to_string()));
+    }
+
+    fn data(&mut self, index: usize, key: &str) -> Option<Data> {
+        self.handle(index, key);
+        Data::from_scalar(self.context.find_value(index, key)?)
+    }
+
+    fn number(&mut self, index: usize, key: &str) -> Option<f64> {
+        match self.data(index, key)? {
+            Data::Int8(v) => Some(v as f64),
+            Data::Int16(v) => Some(v as f64),
+            Data::Int32(v) => Some(v as f64),
+            Data::Int64(v) => Some(v as f64),
+            Data::Float(v) => Some(v as f64),
+            Data::Double(v) => Some(v),
+            Data::Boolean(v) => Some(if v { 1.0 } else { 0.0 }),
+            _ => None,
+        }
+    }
+
+    fn boolean(&mut self, index: usize, key: &str) -> Option<bool> {
+        self.number(index, key).map(|value| value != 0.0)
+    }
+
+    fn reference(&mut self, index: usize, key: &str) -> Option<usize> {
+        self.handle(index, key);
+        self.context
+            .find_value(index, key)?
+            .object_reference()
+            .map(|reference| reference as usize)
+    }
+
+    fn string(&mut self, index: usize, key: &str) -> Option<String> {
+        self.handle(index, key);
+        let value = self.context.find_value(index, key)?;
+        if let Some(reference) = value.object_reference() {
+            self.consume(reference as usize, &["NS.bytes"]);
+        }
+        self.context.string(value)
+    }
+
+    // Marks a helper object (string, collection, ...) as represented by the
+    // element that references it.
+    fn consume(&mut self, index: usize, keys: &[&str]) {
+        self.visited.insert(index);
+        for key in keys {
+            self.handle(index, key);
+        }
+    }
+
+    fn items(&mut self, index: usize, key: &str) -> Vec<usize> {
+        match self.reference(index, key) {
+            Some(collection) => {
+                self.consume(collection, &["NSInlinedValue", "UINibEncoderEmptyKey"]);
+                self.context
+                    .collection_items(collection)
+                    .into_iter()
+                    .map(|item| item as usize)
+                    .collect()
+            }
+            None => vec![],
+        }
+    }
+
+    fn assign_placeholder_ids(&mut self, top_level: &[usize]) {
+        for index in top_level {
+            if self.class_name(*index) != "UIProxyObject" {
+                continue;
+            }
+            let id = match self.string(*index, "UIProxiedObjectIdentifier").as_deref() {
+                Some("IBFilesOwner") => "-1".to_string(),
+                Some("IBFirstResponder") => "-2".to_string(),
+                _ => ib_id(*index),
+            };
+            self.ids.insert(*index, id);
+        }
+    }
+
+    // User defined runtime attributes are stored as key path/value pairs on
+    // the root object.
+    fn collect_runtime_attributes(&mut self) {
+        for pair in self.items(0, "UINibKeyValuePairsKey") {
+            self.visited.insert(pair);
+            let object = self.reference(pair, "UIObject");
+            let key_path = self.string(pair, "UIKeyPath");
+            let (Some(object), Some(key_path)) = (object, key_path) else {
+                self.warnings.push(format!(
+                    "Key value pair {} is missing its object or key path",
+                    pair
+                ));
+                continue;
+            };
+
+            let attribute = Element::new("userDefinedRuntimeAttribute");
+            let attribute = match self.reference(pair, "UIValue") {
+                Some(value) if self.class_name(value).contains("String") => {
+                    let string = self.string(pair, "UIValue").unwrap_or_default();
+                    attribute
+                        .attr("type", "string")
+                        .attr("keyPath", key_path)
+                        .attr("value", string)
+                }
+                Some(value) if self.class_name(value) == "NSNumber" => {
+                    self.consume(value, &["NS.intval", "NS.dblval", "NS.boolval"]);
+                    if let Some(boolean) = self.boolean(value, "NS.boolval") {
+                        attribute
+                            .attr("type", "boolean")
+                            .attr("keyPath", key_path)
+                            .attr("value", if boolean { "YES" } else { "NO" })
+                    } else if let Some(real) = self.number(value, "NS.dblval") {
+                        let mut attribute =
+                            attribute.attr("type", "number").attr("keyPath", key_path);
+                        attribute.push(
+                            Element::new("real")
+                                .attr("key", "value")
+                                .attr("value", format_number(real)),
+                        );
+                        attribute
+                    } else {
+                        let integer = self.number(value, "NS.intval").unwrap_or(0.0);
+                        let mut attribute =
+                            attribute.attr("type", "number").attr("keyPath", key_path);
+                        attribute.push(
+                            Element::new("integer")
+                                .attr("key", "value")
+                                .attr("value", format!("{}", integer as i64)),
+                        );
+                        attribute
+                    }
+                }
+                Some(value) if self.class_name(value) == "UIColor" => {
+                    let mut attribute = attribute.attr("type", "color").attr("keyPath", key_path);
+                    attribute
+                        .children
+                        .extend(self.color(pair, "UIValue", "value"));
+                    attribute
+                }
+                _ => {
+                    self.warnings.push(format!(
+                        "Key value pair {}: value of {} is not supported",
+                        pair, key_path
+                    ));
+                    continue;
+                }
+            };
+            self.runtime_attributes
+                .entry(object)
+                .or_default()
+                .push(attribute);
+        }
+    }
+
+    fn collect_connections(&mut self) {
+        for connection in self.items(0, "UINibConnectionsKey") {
+            self.visited.insert(connection);
+            let class = self.class_name(connection);
+            let source = self.reference(connection, "UISource");
+            let destination = self.reference(connection, "UIDestination");
+            let label = self.string(connection, "UILabel");
+
+            let (Some(source), Some(destination), Some(label)) = (source, destination, label)
+            else {
+                self.warnings.push(format!(
+                    "Connection {} ({}) is missing its source, destination or label",
+                    connection, class
+                ));
+                continue;
+            };
+            let destination = self.id(destination);
+
+            let elements: Vec<Element> = if class.contains("EventConnection") {
+                let mask = self.number(connection, "UIEventMask").unwrap_or(0.0) as u32;
+                let events: Vec<&str> = CONTROL_EVENTS
+                    .iter()
+                    .filter(|(bit, _)| mask & bit != 0)
+                    .map(|(_, name)| *name)
+                    .collect();
+                if events.is_empty() {
+                    self.warnings.push(format!(
+                        "Connection {} has an unknown event mask {}",
+                        connection, mask
+                    ));
+                }
+                events
+                    .iter()
+                    .enumerate()
+                    .map(|(position, event)| {
+                        let id = match position {
+                            0 => ib_id(connection),
+                            _ => ib_id(self.context.objects.len() * (position + 1) + connection),
+                        };
+                        Element::new("action")
+                            .attr("selector", &label)
+                            .attr("destination", &destination)
+                            .attr("eventType", *event)
+                            .attr("id", id)
+                    })
+                    .collect()
+            } else if class == "UIRuntimeOutletCollectionConnection" {
+                vec![Element::new("outletCollection")
+                    .attr("property", label)
+                    .attr("destination", destination)
+                    .attr("collectionClass", "NSMutableArray")
+                    .attr("id", ib_id(connection))]
+            } else if class.contains("OutletConnection") {
+                vec![Element::new("outlet")
+                    .attr("property", label)
+                    .attr("destination", destination)
+                    .attr("id", ib_id(connection))]
+            } else {
+                self.warnings.push(format!(
+                    "Connection {} has unsupported class {}",
+                    connection, class
+                ));
+                vec![]
+            };
+            self.connections.entry(source).or_default().extend(elements);
+        }
+    }
+
+    // None for missing objects and objects already written elsewhere, which
+    // a document cannot express.
+    fn element(&mut self, index: usize) -> Option<Element> {
+        if index >= self.context.objects.len() {
+            self.warnings.push(format!(
+                "Object {} does not exist and was not decompiled",
+                index
+            ));
+            return None;
+        }
+        if !self.elements.insert(index) {
+            self.warnings.push(format!(
+                "Object {} ({}) appears more than once in the tree, only its first occurrence was decompiled",
+                index,
+                self.class_name(index)
+            ));
+            return None;
+        }
+        self.visited.insert(index);
+        let chain = self.class_chain(index);
+        let class = chain.first().cloned().unwrap_or_default();
+
+        let mut element = if class == "UIProxyObject" {
+            let identifier = self
+                .string(index, "UIProxiedObjectIdentifier")
+                .unwrap_or_default();
+            let mut placeholder = Element::new("placeholder")
+                .attr("placeholderIdentifier", &identifier)
+                .attr("id", self.id(index));
+            if identifier == "IBFilesOwner" {
+                placeholder.set_attr("userLabel", "File's Owner");
+            }
+            placeholder
+        } else {
+            let known = chain
+                .iter()
+                .find_map(|name| element_for_class(name).map(|element| (name, element)));
+            let (name, custom) = match known {
+                Some((known_class, element)) => (element, *known_class != class),
+                None if self.context.find_value(index, "UISubviews").is_some()
+                    || self.context.find_value(index, "UIBounds").is_some() =>
+                {
+                    ("view", true)
+                }
+                None => ("customObject", true),
+            };
+            let mut element = Element::new(name);
+            if custom {
+                element.set_attr("customClass", &class);
+            }
+            if is_view_element(name) {
+                self.view(index, &mut element);
+            } else if let Some(view) = self.reference(index, "UIView") {
+                if let Some(mut view) = self.element(view) {
+                    view.attributes
+                        .insert(0, ("key".to_string(), "view".to_string()));
+                    element.push(view);
+                }
+            }
+            element.set_attr("id", self.id(index));
+            element
+        };
+
+        if let Some(attributes) = self.runtime_attributes.remove(&index) {
+            let container = element.child_mut("userDefinedRuntimeAttributes");
+            container.children.extend(attributes);
+        }
+        if let Some(connections) = self.connections.remove(&index) {
+            let container = element.child_mut("connections");
+            container.children.extend(connections);
+        }
+        Some(element)
+    }
+
+    fn view(&mut self, index: usize, element: &mut Element) {
+        if let Some(mode) = self.number(index, "UIContentMode") {
+            if let Some(name) = CONTENT_MODES.get(mode as usize) {
+                element.set_attr("contentMode", *name);
+            }
+        }
+        if self.boolean(index, "UIOpaque") == Some(false) {
+            element.set_attr("opaque", "NO");
+        }
+        if self.boolean(index, "UIClipsToBounds") == Some(true) {
+            element.set_attr("clipsSubviews", "YES");
+        }
+        if self.boolean(index, "UIHidden") == Some(true) {
+            element.set_attr("hidden", "YES");
+        }
+        if let Some(alpha) = self.number(index, "UIAlpha") {
+            element.set_attr("alpha", format_number(alpha));
+        }
+        if let Some(tag) = self.number(index, "UITag").filter(|tag| *tag != 0.0) {
+            element.set_attr("tag", format!("{}", tag as i64));
+        }
+        if self.boolean(index, "UIUserInteractionDisabled") == Some(true) {
+            element.set_attr("userInteractionEnabled", "NO");
+        }
+        if self.boolean(index, "UIMultipleTouchEnabled") == Some(true) {
+            element.set_attr("multipleTouchEnabled", "YES");
+        }
+        if self.boolean(index, "UIAutoresizeSubviews") == Some(false) {
+            element.set_attr("autoresizesSubviews", "NO");
+        }
+        if self.boolean(
+            index,
+            "UIViewDoesNotTranslateAutoresizingMaskIntoConstraints",
+        ) == Some(true)
+        {
+            element.set_attr("translatesAutoresizingMaskIntoConstraints", "NO");
+        }
+        self.control(index, element);
+        self.handle(index, "UISuperview");
+
+        if let Some(frame) = self.frame(index) {
+            element.push(frame);
+        }
+        if let Some(mask) = self.number(index, "UIAutoresizingMask") {
+            let mut autoresizing = Element::new("autoresizingMask").attr("key", "autoresizingMask");
+            for (bit, name) in AUTORESIZING {
+                if mask as i64 & bit != 0 {
+                    autoresizing.set_attr(name, "YES");
+                }
+            }
+            element.push(autoresizing);
+        }
+
+        let subviews = self.items(index, "UISubviews");
+        if !subviews.is_empty() {
+            let mut container = Element::new("subviews");
+            for subview in subviews {
+                container.children.extend(self.element(subview));
+            }
+            element.push(container);
+        }
+
+        if let Some(color) = self.color(index, "UIBackgroundColor", "backgroundColor") {
+            element.push(color);
+        }
+        self.content(index, element);
+
+        let constraints = self.items(index, "UIViewAutolayoutConstraints");
+        if !constraints.is_empty() {
+            self.autolayout = true;
+            let mut container = Element::new("constraints");
+            for constraint in constraints {
+                container.push(self.constraint(index, constraint));
+            }
+            element.push(container);
+        }
+    }
+
+    // Attributes shared by UIControl subclasses.
+    fn control(&mut self, index: usize, element: &mut Element) {
+        if let Some(alignment) = self.number(index, "UIContentHorizontalAlignment") {
+            if let Some(name) = HORIZONTAL_ALIGNMENTS.get(alignment as usize) {
+                element.set_attr("contentHorizontalAlignment", *name);
+            }
+        }
+        if let Some(alignment) = self.number(index, "UIContentVerticalAlignment") {
+            if let Some(name) = VERTICAL_ALIGNMENTS.get(alignment as usize) {
+                element.set_attr("contentVerticalAlignment", *name);
+            }
+        }
+        if self.boolean(index, "UIEnabled") == Some(false) {
+            element.set_attr("enabled", "NO");
+        }
+        if self.boolean(index, "UISelected") == Some(true) {
+            element.set_attr("selected", "YES");
+        }
+        if self.boolean(index, "UIHighlighted") == Some(true) {
+            element.set_attr("highlighted", "YES");
+        }
+    }
+
+    // Class specific content of labels, buttons, text inputs and controls.
+    fn content(&mut self, index: usize, element: &mut Element) {
+        match element.name.as_str() {
+            "label" | "textField" | "textView" => {
+                if let Some(text) = self.string(index, "UIText") {
+                    element.set_attr("text", text);
+                }
+                if let Some(placeholder) = self.string(index, "UIPlaceholder") {
+                    element.set_attr("placeholder", placeholder);
+                }
+                if let Some(lines) = self.number(index, "UINumberOfLines") {
+                    element.set_attr("numberOfLines", format!("{}", lines as i64));
+                }
+                if let Some(alignment) = self.number(index, "UITextAlignment") {
+                    if let Some(name) = TEXT_ALIGNMENTS.get(alignment as usize) {
+                        element.set_attr("textAlignment", *name);
+                    }
+                }
+                if let Some(style) = self.number(index, "UIBorderStyle") {
+                    if let Some(name) = BORDER_STYLES.get(style as usize) {
+                        element.set_attr("borderStyle", *name);
+                    }
+                }
+                if let Some(mode) = self.number(index, "UILineBreakMode") {
+                    if let Some(name) = LINE_BREAK_MODES.get(mode as usize) {
+                        element.set_attr("lineBreakMode", *name);
+                    }
+                }
+                if let Some(font) = self.font(index, "UIFont") {
+                    element.push(font);
+                }
+                if let Some(color) = self.color(index, "UITextColor", "textColor") {
+                    element.push(color);
+                }
+            }
+            "button" => {
+                if let Some(kind) = self.number(index, "UIButtonType") {
+                    if let Some(name) = BUTTON_TYPES.get(kind as usize) {
+                        element.set_attr("buttonType", *name);
+                    }
+                }
+                if let Some(mode) = self.number(index, "UILineBreakMode") {
+                    if let Some(name) = LINE_BREAK_MODES.get(mode as usize) {
+                        element.set_attr("lineBreakMode", *name);
+                    }
+                }
+                if let Some(font) = self.font(index, "UIFont") {
+                    element.push(font);
+                }
+                if let Some(content) = self.reference(index, "UIButtonStatefulContent") {
+                    self.consume(content, &["NSInlinedValue", "UINibEncoderEmptyKey"]);
+                    let items = self.context.collection_items(content);
+                    for pair in items.chunks(2) {
+                        if let [state, content] = pair {
+                            element.push(self.button_state(*state as usize, *content as usize));
+                        }
+                    }
+                }
+            }
+            "imageView" => {
+                if let Some(image) = self.reference(index, "UIImage") {
+                    match self.string(image, "UIResourceName") {
+                        Some(name) => {
+                            self.visited.insert(image);
+                            element.set_attr("image", &name);
+                            self.images.insert(name);
+                        }
+                        None => self.warnings.push(format!(
+                            "Object {}: image {} is not a named resource",
+                            index, image
+                        )),
+                    }
+                }
+            }
+            "switch" => {
+                if let Some(on) = self.boolean(index, "UISwitchOn") {
+                    element.set_attr("on", if on { "YES" } else { "NO" });
+                }
+            }
+            "slider" => {
+                for (key, attribute) in [
+                    ("UIValue", "value"),
+                    ("UIMinValue", "minValue"),
+                    ("UIMaxValue", "maxValue"),
+                ] {
+                    if let Some(value) = self.number(index, key) {
+                        element.set_attr(attribute, format_number(value));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn button_state(&mut self, state: usize, content: usize) -> Element {
+        self.consume(state, &["NS.intval"]);
+        self.visited.insert(content);
+        let state = self.number(state, "NS.intval").unwrap_or(0.0) as i64;
+        let name = CONTROL_STATES
+            .iter()
+            .find(|(value, _)| *value == state)
+            .map(|(_, name)| *name)
+            .unwrap_or("normal");
+
+        let mut element = Element::new("state").attr("key", name);
+        if let Some(title) = self.string(content, "UITitle") {
+            element.set_attr("title", title);
+        }
+        if let Some(image) = self.reference(content, "UIImage") {
+            if let Some(image_name) = self.string(image, "UIResourceName") {
+                self.visited.insert(image);
+                element.set_attr("image", &image_name);
+                self.images.insert(image_name);
+            }
+        }
+        if let Some(color) = self.color(content, "UITitleColor", "titleColor") {
+            element.push(color);
+        }
+        element
+    }
+
+    fn frame(&mut self, index: usize) -> Option<Element> {
+        self.handle(index, "UIBounds");
+        self.handle(index, "UICenter");
+        let bounds = geometry(self.context, self.context.find_value(index, "UIBounds")?)?;
+        let center = self
+            .context
+            .find_value(index, "UICenter")
+            .and_then(|value| geometry(self.context, value));
+
+        let (width, height) = match bounds.as_slice() {
+            [_, _, width, height] => (*width, *height),
+            _ => return None,
+        };
+        let (x, y) = match center.as_deref() {
+            Some([x, y]) => (x - width / 2.0, y - height / 2.0),
+            _ => (bounds[0], bounds[1]),
+        };
+        Some(
+            Element::new("rect")
+                .attr("key", "frame")
+                .attr("x", format_number(x))
+                .attr("y", format_number(y))
+                .attr("width", format_number(width))
+                .attr("height", format_number(height)),
+        )
+    }
+
+    fn color(&mut self, index: usize, key: &str, xib_key: &str) -> Option<Element> {
+        let color = self.reference(index, key)?;
+        self.consume(color, &["UIColorComponentCount"]);
+        let element = Element::new("color").attr("key", xib_key);
+
+        if let Some(name) = self.string(color, "UISystemColorName") {
+            self.system_colors.insert(name.to_owned());
+            return Some(element.attr("systemColor", name));
+        }
+        let alpha = self.number(color, "UIAlpha").unwrap_or(1.0);
+        if let (Some(red), Some(green), Some(blue)) = (
+            self.number(color, "UIRed"),
+            self.number(color, "UIGreen"),
+            self.number(color, "UIBlue"),
+        ) {
+            return Some(
+                element
+                    .attr("red", format_number(red))
+                    .attr("green", format_number(green))
+                    .attr("blue", format_number(blue))
+                    .attr("alpha", format_number(alpha))
+                    .attr("colorSpace", "custom")
+                    .attr("customColorSpace", "sRGB"),
+            );
+        }
+        if let Some(white) = self.number(color, "UIWhite") {
+            return Some(
+                element
+                    .attr("white", format_number(white))
+                    .attr("alpha", format_number(alpha))
+                    .attr("colorSpace", "custom")
+                    .attr("customColorSpace", "genericGamma22GrayColorSpace"),
+            );
+        }
+        self.warnings.push(format!(
+            "Object {}: color {} ({}) has no supported components",
+            index,
+            color,
+            self.class_name(color)
+        ));
+        None
+    }
+
+    fn font(&mut self, index: usize, key: &str) -> Option<Element> {
+        let font = self.reference(index, key)?;
+        self.visited.insert(font);
+        let size = self.number(font, "UIFontPointSize").unwrap_or(17.0);
+        let name = self.string(font, "UIFontName").unwrap_or_default();
+        let system = self.boolean(font, "UISystemFont").unwrap_or(false) || name.starts_with('.');
+        let traits = self.number(font, "UIFontTraits").unwrap_or(0.0) as i64;
+
+        let element = Element::new("fontDescription").attr("key", "fontDescription");
+        if system {
+            let kind = match traits {
+                t if t & 2 != 0 => "boldSystem",
+                t if t & 1 != 0 => "italicSystem",
+                _ => "system",
+            };
+            Some(
+                element
+                    .attr("type", kind)
+                    .attr("pointSize", format_number(size)),
+            )
+        } else {
+            let family = name.split('-').next().unwrap_or_default().to_string();
+            Some(
+                element
+                    .attr("name", &name)
+                    .attr("family", family)
+                    .attr("pointSize", format_number(size)),
+            )
+        }
+    }
+
+    fn constraint(&mut self, owner: usize, index: usize) -> Element {
+        self.visited.insert(index);
+        for key in ["NSShouldBeArchived", "NSContentType"] {
+            self.handle(index, key);
+        }
+        let mut element = Element::new("constraint");
+
+        let first = self.reference(index, "NSFirstItem");
+        if let Some(first) = first.filter(|first| *first != owner) {
+            element.set_attr("firstItem", self.id(first));
+        }
+        let first_attribute = self.number(index, "NSFirstAttribute").unwrap_or(0.0);
+        element.set_attr("firstAttribute", layout_attribute(first_attribute));
+
+        match self.number(index, "NSRelation").map(|r| r as i64) {
+            Some(-1) => element.set_attr("relation", "lessThanOrEqual"),
+            Some(1) => element.set_attr("relation", "greaterThanOrEqual"),
+            _ => {}
+        }
+        if let Some(second) = self.reference(index, "NSSecondItem") {
+            element.set_attr("secondItem", self.id(second));
+            let second_attribute = self.number(index, "NSSecondAttribute").unwrap_or(0.0);
+            element.set_attr("secondAttribute", layout_attribute(second_attribute));
+        }
+        if let Some(multiplier) = self.number(index, "NSMultiplier").filter(|m| *m != 1.0) {
+            element.set_attr("multiplier", format_number(multiplier));
+        }
+        if let Some(constant) = self.number(index, "NSConstant").filter(|c| *c != 0.0) {
+            element.set_attr("constant", format_number(constant));
+        }
+        if let Some(priority) = self.number(index, "NSPriority").filter(|p| *p != 1000.0) {
+            element.set_attr("priority", format!("{}", priority as i64));
+        }
+        if let Some(identifier) = self.string(index, "NSLayoutIdentifier") {
+            element.set_attr("identifier", identifier);
+        }
+        element.set_attr("id", self.id(index));
+        element
+    }
+
+    fn report_unhandled(&mut self) {
+        let visited: Vec<usize> = self.visited.iter().copied().collect();
+        for index in visited {
+            for (key, _) in self.context.named_values(index) {
+                if !self.handled.contains(&(index, key.to_owned())) {
+                    self.warnings.push(format!(
+                        "Object {} ({}): key {} is not modeled",
+                        index,
+                        self.class_name(index),
+                        key
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn layout_attribute(value: f64) -> &'static str {
+    LAYOUT_ATTRIBUTES
+        .get(value as usize)
+        .copied()
+        .unwrap_or("notAnAttribute")
+}
+
+// Reads a point or rect stored either as a "{{x, y}, {w, h}}" string or as
+// packed floats, optionally preceded by a one byte type marker.
+fn geometry(context: &Context, value: &crate::raw_model::raw_value::RawValue) -> Option<Vec<f64>> {
+    if let Some(string) = context.string(value) {
+        let numbers: Vec<f64> = string
+            .split(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == 'e'))
+            .filter(|part| !part.is_empty())
+            .map(|part| part.parse::<f64>())
+            .collect::<Result<_, _>>()
+            .ok()?;
+        if numbers.len() == 2 || numbers.len() == 4 {
+            return Some(numbers);
+        }
+    }
+
+    // 2 or 4 components of 4 or 8 bytes each.
+    let bytes = &value.data;
+    let body = match bytes.len() % 8 {
+        1 => &bytes[1..],
+        _ => &bytes[..],
+    };
+    let width = match body.len() {
+        32 => 8,
+        16 if bytes.len() % 8 == 1 => 8,
+        16 | 8 => 4,
+        _ => return None,
+    };
+    let numbers: Vec<f64> = body
+        .chunks(width)
+        .map(|chunk| match width {
+            8 => f64::from_le_bytes(chunk.try_into().unwrap()),
+            _ => f32::from_le_bytes(chunk.try_into().unwrap()) as f64,
+        })
+        .collect();
+    (numbers.len() == 2 || numbers.len() == 4).then_some(numbers)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{raw_model::builder::ContextBuilder, xib::decompile::decompile};
+
+    #[test]
+    fn view_with_label_and_outlet() {
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let top_level = builder.reserve_object();
+        let owner = builder.reserve_object();
+        let view = builder.reserve_object();
+        let subviews = builder.reserve_object();
+        let label = builder.reserve_object();
+        let connections = builder.reserve_object();
+        let outlet = builder.reserve_object();
+
+        let object = builder.class("NSObject", &[]);
+        let array = builder.class("NSArray", &[]);
+        let proxy = builder.class("UIProxyObject", &[]);
+        let ui_view = builder.class("UIView", &[]);
+        let ui_label = builder.class("UILabel", &[]);
+        let custom = builder.class("FancyLabel", &[ui_label as i32]);
+        let connection = builder.class("UIRuntimeOutletConnection", &[]);
+
+        let values = vec![
+            builder.object_value("UINibTopLevelObjectsKey", top_level),
+            builder.object_value("UINibConnectionsKey", connections),
+        ];
+        builder.set_object(root, object, values);
+        let values = vec![
+            builder.bool_value("NSInlinedValue", true),
+            builder.object_value("UINibEncoderEmptyKey", owner),
+            builder.object_value("UINibEncoderEmptyKey", view),
+        ];
+        builder.set_object(top_level, array, values);
+        let values =
+            vec![builder.bytes_value("UIProxiedObjectIdentifier", b"IBFilesOwner".to_vec())];
+        builder.set_object(owner, proxy, values);
+        let values = vec![
+            builder.bytes_value("UIBounds", b"{{0, 0}, {320, 480}}".to_vec()),
+            builder.bytes_value("UICenter", b"{160, 240}".to_vec()),
+            builder.object_value("UISubviews", subviews),
+            builder.int_value("UIShadowRadius", 3),
+        ];
+        builder.set_object(view, ui_view, values);
+        let values = vec![
+            builder.bool_value("NSInlinedValue", true),
+            builder.object_value("UINibEncoderEmptyKey", label),
+        ];
+        builder.set_object(subviews, array, values);
+        let values = vec![
+            builder.bytes_value("UIText", b"Hello <world>".to_vec()),
+            builder.int_value("UINumberOfLines", 2),
+            builder.object_value("UISuperview", view),
+        ];
+        builder.set_object(label, custom, values);
+        let values = vec![
+            builder.bool_value("NSInlinedValue", true),
+            builder.object_value("UINibEncoderEmptyKey", outlet),
+        ];
+        builder.set_object(connections, array, values);
+        let values = vec![
+            builder.object_value("UISource", owner),
+            builder.object_value("UIDestination", view),
+            builder.bytes_value("UILabel", b"view".to_vec()),
+        ];
+        builder.set_object(outlet, connection, values);
+
+        let context = builder.build().unwrap();
+        let result = decompile(&context);
+        let xml = result.document.to_string();
+
+        assert!(xml.contains(
+            r#"<placeholder placeholderIdentifier="IBFilesOwner" id="-1" userLabel="File's Owner">"#
+        ));
+        assert!(xml.contains(r#"<rect key="frame" x="0.0" y="0.0" width="320.0" height="480.0"/>"#));
+        assert!(xml.contains(r#"<label customClass="FancyLabel" "#));
+        assert!(xml.contains(r#"text="Hello &lt;world&gt;" numberOfLines="2""#));
+        assert!(xml.contains(r#"<outlet property="view" destination=""#));
+        assert_eq!(
+            vec!["Object 3 (UIView): key UIShadowRadius is not modeled".to_string()],
+            result.warnings
+        );
+    }
+
+    #[test]
+    fn malformed_nibs() {
+        let empty = decompile(&ContextBuilder::new().build().unwrap());
+        assert_eq!(vec!["Nib has no objects".to_string()], empty.warnings);
+        assert!(empty.document.child("objects").unwrap().children.is_empty());
+
+        // A root without top level objects, as in a nib not written by UIKit.
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, vec![]);
+        let result = decompile(&builder.build().unwrap());
+        assert_eq!(
+            vec!["Root object has no UINibTopLevelObjectsKey, not a UIKit nib".to_string()],
+            result.warnings
+        );
+
+        // A missing top level object and a view that is its own subview.
+        let mut builder = ContextBuilder::new();
+        let root = builder.reserve_object();
+        let top_level = builder.reserve_object();
+        let view = builder.reserve_object();
+        let subviews = builder.reserve_object();
+        let value = builder.object_value("UINibTopLevelObjectsKey", top_level);
+        let class = builder.class("NSObject", &[]);
+        builder.set_object(root, class, vec![value]);
+        let array = builder.class("NSArray", &[]);
+        let values = vec![
+            builder.bool_value("NSInlinedValue", true),
+            builder.object_value("UINibEncoderEmptyKey", view),
+            builder.object_value("UINibEncoderEmptyKey", 9),
+        ];
+        builder.set_object(top_level, array, values);
+        let values = vec![
+            builder.bool_value("NSInlinedValue", true),
+            builder.object_value("UINibEncoderEmptyKey", view),
+        ];
+        builder.set_object(subviews, array, values);
+        let value = builder.object_value("UISubviews", subviews);
+        let class = builder.class("UIView", &[]);
+        builder.set_object(view, class, vec![value]);
+        let result = decompile(&builder.build().unwrap());
+        assert_eq!(
+            vec![
+                "Object 2 (UIView) appears more than once in the tree, only its first occurrence was decompiled".to_string(),
+                "Object 9 does not exist and was not decompiled".to_string(),
+            ],
+            result.warnings
+        );
+        let objects = &result.document.child("objects").unwrap().children;
+        assert_eq!(1, objects.len());
+        assert!(objects[0].child("subviews").unwrap().children.is_empty());
+    }
+}
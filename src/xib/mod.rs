@@ -0,0 +1,227 @@
+pub mod compile;
+pub mod decompile;
+pub mod xml;
+
+// UIKit classes and the XIB elements Interface Builder uses for them.
+pub const ELEMENTS: &[(&str, &str)] = &[
+    ("UIView", "view"),
+    ("UILabel", "label"),
+    ("UIButton", "button"),
+    ("UIImageView", "imageView"),
+    ("UITextField", "textField"),
+    ("UITextView", "textView"),
+    ("UISwitch", "switch"),
+    ("UISlider", "slider"),
+    ("UIStepper", "stepper"),
+    ("UIStackView", "stackView"),
+    ("UIScrollView", "scrollView"),
+    ("UITableView", "tableView"),
+    ("UITableViewCell", "tableViewCell"),
+    ("UICollectionView", "collectionView"),
+    ("UICollectionViewCell", "collectionViewCell"),
+    ("UIActivityIndicatorView", "activityIndicatorView"),
+    ("UIProgressView", "progressView"),
+    ("UISegmentedControl", "segmentedControl"),
+    ("UIPageControl", "pageControl"),
+    ("UIDatePicker", "datePicker"),
+    ("UIPickerView", "pickerView"),
+    ("UIVisualEffectView", "visualEffectView"),
+    ("UINavigationBar", "navigationBar"),
+    ("UIToolbar", "toolbar"),
+    ("UITabBar", "tabBar"),
+    ("UISearchBar", "searchBar"),
+    ("WKWebView", "wkWebView"),
+    ("UIViewController", "viewController"),
+    ("UINavigationController", "navigationController"),
+    ("UITableViewController", "tableViewController"),
+    ("UITapGestureRecognizer", "tapGestureRecognizer"),
+    ("UIPanGestureRecognizer", "panGestureRecognizer"),
+    // Interface Builder really spells this element with a "p".
+    ("UILongPressGestureRecognizer", "pongPressGestureRecognizer"),
+];
+
+// Elements that are views and take part in the frame/subview hierarchy.
+pub const VIEW_ELEMENTS: &[&str] = &[
+    "view",
+    "label",
+    "button",
+    "imageView",
+    "textField",
+    "textView",
+    "switch",
+    "slider",
+    "stepper",
+    "stackView",
+    "scrollView",
+    "tableView",
+    "tableViewCell",
+    "collectionView",
+    "collectionViewCell",
+    "activityIndicatorView",
+    "progressView",
+    "segmentedControl",
+    "pageControl",
+    "datePicker",
+    "pickerView",
+    "visualEffectView",
+    "navigationBar",
+    "toolbar",
+    "tabBar",
+    "searchBar",
+    "wkWebView",
+];
+
+// UIViewContentMode
+pub const CONTENT_MODES: &[&str] = &[
+    "scaleToFill",
+    "scaleAspectFit",
+    "scaleAspectFill",
+    "redraw",
+    "center",
+    "top",
+    "bottom",
+    "left",
+    "right",
+    "topLeft",
+    "topRight",
+    "bottomLeft",
+    "bottomRight",
+];
+
+// NSLayoutAttribute, indexed by raw value.
+pub const LAYOUT_ATTRIBUTES: &[&str] = &[
+    "notAnAttribute",
+    "left",
+    "right",
+    "top",
+    "bottom",
+    "leading",
+    "trailing",
+    "width",
+    "height",
+    "centerX",
+    "centerY",
+    "lastBaseline",
+    "firstBaseline",
+    "leftMargin",
+    "rightMargin",
+    "topMargin",
+    "bottomMargin",
+    "leadingMargin",
+    "trailingMargin",
+    "centerXWithinMargins",
+    "centerYWithinMargins",
+];
+
+// NSTextAlignment on iOS
+pub const TEXT_ALIGNMENTS: &[&str] = &["left", "center", "right", "justified", "natural"];
+
+// UIControlContentHorizontalAlignment
+pub const HORIZONTAL_ALIGNMENTS: &[&str] =
+    &["center", "left", "right", "fill", "leading", "trailing"];
+
+// UIControlContentVerticalAlignment
+pub const VERTICAL_ALIGNMENTS: &[&str] = &["center", "top", "bottom", "fill"];
+
+// UITextBorderStyle
+pub const BORDER_STYLES: &[&str] = &["none", "line", "bezel", "roundedRect"];
+
+// NSLineBreakMode
+pub const LINE_BREAK_MODES: &[&str] = &[
+    "wordWrap",
+    "characterWrap",
+    "clip",
+    "headTruncation",
+    "tailTruncation",
+    "middleTruncation",
+];
+
+// UIButtonType. XIBs written before iOS 7 call the system type roundedRect.
+pub const BUTTON_TYPES: &[&str] = &[
+    "custom",
+    "system",
+    "detailDisclosure",
+    "infoLight",
+    "infoDark",
+    "contactAdd",
+];
+
+// UIControlEvents bits
+pub const CONTROL_EVENTS: &[(u32, &str)] = &[
+    (1 << 0, "touchDown"),
+    (1 << 1, "touchDownRepeat"),
+    (1 << 2, "touchDragInside"),
+    (1 << 3, "touchDragOutside"),
+    (1 << 4, "touchDragEnter"),
+    (1 << 5, "touchDragExit"),
+    (1 << 6, "touchUpInside"),
+    (1 << 7, "touchUpOutside"),
+    (1 << 8, "touchCancel"),
+    (1 << 12, "valueChanged"),
+    (1 << 13, "primaryActionTriggered"),
+    (1 << 16, "editingDidBegin"),
+    (1 << 17, "editingChanged"),
+    (1 << 18, "editingDidEnd"),
+    (1 << 19, "editingDidEndOnExit"),
+];
+
+// UIControlState values used as keys of a button's stateful content.
+pub const CONTROL_STATES: &[(i64, &str)] = &[
+    (0, "normal"),
+    (1, "highlighted"),
+    (2, "disabled"),
+    (4, "selected"),
+];
+
+// UIViewAutoresizing bits and their XIB attribute names.
+pub const AUTORESIZING: &[(i64, &str)] = &[
+    (1 << 0, "flexibleMinX"),
+    (1 << 1, "widthSizable"),
+    (1 << 2, "flexibleMaxX"),
+    (1 << 3, "flexibleMinY"),
+    (1 << 4, "heightSizable"),
+    (1 << 5, "flexibleMaxY"),
+];
+
+pub fn element_for_class(class: &str) -> Option<&'static str> {
+    ELEMENTS
+        .iter()
+        .find(|(name, _)| *name == class)
+        .map(|(_, element)| *element)
+}
+
+pub fn class_for_element(element: &str) -> Option<&'static str> {
+    ELEMENTS
+        .iter()
+        .find(|(_, name)| *name == element)
+        .map(|(class, _)| *class)
+}
+
+pub fn is_view_element(element: &str) -> bool {
+    VIEW_ELEMENTS.contains(&element)
+}
+
+// Deterministic Interface Builder style identifier ("abc-de-fgh") for a
+// number. The multiplier is coprime with 62^8, so distinct numbers map to
+// distinct identifiers.
+pub fn ib_id(number: usize) -> String {
+    const ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    const SPACE: u128 = 62u128.pow(8);
+
+    let mut value = ((number as u128 % SPACE) * 2_654_435_761) % SPACE;
+    let mut chars = vec![];
+    for _ in 0..8 {
+        chars.push(ALPHABET[(value % 62) as usize] as char);
+        value /= 62;
+    }
+    let id: String = chars.into_iter().collect();
+    format!("{}-{}-{}", &id[0..3], &id[3..5], &id[5..8])
+}
+
+pub fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{:.1}", value)
+    } else {
+        format!("{}", value)
+    }
+}
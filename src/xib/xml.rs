@@ -0,0 +1,376 @@
+use std::fmt::Display;
+
+use crate::raw_model::ParseError;
+
+// Minimal XML element tree, enough to read and write Interface Builder
+// documents. Attribute order is preserved.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    pub text: Option<String>,
+    // Line the element starts on when it was parsed, 0 otherwise.
+    pub line: usize,
+}
+
+impl Element {
+    pub fn new(name: &str) -> Element {
+        Element {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn attr(mut self, key: &str, value: impl Into<String>) -> Element {
+        self.set_attr(key, value);
+        self
+    }
+
+    pub fn set_attr(&mut self, key: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self.attributes.iter_mut().find(|(k, _)| k == key) {
+            Some((_, existing)) => *existing = value,
+            None => self.attributes.push((key.to_string(), value)),
+        }
+    }
+
+    pub fn get_attr(&self, key: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn push(&mut self, child: Element) {
+        self.children.push(child);
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    // First child with the given `key` attribute, e.g. `<rect key="frame">`.
+    pub fn keyed_child(&self, key: &str) -> Option<&Element> {
+        self.children
+            .iter()
+            .find(|child| child.get_attr("key") == Some(key))
+    }
+
+    // Returns the child with the given name, creating it when missing.
+    pub fn child_mut(&mut self, name: &str) -> &mut Element {
+        match self.children.iter().position(|child| child.name == name) {
+            Some(position) => &mut self.children[position],
+            None => {
+                self.children.push(Element::new(name));
+                self.children.last_mut().unwrap()
+            }
+        }
+    }
+
+    fn write(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+        let indent = "    ".repeat(depth);
+        write!(f, "{}<{}", indent, self.name)?;
+        for (key, value) in &self.attributes {
+            write!(f, " {}=\"{}\"", key, escape(value))?;
+        }
+
+        match (&self.text, self.children.is_empty()) {
+            (None, true) => writeln!(f, "/>"),
+            (Some(text), true) => writeln!(f, ">{}</{}>", escape(text), self.name),
+            _ => {
+                writeln!(f, ">")?;
+                if let Some(text) = &self.text {
+                    writeln!(f, "{}    {}", indent, escape(text))?;
+                }
+                for child in &self.children {
+                    child.write(f, depth + 1)?;
+                }
+                writeln!(f, "{}</{}>", indent, self.name)
+            }
+        }
+    }
+}
+
+impl Display for Element {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        self.write(f, 0)
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\n' => escaped.push_str("&#10;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Parses an XML document into its root element. Comments, processing
+// instructions and the doctype are skipped; whitespace only text is dropped.
+pub fn parse(text: &str) -> Result<Element, ParseError> {
+    let mut parser = Parser { text, position: 0 };
+    parser.skip_misc()?;
+    if parser.position >= text.len() {
+        return Err(parser.error(parser.position, "Document has no root element"));
+    }
+    let root = parser.element()?;
+    parser.skip_misc()?;
+    if parser.position < text.len() {
+        return Err(parser.error(parser.position, "Unexpected content after the root element"));
+    }
+    Ok(root)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn line_of(&self, offset: usize) -> usize {
+        self.text[..offset.min(self.text.len())]
+            .matches('\n')
+            .count()
+            + 1
+    }
+
+    fn error(&self, start: usize, reason: &str) -> ParseError {
+        ParseError::new(
+            self.position,
+            start,
+            format!("{} on line {}", reason, self.line_of(self.position)),
+        )
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn skip_past(&mut self, terminator: &str, start: usize, what: &str) -> Result<(), ParseError> {
+        match self.rest().find(terminator) {
+            Some(end) => {
+                self.position += end + terminator.len();
+                Ok(())
+            }
+            None => {
+                self.position = self.text.len();
+                Err(self.error(start, &format!("Unterminated {}", what)))
+            }
+        }
+    }
+
+    // Skips whitespace, comments, processing instructions and doctypes.
+    fn skip_misc(&mut self) -> Result<(), ParseError> {
+        loop {
+            self.skip_whitespace();
+            let start = self.position;
+            if self.rest().starts_with("<?") {
+                self.skip_past("?>", start, "processing instruction")?;
+            } else if self.rest().starts_with("<!--") {
+                self.skip_past("-->", start, "comment")?;
+            } else if self.rest().starts_with("<!DOCTYPE") {
+                self.skip_past(">", start, "doctype")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, ParseError> {
+        let rest = self.rest();
+        let length = rest
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':')))
+            .unwrap_or(rest.len());
+        if length == 0 {
+            return Err(self.error(self.position, "Expected a name"));
+        }
+        self.position += length;
+        Ok(rest[..length].to_string())
+    }
+
+    fn expect(&mut self, token: &str, start: usize) -> Result<(), ParseError> {
+        if !self.rest().starts_with(token) {
+            return Err(self.error(start, &format!("Expected '{}'", token)));
+        }
+        self.position += token.len();
+        Ok(())
+    }
+
+    fn element(&mut self) -> Result<Element, ParseError> {
+        let start = self.position;
+        self.expect("<", start)?;
+        let mut element = Element::new(&self.name()?);
+        element.line = self.line_of(start);
+
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("/>") {
+                self.position += 2;
+                return Ok(element);
+            }
+            if self.rest().starts_with('>') {
+                self.position += 1;
+                break;
+            }
+            if self.position >= self.text.len() {
+                return Err(self.error(start, &format!("Unterminated <{}> tag", element.name)));
+            }
+            let key = self.name()?;
+            self.skip_whitespace();
+            self.expect("=", start)?;
+            self.skip_whitespace();
+            let quote = match self.rest().chars().next() {
+                Some(quote @ ('"' | '\'')) => quote,
+                _ => return Err(self.error(start, "Expected a quoted attribute value")),
+            };
+            self.position += 1;
+            let Some(end) = self.rest().find(quote) else {
+                return Err(self.error(start, "Unterminated attribute value"));
+            };
+            let value = self.unescape(self.position, end)?;
+            self.position += end + 1;
+            if element.get_attr(&key).is_some() {
+                return Err(self.error(start, &format!("Duplicate attribute {}", key)));
+            }
+            element.attributes.push((key, value));
+        }
+
+        let mut text = String::new();
+        loop {
+            if self.position >= self.text.len() {
+                return Err(self.error(start, &format!("Missing </{}>", element.name)));
+            }
+            let rest = self.rest();
+            if rest.starts_with("</") {
+                self.position += 2;
+                let name = self.name()?;
+                if name != element.name {
+                    return Err(self.error(
+                        start,
+                        &format!("Expected </{}> but found </{}>", element.name, name),
+                    ));
+                }
+                self.skip_whitespace();
+                self.expect(">", start)?;
+                break;
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->", self.position, "comment")?;
+            } else if rest.starts_with("<![CDATA[") {
+                let cdata_start = self.position;
+                self.position += 9;
+                let end = self.rest().find("]]>");
+                let Some(end) = end else {
+                    return Err(self.error(cdata_start, "Unterminated CDATA section"));
+                };
+                text.push_str(&self.rest()[..end]);
+                self.position += end + 3;
+            } else if rest.starts_with("<?") {
+                self.skip_past("?>", self.position, "processing instruction")?;
+            } else if rest.starts_with('<') {
+                let child = self.element()?;
+                element.push(child);
+            } else {
+                let end = rest.find('<').unwrap_or(rest.len());
+                text.push_str(&self.unescape(self.position, end)?);
+                self.position += end;
+            }
+        }
+
+        if !text.trim().is_empty() {
+            element.text = Some(text);
+        }
+        Ok(element)
+    }
+
+    fn unescape(&self, start: usize, length: usize) -> Result<String, ParseError> {
+        let raw = &self.text[start..start + length];
+        let mut unescaped = String::with_capacity(raw.len());
+        let mut rest = raw;
+        while let Some(ampersand) = rest.find('&') {
+            unescaped.push_str(&rest[..ampersand]);
+            rest = &rest[ampersand..];
+            let offset = start + raw.len() - rest.len();
+            let Some(end) = rest.find(';') else {
+                return Err(ParseError::new(
+                    offset,
+                    start,
+                    format!("Unterminated entity on line {}", self.line_of(offset)),
+                ));
+            };
+            let entity = &rest[1..end];
+            let character = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => entity
+                    .strip_prefix("#x")
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| entity.strip_prefix('#').map(|decimal| decimal.parse()))
+                    .and_then(|code| code.ok())
+                    .and_then(char::from_u32),
+            };
+            match character {
+                Some(character) => unescaped.push(character),
+                None => {
+                    return Err(ParseError::new(
+                        offset,
+                        start,
+                        format!(
+                            "Unknown entity &{}; on line {}",
+                            entity,
+                            self.line_of(offset)
+                        ),
+                    ))
+                }
+            }
+            rest = &rest[end + 1..];
+        }
+        unescaped.push_str(rest);
+        Ok(unescaped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xib::xml::{parse, Element};
+
+    #[test]
+    fn write_parse_round_trip() {
+        let mut root = Element::new("document").attr("type", "a & \"b\"");
+        let mut child = Element::new("string").attr("key", "text");
+        child.text = Some("<hello>\nworld".to_string());
+        root.push(child);
+        root.push(Element::new("empty"));
+
+        let parsed = parse(&root.to_string()).unwrap();
+        assert_eq!(Some("a & \"b\""), parsed.get_attr("type"));
+        assert_eq!(
+            Some("<hello>\nworld"),
+            parsed.keyed_child("text").unwrap().text.as_deref()
+        );
+        assert_eq!(4, parsed.child("empty").unwrap().line);
+    }
+
+    #[test]
+    fn mismatched_tag() {
+        let error = parse("<a>\n  <b>\n  </c>\n</a>").unwrap_err();
+        assert_eq!("Expected </b> but found </c> on line 3", error.reason);
+        assert_eq!(6, error.rel_offset);
+    }
+}
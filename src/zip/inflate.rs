@@ -0,0 +1,306 @@
+use crate::raw_model::ParseError;
+
+const MAX_BITS: usize = 15;
+
+// Base lengths and extra bits of length codes 257..285.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+// Base distances and extra bits of distance codes 0..29.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// Order in which code length code lengths are stored in a dynamic block.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+// DEFLATE cannot expand its input more than about 1032 times, which bounds
+// the output a stream of a given size can produce.
+const MAX_RATIO: usize = 1032;
+
+// Decompresses a raw DEFLATE stream (RFC 1951), as stored in zip entries.
+// `limit` is the expected output size: decompressing stops with an error
+// once the output would exceed it. The size comes from the archive, so no
+// more memory is reserved up front than the input could expand to.
+pub fn inflate(buffer: &[u8], limit: usize) -> Result<Vec<u8>, ParseError> {
+    let mut inflater = Inflater {
+        buffer,
+        position: 0,
+        bit_buffer: 0,
+        bit_count: 0,
+        output: Vec::with_capacity(limit.min(buffer.len().saturating_mul(MAX_RATIO))),
+        limit,
+    };
+    loop {
+        let last = inflater.bits(1)? == 1;
+        match inflater.bits(2)? {
+            0 => inflater.stored()?,
+            1 => {
+                let (literals, distances) = fixed_codes();
+                inflater.codes(&literals, &distances)?
+            }
+            2 => {
+                let (literals, distances) = inflater.dynamic_codes()?;
+                inflater.codes(&literals, &distances)?
+            }
+            _ => return Err(inflater.error("Invalid DEFLATE block type")),
+        }
+        if last {
+            return Ok(inflater.output);
+        }
+    }
+}
+
+// Canonical Huffman code: the number of codes of each length, and the
+// symbols ordered by code.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman, String> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        // Reject over-subscribed codes; incomplete ones are allowed.
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = left * 2 - count as i32;
+            if left < 0 {
+                return Err("Over-subscribed Huffman code".to_string());
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        counts[0] = 0;
+        Ok(Huffman { counts, symbols })
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (
+        Huffman::new(&lengths).unwrap(),
+        Huffman::new(&[5; 30]).unwrap(),
+    )
+}
+
+struct Inflater<'a> {
+    buffer: &'a [u8],
+    position: usize,
+    bit_buffer: u32,
+    bit_count: u32,
+    output: Vec<u8>,
+    limit: usize,
+}
+
+impl Inflater<'_> {
+    fn error(&self, reason: impl Into<String>) -> ParseError {
+        ParseError::new(self.position, 0, reason)
+    }
+
+    // Fails when `count` more bytes of output would exceed the limit.
+    fn grow(&self, count: usize) -> Result<(), ParseError> {
+        if count > self.limit - self.output.len() {
+            return Err(self.error(format!("Output exceeds the expected {} bytes", self.limit)));
+        }
+        Ok(())
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, ParseError> {
+        while self.bit_count < count {
+            let byte = *self
+                .buffer
+                .get(self.position)
+                .ok_or_else(|| self.error("Unexpected end of DEFLATE stream"))?;
+            self.position += 1;
+            self.bit_buffer |= (byte as u32) << self.bit_count;
+            self.bit_count += 8;
+        }
+        let value = self.bit_buffer & ((1u64 << count) - 1) as u32;
+        self.bit_buffer >>= count;
+        self.bit_count -= count;
+        Ok(value)
+    }
+
+    fn decode(&mut self, huffman: &Huffman) -> Result<u16, ParseError> {
+        // Codes are stored most significant bit first.
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..=MAX_BITS {
+            code |= self.bits(1)? as i32;
+            let count = huffman.counts[length] as i32;
+            if code - count < first {
+                return Ok(huffman.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(self.error("Invalid Huffman code"))
+    }
+
+    fn stored(&mut self) -> Result<(), ParseError> {
+        self.bit_buffer = 0;
+        self.bit_count = 0;
+        let header = self
+            .buffer
+            .get(self.position..self.position + 4)
+            .ok_or_else(|| self.error("Unexpected end of stored block header"))?;
+        let length = u16::from_le_bytes([header[0], header[1]]);
+        let complement = u16::from_le_bytes([header[2], header[3]]);
+        if length != !complement {
+            return Err(self.error("Stored block length does not match its complement"));
+        }
+        self.position += 4;
+        let data = self
+            .buffer
+            .get(self.position..self.position + length as usize)
+            .ok_or_else(|| self.error("Unexpected end of stored block"))?;
+        self.grow(data.len())?;
+        self.output.extend_from_slice(data);
+        self.position += length as usize;
+        Ok(())
+    }
+
+    fn dynamic_codes(&mut self) -> Result<(Huffman, Huffman), ParseError> {
+        let literal_count = self.bits(5)? as usize + 257;
+        let distance_count = self.bits(5)? as usize + 1;
+        let code_length_count = self.bits(4)? as usize + 4;
+        if literal_count > 286 || distance_count > 30 {
+            return Err(self.error("Too many DEFLATE codes"));
+        }
+
+        let mut lengths = [0u8; 19];
+        for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+            lengths[index] = self.bits(3)? as u8;
+        }
+        let code_lengths = Huffman::new(&lengths).map_err(|e| self.error(e))?;
+
+        let mut lengths = vec![0u8; literal_count + distance_count];
+        let mut index = 0;
+        while index < lengths.len() {
+            let symbol = self.decode(&code_lengths)?;
+            let (value, repeat) = match symbol {
+                0..=15 => (symbol as u8, 1),
+                16 => {
+                    let previous = *lengths[..index]
+                        .last()
+                        .ok_or_else(|| self.error("Repeated code length without a previous one"))?;
+                    (previous, 3 + self.bits(2)? as usize)
+                }
+                17 => (0, 3 + self.bits(3)? as usize),
+                _ => (0, 11 + self.bits(7)? as usize),
+            };
+            if index + repeat > lengths.len() {
+                return Err(self.error("Too many code lengths"));
+            }
+            lengths[index..index + repeat].fill(value);
+            index += repeat;
+        }
+        if lengths[256] == 0 {
+            return Err(self.error("Missing end of block code"));
+        }
+
+        let literals = Huffman::new(&lengths[..literal_count]).map_err(|e| self.error(e))?;
+        let distances = Huffman::new(&lengths[literal_count..]).map_err(|e| self.error(e))?;
+        Ok((literals, distances))
+    }
+
+    fn codes(&mut self, literals: &Huffman, distances: &Huffman) -> Result<(), ParseError> {
+        loop {
+            let symbol = self.decode(literals)? as usize;
+            if symbol < 256 {
+                self.grow(1)?;
+                self.output.push(symbol as u8);
+                continue;
+            }
+            if symbol == 256 {
+                return Ok(());
+            }
+
+            let symbol = symbol - 257;
+            if symbol >= LENGTH_BASE.len() {
+                return Err(self.error("Invalid length code"));
+            }
+            let length =
+                LENGTH_BASE[symbol] as usize + self.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+            let symbol = self.decode(distances)? as usize;
+            if symbol >= DISTANCE_BASE.len() {
+                return Err(self.error("Invalid distance code"));
+            }
+            let distance =
+                DISTANCE_BASE[symbol] as usize + self.bits(DISTANCE_EXTRA[symbol] as u32)? as usize;
+            if distance > self.output.len() {
+                return Err(self.error("Distance reaches before the start of the output"));
+            }
+            self.grow(length)?;
+            // Copies byte by byte, as the source may overlap the output.
+            let start = self.output.len() - distance;
+            for i in 0..length {
+                self.output.push(self.output[start + i]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::zip::inflate::inflate;
+
+    #[test]
+    fn blocks() {
+        // Stored block.
+        assert_eq!(
+            b"abc".to_vec(),
+            inflate(&[0x01, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c'], 3).unwrap()
+        );
+        // Fixed Huffman block with a back reference.
+        assert_eq!(
+            b"abcabcabcabc".to_vec(),
+            inflate(&[0x4b, 0x4c, 0x4a, 0x4e, 0x84, 0x21, 0x00], 12).unwrap()
+        );
+        assert!(inflate(&[0x07], 0).is_err());
+        // A claimed size far beyond what the input can expand to.
+        assert_eq!(
+            b"abc".to_vec(),
+            inflate(
+                &[0x01, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c'],
+                usize::MAX
+            )
+            .unwrap()
+        );
+        // Output past the expected size.
+        assert_eq!(
+            "Output exceeds the expected 11 bytes",
+            inflate(&[0x4b, 0x4c, 0x4a, 0x4e, 0x84, 0x21, 0x00], 11)
+                .unwrap_err()
+                .reason
+        );
+        assert!(inflate(&[0x01, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c'], 2).is_err());
+    }
+}
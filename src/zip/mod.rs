@@ -0,0 +1,290 @@
+use crate::raw_model::ParseError;
+
+pub mod inflate;
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+const LOCAL_HEADER_SIZE: usize = 30;
+const CENTRAL_HEADER_SIZE: usize = 46;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+const ENCRYPTED_FLAG: u16 = 1;
+
+pub fn is_zip(buffer: &[u8]) -> bool {
+    // An empty archive consists of only the end record.
+    matches!(
+        read_u32(buffer, 0),
+        Some(LOCAL_HEADER | END_OF_CENTRAL_DIRECTORY)
+    )
+}
+
+#[derive(Debug, Clone)]
+pub struct ZipEntry {
+    pub name: String,
+    pub method: u16,
+    pub flags: u16,
+    pub crc32: u32,
+    pub compressed_size: usize,
+    pub size: usize,
+    // Offset of the local file header.
+    pub offset: usize,
+}
+
+impl ZipEntry {
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with('/')
+    }
+}
+
+// A zip archive such as an .ipa, read from its central directory. Entries
+// are decompressed on demand.
+pub struct ZipArchive<'a> {
+    buffer: &'a [u8],
+    pub entries: Vec<ZipEntry>,
+}
+
+impl<'a> ZipArchive<'a> {
+    pub fn from_buffer(buffer: &'a [u8]) -> Result<ZipArchive<'a>, ParseError> {
+        let end = find_end_of_central_directory(buffer)?;
+        let count = read_u16(buffer, end + 10).unwrap_or_default() as usize;
+        let directory_size = read_u32(buffer, end + 12).unwrap_or_default() as usize;
+        let directory_offset = read_u32(buffer, end + 16).unwrap_or_default() as usize;
+        if count == 0xffff || directory_offset == 0xffff_ffff {
+            return Err(ParseError::new(end, 0, "Zip64 archives are not supported"));
+        }
+        if directory_offset + directory_size > end {
+            return Err(ParseError::new(
+                end,
+                16,
+                "Central directory reaches past its end record",
+            ));
+        }
+
+        let mut entries = Vec::with_capacity(count);
+        let mut offset = directory_offset;
+        for _ in 0..count {
+            let field = |rel: usize, size: usize| {
+                let value = match size {
+                    2 => read_u16(buffer, offset + rel).map(|v| v as u32),
+                    _ => read_u32(buffer, offset + rel),
+                };
+                value.ok_or_else(|| {
+                    ParseError::new(offset, rel, "Unexpected end of central directory")
+                })
+            };
+            if field(0, 4)? != CENTRAL_HEADER {
+                return Err(ParseError::new(
+                    offset,
+                    0,
+                    "Incorrect central directory header signature",
+                ));
+            }
+            let name_length = field(28, 2)? as usize;
+            let extra_length = field(30, 2)? as usize;
+            let comment_length = field(32, 2)? as usize;
+            let name = buffer
+                .get(offset + CENTRAL_HEADER_SIZE..offset + CENTRAL_HEADER_SIZE + name_length)
+                .ok_or_else(|| {
+                    ParseError::new(offset, CENTRAL_HEADER_SIZE, "Unexpected end of file name")
+                })?;
+            entries.push(ZipEntry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                flags: field(8, 2)? as u16,
+                method: field(10, 2)? as u16,
+                crc32: field(16, 4)?,
+                compressed_size: field(20, 4)? as usize,
+                size: field(24, 4)? as usize,
+                offset: field(42, 4)? as usize,
+            });
+            offset += CENTRAL_HEADER_SIZE + name_length + extra_length + comment_length;
+        }
+        Ok(ZipArchive { buffer, entries })
+    }
+
+    pub fn find(&self, name: &str) -> Option<&ZipEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    // Decompresses an entry and checks its CRC.
+    pub fn read(&self, entry: &ZipEntry) -> Result<Vec<u8>, ParseError> {
+        let offset = entry.offset;
+        let error = |rel: usize, reason: String| {
+            ParseError::new(offset, rel, format!("{}: {}", entry.name, reason))
+        };
+        if read_u32(self.buffer, offset) != Some(LOCAL_HEADER) {
+            return Err(error(0, "Incorrect local header signature".to_string()));
+        }
+        if entry.flags & ENCRYPTED_FLAG != 0 {
+            return Err(error(6, "Encrypted entries are not supported".to_string()));
+        }
+        // The local header repeats the name and may have a different extra
+        // field than the central directory.
+        let name_length = read_u16(self.buffer, offset + 26).unwrap_or_default() as usize;
+        let extra_length = read_u16(self.buffer, offset + 28).unwrap_or_default() as usize;
+        let start = offset + LOCAL_HEADER_SIZE + name_length + extra_length;
+        let data = self
+            .buffer
+            .get(start..start + entry.compressed_size)
+            .ok_or_else(|| {
+                error(
+                    LOCAL_HEADER_SIZE,
+                    "Entry data reaches past the end of the file".to_string(),
+                )
+            })?;
+
+        let data = match entry.method {
+            STORED => data.to_vec(),
+            DEFLATED => inflate::inflate(data, entry.size).map_err(|e| {
+                ParseError::new(start + e.offset, 0, format!("{}: {}", entry.name, e.reason))
+            })?,
+            method => {
+                return Err(error(
+                    8,
+                    format!("Compression method {} is not supported", method),
+                ))
+            }
+        };
+        if data.len() != entry.size {
+            return Err(error(
+                22,
+                format!("Expected {} bytes, decompressed {}", entry.size, data.len()),
+            ));
+        }
+        if crc32(&data) != entry.crc32 {
+            return Err(error(14, "CRC mismatch".to_string()));
+        }
+        Ok(data)
+    }
+}
+
+fn find_end_of_central_directory(buffer: &[u8]) -> Result<usize, ParseError> {
+    if buffer.len() < END_OF_CENTRAL_DIRECTORY_SIZE {
+        return Err(ParseError::new(
+            0,
+            buffer.len(),
+            "Too short for a zip archive",
+        ));
+    }
+    // The record is followed by a comment of up to 65535 bytes.
+    let last = buffer.len() - END_OF_CENTRAL_DIRECTORY_SIZE;
+    let first = last.saturating_sub(0xffff);
+    (first..=last)
+        .rev()
+        .find(|&offset| read_u32(buffer, offset) == Some(END_OF_CENTRAL_DIRECTORY))
+        .ok_or_else(|| ParseError::new(last, 0, "End of central directory record not found"))
+}
+
+fn read_u16(buffer: &[u8], offset: usize) -> Option<u16> {
+    let bytes = buffer.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(buffer: &[u8], offset: usize) -> Option<u32> {
+    let bytes = buffer.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::zip::{crc32, ZipArchive};
+
+    // A zip with a stored "a.txt" and a deflated "dir/b.txt".
+    fn archive() -> Vec<u8> {
+        let mut zip = vec![];
+        let mut directory = vec![];
+        for (name, method, data, size) in [
+            ("a.txt", 0u16, b"hello".to_vec(), 5u32),
+            (
+                "dir/b.txt",
+                8,
+                vec![0x4b, 0x4c, 0x4a, 0x4e, 0x84, 0x21, 0x00],
+                12,
+            ),
+        ] {
+            let crc = crc32(if method == 0 {
+                b"hello"
+            } else {
+                b"abcabcabcabc"
+            });
+            let offset = zip.len() as u32;
+            let mut fields = vec![];
+            fields.extend_from_slice(&20u16.to_le_bytes());
+            fields.extend_from_slice(&0u16.to_le_bytes());
+            fields.extend_from_slice(&method.to_le_bytes());
+            fields.extend_from_slice(&[0; 4]);
+            fields.extend_from_slice(&crc.to_le_bytes());
+            fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            fields.extend_from_slice(&size.to_le_bytes());
+            fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            fields.extend_from_slice(&0u16.to_le_bytes());
+
+            zip.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+            zip.extend_from_slice(&fields);
+            zip.extend_from_slice(name.as_bytes());
+            zip.extend_from_slice(&data);
+
+            directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+            directory.extend_from_slice(&20u16.to_le_bytes());
+            directory.extend_from_slice(&fields);
+            directory.extend_from_slice(&[0; 10]);
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+        let directory_offset = zip.len() as u32;
+        zip.extend_from_slice(&directory);
+        zip.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        zip.extend_from_slice(&[0; 4]);
+        zip.extend_from_slice(&2u16.to_le_bytes());
+        zip.extend_from_slice(&2u16.to_le_bytes());
+        zip.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        zip.extend_from_slice(&directory_offset.to_le_bytes());
+        zip.extend_from_slice(&0u16.to_le_bytes());
+        zip
+    }
+
+    #[test]
+    fn entries() {
+        let buffer = archive();
+        let zip = ZipArchive::from_buffer(&buffer).unwrap();
+        assert_eq!(
+            vec!["a.txt", "dir/b.txt"],
+            zip.entries
+                .iter()
+                .map(|e| e.name.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            b"hello".to_vec(),
+            zip.read(zip.find("a.txt").unwrap()).unwrap()
+        );
+        assert_eq!(
+            b"abcabcabcabc".to_vec(),
+            zip.read(zip.find("dir/b.txt").unwrap()).unwrap()
+        );
+
+        let mut corrupt = zip.find("a.txt").unwrap().clone();
+        corrupt.crc32 ^= 1;
+        assert_eq!(
+            "a.txt: CRC mismatch",
+            zip.read(&corrupt).unwrap_err().reason
+        );
+    }
+}